@@ -0,0 +1,126 @@
+//! Derive macro for `tracing_stackdriver_cw::StackdriverFields`, re-exported by that crate with
+//! its `derive` feature enabled.
+#![deny(missing_docs, unreachable_pub)]
+
+use proc_macro::TokenStream;
+use proc_macro2::TokenStream as TokenStream2;
+use quote::quote;
+use syn::{parse_macro_input, Data, DeriveInput, Fields, LitStr};
+
+/// Implement `StackdriverFields` for a struct with named fields, whose fields must implement
+/// `serde::Serialize`. Field names are written in camelCase, and fields can be configured with:
+///
+/// - `#[stackdriver(rename = "name")]` to write the field under another name
+/// - `#[stackdriver(redact)]` to write `[REDACTED]` in place of the field's value
+/// - `#[stackdriver(skip)]` to leave the field out
+#[proc_macro_derive(StackdriverFields, attributes(stackdriver))]
+pub fn derive_stackdriver_fields(input: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(input as DeriveInput);
+
+    expand(input)
+        .unwrap_or_else(syn::Error::into_compile_error)
+        .into()
+}
+
+/// Configuration of a single field, from its `#[stackdriver(...)]` attributes
+#[derive(Default)]
+struct FieldOptions {
+    rename: Option<String>,
+    redact: bool,
+    skip: bool,
+}
+
+fn expand(input: DeriveInput) -> syn::Result<TokenStream2> {
+    let fields = match &input.data {
+        Data::Struct(data) => match &data.fields {
+            Fields::Named(fields) => &fields.named,
+            _ => return Err(unsupported(&input)),
+        },
+        _ => return Err(unsupported(&input)),
+    };
+
+    let mut inserts = vec![];
+
+    for field in fields {
+        let mut options = FieldOptions::default();
+
+        for attribute in field
+            .attrs
+            .iter()
+            .filter(|attribute| attribute.path().is_ident("stackdriver"))
+        {
+            attribute.parse_nested_meta(|meta| {
+                if meta.path.is_ident("rename") {
+                    options.rename = Some(meta.value()?.parse::<LitStr>()?.value());
+                } else if meta.path.is_ident("redact") {
+                    options.redact = true;
+                } else if meta.path.is_ident("skip") {
+                    options.skip = true;
+                } else {
+                    return Err(meta.error("expected `rename`, `redact`, or `skip`"));
+                }
+
+                Ok(())
+            })?;
+        }
+
+        if options.skip {
+            continue;
+        }
+
+        let ident = field.ident.as_ref().expect("named fields have identifiers");
+        let name = options
+            .rename
+            .unwrap_or_else(|| camel_case(&ident.to_string()));
+
+        let value = if options.redact {
+            quote!(::tracing_stackdriver_cw::__private::redacted(&self.#ident))
+        } else {
+            quote!(::tracing_stackdriver_cw::__private::to_value(&self.#ident))
+        };
+
+        inserts.push(quote!(fields.insert(#name.to_string(), #value);));
+    }
+
+    let ident = &input.ident;
+    let (impl_generics, type_generics, where_clause) = input.generics.split_for_impl();
+
+    Ok(quote! {
+        impl #impl_generics ::tracing_stackdriver_cw::StackdriverFields for #ident #type_generics
+        #where_clause
+        {
+            fn to_fields(&self) -> ::tracing_stackdriver_cw::__private::Map {
+                let mut fields = ::tracing_stackdriver_cw::__private::Map::new();
+                #(#inserts)*
+                fields
+            }
+        }
+    })
+}
+
+fn unsupported(input: &DeriveInput) -> syn::Error {
+    syn::Error::new_spanned(
+        &input.ident,
+        "StackdriverFields can only be derived for structs with named fields",
+    )
+}
+
+/// Convert a snake_case field name to camelCase, matching the keys of other entry fields
+fn camel_case(name: &str) -> String {
+    let mut camel_case = String::with_capacity(name.len());
+    let mut uppercase = false;
+
+    for character in name.trim_start_matches("r#").chars() {
+        match character {
+            '_' if !camel_case.is_empty() => uppercase = true,
+            '_' => (),
+            character if uppercase => {
+                camel_case.extend(character.to_uppercase());
+                uppercase = false;
+            }
+            character => camel_case.push(character),
+        }
+    }
+
+    camel_case
+}
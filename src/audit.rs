@@ -0,0 +1,304 @@
+use serde_json::{Map, Value};
+use std::{
+    collections::BTreeMap,
+    process,
+    sync::atomic::{AtomicU64, Ordering},
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+/// Fields of audit events gathered into their `audit` record
+const AUDIT_FIELDS: [&str; 4] = ["actor", "action", "resource", "outcome"];
+
+/// Configuration for shaping audit events, those from the audit targets or recording
+/// `audit = true`, into a consistent `audit` record with their `actor`, `action`, `resource`, and
+/// `outcome` fields, written to a separate log so that they can be routed to their own sink.
+/// Audit entries are numbered with a `sequence` and given a unique `insertId`, so that gaps and
+/// duplicates are detectable downstream.
+#[derive(Clone, Debug)]
+pub struct AuditLog {
+    /// Targets whose events, and those of their submodules, are audit events
+    pub targets: Vec<String>,
+    /// ID of the log that audit entries are written to, as their `logName`
+    pub log_name: String,
+}
+
+impl Default for AuditLog {
+    fn default() -> Self {
+        Self {
+            targets: vec!["audit".to_string()],
+            log_name: "audit".to_string(),
+        }
+    }
+}
+
+/// Shaper numbering the audit entries of an [`AuditLog`]
+#[derive(Debug)]
+pub(crate) struct AuditTrail {
+    configuration: AuditLog,
+    instance: String,
+    sequence: AtomicU64,
+}
+
+impl AuditTrail {
+    pub(crate) fn new(configuration: AuditLog) -> Self {
+        let started = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_nanos();
+
+        Self {
+            configuration,
+            instance: format!("{:x}-{:x}", process::id(), started),
+            sequence: AtomicU64::new(0),
+        }
+    }
+
+    /// Whether an event from a target with the given fields is an audit event
+    fn matches(&self, target: &str, values: &BTreeMap<&str, Value>) -> bool {
+        values.get("audit") == Some(&Value::Bool(true))
+            || self.configuration.targets.iter().any(|audit_target| {
+                target == audit_target
+                    || target
+                        .strip_prefix(audit_target.as_str())
+                        .is_some_and(|submodule| submodule.starts_with("::"))
+            })
+    }
+
+    /// Replace the fields of an audit event with its audit record
+    pub(crate) fn reshape(&self, target: &str, values: &mut BTreeMap<&str, Value>) {
+        if !self.matches(target, values) {
+            return;
+        }
+
+        let sequence = self.sequence.fetch_add(1, Ordering::Relaxed) + 1;
+        let mut audit: Map<String, Value> = AUDIT_FIELDS
+            .iter()
+            .map(|field| {
+                (
+                    field.to_string(),
+                    values.remove(field).unwrap_or(Value::Null),
+                )
+            })
+            .collect();
+
+        audit.entry("outcome").and_modify(|outcome| {
+            if outcome.is_null() {
+                *outcome = Value::String("unknown".to_string());
+            }
+        });
+        audit.insert("sequence".to_string(), sequence.into());
+
+        values.insert("audit", Value::Object(audit));
+        values
+            .entry("log_name")
+            .or_insert_with(|| Value::String(self.configuration.log_name.clone()));
+        values
+            .entry("insert_id")
+            .or_insert_with(|| Value::String(format!("audit-{}-{}", self.instance, sequence)));
+    }
+}
+
+/// Hash that the first entry of an audit chain links to
+#[cfg(feature = "audit-chain")]
+const GENESIS_HASH: &str = "0000000000000000000000000000000000000000000000000000000000000000";
+
+/// Upper bound of the length that the `prevHash` and `entryHash` of a linked entry add to its line
+#[cfg(feature = "audit-chain")]
+pub(crate) const LINK_LENGTH: usize =
+    2 * (GENESIS_HASH.len() + 2) + r#","prevHash":,"entryHash":"#.len();
+
+/// Chain of the hashes of consecutive audit entries, each linking to the hash of the one before
+#[cfg(feature = "audit-chain")]
+#[derive(Debug)]
+pub(crate) struct HashChain {
+    previous: std::sync::Mutex<String>,
+}
+
+#[cfg(feature = "audit-chain")]
+impl HashChain {
+    pub(crate) fn new() -> Self {
+        Self {
+            previous: std::sync::Mutex::new(GENESIS_HASH.to_string()),
+        }
+    }
+
+    /// Write a formatted entry along with its newline, linking it into the chain if it's an audit
+    /// entry, with the hash of the previous audit entry as its `prevHash` and the hash of its own
+    /// content as its `entryHash`. The chain is locked until the entry is written, so that audit
+    /// entries are written in the order they're linked in, and only advances once it's written.
+    pub(crate) fn write(
+        &self,
+        writer: &mut dyn std::io::Write,
+        entry: String,
+    ) -> std::io::Result<()> {
+        let mut previous = match self.previous.lock() {
+            Ok(previous) => previous,
+            Err(poisoned) => poisoned.into_inner(),
+        };
+
+        let (mut line, entry_hash) = match self.link(entry, &previous) {
+            Ok((line, entry_hash)) => (line, Some(entry_hash)),
+            Err(entry) => (entry, None),
+        };
+
+        line.push('\n');
+        writer.write_all(line.as_bytes())?;
+
+        if let Some(entry_hash) = entry_hash {
+            *previous = entry_hash;
+        }
+
+        Ok(())
+    }
+
+    /// Link a formatted entry into the chain without waiting for it to be written, as when it's
+    /// written by another fmt Layer, returning it as is if it isn't an audit entry
+    pub(crate) fn link_unwritten(&self, entry: String) -> String {
+        let mut previous = match self.previous.lock() {
+            Ok(previous) => previous,
+            Err(poisoned) => poisoned.into_inner(),
+        };
+
+        match self.link(entry, &previous) {
+            Ok((line, entry_hash)) => {
+                *previous = entry_hash;
+                line
+            }
+            Err(entry) => entry,
+        }
+    }
+
+    /// Link an audit entry to the hash of the previous one, returning it along with its own hash,
+    /// or returning it as is if it isn't an audit entry
+    fn link(&self, entry: String, previous: &str) -> Result<(String, String), String> {
+        let Ok(Value::Object(mut fields)) = serde_json::from_str::<Value>(&entry) else {
+            return Err(entry);
+        };
+
+        if !fields.get("audit").is_some_and(Value::is_object) {
+            return Err(entry);
+        }
+
+        if let Some(Value::Object(audit)) = fields.get_mut("audit") {
+            audit.insert("prevHash".to_string(), Value::String(previous.to_string()));
+        }
+
+        let entry_hash = hash(&fields);
+
+        if let Some(Value::Object(audit)) = fields.get_mut("audit") {
+            audit.insert("entryHash".to_string(), Value::String(entry_hash.clone()));
+        }
+
+        Ok((Value::Object(fields).to_string(), entry_hash))
+    }
+}
+
+/// Hex-encoded SHA-256 hash of the canonical JSON of an entry
+#[cfg(feature = "audit-chain")]
+fn hash(fields: &Map<String, Value>) -> String {
+    let mut content = String::new();
+    canonicalize(&Value::Object(fields.clone()), &mut content);
+
+    ring::digest::digest(&ring::digest::SHA256, content.as_bytes())
+        .as_ref()
+        .iter()
+        .map(|byte| format!("{:02x}", byte))
+        .collect()
+}
+
+/// Write the canonical JSON of a value, without whitespace and with the keys of its objects
+/// sorted, regardless of how `serde_json` orders maps
+#[cfg(feature = "audit-chain")]
+fn canonicalize(value: &Value, content: &mut String) {
+    match value {
+        Value::Object(fields) => {
+            let mut keys: Vec<_> = fields.keys().collect();
+            keys.sort();
+
+            content.push('{');
+
+            for (index, key) in keys.into_iter().enumerate() {
+                if index > 0 {
+                    content.push(',');
+                }
+
+                content.push_str(&Value::String(key.clone()).to_string());
+                content.push(':');
+                canonicalize(&fields[key], content);
+            }
+
+            content.push('}');
+        }
+        Value::Array(values) => {
+            content.push('[');
+
+            for (index, value) in values.iter().enumerate() {
+                if index > 0 {
+                    content.push(',');
+                }
+
+                canonicalize(value, content);
+            }
+
+            content.push(']');
+        }
+        value => content.push_str(&value.to_string()),
+    }
+}
+
+/// Broken link in a chain of audit entries, as found by [`verify_audit_chain`]
+#[cfg_attr(docsrs, doc(cfg(feature = "audit-chain")))]
+#[cfg(feature = "audit-chain")]
+#[derive(Debug, thiserror::Error, PartialEq, Eq)]
+pub enum AuditChainError {
+    /// The audit entry at the index isn't valid JSON or has no hashes
+    #[error("audit entry {0} is malformed")]
+    Malformed(usize),
+    /// The content of the audit entry at the index doesn't match its `entryHash`
+    #[error("audit entry {0} was altered")]
+    Altered(usize),
+    /// The `prevHash` of the audit entry at the index doesn't match the entry before it, as when
+    /// entries were deleted or reordered
+    #[error("audit entry {0} doesn't follow the entry before it")]
+    Broken(usize),
+}
+
+/// Verifies a chain of audit entries, as written by a Layer with
+/// [`with_audit_hash_chain`](crate::Layer::with_audit_hash_chain) since it started, returning the
+/// first entry that was altered or doesn't follow the entry before it
+#[cfg_attr(docsrs, doc(cfg(feature = "audit-chain")))]
+#[cfg(feature = "audit-chain")]
+pub fn verify_audit_chain<'a>(
+    entries: impl IntoIterator<Item = &'a str>,
+) -> Result<(), AuditChainError> {
+    let mut previous = GENESIS_HASH.to_string();
+
+    for (index, entry) in entries.into_iter().enumerate() {
+        let Ok(Value::Object(mut fields)) = serde_json::from_str::<Value>(entry) else {
+            return Err(AuditChainError::Malformed(index));
+        };
+
+        let hashes = match fields.get_mut("audit") {
+            Some(Value::Object(audit)) => audit
+                .remove("entryHash")
+                .zip(audit.get("prevHash").cloned()),
+            _ => None,
+        };
+
+        let Some((Value::String(entry_hash), Value::String(prev_hash))) = hashes else {
+            return Err(AuditChainError::Malformed(index));
+        };
+
+        if hash(&fields) != entry_hash {
+            return Err(AuditChainError::Altered(index));
+        }
+
+        if prev_hash != previous {
+            return Err(AuditChainError::Broken(index));
+        }
+
+        previous = entry_hash;
+    }
+
+    Ok(())
+}
@@ -0,0 +1,447 @@
+use base64::{engine::general_purpose::URL_SAFE_NO_PAD, Engine as _};
+use serde::Deserialize;
+use std::{
+    env, fmt, fs,
+    path::{Path, PathBuf},
+    sync::OnceLock,
+    time::{Duration, Instant, SystemTime, UNIX_EPOCH},
+};
+
+/// OAuth 2.0 scope required for writing log entries
+pub(crate) const LOGGING_WRITE_SCOPE: &str = "https://www.googleapis.com/auth/logging.write";
+
+/// OAuth 2.0 scope covering all Google Cloud APIs, required for reporting errors
+#[cfg(feature = "error-reporting")]
+pub(crate) const CLOUD_PLATFORM_SCOPE: &str = "https://www.googleapis.com/auth/cloud-platform";
+
+/// OAuth 2.0 scope required for writing trace spans
+#[cfg(feature = "trace-export")]
+pub(crate) const TRACE_APPEND_SCOPE: &str = "https://www.googleapis.com/auth/trace.append";
+
+/// Google's OAuth 2.0 token endpoint
+const DEFAULT_TOKEN_URI: &str = "https://oauth2.googleapis.com/token";
+
+/// Metadata server endpoint for the default service account's access token
+const METADATA_TOKEN_URL: &str =
+    "http://169.254.169.254/computeMetadata/v1/instance/service-accounts/default/token";
+
+/// Lifetime requested for service account assertions
+const ASSERTION_LIFETIME: Duration = Duration::from_secs(3600);
+
+/// Tokens are refreshed this long before they expire, to account for clock skew and latency
+const EXPIRY_MARGIN: Duration = Duration::from_secs(60);
+
+/// Timeout for token requests
+const TOKEN_REQUEST_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Errors encountered while obtaining access tokens
+#[derive(Debug, thiserror::Error)]
+pub enum AuthError {
+    /// A credentials file could not be read
+    #[error("Unable to read credentials file: {0}")]
+    Io(#[from] std::io::Error),
+    /// A credentials file or token response was malformed
+    #[error("Invalid credentials: {0}")]
+    InvalidCredentials(String),
+    /// A token request failed
+    #[error("Token request failed: {0}")]
+    Request(String),
+    /// A custom token provider failed
+    #[error(transparent)]
+    Other(Box<dyn std::error::Error + Send + Sync>),
+}
+
+/// OAuth 2.0 access token used to authorize exporter requests
+#[derive(Clone)]
+pub struct AccessToken {
+    /// The bearer token itself
+    pub token: String,
+    /// When the token expires, or `None` if it never needs refreshing
+    pub expires_at: Option<Instant>,
+}
+
+impl AccessToken {
+    /// Whether the token is still usable, leaving a margin before its expiry
+    fn is_fresh(&self) -> bool {
+        self.expires_at
+            .map(|expires_at| Instant::now() + EXPIRY_MARGIN < expires_at)
+            .unwrap_or(true)
+    }
+}
+
+impl fmt::Debug for AccessToken {
+    fn fmt(&self, formatter: &mut fmt::Formatter<'_>) -> fmt::Result {
+        formatter
+            .debug_struct("AccessToken")
+            .field("token", &"<redacted>")
+            .field("expires_at", &self.expires_at)
+            .finish()
+    }
+}
+
+/// Source of access tokens for exporter requests. Tokens are cached by the exporter and only
+/// fetched again when they are about to expire, so implementations don't need their own caching.
+pub trait TokenProvider: Send + Sync {
+    /// Fetch a new access token
+    fn fetch_token(&self) -> Result<AccessToken, AuthError>;
+}
+
+/// A fixed access token, e.g. for tests or short-lived processes
+#[derive(Clone)]
+pub struct StaticToken(pub String);
+
+impl TokenProvider for StaticToken {
+    fn fetch_token(&self) -> Result<AccessToken, AuthError> {
+        Ok(AccessToken {
+            token: self.0.clone(),
+            expires_at: None,
+        })
+    }
+}
+
+impl fmt::Debug for StaticToken {
+    fn fmt(&self, formatter: &mut fmt::Formatter<'_>) -> fmt::Result {
+        formatter.pad("StaticToken(<redacted>)")
+    }
+}
+
+/// Tokens for the instance's attached service account from the metadata server, which also
+/// covers GKE Workload Identity
+#[derive(Debug, Default)]
+pub struct MetadataServer;
+
+impl TokenProvider for MetadataServer {
+    fn fetch_token(&self) -> Result<AccessToken, AuthError> {
+        let response = agent()
+            .get(METADATA_TOKEN_URL)
+            .set("Metadata-Flavor", "Google")
+            .call()
+            .map_err(|error| AuthError::Request(error.to_string()))?;
+
+        TokenResponse::parse(response)
+    }
+}
+
+/// Tokens for a service account, signed with its JSON key. Tokens are requested for the logging
+/// write scope unless other scopes are set with [`ServiceAccountKey::with_scopes`].
+#[derive(Deserialize)]
+pub struct ServiceAccountKey {
+    client_email: String,
+    private_key: String,
+    private_key_id: Option<String>,
+    token_uri: Option<String>,
+    #[serde(skip, default = "default_scopes")]
+    scopes: Vec<String>,
+}
+
+impl ServiceAccountKey {
+    /// Read a service account JSON key file
+    pub fn from_file(path: impl AsRef<Path>) -> Result<Self, AuthError> {
+        Self::from_json(&fs::read_to_string(path)?)
+    }
+
+    /// Parse the contents of a service account JSON key file
+    pub fn from_json(json: &str) -> Result<Self, AuthError> {
+        serde_json::from_str(json).map_err(|error| AuthError::InvalidCredentials(error.to_string()))
+    }
+
+    /// Sets the OAuth 2.0 scopes that tokens are requested for, e.g. the `trace.append` scope for
+    /// a [`TokenProvider`] of the `TraceExporter`
+    pub fn with_scopes<S>(self, scopes: impl IntoIterator<Item = S>) -> Self
+    where
+        S: Into<String>,
+    {
+        Self {
+            scopes: scopes.into_iter().map(Into::into).collect(),
+            ..self
+        }
+    }
+
+    /// Build a signed JWT assertion requesting the key's scopes
+    fn assertion(&self, token_uri: &str) -> Result<String, AuthError> {
+        let issued_at = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_secs();
+
+        let header = serde_json::json!({
+            "alg": "RS256",
+            "typ": "JWT",
+            "kid": self.private_key_id,
+        });
+
+        let claims = serde_json::json!({
+            "iss": self.client_email,
+            "scope": self.scopes.join(" "),
+            "aud": token_uri,
+            "iat": issued_at,
+            "exp": issued_at + ASSERTION_LIFETIME.as_secs(),
+        });
+
+        let message = format!(
+            "{}.{}",
+            URL_SAFE_NO_PAD.encode(header.to_string()),
+            URL_SAFE_NO_PAD.encode(claims.to_string())
+        );
+
+        let key_pair = ring::signature::RsaKeyPair::from_pkcs8(&pem_to_der(&self.private_key)?)
+            .map_err(|error| AuthError::InvalidCredentials(error.to_string()))?;
+        let mut signature = vec![0; key_pair.public().modulus_len()];
+
+        key_pair
+            .sign(
+                &ring::signature::RSA_PKCS1_SHA256,
+                &ring::rand::SystemRandom::new(),
+                message.as_bytes(),
+                &mut signature,
+            )
+            .map_err(|error| AuthError::InvalidCredentials(error.to_string()))?;
+
+        Ok(format!("{}.{}", message, URL_SAFE_NO_PAD.encode(signature)))
+    }
+}
+
+impl TokenProvider for ServiceAccountKey {
+    fn fetch_token(&self) -> Result<AccessToken, AuthError> {
+        let token_uri = self.token_uri.as_deref().unwrap_or(DEFAULT_TOKEN_URI);
+        let assertion = self.assertion(token_uri)?;
+
+        let response = agent()
+            .post(token_uri)
+            .send_form(&[
+                ("grant_type", "urn:ietf:params:oauth:grant-type:jwt-bearer"),
+                ("assertion", &assertion),
+            ])
+            .map_err(|error| AuthError::Request(error.to_string()))?;
+
+        TokenResponse::parse(response)
+    }
+}
+
+impl fmt::Debug for ServiceAccountKey {
+    fn fmt(&self, formatter: &mut fmt::Formatter<'_>) -> fmt::Result {
+        formatter
+            .debug_struct("ServiceAccountKey")
+            .field("client_email", &self.client_email)
+            .field("private_key", &"<redacted>")
+            .field("scopes", &self.scopes)
+            .finish()
+    }
+}
+
+/// Tokens for a user account, refreshed from the credentials written by
+/// `gcloud auth application-default login`
+#[derive(Deserialize)]
+pub struct AuthorizedUser {
+    client_id: String,
+    client_secret: String,
+    refresh_token: String,
+}
+
+impl TokenProvider for AuthorizedUser {
+    fn fetch_token(&self) -> Result<AccessToken, AuthError> {
+        let response = agent()
+            .post(DEFAULT_TOKEN_URI)
+            .send_form(&[
+                ("grant_type", "refresh_token"),
+                ("client_id", &self.client_id),
+                ("client_secret", &self.client_secret),
+                ("refresh_token", &self.refresh_token),
+            ])
+            .map_err(|error| AuthError::Request(error.to_string()))?;
+
+        TokenResponse::parse(response)
+    }
+}
+
+impl fmt::Debug for AuthorizedUser {
+    fn fmt(&self, formatter: &mut fmt::Formatter<'_>) -> fmt::Result {
+        formatter
+            .debug_struct("AuthorizedUser")
+            .field("client_id", &self.client_id)
+            .finish_non_exhaustive()
+    }
+}
+
+/// [Application Default Credentials](https://cloud.google.com/docs/authentication/application-default-credentials),
+/// discovered on first use from the `GOOGLE_APPLICATION_CREDENTIALS` key file, then the gcloud
+/// CLI's well-known credentials file, then the metadata server. Service account keys request
+/// tokens for the logging write scope unless other scopes are set with
+/// [`ApplicationDefaultCredentials::with_scopes`], while the scopes of user credentials and the
+/// metadata server's tokens are those they were granted.
+pub struct ApplicationDefaultCredentials {
+    provider: OnceLock<Box<dyn TokenProvider>>,
+    scopes: Vec<String>,
+}
+
+impl ApplicationDefaultCredentials {
+    /// Create credentials that are discovered when the first token is fetched
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Sets the OAuth 2.0 scopes that service account keys request tokens for
+    pub fn with_scopes<S>(self, scopes: impl IntoIterator<Item = S>) -> Self
+    where
+        S: Into<String>,
+    {
+        Self {
+            scopes: scopes.into_iter().map(Into::into).collect(),
+            ..self
+        }
+    }
+
+    fn discover(&self) -> Result<Box<dyn TokenProvider>, AuthError> {
+        if let Some(path) = env::var_os("GOOGLE_APPLICATION_CREDENTIALS") {
+            return credentials_file(Path::new(&path), &self.scopes);
+        }
+
+        if let Some(path) = well_known_file().filter(|path| path.exists()) {
+            return credentials_file(&path, &self.scopes);
+        }
+
+        Ok(Box::new(MetadataServer))
+    }
+}
+
+impl TokenProvider for ApplicationDefaultCredentials {
+    fn fetch_token(&self) -> Result<AccessToken, AuthError> {
+        if let Some(provider) = self.provider.get() {
+            return provider.fetch_token();
+        }
+
+        let provider = self.discover()?;
+        self.provider.get_or_init(|| provider).fetch_token()
+    }
+}
+
+impl Default for ApplicationDefaultCredentials {
+    fn default() -> Self {
+        Self {
+            provider: OnceLock::new(),
+            scopes: default_scopes(),
+        }
+    }
+}
+
+impl fmt::Debug for ApplicationDefaultCredentials {
+    fn fmt(&self, formatter: &mut fmt::Formatter<'_>) -> fmt::Result {
+        formatter
+            .debug_struct("ApplicationDefaultCredentials")
+            .field("scopes", &self.scopes)
+            .finish_non_exhaustive()
+    }
+}
+
+/// Exporter-side cache of a provider's most recent token
+pub(crate) struct TokenCache {
+    provider: Box<dyn TokenProvider>,
+    token: Option<AccessToken>,
+}
+
+impl TokenCache {
+    pub(crate) fn new(provider: Box<dyn TokenProvider>) -> Self {
+        Self {
+            provider,
+            token: None,
+        }
+    }
+
+    /// The cached token, refreshed from the provider when missing or about to expire
+    pub(crate) fn token(&mut self) -> Result<&str, AuthError> {
+        let token = match self.token.take().filter(AccessToken::is_fresh) {
+            Some(token) => token,
+            None => self.provider.fetch_token()?,
+        };
+
+        Ok(&self.token.insert(token).token)
+    }
+}
+
+/// Successful response from a token endpoint
+#[derive(Deserialize)]
+struct TokenResponse {
+    access_token: String,
+    expires_in: Option<u64>,
+}
+
+impl TokenResponse {
+    fn parse(response: ureq::Response) -> Result<AccessToken, AuthError> {
+        let response: Self = response
+            .into_json()
+            .map_err(|error| AuthError::InvalidCredentials(error.to_string()))?;
+
+        Ok(AccessToken {
+            token: response.access_token,
+            expires_at: response
+                .expires_in
+                .map(|expires_in| Instant::now() + Duration::from_secs(expires_in)),
+        })
+    }
+}
+
+/// Scopes requested by service account keys unless others are set
+fn default_scopes() -> Vec<String> {
+    vec![LOGGING_WRITE_SCOPE.to_string()]
+}
+
+/// Read a credentials file of either the `service_account` or `authorized_user` type, requesting
+/// the given scopes for service accounts
+fn credentials_file(path: &Path, scopes: &[String]) -> Result<Box<dyn TokenProvider>, AuthError> {
+    let json = fs::read_to_string(path)?;
+
+    #[derive(Deserialize)]
+    struct CredentialsType {
+        #[serde(rename = "type")]
+        kind: String,
+    }
+
+    let invalid = |error: serde_json::Error| AuthError::InvalidCredentials(error.to_string());
+    let CredentialsType { kind } = serde_json::from_str(&json).map_err(invalid)?;
+
+    match kind.as_str() {
+        "service_account" => Ok(Box::new(
+            ServiceAccountKey::from_json(&json)?.with_scopes(scopes.iter().cloned()),
+        )),
+        "authorized_user" => Ok(Box::new(
+            serde_json::from_str::<AuthorizedUser>(&json).map_err(invalid)?,
+        )),
+        kind => Err(AuthError::InvalidCredentials(format!(
+            "unsupported credentials type \"{}\"",
+            kind
+        ))),
+    }
+}
+
+/// Location of the credentials written by `gcloud auth application-default login`
+fn well_known_file() -> Option<PathBuf> {
+    let config = if cfg!(windows) {
+        PathBuf::from(env::var_os("APPDATA")?)
+    } else {
+        PathBuf::from(env::var_os("HOME")?).join(".config")
+    };
+
+    Some(
+        config
+            .join("gcloud")
+            .join("application_default_credentials.json"),
+    )
+}
+
+/// Decode a PEM-encoded private key to DER
+fn pem_to_der(pem: &str) -> Result<Vec<u8>, AuthError> {
+    let base64: String = pem
+        .lines()
+        .filter(|line| !line.starts_with("-----"))
+        .collect();
+
+    base64::engine::general_purpose::STANDARD
+        .decode(base64.trim())
+        .map_err(|error| AuthError::InvalidCredentials(error.to_string()))
+}
+
+fn agent() -> ureq::Agent {
+    ureq::AgentBuilder::new()
+        .timeout(TOKEN_REQUEST_TIMEOUT)
+        .build()
+}
@@ -0,0 +1,75 @@
+use crate::{layer::layer, pretty::PrettyFormatter};
+use std::{
+    env,
+    io::{self, IsTerminal},
+};
+use tracing_core::Subscriber;
+use tracing_subscriber::registry::LookupSpan;
+
+/// Environment variables set by the Google Cloud runtimes whose logging agents ingest JSON
+const GOOGLE_CLOUD_VARIABLES: [&str; 4] = [
+    "K_SERVICE",
+    "FUNCTION_TARGET",
+    "GAE_SERVICE",
+    "KUBERNETES_SERVICE_HOST",
+];
+
+/// Output format picked by the [`auto`] layer
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum LogFormat {
+    /// Stackdriver-compatible JSON entries
+    Json,
+    /// Human-readable output of Stackdriver entries, colored by severity, as written by a
+    /// [`PrettyFormatter`]
+    Pretty,
+    /// Single-line human-readable output
+    Compact,
+}
+
+impl LogFormat {
+    /// Detect the output format, forced through `LOG_FORMAT=json|pretty|compact` or otherwise
+    /// JSON on Google Cloud or when stdout isn't a terminal, and pretty output everywhere else
+    pub fn detect() -> Self {
+        let forced = env::var("LOG_FORMAT").ok().and_then(|format| {
+            match format.to_ascii_lowercase().as_str() {
+                "json" => Some(Self::Json),
+                "pretty" => Some(Self::Pretty),
+                "compact" => Some(Self::Compact),
+                _ => None,
+            }
+        });
+
+        forced.unwrap_or_else(|| {
+            let google_cloud = GOOGLE_CLOUD_VARIABLES
+                .iter()
+                .any(|variable| env::var_os(variable).is_some());
+
+            if google_cloud || !io::stdout().is_terminal() {
+                Self::Json
+            } else {
+                Self::Pretty
+            }
+        })
+    }
+}
+
+/// Create a Layer that writes Stackdriver entries in production and human-readable output in a
+/// terminal, as picked by [`LogFormat::detect`]. Human-readable output is only colored when
+/// `NO_COLOR` is unset or empty.
+pub fn auto<S>() -> Box<dyn tracing_subscriber::Layer<S> + Send + Sync>
+where
+    S: Subscriber + for<'span> LookupSpan<'span>,
+{
+    // https://no-color.org
+    let ansi = env::var_os("NO_COLOR").is_none_or(|value| value.is_empty());
+
+    match LogFormat::detect() {
+        LogFormat::Json => Box::new(layer()),
+        LogFormat::Pretty => Box::new(
+            tracing_subscriber::fmt::layer()
+                .event_format(PrettyFormatter::default())
+                .with_ansi(ansi),
+        ),
+        LogFormat::Compact => Box::new(tracing_subscriber::fmt::layer().compact().with_ansi(ansi)),
+    }
+}
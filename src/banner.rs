@@ -0,0 +1,76 @@
+use crate::{
+    entry::LogEntry, event_formatter::EventFormatter, google::LogSeverity,
+    sampling::AdaptiveSampling,
+};
+use serde_json::{json, Value};
+use std::io;
+
+/// Write the NOTICE entry summarizing the effective configuration of a layer
+pub(crate) fn report(
+    writer: &mut dyn io::Write,
+    event_formatter: &EventFormatter,
+    sampling: Option<&AdaptiveSampling>,
+) {
+    let format = if event_formatter.legacy_agent_fields {
+        "legacyAgent"
+    } else {
+        "json"
+    };
+
+    let sampling = sampling.map(|sampling| {
+        json!({
+            "maxEntriesPerSecond": sampling.max_entries_per_second,
+            "interval": format!("{}s", sampling.interval.as_secs_f64()),
+            "minRate": sampling.min_rate,
+        })
+    });
+
+    let project_id = event_formatter
+        .cloud_trace
+        .as_ref()
+        .map(|cloud_trace| cloud_trace.project_id.clone())
+        .or_else(detect_project_id);
+
+    let entry = LogEntry::report(
+        module_path!(),
+        LogSeverity::Notice,
+        "tracing-stackdriver configuration",
+    )
+    .with_field(
+        "configuration",
+        json!({
+            "version": env!("CARGO_PKG_VERSION"),
+            "format": format,
+            "strict": event_formatter.strict,
+            "sampling": sampling,
+            "projectId": project_id,
+            "resource": project_id.as_deref().and_then(detect_resource),
+        }),
+    );
+
+    let _ = event_formatter.write_report(writer, entry);
+}
+
+// the banner is written as the layer is installed, so it doesn't wait for the metadata server
+#[cfg(feature = "metadata")]
+fn detect_project_id() -> Option<String> {
+    crate::metadata::cached_only(crate::metadata::project_id)
+}
+
+#[cfg(not(feature = "metadata"))]
+fn detect_project_id() -> Option<String> {
+    None
+}
+
+#[cfg(any(feature = "exporter", feature = "protobuf"))]
+fn detect_resource(project_id: &str) -> Option<Value> {
+    let resource =
+        crate::metadata::cached_only(|| crate::resource::MonitoredResource::detect(project_id));
+
+    serde_json::to_value(resource).ok()
+}
+
+#[cfg(not(any(feature = "exporter", feature = "protobuf")))]
+fn detect_resource(_project_id: &str) -> Option<Value> {
+    None
+}
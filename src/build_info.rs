@@ -0,0 +1,61 @@
+use std::collections::BTreeMap;
+
+/// Prefix of the labels of build information
+const LABEL_PREFIX: &str = "build_";
+
+/// Build information of a binary, attached to every entry as labels so that any entry identifies
+/// the binary that wrote it. Usually captured at compile time with [`build_info!`](crate::build_info).
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct BuildInfo {
+    /// Version of the crate, written as the `build_version` label
+    pub version: Option<String>,
+    /// Git commit SHA, written as the `build_sha` label
+    pub sha: Option<String>,
+    /// Time of the build, written as the `build_timestamp` label
+    pub timestamp: Option<String>,
+    /// Version of the compiler, written as the `build_rustc_version` label
+    pub rustc_version: Option<String>,
+}
+
+impl BuildInfo {
+    /// Labels of the known build information
+    pub(crate) fn labels(&self) -> BTreeMap<String, String> {
+        [
+            ("version", &self.version),
+            ("sha", &self.sha),
+            ("timestamp", &self.timestamp),
+            ("rustc_version", &self.rustc_version),
+        ]
+        .into_iter()
+        .filter_map(|(key, value)| {
+            value
+                .clone()
+                .map(|value| (format!("{}{}", LABEL_PREFIX, key), value))
+        })
+        .collect()
+    }
+
+    /// Whether a label holds build information
+    pub(crate) fn is_label(key: &str) -> bool {
+        key.starts_with(LABEL_PREFIX)
+    }
+}
+
+/// Captures the [`BuildInfo`] of the calling crate at compile time: its `CARGO_PKG_VERSION`, along
+/// with the `GIT_SHA`, `BUILD_TIMESTAMP`, and `RUSTC_VERSION` environment variables when set,
+/// e.g. by a build script through `cargo:rustc-env`
+#[macro_export]
+macro_rules! build_info {
+    () => {
+        $crate::BuildInfo {
+            version: ::core::option::Option::Some(::std::string::ToString::to_string(
+                ::core::env!("CARGO_PKG_VERSION"),
+            )),
+            sha: ::core::option_env!("GIT_SHA").map(::std::string::ToString::to_string),
+            timestamp: ::core::option_env!("BUILD_TIMESTAMP")
+                .map(::std::string::ToString::to_string),
+            rustc_version: ::core::option_env!("RUSTC_VERSION")
+                .map(::std::string::ToString::to_string),
+        }
+    };
+}
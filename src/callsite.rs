@@ -0,0 +1,16 @@
+use crate::stack_trace::fnv1a;
+use tracing_core::Metadata;
+
+/// Fingerprint of the callsite of an event, a hash of its target, file, line, and name that's
+/// stable across processes and releases as long as the statement doesn't move
+pub(crate) fn fingerprint(meta: &Metadata) -> String {
+    let callsite = format!(
+        "{}\0{}\0{}\0{}",
+        meta.target(),
+        meta.file().unwrap_or_default(),
+        meta.line().map(|line| line.to_string()).unwrap_or_default(),
+        meta.name(),
+    );
+
+    format!("{:016x}", fnv1a(callsite.as_bytes()))
+}
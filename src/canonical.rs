@@ -0,0 +1,97 @@
+use serde_json::Value;
+use std::{
+    collections::BTreeMap,
+    fmt,
+    sync::atomic::{AtomicU64, Ordering},
+};
+use tracing_core::{Event, Field, Level};
+use tracing_subscriber::field::Visit;
+
+/// Configuration for canonical entries, folding the INFO events within request spans into a
+/// single entry per request, written when its span closes with the fields of every folded event.
+/// Request spans are those declaring `http_request.` fields. DEBUG, WARN, and ERROR events, and
+/// events outside of request spans, are written as usual.
+#[derive(Clone, Debug, Default)]
+pub struct CanonicalEntries {
+    /// Still write 1 in every `sample_rate` folded events, or none when `0`
+    pub sample_rate: u64,
+}
+
+/// Filter counting the events folded into canonical entries
+#[derive(Debug)]
+pub(crate) struct Canonical {
+    configuration: CanonicalEntries,
+    folded: AtomicU64,
+}
+
+impl Canonical {
+    pub(crate) fn new(configuration: CanonicalEntries) -> Self {
+        Self {
+            configuration,
+            folded: AtomicU64::new(0),
+        }
+    }
+
+    /// Whether an event is folded into the canonical entry of its request span
+    pub(crate) fn is_folded(event: &Event) -> bool {
+        *event.metadata().level() == Level::INFO && !event.metadata().is_span()
+    }
+
+    /// Whether a folded event is still written
+    pub(crate) fn keep(&self) -> bool {
+        let folded = self.folded.fetch_add(1, Ordering::Relaxed);
+
+        match self.configuration.sample_rate {
+            0 => false,
+            sample_rate => folded.is_multiple_of(sample_rate),
+        }
+    }
+}
+
+/// Fields of the events folded into the canonical entry of a request span, cached in that span's
+/// extensions and attached to its close entry, with later events taking precedence
+#[derive(Clone, Debug, Default)]
+pub(crate) struct CanonicalFields {
+    pub(crate) values: BTreeMap<&'static str, Value>,
+    pub(crate) events: u64,
+}
+
+impl CanonicalFields {
+    /// Fold the fields of an event, except for its message
+    pub(crate) fn fold(&mut self, event: &Event) {
+        self.events += 1;
+        event.record(self);
+    }
+
+    fn record(&mut self, field: &Field, value: Value) {
+        if field.name() != "message" {
+            self.values.insert(field.name(), value);
+        }
+    }
+}
+
+impl Visit for CanonicalFields {
+    fn record_f64(&mut self, field: &Field, value: f64) {
+        self.record(field, Value::from(value));
+    }
+
+    fn record_i64(&mut self, field: &Field, value: i64) {
+        self.record(field, Value::from(value));
+    }
+
+    fn record_u64(&mut self, field: &Field, value: u64) {
+        self.record(field, Value::from(value));
+    }
+
+    fn record_bool(&mut self, field: &Field, value: bool) {
+        self.record(field, Value::from(value));
+    }
+
+    fn record_str(&mut self, field: &Field, value: &str) {
+        self.record(field, Value::from(value));
+    }
+
+    fn record_debug(&mut self, field: &Field, value: &dyn fmt::Debug) {
+        self.record(field, Value::from(format!("{:?}", value)));
+    }
+}
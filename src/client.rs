@@ -0,0 +1,79 @@
+use crate::{
+    auth::{ApplicationDefaultCredentials, StaticToken, TokenProvider},
+    exporter::{report_to_stderr, Diagnostics, ExportFailure},
+};
+use std::{fmt, sync::Arc, time::Duration};
+
+/// Default timeout for a single request
+const DEFAULT_REQUEST_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// Authorization, timeout, and diagnostics settings of a builder implementing
+/// [`ApiClientBuilder`]
+pub struct ClientConfig {
+    pub(crate) token_provider: Box<dyn TokenProvider>,
+    pub(crate) request_timeout: Duration,
+    pub(crate) diagnostics: Diagnostics,
+}
+
+impl ClientConfig {
+    /// Settings authorizing requests with Application Default Credentials for the given scope
+    pub(crate) fn new(scope: &str) -> Self {
+        Self {
+            token_provider: Box::new(ApplicationDefaultCredentials::new().with_scopes([scope])),
+            request_timeout: DEFAULT_REQUEST_TIMEOUT,
+            diagnostics: Arc::new(report_to_stderr),
+        }
+    }
+
+    /// HTTP agent sending requests with the configured timeout
+    pub(crate) fn agent(&self) -> ureq::Agent {
+        ureq::AgentBuilder::new()
+            .timeout(self.request_timeout)
+            .build()
+    }
+}
+
+impl fmt::Debug for ClientConfig {
+    fn fmt(&self, formatter: &mut fmt::Formatter<'_>) -> fmt::Result {
+        formatter
+            .debug_struct("ClientConfig")
+            .field("request_timeout", &self.request_timeout)
+            .finish_non_exhaustive()
+    }
+}
+
+/// Settings shared by the builders of the writers sending to Google Cloud APIs: the
+/// [`ExporterBuilder`](crate::ExporterBuilder), and the `ErrorReporterBuilder` and
+/// `TraceExporterBuilder` of the `error-reporting` and `trace-export` features
+pub trait ApiClientBuilder: Sized {
+    #[doc(hidden)]
+    fn client_config(&mut self) -> &mut ClientConfig;
+
+    /// Sets a fixed OAuth 2.0 access token used to authorize requests
+    fn with_access_token(self, access_token: impl Into<String>) -> Self {
+        self.with_token_provider(StaticToken(access_token.into()))
+    }
+
+    /// Sets the source of access tokens used to authorize requests, which defaults to
+    /// [`ApplicationDefaultCredentials`] requesting the scope of the builder's API
+    fn with_token_provider(mut self, token_provider: impl TokenProvider + 'static) -> Self {
+        self.client_config().token_provider = Box::new(token_provider);
+        self
+    }
+
+    /// Sets the timeout for a single request
+    fn with_request_timeout(mut self, request_timeout: Duration) -> Self {
+        self.client_config().request_timeout = request_timeout;
+        self
+    }
+
+    /// Sets the hook notified of the entries, error events, or spans that could not be sent, and
+    /// why. By default, failures are reported on stderr.
+    fn with_diagnostics<F>(mut self, diagnostics: F) -> Self
+    where
+        F: Fn(&ExportFailure) + Send + Sync + 'static,
+    {
+        self.client_config().diagnostics = Arc::new(diagnostics);
+        self
+    }
+}
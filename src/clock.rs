@@ -0,0 +1,13 @@
+use std::{sync::OnceLock, time::Instant};
+use time::OffsetDateTime;
+
+/// Wall-clock time and monotonic instant read together when the clock is first used
+static ANCHOR: OnceLock<(OffsetDateTime, Instant)> = OnceLock::new();
+
+/// Current time as the wall-clock anchor plus the monotonic time elapsed since, which never
+/// decreases even if the system clock steps backwards, e.g. during VM live migration
+pub(crate) fn monotonic_now() -> OffsetDateTime {
+    let (wall_clock, instant) = ANCHOR.get_or_init(|| (OffsetDateTime::now_utc(), Instant::now()));
+
+    *wall_clock + instant.elapsed()
+}
@@ -0,0 +1,132 @@
+use crate::fields;
+use std::{cell::RefCell, env, fmt, future::Future};
+use tracing::Instrument;
+use tracing_core::Field;
+use tracing_subscriber::{
+    field::Visit,
+    registry::{LookupSpan, SpanRef},
+};
+
+/// Request header used by Cloud Functions to identify a single function invocation
+pub const FUNCTION_EXECUTION_ID_HEADER: &str = "Function-Execution-Id";
+
+/// Target of the spans created by [`function_execution_span`]
+const FUNCTION_EXECUTION_TARGET: &str = module_path!();
+
+thread_local! {
+    /// Execution ID of the invocation running on this thread, set independently of spans so that
+    /// it's kept when filters disable the function execution span
+    static CURRENT_EXECUTION_ID: RefCell<Option<String>> = const { RefCell::new(None) };
+}
+
+/// Find the execution ID of the current Cloud Functions invocation from a request's headers,
+/// falling back to the `FUNCTION_EXECUTION_ID` runtime environment variable.
+pub fn function_execution_id<'a, H>(headers: H) -> Option<String>
+where
+    H: IntoIterator<Item = (&'a str, &'a str)>,
+{
+    headers
+        .into_iter()
+        .find(|(name, _)| name.eq_ignore_ascii_case(FUNCTION_EXECUTION_ID_HEADER))
+        .map(|(_, value)| value.to_string())
+        .or_else(|| env::var("FUNCTION_EXECUTION_ID").ok())
+        .filter(|execution_id| !execution_id.is_empty())
+}
+
+/// Create a span for a single Cloud Functions invocation. Every entry emitted within this span is
+/// labeled with `execution_id`, matching the managed runtime loggers. The span is created at INFO,
+/// so level filters above INFO disable it: use [`instrument_function_execution`] or
+/// [`function_execution`] to label entries regardless of filters.
+pub fn function_execution_span(execution_id: Option<&str>) -> tracing::Span {
+    tracing::info_span!(
+        target: FUNCTION_EXECUTION_TARGET,
+        "function_execution",
+        execution_id
+    )
+}
+
+/// Run a Cloud Functions invocation wrapper within a [`function_execution_span`], using the
+/// execution ID found in the request's headers. Entries emitted by the wrapper are labeled with
+/// the execution ID even when filters disable the span.
+pub fn instrument_function_execution<'a, H, F, T>(headers: H, function: F) -> T
+where
+    H: IntoIterator<Item = (&'a str, &'a str)>,
+    F: FnOnce() -> T,
+{
+    let execution_id = function_execution_id(headers);
+    let span = function_execution_span(execution_id.as_deref());
+    let _scope = ExecutionScope::enter(execution_id);
+    span.in_scope(function)
+}
+
+/// Run an asynchronous Cloud Functions invocation within a [`function_execution_span`]. Entries
+/// emitted while the future is polled are labeled with `execution_id` even when filters disable
+/// the span.
+pub fn function_execution<F>(
+    execution_id: Option<&str>,
+    future: F,
+) -> impl Future<Output = F::Output>
+where
+    F: Future,
+{
+    let execution_id = execution_id.map(str::to_string);
+    let mut future = Box::pin(future.instrument(function_execution_span(execution_id.as_deref())));
+
+    std::future::poll_fn(move |context| {
+        let _scope = ExecutionScope::enter(execution_id.clone());
+        future.as_mut().poll(context)
+    })
+}
+
+/// Sets the execution ID of the current thread until dropped, restoring the previous one even if
+/// the invocation panics
+struct ExecutionScope(Option<String>);
+
+impl ExecutionScope {
+    fn enter(execution_id: Option<String>) -> Self {
+        Self(CURRENT_EXECUTION_ID.replace(execution_id))
+    }
+}
+
+impl Drop for ExecutionScope {
+    fn drop(&mut self) {
+        CURRENT_EXECUTION_ID.set(self.0.take());
+    }
+}
+
+/// Execution ID recorded on a function execution span, cached in that span's extensions
+#[derive(Clone, Debug, Default)]
+pub(crate) struct ExecutionId(Option<String>);
+
+impl ExecutionId {
+    /// Whether a span was created by [`function_execution_span`]
+    pub(crate) fn is_execution_span(metadata: &tracing_core::Metadata) -> bool {
+        metadata.target() == FUNCTION_EXECUTION_TARGET
+    }
+
+    /// Find the execution ID of the nearest function execution span in the scope
+    pub(crate) fn from_scope<S>(span: &SpanRef<S>) -> Option<String>
+    where
+        S: for<'lookup> LookupSpan<'lookup>,
+    {
+        span.scope()
+            .find_map(|span| span.extensions().get::<Self>().and_then(|id| id.0.clone()))
+    }
+
+    /// Execution ID of the invocation running on this thread, if any
+    pub(crate) fn current() -> Option<String> {
+        CURRENT_EXECUTION_ID.with_borrow(Clone::clone)
+    }
+}
+
+impl Visit for ExecutionId {
+    fn record_str(&mut self, field: &Field, value: &str) {
+        if field.name() == "execution_id" {
+            self.0 = Some(value.to_string());
+        }
+    }
+
+    fn record_debug(&mut self, field: &Field, value: &dyn fmt::Debug) {
+        self.record_str(field, fields::unquote(&format!("{:?}", value)))
+    }
+}
@@ -0,0 +1,42 @@
+use std::{collections::BTreeMap, env};
+
+/// Detect Cloud Run service, revision, configuration, and location labels from the runtime
+/// environment, returning `None` when not running on Cloud Run.
+pub(crate) fn cloud_run_labels() -> Option<BTreeMap<String, String>> {
+    let service = env::var("K_SERVICE").ok()?;
+    let mut labels = BTreeMap::new();
+
+    labels.insert("run.googleapis.com/service_name".to_string(), service);
+
+    if let Ok(revision) = env::var("K_REVISION") {
+        labels.insert("run.googleapis.com/revision_name".to_string(), revision);
+    }
+
+    if let Ok(configuration) = env::var("K_CONFIGURATION") {
+        labels.insert(
+            "run.googleapis.com/configuration_name".to_string(),
+            configuration,
+        );
+    }
+
+    if let Some(region) = metadata_region() {
+        labels.insert("run.googleapis.com/location".to_string(), region);
+    }
+
+    Some(labels)
+}
+
+/// Fetch the instance's region from the metadata server, formatted there as
+/// `projects/PROJECT_NUMBER/regions/REGION`
+#[cfg(feature = "metadata")]
+pub(crate) fn metadata_region() -> Option<String> {
+    use crate::metadata;
+
+    metadata::last_segment(&metadata::get("instance/region")?)
+}
+
+/// The region is unknown without the metadata client
+#[cfg(not(feature = "metadata"))]
+pub(crate) fn metadata_region() -> Option<String> {
+    None
+}
@@ -0,0 +1,50 @@
+use std::{fmt::Write, time::Duration};
+use time::{format_description::well_known::Rfc3339, OffsetDateTime};
+
+/// Base URL of the Logs Explorer
+const LOGS_EXPLORER_URL: &str = "https://console.cloud.google.com/logs/query";
+
+/// Logs Explorer URL of the entries of a trace, within a window either side of an entry's time
+pub(crate) fn logs_explorer_url(
+    project_id: &str,
+    trace: &str,
+    time: OffsetDateTime,
+    window: Duration,
+) -> Option<String> {
+    let start = time
+        .checked_sub(window.try_into().ok()?)?
+        .format(&Rfc3339)
+        .ok()?;
+    let end = time
+        .checked_add(window.try_into().ok()?)?
+        .format(&Rfc3339)
+        .ok()?;
+    let cursor = time.format(&Rfc3339).ok()?;
+
+    Some(format!(
+        "{};query={};timeRange={};cursorTimestamp={}?project={}",
+        LOGS_EXPLORER_URL,
+        encode(&format!("trace=\"{}\"", trace)),
+        encode(&format!("{}/{}", start, end)),
+        encode(&cursor),
+        encode(project_id),
+    ))
+}
+
+/// Percent-encode every character but the unreserved ones of RFC 3986
+fn encode(value: &str) -> String {
+    let mut encoded = String::with_capacity(value.len());
+
+    for byte in value.bytes() {
+        match byte {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'.' | b'_' | b'~' => {
+                encoded.push(byte as char)
+            }
+            _ => {
+                let _ = write!(encoded, "%{:02X}", byte);
+            }
+        }
+    }
+
+    encoded
+}
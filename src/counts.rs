@@ -0,0 +1,72 @@
+use crate::google::LogSeverity;
+use std::{
+    collections::{BTreeMap, HashMap},
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc, RwLock,
+    },
+};
+
+/// Severities in the order of their counters
+const SEVERITIES: [LogSeverity; 9] = [
+    LogSeverity::Default,
+    LogSeverity::Debug,
+    LogSeverity::Info,
+    LogSeverity::Notice,
+    LogSeverity::Warning,
+    LogSeverity::Error,
+    LogSeverity::Critical,
+    LogSeverity::Alert,
+    LogSeverity::Emergency,
+];
+
+/// Handle for inspecting the number of entries a Layer wrote by top-level target and severity,
+/// e.g. to expose log volume by component as metrics
+#[derive(Clone, Debug, Default)]
+pub struct EntryCounts {
+    inner: Arc<RwLock<HashMap<String, [AtomicU64; 9]>>>,
+}
+
+impl EntryCounts {
+    /// Current number of entries by top-level target, such as `hyper` for `hyper::client`, and
+    /// severity, omitting severities without entries
+    pub fn snapshot(&self) -> BTreeMap<String, BTreeMap<LogSeverity, u64>> {
+        let targets = match self.inner.read() {
+            Ok(targets) => targets,
+            Err(poisoned) => poisoned.into_inner(),
+        };
+
+        targets
+            .iter()
+            .map(|(target, counts)| {
+                let counts = SEVERITIES
+                    .iter()
+                    .zip(counts)
+                    .map(|(severity, count)| (*severity, count.load(Ordering::Relaxed)))
+                    .filter(|(_, count)| *count > 0)
+                    .collect();
+
+                (target.clone(), counts)
+            })
+            .collect()
+    }
+
+    pub(crate) fn record(&self, target: &str, severity: LogSeverity) {
+        let target = target.split("::").next().unwrap_or(target);
+        let index = severity as usize;
+
+        if let Ok(targets) = self.inner.read() {
+            if let Some(counts) = targets.get(target) {
+                counts[index].fetch_add(1, Ordering::Relaxed);
+                return;
+            }
+        }
+
+        let mut targets = match self.inner.write() {
+            Ok(targets) => targets,
+            Err(poisoned) => poisoned.into_inner(),
+        };
+
+        targets.entry(target.to_string()).or_default()[index].fetch_add(1, Ordering::Relaxed);
+    }
+}
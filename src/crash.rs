@@ -0,0 +1,96 @@
+use crate::{
+    entry::{self, EntrySourceLocation, LogEntry},
+    google::LogSeverity,
+    recent::RecentEntries,
+};
+use serde_json::{Map, Value};
+use std::{
+    backtrace::{Backtrace, BacktraceStatus},
+    io::{self, Write},
+    panic::{self, PanicHookInfo},
+};
+use tracing_subscriber::fmt::MakeWriter;
+
+/// Label marking the entries written as the context of a crash
+const CRASH_CONTEXT_LABEL: &str = "crash_context";
+
+/// Installs a panic hook that writes the entries kept by `recent_entries` to `make_writer`,
+/// marked with a `crash_context: true` label, followed by a CRITICAL entry for the panic itself,
+/// before running the previously installed hook. This gives postmortems the lead-up to a crash,
+/// including DEBUG entries that were sampled away or never written. Only panics are covered: no
+/// signal handlers are installed, so fatal signals end the process without writing the context.
+pub fn install_crash_hook<W>(recent_entries: RecentEntries, make_writer: W)
+where
+    W: for<'writer> MakeWriter<'writer> + Send + Sync + 'static,
+{
+    let previous = panic::take_hook();
+
+    panic::set_hook(Box::new(move |info| {
+        let mut writer = make_writer.make_writer();
+        let _ = write_crash_context(&recent_entries, &mut writer);
+        let _ = write_panic_entry(info, &mut writer);
+        let _ = writer.flush();
+        drop(writer);
+
+        previous(info);
+    }));
+}
+
+/// Writes the entries kept by `recent_entries`, marked with a `crash_context: true` label, e.g.
+/// before exiting on an unrecoverable error that doesn't panic
+pub fn write_crash_context(
+    recent_entries: &RecentEntries,
+    writer: &mut dyn io::Write,
+) -> io::Result<()> {
+    for entry in recent_entries.entries() {
+        let mut entry = match serde_json::from_str::<Value>(&entry) {
+            Ok(Value::Object(entry)) => entry,
+            _ => continue,
+        };
+
+        let labels = entry
+            .entry("logging.googleapis.com/labels")
+            .or_insert_with(|| Value::Object(Map::new()));
+
+        if let Value::Object(labels) = labels {
+            labels.insert(
+                CRASH_CONTEXT_LABEL.to_string(),
+                Value::String("true".to_string()),
+            );
+        }
+
+        entry::write_line(writer, &entry)?;
+    }
+
+    Ok(())
+}
+
+/// Write a CRITICAL entry for a panic, with its backtrace when backtraces are enabled
+fn write_panic_entry(info: &PanicHookInfo, writer: &mut dyn io::Write) -> io::Result<()> {
+    let payload = info
+        .payload()
+        .downcast_ref::<&str>()
+        .copied()
+        .or_else(|| info.payload().downcast_ref::<String>().map(String::as_str))
+        .unwrap_or("Box<dyn Any>");
+
+    let mut message = match info.location() {
+        Some(location) => format!("panicked at {}: {}", location, payload),
+        None => format!("panicked: {}", payload),
+    };
+
+    let backtrace = Backtrace::capture();
+
+    if backtrace.status() == BacktraceStatus::Captured {
+        message = format!("{}\n{}", message, backtrace);
+    }
+
+    let mut entry = LogEntry::report("panic", LogSeverity::Critical, message);
+    entry.source_location = info.location().map(|location| EntrySourceLocation {
+        file: location.file().to_string(),
+        line: Some(location.line().to_string()),
+        function: None,
+    });
+
+    entry::write_report(writer, entry)
+}
@@ -0,0 +1,161 @@
+use serde_json::Value;
+use std::collections::BTreeMap;
+
+/// Targets of the query logs of database crates
+const QUERY_TARGETS: [&str; 2] = ["sqlx::query", "diesel_logger"];
+
+/// Maximum length of the summary of a statement used as the message of its entry
+const SUMMARY_LENGTH: usize = 64;
+
+/// Configuration for reshaping the query logs of `sqlx` (target `sqlx::query`) and
+/// `diesel_logger` into structured `db.statement`, `db.rows`, and `db.elapsed_ms` fields, with a
+/// summary of the statement as the message
+#[derive(Clone, Debug)]
+pub struct QueryLogging {
+    /// Length in bytes beyond which statements are truncated
+    pub max_statement_length: usize,
+    /// Whether string and numeric literals in statements are replaced with `?`
+    pub redact_literals: bool,
+}
+
+impl Default for QueryLogging {
+    fn default() -> Self {
+        Self {
+            max_statement_length: 2_048,
+            redact_literals: true,
+        }
+    }
+}
+
+impl QueryLogging {
+    /// Whether events from a target are query logs
+    pub(crate) fn matches(target: &str) -> bool {
+        QUERY_TARGETS.contains(&target)
+    }
+
+    /// Replace the fields of a query log with structured query fields
+    pub(crate) fn reshape(&self, values: &mut BTreeMap<&str, Value>) {
+        let (statement, rows, elapsed_ms) = match values.remove("db.statement") {
+            // sqlx records the statement, its row counts and elapsed time as separate fields
+            Some(statement) => {
+                let rows = ["rows_returned", "rows_affected"]
+                    .iter()
+                    .filter_map(|key| values.remove(key).and_then(|rows| rows.as_u64()))
+                    .max();
+                let elapsed_ms = values
+                    .remove("elapsed_secs")
+                    .and_then(|elapsed| match elapsed {
+                        // floats are recorded through their Debug representation
+                        Value::String(elapsed) => elapsed.parse().ok(),
+                        elapsed => elapsed.as_f64(),
+                    })
+                    .map(|elapsed| elapsed * 1_000.0);
+
+                values.remove("elapsed");
+                values.remove("summary");
+
+                (statement.as_str().map(str::to_string), rows, elapsed_ms)
+            }
+            // diesel_logger writes "Query ran in 1.2 ms: SELECT ..." messages
+            None => match values
+                .get("message")
+                .and_then(Value::as_str)
+                .and_then(parse_diesel_message)
+            {
+                Some((elapsed_ms, statement)) => (Some(statement), None, Some(elapsed_ms)),
+                None => return,
+            },
+        };
+
+        let Some(statement) = statement else {
+            return;
+        };
+
+        let statement = match self.redact_literals {
+            true => redact(&statement),
+            false => statement,
+        };
+
+        values.insert("message", Value::String(summary(&statement)));
+        values.insert(
+            "db.statement",
+            Value::String(truncate(statement, self.max_statement_length)),
+        );
+
+        if let Some(rows) = rows {
+            values.insert("db.rows", rows.into());
+        }
+
+        if let Some(elapsed_ms) = elapsed_ms {
+            values.insert("db.elapsed_ms", elapsed_ms.into());
+        }
+    }
+}
+
+/// Elapsed milliseconds and statement of a `diesel_logger` message
+fn parse_diesel_message(message: &str) -> Option<(f64, String)> {
+    let (elapsed, statement) = message.strip_prefix("Query ran in ")?.split_once(" ms: ")?;
+
+    Some((elapsed.parse().ok()?, statement.to_string()))
+}
+
+/// Replace the string and numeric literals of a statement with `?`, keeping placeholders such
+/// as `$1` and identifiers such as `t1`
+fn redact(statement: &str) -> String {
+    let mut redacted = String::with_capacity(statement.len());
+    let mut chars = statement.chars().peekable();
+    let mut previous = ' ';
+
+    while let Some(char) = chars.next() {
+        match char {
+            '\'' => {
+                // doubled quotes escape a quote within the literal
+                while let Some(char) = chars.next() {
+                    if char == '\'' && chars.next_if_eq(&'\'').is_none() {
+                        break;
+                    }
+                }
+
+                redacted.push('?');
+            }
+            '0'..='9' if !(previous.is_alphanumeric() || matches!(previous, '_' | '$')) => {
+                while chars
+                    .next_if(|char| char.is_ascii_digit() || *char == '.')
+                    .is_some()
+                {}
+                redacted.push('?');
+            }
+            char => redacted.push(char),
+        }
+
+        previous = char;
+    }
+
+    redacted
+}
+
+/// Collapse a statement's whitespace into a short summary
+fn summary(statement: &str) -> String {
+    let summary = statement.split_whitespace().collect::<Vec<_>>().join(" ");
+
+    match summary.char_indices().nth(SUMMARY_LENGTH) {
+        Some((index, _)) => format!("{}…", &summary[..index]),
+        None => summary,
+    }
+}
+
+/// Truncate a statement to at most `max_length` bytes, on a character boundary
+fn truncate(mut statement: String, max_length: usize) -> String {
+    if statement.len() > max_length {
+        let mut length = max_length;
+
+        while !statement.is_char_boundary(length) {
+            length -= 1;
+        }
+
+        statement.truncate(length);
+        statement.push('…');
+    }
+
+    statement
+}
@@ -0,0 +1,18 @@
+use crate::entry::LogEntry;
+
+/// Hook receiving every entry once it's assembled, right before it's written, for logic across
+/// its fields in one place, e.g. deriving a label from its target and severity. Closures taking a
+/// mutable [`LogEntry`] are decorators.
+pub trait EntryDecorator: Send + Sync {
+    /// Mutates an entry before it's written
+    fn decorate(&self, entry: &mut LogEntry);
+}
+
+impl<F> EntryDecorator for F
+where
+    F: Fn(&mut LogEntry) + Send + Sync,
+{
+    fn decorate(&self, entry: &mut LogEntry) {
+        self(entry)
+    }
+}
@@ -0,0 +1,238 @@
+use crate::{
+    event_formatter::{Error, EventFormatter},
+    google::{CloudTraceConfiguration, LogSeverity},
+    raw::emit_raw_entry,
+    trace::{TraceContext, WithTraceContext},
+};
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+use std::{
+    collections::{BTreeMap, VecDeque},
+    io,
+    sync::Mutex,
+    time::SystemTime,
+};
+use time::{format_description::well_known::Rfc3339, OffsetDateTime};
+use tracing_core::Metadata;
+use tracing_subscriber::field::RecordFields;
+
+/// Maximum number of entries emitted before any layer exists that are kept until one does
+const MAX_PENDING_ENTRIES: usize = 1_024;
+
+/// Entries emitted before any layer existed, oldest first
+static PENDING: Mutex<VecDeque<LogEntry>> = Mutex::new(VecDeque::new());
+
+/// A structured log entry as written by the layers of this crate, with the special fields that
+/// Cloud Logging interprets and any other fields of its `jsonPayload`. Entries deserialize from
+/// the output of the layers, for typed assertions on it.
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct LogEntry {
+    /// Time of the entry in RFC 3339 format, which defaults to the time it's emitted
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub time: Option<String>,
+    /// Severity of the entry
+    #[serde(default)]
+    pub severity: LogSeverity,
+    /// Message of the entry
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub message: Option<String>,
+    /// Fields of the HTTP request the entry is about, e.g. `requestMethod` and `status`
+    #[serde(rename = "httpRequest", skip_serializing_if = "Option::is_none")]
+    pub http_request: Option<Map<String, Value>>,
+    /// Unique ID of the entry, which Cloud Logging deduplicates entries by
+    #[serde(
+        rename = "logging.googleapis.com/insertId",
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub insert_id: Option<String>,
+    /// Labels of the entry
+    #[serde(
+        rename = "logging.googleapis.com/labels",
+        default,
+        skip_serializing_if = "BTreeMap::is_empty"
+    )]
+    pub labels: BTreeMap<String, String>,
+    /// Operation that the entry is part of
+    #[serde(
+        rename = "logging.googleapis.com/operation",
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub operation: Option<EntryOperation>,
+    /// Location in the source code that the entry was written from
+    #[serde(
+        rename = "logging.googleapis.com/sourceLocation",
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub source_location: Option<EntrySourceLocation>,
+    /// Resource name of the trace the entry is correlated with, e.g.
+    /// `projects/my-project/traces/4bf92f3577b34da6a3ce929d0e0e4736`
+    #[serde(
+        rename = "logging.googleapis.com/trace",
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub trace: Option<String>,
+    /// ID of the span within the trace
+    #[serde(
+        rename = "logging.googleapis.com/spanId",
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub span_id: Option<String>,
+    /// Whether the trace is sampled
+    #[serde(
+        rename = "logging.googleapis.com/trace_sampled",
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub trace_sampled: Option<bool>,
+    /// Every other field of the entry
+    #[serde(flatten)]
+    pub fields: Map<String, Value>,
+}
+
+impl LogEntry {
+    /// An entry reported by this crate itself from `target`, e.g. a count of dropped entries
+    pub(crate) fn report(target: &str, severity: LogSeverity, message: impl Into<String>) -> Self {
+        Self {
+            severity,
+            message: Some(message.into()),
+            fields: Map::from_iter([("target".to_string(), Value::from(target))]),
+            ..Self::default()
+        }
+    }
+
+    /// The entry with another field of its `jsonPayload`
+    pub(crate) fn with_field(mut self, key: &str, value: impl Serialize) -> Self {
+        let value = serde_json::to_value(value).unwrap_or_default();
+        self.fields.insert(key.to_string(), value);
+        self
+    }
+
+    /// Set the time of the entry to the current time, unless it already has one
+    pub(crate) fn stamp(&mut self) {
+        if self.time.is_none() {
+            self.time = OffsetDateTime::now_utc().format(&Rfc3339).ok();
+        }
+    }
+}
+
+/// Operation that a [`LogEntry`] is part of
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct EntryOperation {
+    /// ID of the operation, shared by all of its entries
+    #[serde(default)]
+    pub id: String,
+    /// Name of the producer of the operation, e.g. the root span of a request
+    #[serde(default)]
+    pub producer: String,
+    /// Whether the entry is the first of the operation
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub first: Option<bool>,
+    /// Whether the entry is the last of the operation
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub last: Option<bool>,
+}
+
+/// Location in the source code that a [`LogEntry`] was written from
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct EntrySourceLocation {
+    /// Path of the source file
+    pub file: String,
+    /// Line within the source file, written as a string as Cloud Logging expects
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub line: Option<String>,
+    /// Name of the function that wrote the entry
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub function: Option<String>,
+}
+
+/// Writes an entry outside of any tracing event, e.g. one translated from another logging
+/// system. Entries are written through the same writer or exporter as the
+/// [`Layer`](crate::Layer) of the current subscriber, like
+/// [`emit_raw_entry`](crate::emit_raw_entry). Entries emitted before such a subscriber exists,
+/// e.g. during early startup, are kept until the next Layer is added to a subscriber, up to the
+/// last 1024 entries, and written with its labels then, at the time they were emitted.
+pub fn emit(mut entry: LogEntry) {
+    entry.stamp();
+
+    let has_layer = tracing::dispatcher::get_default(|dispatch| {
+        dispatch.downcast_ref::<WithTraceContext>().is_some()
+    });
+
+    if has_layer {
+        if let Ok(entry) = serde_json::to_value(&entry) {
+            emit_raw_entry(&entry);
+        }
+
+        return;
+    }
+
+    let mut pending = match PENDING.lock() {
+        Ok(pending) => pending,
+        Err(poisoned) => poisoned.into_inner(),
+    };
+
+    if pending.len() >= MAX_PENDING_ENTRIES {
+        pending.pop_front();
+    }
+
+    pending.push_back(entry);
+}
+
+/// Write an entry as a line of JSON in a single write, so that writers which flush or fail write
+/// by write never separate an entry from its newline
+pub(crate) fn write_line(writer: &mut dyn io::Write, entry: &impl Serialize) -> io::Result<()> {
+    let mut line = serde_json::to_vec(entry)?;
+    line.push(b'\n');
+    writer.write_all(&line)
+}
+
+/// Write an entry reported by this crate itself outside of any layer, e.g. from a background
+/// thread, stamped with the current time unless it has one
+pub(crate) fn write_report(writer: &mut dyn io::Write, mut entry: LogEntry) -> io::Result<()> {
+    entry.stamp();
+    write_line(writer, &entry)
+}
+
+/// Write the entries emitted before any layer existed with the labels of the formatter, keeping
+/// the time they were emitted at, each in a single write
+pub(crate) fn write_pending(
+    event_formatter: &EventFormatter,
+    writer: &mut dyn io::Write,
+) -> Result<(), Error> {
+    let pending = match PENDING.lock() {
+        Ok(mut pending) => std::mem::take(&mut *pending),
+        Err(poisoned) => std::mem::take(&mut *poisoned.into_inner()),
+    };
+
+    for entry in pending {
+        event_formatter.write_report(writer, entry)?;
+    }
+
+    Ok(writer.flush()?)
+}
+
+/// Context of an entry formatted with [`format_entry`], standing in for the spans that a
+/// subscriber would otherwise look up
+#[derive(Clone, Debug, Default)]
+pub struct EntryContext {
+    /// Time of the entry, which defaults to the current time
+    pub time: Option<SystemTime>,
+    /// Name and fields of the span that the entry was recorded within, written as its `span`
+    pub span: Option<(String, Map<String, Value>)>,
+    /// Trace that the entry is correlated with, in the given project
+    pub trace: Option<(CloudTraceConfiguration, TraceContext)>,
+    /// Labels of the entry, which its own `labels.*` fields take precedence over
+    pub labels: BTreeMap<String, String>,
+}
+
+/// Formats the fields of an event or span as a Stackdriver entry, exactly as a default
+/// [`Layer`](crate::Layer) would, for custom subscribers and test harnesses that don't go through
+/// the `tracing_subscriber::fmt` machinery, e.g. `format_entry(event.metadata(), event, &context)`.
+/// [`Layer::format_entry`](crate::Layer::format_entry) formats entries with the options of a
+/// configured Layer instead.
+pub fn format_entry(
+    metadata: &Metadata<'_>,
+    fields: &impl RecordFields,
+    context: &EntryContext,
+) -> Value {
+    EventFormatter::default().format_entry(metadata, fields, context)
+}
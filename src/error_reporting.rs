@@ -0,0 +1,391 @@
+use crate::{
+    auth::{TokenCache, CLOUD_PLATFORM_SCOPE},
+    client::{ApiClientBuilder, ClientConfig},
+    exporter::{Diagnostics, ExportFailure},
+    google::LogSeverity,
+    line_buffer::LineBuffer,
+};
+use serde_json::{Map, Value};
+use std::{
+    fmt, io, mem,
+    time::{Duration, Instant},
+};
+
+/// Error Reporting API endpoint for reporting error events, relative to a project
+const DEFAULT_ENDPOINT: &str = "https://clouderrorreporting.googleapis.com/v1beta1";
+
+/// Default maximum number of error events queued before they're sent
+const DEFAULT_MAX_BATCH_EVENTS: usize = 100;
+
+/// Default maximum number of error events reported per minute
+const DEFAULT_MAX_EVENTS_PER_MINUTE: u32 = 60;
+
+/// Interval over which the number of reported error events is limited
+const RATE_LIMIT_INTERVAL: Duration = Duration::from_secs(60);
+
+/// Builder for configuring an [`ErrorReporter`]. Authorization, request timeouts, and the
+/// diagnostics hook, which is notified of error events whose request failed or that were beyond
+/// the rate limit, are configured with the methods of [`ApiClientBuilder`].
+pub struct ErrorReporterBuilder {
+    project_id: String,
+    service: String,
+    version: Option<String>,
+    endpoint: String,
+    client: ClientConfig,
+    max_batch_events: usize,
+    max_events_per_minute: u32,
+}
+
+impl ErrorReporterBuilder {
+    /// Create a builder for a reporter of the errors of a service in the given project
+    pub fn new(project_id: impl Into<String>, service: impl Into<String>) -> Self {
+        Self {
+            project_id: project_id.into(),
+            service: service.into(),
+            version: None,
+            endpoint: DEFAULT_ENDPOINT.to_string(),
+            client: ClientConfig::new(CLOUD_PLATFORM_SCOPE),
+            max_batch_events: DEFAULT_MAX_BATCH_EVENTS,
+            max_events_per_minute: DEFAULT_MAX_EVENTS_PER_MINUTE,
+        }
+    }
+
+    /// Sets the version of the service that errors are reported for, e.g. a release or revision
+    pub fn with_version(self, version: impl Into<String>) -> Self {
+        Self {
+            version: Some(version.into()),
+            ..self
+        }
+    }
+
+    /// Sets the base URL of the Error Reporting API, e.g. for a test server
+    pub fn with_endpoint(self, endpoint: impl Into<String>) -> Self {
+        Self {
+            endpoint: endpoint.into(),
+            ..self
+        }
+    }
+
+    /// Sets the maximum number of error events queued before they're sent
+    pub fn with_max_batch_events(self, max_batch_events: usize) -> Self {
+        Self {
+            max_batch_events: max_batch_events.max(1),
+            ..self
+        }
+    }
+
+    /// Sets the maximum number of error events reported per minute, beyond which events are
+    /// dropped until the next minute
+    pub fn with_max_events_per_minute(self, max_events_per_minute: u32) -> Self {
+        Self {
+            max_events_per_minute,
+            ..self
+        }
+    }
+
+    /// Build the configured reporter
+    pub fn build(self) -> ErrorReporter {
+        let agent = self.client.agent();
+
+        let mut service_context = Map::new();
+        service_context.insert("service".to_string(), Value::String(self.service));
+
+        if let Some(version) = self.version {
+            service_context.insert("version".to_string(), Value::String(version));
+        }
+
+        ErrorReporter {
+            url: format!(
+                "{}/projects/{}/events:report",
+                self.endpoint.trim_end_matches('/'),
+                self.project_id
+            ),
+            project_id: self.project_id,
+            service_context: Value::Object(service_context),
+            tokens: TokenCache::new(self.client.token_provider),
+            max_batch_events: self.max_batch_events,
+            max_events_per_minute: self.max_events_per_minute,
+            window: (Instant::now(), 0),
+            dropped: Vec::new(),
+            diagnostics: self.client.diagnostics,
+            agent,
+            lines: LineBuffer::default(),
+            batch: Vec::new(),
+        }
+    }
+}
+
+impl ApiClientBuilder for ErrorReporterBuilder {
+    fn client_config(&mut self) -> &mut ClientConfig {
+        &mut self.client
+    }
+}
+
+impl fmt::Debug for ErrorReporterBuilder {
+    fn fmt(&self, formatter: &mut fmt::Formatter<'_>) -> fmt::Result {
+        formatter
+            .debug_struct("ErrorReporterBuilder")
+            .field("project_id", &self.project_id)
+            .field("service", &self.service)
+            .field("version", &self.version)
+            .field("endpoint", &self.endpoint)
+            .field("max_batch_events", &self.max_batch_events)
+            .field("max_events_per_minute", &self.max_events_per_minute)
+            .field("client", &self.client)
+            .finish_non_exhaustive()
+    }
+}
+
+/// Writer that sends ERROR and more severe entries directly to the Error Reporting API's
+/// [`report`](https://cloud.google.com/error-reporting/reference/rest/v1beta1/projects.events/report)
+/// method, for setups where log-based error ingestion is disabled or too slow. Entries are
+/// reported with the `stack_trace` field of their event appended to their message, and with their source
+/// location as the location of the error. Every other entry is ignored.
+pub struct ErrorReporter {
+    project_id: String,
+    url: String,
+    service_context: Value,
+    tokens: TokenCache,
+    max_batch_events: usize,
+    max_events_per_minute: u32,
+    window: (Instant, u32),
+    dropped: Vec<Value>,
+    diagnostics: Diagnostics,
+    agent: ureq::Agent,
+    lines: LineBuffer,
+    batch: Vec<Value>,
+}
+
+impl ErrorReporter {
+    /// Create a builder for a reporter of the errors of a service in the given project
+    pub fn builder(
+        project_id: impl Into<String>,
+        service: impl Into<String>,
+    ) -> ErrorReporterBuilder {
+        ErrorReporterBuilder::new(project_id, service)
+    }
+
+    /// Queue the error event of a single formatted entry, sending the batch once it's full
+    fn push_line(&mut self, line: &[u8]) {
+        let Ok(Value::Object(entry)) = serde_json::from_slice::<Value>(line) else {
+            return;
+        };
+
+        let Some(event) = self.error_event(entry) else {
+            return;
+        };
+
+        if !self.within_rate_limit() {
+            self.dropped.push(event);
+
+            if self.dropped.len() >= self.max_batch_events {
+                self.report_dropped();
+            }
+
+            return;
+        }
+
+        self.batch.push(event);
+
+        if self.batch.len() >= self.max_batch_events {
+            self.send_batch();
+        }
+    }
+
+    /// Whether another event can be reported within the current minute
+    fn within_rate_limit(&mut self) -> bool {
+        let (since, count) = &mut self.window;
+
+        if since.elapsed() >= RATE_LIMIT_INTERVAL {
+            *since = Instant::now();
+            *count = 0;
+        }
+
+        *count += 1;
+        *count <= self.max_events_per_minute
+    }
+
+    /// The `ReportedErrorEvent` of an entry, if it's an error with a stack trace or location
+    fn error_event(&self, mut entry: Map<String, Value>) -> Option<Value> {
+        let severity = entry.get("severity").cloned().map(LogSeverity::from)?;
+
+        if severity < LogSeverity::Error {
+            return None;
+        }
+
+        let stack_trace = entry
+            .remove("stackTrace")
+            .and_then(|stack_trace| stack_trace.as_str().map(str::to_string));
+        let report_location = entry
+            .get("logging.googleapis.com/sourceLocation")
+            .map(|location| report_location(location, entry.get("target")));
+
+        // events are only accepted with either a stack trace or a report location
+        if stack_trace.is_none() && report_location.is_none() {
+            return None;
+        }
+
+        let message = entry
+            .get("message")
+            .map(|message| match message {
+                Value::String(message) => message.clone(),
+                message => message.to_string(),
+            })
+            .unwrap_or_default();
+
+        let message = match stack_trace {
+            Some(stack_trace) => format!("{}\n{}", message, stack_trace),
+            None => message,
+        };
+
+        let mut context = Map::new();
+
+        if let Some(Value::Object(http_request)) = entry.get("httpRequest") {
+            context.insert(
+                "httpRequest".to_string(),
+                http_request_context(http_request),
+            );
+        }
+
+        if let Some(report_location) = report_location {
+            context.insert("reportLocation".to_string(), report_location);
+        }
+
+        let mut event = serde_json::json!({
+            "serviceContext": self.service_context,
+            "message": message,
+            "context": context,
+        });
+
+        if let Some(time) = entry.get("time") {
+            event["eventTime"] = time.clone();
+        }
+
+        Some(event)
+    }
+
+    /// Send every queued event, one request each, since the API doesn't batch events
+    fn send_batch(&mut self) {
+        let events = mem::take(&mut self.batch);
+        let mut failed = Vec::new();
+        let mut message = None;
+
+        for event in events {
+            if let Err(error) = self.send_request(&event) {
+                failed.push(event);
+                message = Some(error);
+            }
+        }
+
+        if let Some(message) = message {
+            (self.diagnostics)(&ExportFailure {
+                entries: failed,
+                code: None,
+                message,
+            });
+        }
+
+        if !self.dropped.is_empty() {
+            self.report_dropped();
+        }
+    }
+
+    /// Notify the diagnostics hook of the error events dropped beyond the rate limit
+    fn report_dropped(&mut self) {
+        (self.diagnostics)(&ExportFailure {
+            entries: mem::take(&mut self.dropped),
+            code: None,
+            message: "error events beyond the rate limit were dropped".to_string(),
+        });
+    }
+
+    /// Send an event in a single `report` request
+    fn send_request(&mut self, event: &Value) -> Result<(), String> {
+        let token = self.tokens.token().map_err(|error| error.to_string())?;
+
+        self.agent
+            .post(&self.url)
+            .set("Content-Type", "application/json")
+            .set("Authorization", &format!("Bearer {}", token))
+            .send_json(event)
+            .map(|_| ())
+            .map_err(|error| match error {
+                ureq::Error::Status(status, _) => {
+                    format!("report failed with HTTP status {}", status)
+                }
+                error => error.to_string(),
+            })
+    }
+}
+
+/// The `SourceLocation` of an error from the source location of its entry
+fn report_location(location: &Value, target: Option<&Value>) -> Value {
+    let mut report_location = Map::new();
+
+    if let Some(file) = location.get("file") {
+        report_location.insert("filePath".to_string(), file.clone());
+    }
+
+    let line = match location.get("line") {
+        Some(Value::String(line)) => line.parse().ok(),
+        Some(Value::Number(line)) => line.as_i64(),
+        _ => None,
+    };
+
+    report_location.insert("lineNumber".to_string(), line.unwrap_or_default().into());
+
+    let function = location
+        .get("function")
+        .or(target)
+        .cloned()
+        .unwrap_or_else(|| Value::String(String::new()));
+
+    report_location.insert("functionName".to_string(), function);
+    Value::Object(report_location)
+}
+
+/// The `HttpRequestContext` of an error from the `httpRequest` of its entry
+fn http_request_context(http_request: &Map<String, Value>) -> Value {
+    let mut context = Map::new();
+
+    for (key, context_key) in [
+        ("requestMethod", "method"),
+        ("requestUrl", "url"),
+        ("userAgent", "userAgent"),
+        ("referer", "referrer"),
+        ("status", "responseStatusCode"),
+        ("remoteIp", "remoteIp"),
+    ] {
+        if let Some(value) = http_request.get(key) {
+            context.insert(context_key.to_string(), value.clone());
+        }
+    }
+
+    Value::Object(context)
+}
+
+impl io::Write for ErrorReporter {
+    fn write(&mut self, buffer: &[u8]) -> io::Result<usize> {
+        for line in self.lines.push(buffer) {
+            self.push_line(&line);
+        }
+
+        Ok(buffer.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.send_batch();
+        Ok(())
+    }
+}
+
+impl fmt::Debug for ErrorReporter {
+    fn fmt(&self, formatter: &mut fmt::Formatter<'_>) -> fmt::Result {
+        formatter
+            .debug_struct("ErrorReporter")
+            .field("project_id", &self.project_id)
+            .field("url", &self.url)
+            .field("queued", &self.batch.len())
+            .finish()
+    }
+}
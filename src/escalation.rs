@@ -0,0 +1,37 @@
+use crate::google::LogSeverity;
+use serde_json::Value;
+use std::collections::BTreeMap;
+
+/// Rule raising the severity of events whose numeric field reaches a threshold, such as
+/// `status >= 500` or `retries >= 5`
+#[derive(Clone, Debug)]
+pub struct SeverityEscalation {
+    /// Name of the event field, e.g. `retries` or `http_request.status`
+    pub field: String,
+    /// Minimum value of the field that triggers the rule
+    pub threshold: f64,
+    /// Severity that events triggering the rule are raised to
+    pub severity: LogSeverity,
+}
+
+impl SeverityEscalation {
+    /// Create a rule raising events to `severity` when `field >= threshold`
+    pub fn new(field: impl Into<String>, threshold: impl Into<f64>, severity: LogSeverity) -> Self {
+        Self {
+            field: field.into(),
+            threshold: threshold.into(),
+            severity,
+        }
+    }
+
+    /// Whether an event's values trigger the rule, reading numbers written as strings as well
+    pub(crate) fn matches(&self, values: &BTreeMap<&str, Value>) -> bool {
+        let value = match values.get(self.field.as_str()) {
+            Some(Value::Number(number)) => number.as_f64(),
+            Some(Value::String(string)) => string.parse().ok(),
+            _ => None,
+        };
+
+        value.is_some_and(|value| value >= self.threshold)
+    }
+}
@@ -1,24 +1,54 @@
 use crate::{
-    google::LogSeverity,
+    audit::AuditTrail,
+    callsite,
+    canonical::CanonicalFields,
+    clock,
+    cloud_functions::ExecutionId,
+    console,
+    counts::EntryCounts,
+    database::QueryLogging,
+    entry::{self, EntryContext, LogEntry},
+    escalation::SeverityEscalation,
+    fields::{DebugLimits, DottedKeys, DurationFormat, ErrorFormat},
+    google::{CloudTraceConfiguration, LogSeverity},
+    http_request::HttpRequest,
+    inherited::InheritedFields,
+    instrument::{InstrumentConfiguration, InstrumentField},
+    layer::BuildError,
+    line_limit::LineLimit,
+    processor::{self, EntryProcessor},
+    raw::{RawEntry, RAW_ENTRY_TARGET},
+    recent::RecentEntries,
     serializers::{SerializableSpan, SourceLocation},
-    visitor::Visitor,
-    writer::WriteAdaptor,
+    span_events,
+    stack_trace::StackTraces,
+    strict::{self, StrictFields},
+    summary::{ErrorSummary, EventCounts},
+    tail,
+    timestamp::Timestamp,
+    timings::Timings,
+    trace::{SpanIdEncoding, TraceFields, TraceResolution},
+    transform::FieldTransformer,
+    visitor::{Visitor, DEFAULT_RESERVED_KEY_PREFIX},
+    writer::with_entry_buffer,
 };
 use serde::ser::{SerializeMap, Serializer as _};
-use std::fmt;
+use serde_json::Value;
+use std::{collections::BTreeMap, fmt, io, time::Duration};
 use time::{format_description::well_known::Rfc3339, OffsetDateTime};
-use tracing_core::{Event, Subscriber};
+use tracing_core::{Event, Metadata, Subscriber};
 use tracing_subscriber::{
-    field::VisitOutput,
+    field::{RecordFields, VisitOutput},
     fmt::{
-        format::{self, JsonFields},
-        FmtContext, FormatEvent,
+        format::{self, Writer},
+        time::FormatTime,
+        FmtContext, FormatEvent, FormatFields,
     },
-    registry::LookupSpan,
+    registry::{LookupSpan, SpanRef},
 };
 
 #[derive(Debug, thiserror::Error)]
-enum Error {
+pub(crate) enum Error {
     #[error(transparent)]
     Formatting(#[from] fmt::Error),
     #[error("JSON serialization error: {0}")]
@@ -35,38 +65,445 @@ impl From<Error> for fmt::Error {
     }
 }
 
-/// Tracing Event formatter for Stackdriver layers
+/// Tracing Event formatter for Stackdriver layers, which also formats events for
+/// `tracing_subscriber::fmt` layers with custom field formatters, e.g. through
+/// `fmt::layer().fmt_fields(fields).event_format(EventFormatter::default())`. Span fields
+/// formatted as JSON or as `key=value` pairs are written to the `span` of entries.
 pub struct EventFormatter {
     pub(crate) include_source_location: bool,
+    pub(crate) cloud_trace: Option<CloudTraceConfiguration>,
+    pub(crate) span_id_encoding: SpanIdEncoding,
+    pub(crate) trace_resolution: TraceResolution,
+    pub(crate) sampled_traces_only: bool,
+    pub(crate) labels: BTreeMap<String, String>,
+    pub(crate) legacy_agent_fields: bool,
+    pub(crate) max_value_length: Option<usize>,
+    pub(crate) span_operations: bool,
+    pub(crate) instrument: Option<InstrumentConfiguration>,
+    pub(crate) severity_caps: BTreeMap<String, LogSeverity>,
+    pub(crate) severity_escalations: Vec<SeverityEscalation>,
+    pub(crate) stack_traces: Option<StackTraces>,
+    pub(crate) stack_trace_detection: bool,
+    pub(crate) entry_counts: Option<EntryCounts>,
+    pub(crate) strict: bool,
+    pub(crate) logfmt_messages: bool,
+    pub(crate) message_templates: bool,
+    pub(crate) callsite_fingerprints: bool,
+    pub(crate) metric_fields: bool,
+    pub(crate) duration_format: DurationFormat,
+    pub(crate) debug_limits: Option<DebugLimits>,
+    pub(crate) include_span_ids: bool,
+    pub(crate) field_namespace: Option<String>,
+    pub(crate) dotted_keys: DottedKeys,
+    pub(crate) error_format: ErrorFormat,
+    pub(crate) reserved_key_prefix: String,
+    pub(crate) console_url_window: Option<Duration>,
+    pub(crate) monotonic_time: bool,
+    pub(crate) timer: Option<Box<dyn FormatTime + Send + Sync>>,
+    pub(crate) timestamp_overrides: bool,
+    pub(crate) raw_entries: bool,
+    pub(crate) line_limit: Option<LineLimit>,
+    pub(crate) recent_entries: Option<RecentEntries>,
+    pub(crate) query_logging: Option<QueryLogging>,
+    pub(crate) audit_trail: Option<AuditTrail>,
+    pub(crate) field_transformer: Option<Box<dyn FieldTransformer>>,
+    pub(crate) processors: Vec<Box<dyn EntryProcessor>>,
+    #[cfg(feature = "audit-chain")]
+    pub(crate) audit_chain: Option<crate::audit::HashChain>,
+    #[cfg(feature = "scrubbing")]
+    pub(crate) scrubbing: Option<crate::scrubbing::ValueScrubbing>,
 }
 
 impl EventFormatter {
+    /// Validate the configuration of the formatter
+    pub(crate) fn validate(&self) -> Result<(), BuildError> {
+        if let Some(cloud_trace) = &self.cloud_trace {
+            if !is_project_id(&cloud_trace.project_id) {
+                return Err(BuildError::ProjectId(cloud_trace.project_id.clone()));
+            }
+        }
+
+        if self.timer.is_some() && self.legacy_agent_fields {
+            return Err(BuildError::Conflict(
+                "with_timer",
+                "with_legacy_agent_fields",
+            ));
+        }
+
+        if self.timer.is_some() && self.monotonic_time {
+            return Err(BuildError::Conflict(
+                "with_timer",
+                "with_monotonic_timestamps",
+            ));
+        }
+
+        if self.strict && self.raw_entries {
+            return Err(BuildError::StrictRawEntries);
+        }
+
+        if self.console_url_window.is_some() && self.cloud_trace.is_none() {
+            return Err(BuildError::Requires(
+                "with_console_urls",
+                "with_cloud_trace",
+            ));
+        }
+
+        #[cfg(feature = "audit-chain")]
+        if self.audit_chain.is_some() && self.audit_trail.is_none() {
+            return Err(BuildError::Requires(
+                "with_audit_hash_chain",
+                "with_audit_log",
+            ));
+        }
+
+        if self.field_namespace.as_deref() == Some("") {
+            return Err(BuildError::EmptyFieldNamespace);
+        }
+
+        if self.reserved_key_prefix.is_empty() {
+            return Err(BuildError::EmptyReservedKeyPrefix);
+        }
+
+        Ok(())
+    }
+
+    /// The maximum severity of events from a target, as capped by its most specific rule
+    fn severity_cap(&self, target: &str) -> Option<LogSeverity> {
+        self.severity_caps
+            .iter()
+            .filter(|(prefix, _)| {
+                target == prefix.as_str()
+                    || target
+                        .strip_prefix(prefix.as_str())
+                        .is_some_and(|rest| prefix.ends_with("::") || rest.starts_with("::"))
+            })
+            .max_by_key(|(prefix, _)| prefix.len())
+            .map(|(_, severity)| *severity)
+    }
+
+    /// The `time` of an entry, as written by the configured timer unless its event overrides it
+    fn format_time(&self, now: OffsetDateTime, overridden: bool) -> Result<String, Error> {
+        match self.timer.as_ref().filter(|_| !overridden) {
+            Some(timer) => {
+                let mut time = String::new();
+                timer.format_time(&mut Writer::new(&mut time))?;
+                Ok(time)
+            }
+            None => Ok(now.format(&Rfc3339)?),
+        }
+    }
+
+    /// Whether entries are formatted into a buffer before they're written, to be validated or
+    /// transformed by what the formatter is configured with
+    fn is_buffered(&self) -> bool {
+        #[cfg(feature = "audit-chain")]
+        if self.audit_chain.is_some() {
+            return true;
+        }
+
+        #[cfg(feature = "scrubbing")]
+        if self.scrubbing.is_some() {
+            return true;
+        }
+
+        self.strict
+            || !self.processors.is_empty()
+            || self.line_limit.is_some()
+            || self.recent_entries.is_some()
+            || tail::is_capturing()
+    }
+
+    /// The visitor formatting the fields of an event with the configured options
+    pub(crate) fn visitor<'a, M>(
+        &'a self,
+        meta: &Metadata<'a>,
+        map: M,
+        labels: BTreeMap<String, String>,
+        console_url: Option<String>,
+        timestamp_override: bool,
+    ) -> Visitor<'a, M>
+    where
+        M: SerializeMap,
+    {
+        let visitor = Visitor::new(LogSeverity::from(meta.level()), map)
+            .with_labels(labels)
+            .with_max_value_length(self.max_value_length)
+            .with_escalations(&self.severity_escalations)
+            .with_stack_traces(self.stack_traces.as_ref())
+            .with_stack_trace_detection(self.stack_trace_detection)
+            .with_duration_format(self.duration_format)
+            .with_debug_limits(self.debug_limits)
+            .with_field_namespace(self.field_namespace.as_deref())
+            .with_dotted_keys(self.dotted_keys)
+            .with_logfmt_messages(self.logfmt_messages)
+            .with_message_templates(self.message_templates)
+            .with_metric_fields(self.metric_fields)
+            .with_error_format(self.error_format)
+            .with_reserved_key_prefix(&self.reserved_key_prefix)
+            .with_console_url(console_url)
+            .with_timestamp_override(timestamp_override)
+            .with_query_logging(
+                self.query_logging
+                    .as_ref()
+                    .filter(|_| QueryLogging::matches(meta.target())),
+            )
+            .with_audit_trail(
+                self.audit_trail
+                    .as_ref()
+                    .map(|audit_trail| (audit_trail, meta.target())),
+            )
+            .with_field_transformer(self.field_transformer.as_deref())
+            .with_entry_counts(
+                self.entry_counts
+                    .as_ref()
+                    .map(|entry_counts| (entry_counts, meta.target())),
+            )
+            .with_max_severity(self.severity_cap(meta.target()))
+            .with_instrument(self.instrument.clone().and_then(|configuration| {
+                InstrumentField::from_metadata(meta).map(|field| (field, configuration))
+            }));
+
+        #[cfg(feature = "scrubbing")]
+        let visitor = visitor.with_scrubbing(self.scrubbing.as_ref());
+
+        visitor
+    }
+
+    /// Format an event within `span`, its explicit parent or the current span, and write its
+    /// entry to `writer` at once, along with its newline
+    pub(crate) fn write_event<S, N>(
+        &self,
+        span: Option<SpanRef<'_, S>>,
+        writer: &mut impl io::Write,
+        event: &Event,
+    ) -> Result<(), Error>
+    where
+        S: Subscriber + for<'span> LookupSpan<'span>,
+        N: for<'writer> FormatFields<'writer> + 'static,
+    {
+        if self.is_buffered() {
+            return self
+                .format_buffered_event::<S, N>(span, event)?
+                .into_iter()
+                .try_for_each(|line| self.write_line(writer, line))
+                .map_err(Error::from);
+        }
+
+        with_entry_buffer(|buffer| {
+            self.format_event::<S, N, _>(span, serde_json::Serializer::new(&mut *buffer), event)?;
+            buffer.push(b'\n');
+            writer.write_all(buffer).map_err(Error::from)
+        })
+    }
+
+    /// Write a formatted line along with its newline, linking it into the audit chain if there's
+    /// one, once it's neither split by the line limit nor captured by tail buffering
+    pub(crate) fn write_line(
+        &self,
+        writer: &mut dyn io::Write,
+        mut line: String,
+    ) -> io::Result<()> {
+        #[cfg(feature = "audit-chain")]
+        if let Some(audit_chain) = &self.audit_chain {
+            return audit_chain.write(writer, line);
+        }
+
+        line.push('\n');
+        writer.write_all(line.as_bytes())
+    }
+
+    /// Length reserved on each line for the hashes linking it into the audit chain, if there's one
+    fn link_length(&self) -> usize {
+        #[cfg(feature = "audit-chain")]
+        if self.audit_chain.is_some() {
+            return crate::audit::LINK_LENGTH;
+        }
+
+        0
+    }
+
     /// Internal event formatting for a given serializer
-    fn format_event<S>(
+    fn format_event<S, N, W>(
         &self,
-        context: &FmtContext<S, JsonFields>,
-        mut serializer: serde_json::Serializer<WriteAdaptor>,
+        span: Option<SpanRef<'_, S>>,
+        mut serializer: serde_json::Serializer<W>,
         event: &Event,
     ) -> Result<(), Error>
     where
         S: Subscriber + for<'span> LookupSpan<'span>,
+        N: for<'writer> FormatFields<'writer> + 'static,
+        W: io::Write,
     {
-        let time = OffsetDateTime::now_utc().format(&Rfc3339)?;
-        let meta = event.metadata();
-        let severity = LogSeverity::from(meta.level());
+        if self.raw_entries && event.metadata().target() == RAW_ENTRY_TARGET {
+            return self.format_raw_entry(span, serializer, event);
+        }
 
-        let span = event
-            .parent()
-            .and_then(|id| context.span(id))
-            .or_else(|| context.lookup_current());
+        let timestamp = self
+            .timestamp_overrides
+            .then(|| Timestamp::from_event(event))
+            .flatten();
+
+        let now = match timestamp {
+            Some(timestamp) => timestamp,
+            None if self.monotonic_time => clock::monotonic_now(),
+            None => OffsetDateTime::now_utc(),
+        };
+        let meta = event.metadata();
 
         // FIXME: derive an accurate entry count ahead of time
         let mut map = serializer.serialize_map(None)?;
 
         // serialize custom fields
-        map.serialize_entry("time", &time)?;
+        self.write_common_fields(&mut map, meta, now, timestamp.is_some())?;
+
+        // serialize the current span and its leaves
+        if let Some(span) = &span {
+            map.serialize_entry(
+                "span",
+                &SerializableSpan::<_, N>::new(span).with_ids(self.include_span_ids),
+            )?;
+            // map.serialize_entry("spans", &SerializableContext::new(context))?; TODO: remove
+        }
+
+        // time spent within and outside of a span, and a summary of its events, on its close entry
+        let is_close_entry = meta.is_span() && !span_events::is_lifecycle_event(event);
+
+        if is_close_entry {
+            if let Some(timings) = span
+                .as_ref()
+                .and_then(|span| span.extensions().get::<Timings>().cloned())
+            {
+                map.serialize_entry("busyNs", &timings.busy_ns)?;
+                map.serialize_entry("idleNs", &timings.idle_ns)?;
+            }
+
+            if let Some(summary) = span
+                .as_ref()
+                .and_then(|span| span.extensions().get::<ErrorSummary>().cloned())
+            {
+                map.serialize_entry("errorCount", &summary.error_count)?;
+                map.serialize_entry("firstError", &summary.first_error)?;
+                map.serialize_entry("lastError", &summary.last_error)?;
+            }
+
+            if let Some(counts) = span
+                .as_ref()
+                .and_then(|span| span.extensions().get::<EventCounts>().cloned())
+            {
+                map.serialize_entry("eventCounts", &counts.0)?;
+            }
+        }
+
+        let mut console_url = None;
+
+        // serialize trace correlation fields from the nearest span with a trace_id
+        if let Some(cloud_trace) = &self.cloud_trace {
+            let trace = span
+                .as_ref()
+                .map(|span| TraceFields::resolve(span, self.trace_resolution))
+                .unwrap_or(Ok(None));
+
+            console_url = self.write_trace_fields(&mut map, cloud_trace, trace, now)?;
+        }
+
+        // group entries of the same unit of work by their root span
+        if self.span_operations {
+            if let Some(root) = span
+                .as_ref()
+                .and_then(|span| span.scope().from_root().next())
+            {
+                map.serialize_entry(
+                    "logging.googleapis.com/operation",
+                    &serde_json::json!({
+                        "id": root.id().into_u64().to_string(),
+                        "producer": root.name(),
+                    }),
+                )?;
+            }
+        }
+
+        // labels configured on the layer, then inherited from the event's spans
+        let mut labels = self.labels.clone();
+
+        if let Some(execution_id) = span
+            .as_ref()
+            .and_then(ExecutionId::from_scope)
+            .or_else(ExecutionId::current)
+        {
+            labels.insert("execution_id".to_string(), execution_id);
+        }
+
+        let inherited_values = span
+            .as_ref()
+            .and_then(|span| {
+                span.extensions()
+                    .get::<InheritedFields>()
+                    .map(|inherited_fields| inherited_fields.values.clone())
+            })
+            .unwrap_or_default();
+
+        // requests recorded on the event's spans, which inherited fields take precedence over
+        let inherited_values = match span.as_ref().and_then(HttpRequest::from_scope) {
+            Some(http_request) => {
+                let mut values = http_request.values;
+                values.extend(inherited_values);
+                values
+            }
+            None => inherited_values,
+        };
+
+        // fields of the events folded into a request's canonical entry, on its close entry
+        let inherited_values = match span
+            .as_ref()
+            .filter(|_| is_close_entry)
+            .and_then(|span| span.extensions().get::<CanonicalFields>().cloned())
+        {
+            Some(canonical) => {
+                map.serialize_entry("canonicalEvents", &canonical.events)?;
+                let mut values = inherited_values;
+                values.extend(canonical.values);
+                values
+            }
+            None => inherited_values,
+        };
+
+        // serialize the stackdriver-specific fields with a visitor
+        let mut visitor = self
+            .visitor(meta, map, labels, console_url, timestamp.is_some())
+            .with_inherited_values(inherited_values);
+        event.record(&mut visitor);
+        visitor.finish().map_err(Error::from)?;
+        Ok(())
+    }
+}
+
+impl EventFormatter {
+    /// Write the fields of every entry that don't depend on its spans: its time, target,
+    /// callsite, and source location
+    fn write_common_fields<M>(
+        &self,
+        map: &mut M,
+        meta: &Metadata<'_>,
+        now: OffsetDateTime,
+        overridden: bool,
+    ) -> Result<(), Error>
+    where
+        M: SerializeMap<Error = serde_json::Error>,
+    {
+        if self.legacy_agent_fields {
+            // the split timestamp recognized by google-fluentd's structured log parser
+            map.serialize_entry("timestampSeconds", &now.unix_timestamp())?;
+            map.serialize_entry("timestampNanos", &now.nanosecond())?;
+        } else {
+            map.serialize_entry("time", &self.format_time(now, overridden)?)?;
+        }
+
         map.serialize_entry("target", &meta.target())?;
 
+        if self.callsite_fingerprints {
+            map.serialize_entry("callsite", &callsite::fingerprint(meta))?;
+        }
+
         if self.include_source_location {
             if let Some(file) = meta.file() {
                 map.serialize_entry(
@@ -79,44 +516,363 @@ impl EventFormatter {
             }
         }
 
-        // serialize the current span and its leaves
-        if let Some(span) = span {
-            map.serialize_entry("span", &SerializableSpan::new(&span))?;
-            // map.serialize_entry("spans", &SerializableContext::new(context))?; TODO: remove
+        Ok(())
+    }
+
+    /// Write the Cloud Trace fields of an entry's trace, or its conflicting trace IDs, returning
+    /// the Logs Explorer URL of the trace when console URLs are enabled
+    fn write_trace_fields<M>(
+        &self,
+        map: &mut M,
+        cloud_trace: &CloudTraceConfiguration,
+        trace: Result<Option<TraceFields>, Vec<String>>,
+        now: OffsetDateTime,
+    ) -> Result<Option<String>, Error>
+    where
+        M: SerializeMap<Error = serde_json::Error>,
+    {
+        let trace = match trace {
+            Ok(Some(trace)) => trace,
+            Ok(None) => return Ok(None),
+            Err(trace_ids) => {
+                map.serialize_entry("traceConflict", &trace_ids)?;
+                return Ok(None);
+            }
+        };
+
+        let correlated = !self.sampled_traces_only || trace.sampled != Some(false);
+        let mut console_url = None;
+
+        if let Some(trace_id) = trace.trace_id.as_ref().filter(|_| !correlated) {
+            map.serialize_entry("traceId", trace_id)?;
         }
 
-        // TODO: obtain and serialize trace_id here.
-        // if let Some(trace_id) = trace_id {
-        //     map.serialize_entry(
-        //         "logging.googleapis.com/trace",
-        //         &format!("projects/{project_id}/traces/{trace_id}",),
-        //     )?;
-        // }
+        if let Some(trace_id) = trace.trace_id.as_ref().filter(|_| correlated) {
+            let project_id = trace.project_id.as_ref().unwrap_or(&cloud_trace.project_id);
+            let trace = format!("projects/{}/traces/{}", project_id, trace_id);
+
+            console_url = self
+                .console_url_window
+                .and_then(|window| console::logs_explorer_url(project_id, &trace, now, window));
+
+            map.serialize_entry("logging.googleapis.com/trace", &trace)?;
+        }
+
+        if let Some(span_id) = trace.span_id.filter(|_| correlated) {
+            map.serialize_entry(
+                "logging.googleapis.com/spanId",
+                &self.span_id_encoding.encode(span_id),
+            )?;
+        }
+
+        if let Some(sampled) = trace.sampled {
+            map.serialize_entry("logging.googleapis.com/trace_sampled", &sampled)?;
+        }
+
+        Ok(console_url)
+    }
+
+    /// Format the fields of an event or span with a context standing in for its spans, as
+    /// [`format_entry`](crate::format_entry) does
+    pub(crate) fn format_entry(
+        &self,
+        meta: &Metadata<'_>,
+        fields: &impl RecordFields,
+        context: &EntryContext,
+    ) -> Value {
+        let mut entry = Vec::new();
+
+        match self.write_entry(
+            meta,
+            fields,
+            context,
+            serde_json::Serializer::new(&mut entry),
+        ) {
+            Ok(()) => serde_json::from_slice(&entry).unwrap_or(Value::Null),
+            Err(_) => Value::Null,
+        }
+    }
+
+    fn write_entry<W>(
+        &self,
+        meta: &Metadata<'_>,
+        fields: &impl RecordFields,
+        context: &EntryContext,
+        mut serializer: serde_json::Serializer<W>,
+    ) -> Result<(), Error>
+    where
+        W: io::Write,
+    {
+        let now = match context.time {
+            Some(time) => OffsetDateTime::from(time),
+            None if self.monotonic_time => clock::monotonic_now(),
+            None => OffsetDateTime::now_utc(),
+        };
+        let mut map = serializer.serialize_map(None)?;
+
+        self.write_common_fields(&mut map, meta, now, context.time.is_some())?;
+
+        if let Some((name, fields)) = &context.span {
+            let mut span = fields.clone();
+            span.insert("name".to_string(), Value::String(name.clone()));
+            map.serialize_entry("span", &span)?;
+        }
+
+        let console_url = match &context.trace {
+            Some((cloud_trace, trace)) => self.write_trace_fields(
+                &mut map,
+                cloud_trace,
+                Ok(Some(TraceFields::from(trace.clone()))),
+                now,
+            )?,
+            None => None,
+        };
+
+        let mut labels = self.labels.clone();
+        labels.extend(context.labels.clone());
+
+        let mut visitor = self.visitor(meta, map, labels, console_url, false);
+        fields.record(&mut visitor);
+        visitor.finish()?;
 
-        // serialize the stackdriver-specific fields with a visitor
-        let mut visitor = Visitor::new(severity, map);
-        event.record(&mut visitor);
-        visitor.finish().map_err(Error::from)?;
         Ok(())
     }
+
+    /// Format the raw entry of an event, merged with the context it doesn't set itself
+    fn format_raw_entry<S, W>(
+        &self,
+        span: Option<SpanRef<'_, S>>,
+        mut serializer: serde_json::Serializer<W>,
+        event: &Event,
+    ) -> Result<(), Error>
+    where
+        S: Subscriber + for<'span> LookupSpan<'span>,
+        W: io::Write,
+    {
+        let mut raw_entry = RawEntry::default();
+        event.record(&mut raw_entry);
+
+        let mut entry = match raw_entry.0 {
+            Some(serde_json::Value::Object(entry)) => entry,
+            Some(message) => serde_json::Map::from_iter([("message".to_string(), message)]),
+            None => serde_json::Map::new(),
+        };
+
+        if !entry.contains_key("severity") {
+            let severity = serde_json::to_value(LogSeverity::from(event.metadata().level()))?;
+            entry.insert("severity".to_string(), severity);
+        }
+
+        if let Some(cloud_trace) = &self.cloud_trace {
+            let trace = match span
+                .as_ref()
+                .map(|span| TraceFields::resolve(span, self.trace_resolution))
+            {
+                Some(Ok(trace)) => trace,
+                Some(Err(trace_ids)) => {
+                    entry.insert("traceConflict".to_string(), trace_ids.into());
+                    None
+                }
+                None => None,
+            };
+
+            if let Some(trace) = trace {
+                let correlated = !self.sampled_traces_only || trace.sampled != Some(false);
+
+                if let Some(trace_id) = trace.trace_id.as_ref().filter(|_| !correlated) {
+                    entry
+                        .entry("traceId")
+                        .or_insert_with(|| trace_id.clone().into());
+                }
+
+                if let Some(trace_id) = trace.trace_id.as_ref().filter(|_| correlated) {
+                    let project_id = trace.project_id.as_ref().unwrap_or(&cloud_trace.project_id);
+
+                    entry
+                        .entry("logging.googleapis.com/trace")
+                        .or_insert_with(|| {
+                            format!("projects/{}/traces/{}", project_id, trace_id).into()
+                        });
+                }
+
+                if let Some(span_id) = trace.span_id.filter(|_| correlated) {
+                    entry
+                        .entry("logging.googleapis.com/spanId")
+                        .or_insert_with(|| self.span_id_encoding.encode(span_id).into());
+                }
+
+                if let Some(sampled) = trace.sampled {
+                    entry
+                        .entry("logging.googleapis.com/trace_sampled")
+                        .or_insert(sampled.into());
+                }
+            }
+        }
+
+        let execution_id = span
+            .as_ref()
+            .and_then(ExecutionId::from_scope)
+            .or_else(ExecutionId::current);
+        self.complete_entry(&mut entry, execution_id)?;
+
+        serde::Serialize::serialize(&entry, &mut serializer)?;
+        Ok(())
+    }
+
+    /// Complete an entry built outside of the formatter with the configured time format and the
+    /// labels of its context, which its own labels take precedence over
+    fn complete_entry(
+        &self,
+        entry: &mut serde_json::Map<String, Value>,
+        execution_id: Option<String>,
+    ) -> Result<(), Error> {
+        if !entry.contains_key("time") {
+            let now = self.format_time(OffsetDateTime::now_utc(), false)?;
+            entry.insert("time".to_string(), now.into());
+        }
+
+        let mut labels = serde_json::Map::from_iter(
+            self.labels
+                .iter()
+                .map(|(key, value)| (key.clone(), value.clone().into())),
+        );
+
+        if let Some(execution_id) = execution_id {
+            labels.insert("execution_id".to_string(), execution_id.into());
+        }
+
+        if let Some(Value::Object(own_labels)) = entry.remove("logging.googleapis.com/labels") {
+            labels.extend(own_labels);
+        }
+
+        if !labels.is_empty() {
+            entry.insert("logging.googleapis.com/labels".to_string(), labels.into());
+        }
+
+        Ok(())
+    }
+
+    /// Write an entry reported by a layer itself, such as a change of its sampling rate, with
+    /// the configured time format and labels
+    pub(crate) fn write_report(
+        &self,
+        writer: &mut dyn io::Write,
+        entry: LogEntry,
+    ) -> Result<(), Error> {
+        let Value::Object(mut entry) = serde_json::to_value(entry)? else {
+            return Ok(());
+        };
+
+        self.complete_entry(&mut entry, None)?;
+        Ok(entry::write_line(writer, &entry)?)
+    }
+
+    /// Format an event into a buffer, for strict validation, which panics on malformed output in
+    /// debug builds and drops it otherwise, for running it through processors, and for limiting the
+    /// length of its lines, returning the lines to write that tail buffering doesn't capture
+    fn format_buffered_event<S, N>(
+        &self,
+        span: Option<SpanRef<'_, S>>,
+        event: &Event,
+    ) -> Result<Vec<String>, Error>
+    where
+        S: Subscriber + for<'span> LookupSpan<'span>,
+        N: for<'writer> FormatFields<'writer> + 'static,
+    {
+        let mut entry = Vec::new();
+        self.format_event::<S, N, _>(span, serde_json::Serializer::new(&mut entry), event)?;
+
+        // serde_json only writes valid UTF-8, so this never fails
+        let entry = String::from_utf8(entry).map_err(|_| fmt::Error)?;
+
+        #[cfg(feature = "scrubbing")]
+        let entry = match &self.scrubbing {
+            Some(scrubbing) => scrubbing.scrub_entry(entry),
+            None => entry,
+        };
+
+        if self.strict {
+            let mut fields = StrictFields::default();
+            event.record(&mut fields);
+
+            if let Some(violation) = fields.0.or_else(|| strict::validate(&entry).err()) {
+                if cfg!(debug_assertions) {
+                    panic!("malformed Stackdriver entry: {}\n{}", violation, entry);
+                }
+
+                return Err(Error::Formatting(fmt::Error));
+            }
+        }
+
+        let entry = match self.processors.as_slice() {
+            [] => entry,
+            processors => match processor::process(processors, entry) {
+                Some(entry) => entry,
+                None => return Ok(vec![]),
+            },
+        };
+
+        let lines = match self.line_limit {
+            Some(line_limit) => line_limit.reserving(self.link_length()).apply(entry),
+            None => vec![entry],
+        };
+
+        if let Some(recent_entries) = &self.recent_entries {
+            lines.iter().for_each(|line| recent_entries.push(line));
+        }
+
+        Ok(lines
+            .into_iter()
+            .filter(|line| !tail::capture_line(line))
+            .collect())
+    }
 }
 
-impl<S> FormatEvent<S, JsonFields> for EventFormatter
+impl<S, N> FormatEvent<S, N> for EventFormatter
 where
     S: Subscriber + for<'span> LookupSpan<'span>,
+    N: for<'writer> FormatFields<'writer> + 'static,
 {
     fn format_event(
         &self,
-        context: &FmtContext<S, JsonFields>,
+        context: &FmtContext<S, N>,
         mut writer: format::Writer,
         event: &Event,
     ) -> fmt::Result
     where
         S: Subscriber + for<'span> LookupSpan<'span>,
     {
-        let serializer = serde_json::Serializer::new(WriteAdaptor::new(&mut writer));
-        self.format_event(context, serializer, event)?;
-        writeln!(writer)
+        let span = event
+            .parent()
+            .and_then(|id| context.span(id))
+            .or_else(|| context.lookup_current());
+
+        if self.is_buffered() {
+            return self
+                .format_buffered_event::<S, N>(span, event)?
+                .into_iter()
+                .try_for_each(|line| {
+                    // the entry is written by the fmt Layer once it's formatted, so it's linked
+                    // without holding the chain until then
+                    #[cfg(feature = "audit-chain")]
+                    let line = match &self.audit_chain {
+                        Some(audit_chain) => audit_chain.link_unwritten(line),
+                        None => line,
+                    };
+
+                    writeln!(writer, "{}", line)
+                });
+        }
+
+        // serialized into a byte buffer and written at once, rather than converted to UTF-8
+        // strings piece by piece as serde_json writes them
+        with_entry_buffer(|buffer| {
+            self.format_event::<S, N, _>(span, serde_json::Serializer::new(&mut *buffer), event)?;
+            buffer.push(b'\n');
+
+            let entry = std::str::from_utf8(buffer).map_err(|_| fmt::Error)?;
+            writer.write_str(entry)
+        })
     }
 }
 
@@ -124,6 +880,62 @@ impl Default for EventFormatter {
     fn default() -> Self {
         Self {
             include_source_location: true,
+            cloud_trace: None,
+            span_id_encoding: SpanIdEncoding::default(),
+            trace_resolution: TraceResolution::default(),
+            sampled_traces_only: false,
+            labels: BTreeMap::new(),
+            legacy_agent_fields: false,
+            max_value_length: None,
+            span_operations: false,
+            instrument: None,
+            severity_caps: BTreeMap::new(),
+            severity_escalations: Vec::new(),
+            stack_traces: None,
+            stack_trace_detection: false,
+            entry_counts: None,
+            strict: false,
+            logfmt_messages: false,
+            message_templates: false,
+            callsite_fingerprints: false,
+            metric_fields: false,
+            duration_format: DurationFormat::default(),
+            debug_limits: None,
+            include_span_ids: false,
+            field_namespace: None,
+            dotted_keys: DottedKeys::default(),
+            error_format: ErrorFormat::default(),
+            reserved_key_prefix: DEFAULT_RESERVED_KEY_PREFIX.to_string(),
+            console_url_window: None,
+            monotonic_time: false,
+            timer: None,
+            timestamp_overrides: false,
+            raw_entries: true,
+            line_limit: None,
+            recent_entries: None,
+            query_logging: None,
+            audit_trail: None,
+            field_transformer: None,
+            processors: vec![],
+            #[cfg(feature = "audit-chain")]
+            audit_chain: None,
+            #[cfg(feature = "scrubbing")]
+            scrubbing: None,
         }
     }
 }
+
+/// Whether an ID is a valid Google Cloud project ID, optionally scoped to a domain as in
+/// `example.com:my-project`
+fn is_project_id(project_id: &str) -> bool {
+    let project_id = project_id
+        .rsplit_once(':')
+        .map_or(project_id, |(_, project_id)| project_id);
+
+    (6..=30).contains(&project_id.len())
+        && project_id.starts_with(|char: char| char.is_ascii_lowercase())
+        && !project_id.ends_with('-')
+        && project_id
+            .chars()
+            .all(|char| char.is_ascii_lowercase() || char.is_ascii_digit() || char == '-')
+}
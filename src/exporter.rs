@@ -0,0 +1,812 @@
+use crate::{
+    auth::{TokenCache, LOGGING_WRITE_SCOPE},
+    client::{ApiClientBuilder, ClientConfig},
+    entry::LogEntry,
+    google::LogSeverity,
+    line_buffer::LineBuffer,
+    log_entry::{api_entry, encode_log_id, DEFAULT_LOG_ID},
+    metrics::ExporterMetrics,
+    resource::MonitoredResource,
+    spool::Spool,
+};
+use flate2::{write::GzEncoder, Compression};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::{
+    collections::hash_map::RandomState,
+    fmt,
+    hash::{BuildHasher, Hasher},
+    io, mem,
+    path::PathBuf,
+    sync::Arc,
+    thread,
+    time::{Duration, Instant},
+};
+
+/// Cloud Logging API endpoint for writing log entries
+const DEFAULT_ENDPOINT: &str = "https://logging.googleapis.com/v2/entries:write";
+
+/// Default maximum number of entries per WriteLogEntries request
+const DEFAULT_MAX_BATCH_ENTRIES: usize = 1_000;
+
+/// Default maximum size of the uncompressed entries in a WriteLogEntries request, well under the
+/// API's 10MB request limit
+const DEFAULT_MAX_BATCH_BYTES: usize = 5 * 1024 * 1024;
+
+/// Builder for configuring an [`Exporter`]. Authorization, request timeouts, and the diagnostics
+/// hook, which is notified of entries that the API rejected permanently or that are still failing
+/// once retries are exhausted, are configured with the methods of [`ApiClientBuilder`].
+pub struct ExporterBuilder {
+    project_id: String,
+    endpoint: String,
+    log_id: String,
+    log_name: Option<String>,
+    resource: Option<MonitoredResource>,
+    client: ClientConfig,
+    gzip: bool,
+    max_batch_entries: usize,
+    max_batch_bytes: usize,
+    retry_policy: RetryPolicy,
+    spool: Option<(PathBuf, u64)>,
+    metrics: ExporterMetrics,
+    metrics_report_interval: Option<Duration>,
+}
+
+impl ExporterBuilder {
+    /// Create a builder for an exporter writing to the logs of the given project
+    pub fn new(project_id: impl Into<String>) -> Self {
+        Self {
+            project_id: project_id.into(),
+            endpoint: DEFAULT_ENDPOINT.to_string(),
+            log_id: DEFAULT_LOG_ID.to_string(),
+            log_name: None,
+            resource: None,
+            client: ClientConfig::new(LOGGING_WRITE_SCOPE),
+            gzip: false,
+            max_batch_entries: DEFAULT_MAX_BATCH_ENTRIES,
+            max_batch_bytes: DEFAULT_MAX_BATCH_BYTES,
+            retry_policy: RetryPolicy::default(),
+            spool: None,
+            metrics: ExporterMetrics::default(),
+            metrics_report_interval: None,
+        }
+    }
+
+    /// Sets the URL of the `entries:write` endpoint, e.g. for a regional endpoint or a test server
+    pub fn with_endpoint(self, endpoint: impl Into<String>) -> Self {
+        Self {
+            endpoint: endpoint.into(),
+            ..self
+        }
+    }
+
+    /// Sets the ID of the log that entries are written to, as in `projects/PROJECT_ID/logs/LOG_ID`
+    pub fn with_log_id(self, log_id: impl Into<String>) -> Self {
+        Self {
+            log_id: log_id.into(),
+            log_name: None,
+            ..self
+        }
+    }
+
+    /// Sets the full resource name of the log that entries are written to, e.g.
+    /// `folders/FOLDER_ID/logs/LOG_ID` or `organizations/ORGANIZATION_ID/logs/LOG_ID`, overriding
+    /// the project and log ID. Events can override the log of their own entry with a `log_name`
+    /// field holding either a full log name or a log ID under the same parent.
+    pub fn with_log_name(self, log_name: impl Into<String>) -> Self {
+        Self {
+            log_name: Some(log_name.into()),
+            ..self
+        }
+    }
+
+    /// Sets the monitored resource that entries are attributed to, which defaults to the `global`
+    /// resource of the project. Use [`MonitoredResource::detect`] to attribute entries to the
+    /// resource the process is running on.
+    pub fn with_resource(self, resource: MonitoredResource) -> Self {
+        Self {
+            resource: Some(resource),
+            ..self
+        }
+    }
+
+    /// Configures whether or not request bodies are gzip-compressed
+    pub fn with_gzip(self, gzip: bool) -> Self {
+        Self { gzip, ..self }
+    }
+
+    /// Sets the maximum number of entries sent in a single request
+    pub fn with_max_batch_entries(self, max_batch_entries: usize) -> Self {
+        Self {
+            max_batch_entries: max_batch_entries.max(1),
+            ..self
+        }
+    }
+
+    /// Sets the maximum size in bytes of the uncompressed entries sent in a single request
+    pub fn with_max_batch_bytes(self, max_batch_bytes: usize) -> Self {
+        Self {
+            max_batch_bytes,
+            ..self
+        }
+    }
+
+    /// Sets how failed requests and rejected entries are retried
+    pub fn with_retry_policy(self, retry_policy: RetryPolicy) -> Self {
+        Self {
+            retry_policy,
+            ..self
+        }
+    }
+
+    /// Enables a write-ahead spool in `directory`: batches that are still failing once retries
+    /// are exhausted, or that are unauthorized or forbidden as a whole, are written there instead
+    /// of being dropped, and replayed by the first request that succeeds again, including by later
+    /// processes. The spool's batches are indexed in memory from those in `directory` when the
+    /// exporter is built, so sends don't read the directory. When the spool grows past `max_bytes`, its oldest batches are dropped.
+    pub fn with_spool(self, directory: impl Into<PathBuf>, max_bytes: u64) -> Self {
+        Self {
+            spool: Some((directory.into(), max_bytes)),
+            ..self
+        }
+    }
+
+    /// Handle for inspecting the metrics of the exporter being built
+    pub fn metrics(&self) -> ExporterMetrics {
+        self.metrics.clone()
+    }
+
+    /// Sets how often the exporter writes a NOTICE entry with its own metrics, or disables these
+    /// reports with `None`, the default
+    pub fn with_metrics_report_interval(self, metrics_report_interval: Option<Duration>) -> Self {
+        Self {
+            metrics_report_interval,
+            ..self
+        }
+    }
+
+    /// Build the configured exporter
+    pub fn build(self) -> Exporter {
+        let agent = self.client.agent();
+
+        let ExporterBuilder {
+            project_id,
+            endpoint,
+            log_id,
+            log_name,
+            resource,
+            client,
+            gzip,
+            max_batch_entries,
+            max_batch_bytes,
+            retry_policy,
+            spool,
+            metrics,
+            metrics_report_interval,
+        } = self;
+
+        Exporter {
+            config: ExporterConfig {
+                resource: resource.unwrap_or_else(|| MonitoredResource::global(&project_id)),
+                log_name: log_name.unwrap_or_else(|| {
+                    format!("projects/{}/logs/{}", project_id, encode_log_id(&log_id))
+                }),
+                project_id,
+                endpoint,
+                gzip,
+                max_batch_entries,
+                max_batch_bytes,
+                retry_policy,
+                diagnostics: client.diagnostics,
+            },
+            tokens: TokenCache::new(client.token_provider),
+            spool: spool.map(|(directory, max_bytes)| Spool::new(directory, max_bytes)),
+            metrics,
+            metrics_report: metrics_report_interval.map(|interval| (interval, Instant::now())),
+            agent,
+            lines: LineBuffer::default(),
+            batch: Vec::new(),
+            batch_bytes: 0,
+        }
+    }
+}
+
+impl ApiClientBuilder for ExporterBuilder {
+    fn client_config(&mut self) -> &mut ClientConfig {
+        &mut self.client
+    }
+}
+
+impl fmt::Debug for ExporterBuilder {
+    fn fmt(&self, formatter: &mut fmt::Formatter<'_>) -> fmt::Result {
+        formatter
+            .debug_struct("ExporterBuilder")
+            .field("project_id", &self.project_id)
+            .field("endpoint", &self.endpoint)
+            .field("log_id", &self.log_id)
+            .field("log_name", &self.log_name)
+            .field("resource", &self.resource)
+            .field("gzip", &self.gzip)
+            .field("max_batch_entries", &self.max_batch_entries)
+            .field("max_batch_bytes", &self.max_batch_bytes)
+            .field("client", &self.client)
+            .field("retry_policy", &self.retry_policy)
+            .field("spool", &self.spool)
+            .field("metrics_report_interval", &self.metrics_report_interval)
+            .finish_non_exhaustive()
+    }
+}
+
+/// Settings of a built [`Exporter`]
+struct ExporterConfig {
+    project_id: String,
+    endpoint: String,
+    log_name: String,
+    resource: MonitoredResource,
+    gzip: bool,
+    max_batch_entries: usize,
+    max_batch_bytes: usize,
+    retry_policy: RetryPolicy,
+    diagnostics: Diagnostics,
+}
+
+/// Queued entries sharing a log name and resource, sent in the same request
+#[derive(Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct EntryGroup {
+    log_name: String,
+    resource: Value,
+    entries: Vec<Value>,
+}
+
+/// Hook notified of entries that could not be exported
+pub(crate) type Diagnostics = Arc<dyn Fn(&ExportFailure) + Send + Sync>;
+
+/// How failed requests and rejected entries are retried, with jittered exponential backoff
+#[derive(Clone, Debug)]
+pub struct RetryPolicy {
+    /// Maximum number of attempts per batch, including the first
+    pub max_attempts: u32,
+    /// Backoff before the first retry, doubled for each subsequent retry
+    pub initial_backoff: Duration,
+    /// Upper bound for the backoff between retries
+    pub max_backoff: Duration,
+}
+
+impl RetryPolicy {
+    /// Jittered backoff before the given retry, between half and all of the exponential backoff
+    fn backoff(&self, attempt: u32) -> Duration {
+        let exponential = self
+            .initial_backoff
+            .saturating_mul(2u32.saturating_pow(attempt.saturating_sub(1)))
+            .min(self.max_backoff);
+
+        let jitter = RandomState::new().build_hasher().finish() as f64 / u64::MAX as f64;
+        exponential.mul_f64(0.5 + jitter / 2.0)
+    }
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        Self {
+            max_attempts: 5,
+            initial_backoff: Duration::from_millis(500),
+            max_backoff: Duration::from_secs(30),
+        }
+    }
+}
+
+/// Entries that could not be exported, as reported to the diagnostics hook
+#[derive(Debug)]
+pub struct ExportFailure {
+    /// The `LogEntry` resources that were not written, or the error events and spans of the
+    /// Error Reporting and Cloud Trace exporters
+    pub entries: Vec<Value>,
+    /// The [gRPC status code](https://cloud.google.com/logging/docs/reference/v2/rpc/google.rpc#google.rpc.Code)
+    /// the API rejected the entries with, if any
+    pub code: Option<i64>,
+    /// Description of the failure
+    pub message: String,
+}
+
+/// Default diagnostics hook, since failures can't be logged through the failing exporter
+pub(crate) fn report_to_stderr(failure: &ExportFailure) {
+    eprintln!(
+        "tracing-stackdriver: failed to export {} entries: {}",
+        failure.entries.len(),
+        failure.message
+    );
+}
+
+/// Failure of a single WriteLogEntries request
+#[derive(Debug)]
+struct RequestError {
+    status: Option<u16>,
+    body: Option<Value>,
+    message: String,
+}
+
+impl RequestError {
+    /// A failure unrelated to the API response that is worth retrying, e.g. a network error
+    fn transient(message: String) -> Self {
+        Self {
+            status: None,
+            body: None,
+            message,
+        }
+    }
+
+    /// A failure unrelated to the API response that won't go away on retry
+    fn permanent(message: String) -> Self {
+        Self {
+            status: Some(400),
+            body: None,
+            message,
+        }
+    }
+
+    /// Whether the whole request was rejected for its credentials or permissions, which retrying
+    /// won't fix but which may be fixed before the entries are replayed from a spool
+    fn is_spoolable(&self) -> bool {
+        self.status.is_some_and(is_spoolable_status)
+    }
+
+    /// Split the entries of a failed request into those worth retrying and permanent failures,
+    /// using per-entry errors from a partial success response when available
+    fn classify(self, entries: Vec<Value>) -> (Vec<Value>, Vec<ExportFailure>) {
+        let entry_errors = self
+            .body
+            .as_ref()
+            .and_then(|body| body["error"]["details"].as_array())
+            .and_then(|details| {
+                details
+                    .iter()
+                    .find_map(|detail| detail["logEntryErrors"].as_object())
+            });
+
+        let mut retryable = vec![];
+        let mut failures = vec![];
+
+        match entry_errors {
+            Some(entry_errors) => {
+                for (index, entry) in entries.into_iter().enumerate() {
+                    // entries without their own error were written successfully
+                    let Some(status) = entry_errors.get(&index.to_string()) else {
+                        continue;
+                    };
+
+                    let code = status["code"].as_i64().unwrap_or_default();
+
+                    if is_retryable_code(code) {
+                        retryable.push(entry);
+                    } else {
+                        failures.push(ExportFailure {
+                            entries: vec![entry],
+                            code: Some(code),
+                            message: status["message"]
+                                .as_str()
+                                .unwrap_or(&self.message)
+                                .to_string(),
+                        });
+                    }
+                }
+            }
+            None if self.status.map(is_retryable_status).unwrap_or(true) => retryable = entries,
+            None => failures.push(ExportFailure {
+                entries,
+                code: self
+                    .body
+                    .as_ref()
+                    .and_then(|body| body["error"]["code"].as_i64()),
+                message: self
+                    .body
+                    .as_ref()
+                    .and_then(|body| body["error"]["message"].as_str())
+                    .map(str::to_string)
+                    .unwrap_or(self.message),
+            }),
+        }
+
+        (retryable, failures)
+    }
+}
+
+/// Whether a per-entry gRPC status code indicates a transient failure
+fn is_retryable_code(code: i64) -> bool {
+    // DEADLINE_EXCEEDED, RESOURCE_EXHAUSTED, ABORTED, INTERNAL, UNAVAILABLE
+    matches!(code, 4 | 8 | 10 | 13 | 14)
+}
+
+/// Whether an HTTP status indicates a transient failure of the whole request
+fn is_retryable_status(status: u16) -> bool {
+    matches!(status, 408 | 429 | 500 | 502 | 503 | 504)
+}
+
+/// Whether an HTTP status indicates the whole request was unauthorized or forbidden
+fn is_spoolable_status(status: u16) -> bool {
+    matches!(status, 401 | 403)
+}
+
+/// Writer that sends entries directly to the Cloud Logging API in batches, for environments
+/// without a logging agent. Entries are sent when a batch fills up or the writer is flushed,
+/// so exporters are typically used through a [`NonBlocking`](crate::NonBlocking) writer.
+pub struct Exporter {
+    config: ExporterConfig,
+    tokens: TokenCache,
+    spool: Option<Spool>,
+    metrics: ExporterMetrics,
+    metrics_report: Option<(Duration, Instant)>,
+    agent: ureq::Agent,
+    lines: LineBuffer,
+    batch: Vec<Value>,
+    batch_bytes: usize,
+}
+
+impl Exporter {
+    /// Create a builder for an exporter writing to the logs of the given project
+    pub fn builder(project_id: impl Into<String>) -> ExporterBuilder {
+        ExporterBuilder::new(project_id)
+    }
+
+    /// Handle for inspecting the metrics of this exporter
+    pub fn metrics(&self) -> ExporterMetrics {
+        self.metrics.clone()
+    }
+
+    /// Queue a single formatted entry, sending the current batch first if the entry won't fit
+    fn push_line(&mut self, line: &[u8]) {
+        let structured = match serde_json::from_slice::<Value>(line) {
+            Ok(Value::Object(structured)) => structured,
+            // blank or malformed lines can't be exported as entries
+            _ => return,
+        };
+
+        let full = self.batch.len() >= self.config.max_batch_entries
+            || self.batch_bytes + line.len() > self.config.max_batch_bytes;
+
+        if full && !self.batch.is_empty() {
+            self.send_batch();
+        }
+
+        self.batch
+            .push(api_entry(structured, &self.config.log_name));
+        self.batch_bytes += line.len();
+    }
+
+    /// Send all queued entries, with one request per log name and resource
+    fn send_batch(&mut self) {
+        if self.batch.is_empty() {
+            return;
+        }
+
+        let entries = mem::take(&mut self.batch);
+        self.batch_bytes = 0;
+
+        for group in self.group_entries(entries) {
+            self.send_group(group);
+        }
+    }
+
+    /// Group entries by their log name and resource, lifting both to the request level so that
+    /// they aren't repeated for every entry
+    fn group_entries(&self, entries: Vec<Value>) -> Vec<EntryGroup> {
+        let default_resource = serde_json::to_value(&self.config.resource).unwrap_or_default();
+        let mut groups: Vec<EntryGroup> = vec![];
+
+        for mut entry in entries {
+            let (log_name, resource) = match entry.as_object_mut() {
+                Some(entry) => (
+                    entry.remove("logName").and_then(|log_name| match log_name {
+                        Value::String(log_name) => Some(log_name),
+                        _ => None,
+                    }),
+                    entry.remove("resource"),
+                ),
+                None => (None, None),
+            };
+
+            let log_name = log_name.unwrap_or_else(|| self.config.log_name.clone());
+            let resource = resource.unwrap_or_else(|| default_resource.clone());
+
+            match groups
+                .iter_mut()
+                .find(|group| group.log_name == log_name && group.resource == resource)
+            {
+                Some(group) => group.entries.push(entry),
+                None => groups.push(EntryGroup {
+                    log_name,
+                    resource,
+                    entries: vec![entry],
+                }),
+            }
+        }
+
+        groups
+    }
+
+    /// Send a group of entries, retrying retryable failures with jittered exponential backoff and
+    /// reporting entries that can't be written through the diagnostics hook
+    fn send_group(&mut self, group: EntryGroup) {
+        let EntryGroup {
+            log_name,
+            resource,
+            mut entries,
+        } = group;
+
+        let retry_policy = self.config.retry_policy.clone();
+        let mut attempt = 0;
+
+        loop {
+            let retryable = match self.write_entries(&log_name, &resource, &entries) {
+                Ok(()) => {
+                    // the spool is only left with batches by failed requests, so it's replayed by
+                    // the first request to succeed after them
+                    if self.spool.as_ref().is_some_and(|spool| !spool.is_empty()) {
+                        self.replay_spool();
+                    }
+
+                    return;
+                }
+                // spooled as is, since retrying won't fix credentials or permissions
+                Err(error) if error.is_spoolable() && self.spool.is_some() => {
+                    let group = EntryGroup {
+                        log_name,
+                        resource,
+                        entries,
+                    };
+
+                    return self.spool_group(group, error.message);
+                }
+                Err(error) => self.handle_error(error, entries),
+            };
+
+            attempt += 1;
+
+            if retryable.is_empty() {
+                return;
+            }
+
+            if attempt >= retry_policy.max_attempts {
+                let group = EntryGroup {
+                    log_name,
+                    resource,
+                    entries: retryable,
+                };
+
+                let message = format!("retries exhausted after {} attempts", attempt);
+                return self.spool_group(group, message);
+            }
+
+            thread::sleep(retry_policy.backoff(attempt));
+            self.metrics.record_retry();
+            entries = retryable;
+        }
+    }
+
+    /// Write a group that failed with `message` to the spool, or report it as failed without one
+    fn spool_group(&mut self, group: EntryGroup, message: String) {
+        let Some(spool) = &mut self.spool else {
+            return self.report_failure(ExportFailure {
+                entries: group.entries,
+                code: None,
+                message,
+            });
+        };
+
+        let spooled = serde_json::to_vec(&group)
+            .map_err(io::Error::from)
+            .and_then(|batch| spool.push(&batch));
+
+        if spooled.is_ok() {
+            self.metrics.record_spooled();
+        }
+
+        match spooled {
+            Ok(0) => (),
+            Ok(dropped) => self.report_failure(ExportFailure {
+                entries: vec![],
+                code: None,
+                message: format!("dropped {} spooled batches to stay within bounds", dropped),
+            }),
+            Err(error) => self.report_failure(ExportFailure {
+                entries: group.entries,
+                code: None,
+                message: format!("{}, and spooling failed: {}", message, error),
+            }),
+        }
+    }
+
+    /// Replay spooled batches, oldest first, until the spool is empty or a request fails
+    fn replay_spool(&mut self) {
+        let Some(mut spool) = self.spool.take() else {
+            return;
+        };
+
+        while let Some((path, batch)) = spool.oldest() {
+            // corrupt batches can never be replayed
+            let Ok(group) = serde_json::from_slice::<EntryGroup>(&batch) else {
+                if spool.remove(&path).is_err() {
+                    break;
+                }
+
+                continue;
+            };
+
+            let retryable =
+                match self.write_entries(&group.log_name, &group.resource, &group.entries) {
+                    Ok(()) => vec![],
+                    Err(error) if error.is_spoolable() => group.entries,
+                    Err(error) => self.handle_error(error, group.entries),
+                };
+
+            if retryable.is_empty() {
+                if spool.remove(&path).is_err() {
+                    break;
+                }
+
+                continue;
+            }
+
+            // keep only the entries that still need replaying, and try again later
+            let group = EntryGroup {
+                log_name: group.log_name,
+                resource: group.resource,
+                entries: retryable,
+            };
+
+            if let Ok(batch) = serde_json::to_vec(&group) {
+                let _ = spool.replace(&path, &batch);
+            }
+
+            break;
+        }
+
+        self.spool = Some(spool);
+    }
+
+    /// Report the permanent failures of a failed request, returning the entries worth retrying
+    fn handle_error(&self, error: RequestError, entries: Vec<Value>) -> Vec<Value> {
+        let sent = entries.len();
+        let (retryable, failures) = error.classify(entries);
+        let failed: usize = failures.iter().map(|failure| failure.entries.len()).sum();
+
+        // entries of a partially successful request without their own error were written
+        self.metrics
+            .record_exported(sent.saturating_sub(retryable.len() + failed));
+
+        for failure in failures {
+            self.report_failure(failure);
+        }
+
+        retryable
+    }
+
+    /// Notify the diagnostics hook of entries that could not be exported
+    fn report_failure(&self, failure: ExportFailure) {
+        self.metrics.record_failed(failure.entries.len());
+        (self.config.diagnostics)(&failure);
+    }
+
+    /// Queue a NOTICE entry with the exporter's metrics, if one is due
+    fn report_metrics(&mut self) {
+        let Some((interval, since)) = &mut self.metrics_report else {
+            return;
+        };
+
+        if since.elapsed() < *interval {
+            return;
+        }
+
+        *since = Instant::now();
+
+        let mut entry = LogEntry::report(module_path!(), LogSeverity::Notice, "exporter metrics")
+            .with_field("exporterMetrics", self.metrics.snapshot());
+        entry.stamp();
+
+        if let Ok(Value::Object(structured)) = serde_json::to_value(entry) {
+            self.batch
+                .push(api_entry(structured, &self.config.log_name));
+        }
+    }
+
+    /// Send entries in a single WriteLogEntries request, recording its metrics
+    fn write_entries(
+        &mut self,
+        log_name: &str,
+        resource: &Value,
+        entries: &[Value],
+    ) -> Result<(), RequestError> {
+        let started = Instant::now();
+        let result = self.send_request(log_name, resource, entries);
+
+        self.metrics
+            .record_request(entries.len(), started.elapsed(), result.is_ok());
+
+        if result.is_ok() {
+            self.metrics.record_exported(entries.len());
+        }
+
+        result
+    }
+
+    /// Send entries in a single WriteLogEntries request
+    fn send_request(
+        &mut self,
+        log_name: &str,
+        resource: &Value,
+        entries: &[Value],
+    ) -> Result<(), RequestError> {
+        let body = serde_json::to_vec(&serde_json::json!({
+            "logName": log_name,
+            "resource": resource,
+            "partialSuccess": true,
+            "entries": entries,
+        }))
+        .map_err(|error| RequestError::permanent(error.to_string()))?;
+
+        let token = self
+            .tokens
+            .token()
+            .map_err(|error| RequestError::transient(error.to_string()))?;
+
+        let request = self
+            .agent
+            .post(&self.config.endpoint)
+            .set("Content-Type", "application/json")
+            .set("Authorization", &format!("Bearer {}", token));
+
+        let response = if self.config.gzip {
+            let mut encoder = GzEncoder::new(Vec::new(), Compression::default());
+            io::Write::write_all(&mut encoder, &body)
+                .map_err(|error| RequestError::permanent(error.to_string()))?;
+            let body = encoder
+                .finish()
+                .map_err(|error| RequestError::permanent(error.to_string()))?;
+
+            request.set("Content-Encoding", "gzip").send_bytes(&body)
+        } else {
+            request.send_bytes(&body)
+        };
+
+        match response {
+            Ok(_) => Ok(()),
+            Err(ureq::Error::Status(status, response)) => {
+                let body = response.into_json::<Value>().ok();
+                Err(RequestError {
+                    status: Some(status),
+                    body,
+                    message: format!("WriteLogEntries failed with HTTP status {}", status),
+                })
+            }
+            Err(error) => Err(RequestError::transient(error.to_string())),
+        }
+    }
+}
+
+impl io::Write for Exporter {
+    fn write(&mut self, buffer: &[u8]) -> io::Result<usize> {
+        for line in self.lines.push(buffer) {
+            self.push_line(&line);
+        }
+
+        Ok(buffer.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.report_metrics();
+        self.send_batch();
+        Ok(())
+    }
+}
+
+impl fmt::Debug for Exporter {
+    fn fmt(&self, formatter: &mut fmt::Formatter<'_>) -> fmt::Result {
+        formatter
+            .debug_struct("Exporter")
+            .field("project_id", &self.config.project_id)
+            .field("endpoint", &self.config.endpoint)
+            .field("log_name", &self.config.log_name)
+            .field("queued", &self.batch.len())
+            .finish()
+    }
+}
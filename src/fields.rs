@@ -0,0 +1,459 @@
+use inflector::Inflector;
+use serde::Serialize;
+use serde_json::{Map, Value};
+use std::{
+    error::Error,
+    fmt::{self, Write},
+    iter,
+    time::Duration,
+};
+
+/// Prefix of JSON written for the visitor, which requests it by formatting with the otherwise
+/// meaningless `0` flag, so that other layers only ever see the JSON itself
+const JSON_MARKER: &str = "\u{0}json:";
+
+/// Prefix of durations written for the visitor, as whole seconds and nanoseconds
+const DURATION_MARKER: &str = "\u{0}duration:";
+
+/// Prefix of errors written for the visitor, as JSON of their Display and Debug representations
+/// and source chain
+const ERROR_MARKER: &str = "\u{0}error:";
+
+/// Marker appended to Debug representations cut off at their maximum length
+const TRUNCATION_MARKER: &str = "…[truncated]";
+
+/// Format of durations recorded with [`as_duration`]
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum DurationFormat {
+    /// An object of whole `seconds` and `nanos`, e.g. `{ "seconds": 1, "nanos": 200000000 }`
+    #[default]
+    Object,
+    /// Fractional seconds with an `s` suffix, e.g. `"1.2s"`, as in `httpRequest.latency`
+    Seconds,
+}
+
+impl DurationFormat {
+    fn format(self, seconds: u64, nanos: u32) -> Value {
+        match self {
+            Self::Object => serde_json::json!({ "seconds": seconds, "nanos": nanos }),
+            Self::Seconds => Value::String(fractional_seconds(seconds, nanos)),
+        }
+    }
+}
+
+/// Limits on the Debug representation of fields recorded with `?`, so that an accidental
+/// `?huge_state_struct` can't produce megabyte entries. Representations are cut off at
+/// `max_length` bytes with a `…[truncated]` marker, and anything nested deeper than `max_depth`
+/// braces, brackets or parentheses is elided as `…`, e.g. `Outer { inner: Inner {…} }`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct DebugLimits {
+    /// Length in bytes beyond which Debug representations are cut off
+    pub max_length: usize,
+    /// Depth of nesting beyond which Debug representations are elided
+    pub max_depth: usize,
+}
+
+impl Default for DebugLimits {
+    fn default() -> Self {
+        Self {
+            max_length: 16 * 1024,
+            max_depth: 8,
+        }
+    }
+}
+
+impl DebugLimits {
+    /// Format a value's Debug representation within the limits, stopping the formatting of a
+    /// value as soon as it exceeds them
+    fn format(self, value: &dyn fmt::Debug) -> String {
+        let mut writer = LimitedWriter {
+            limits: self,
+            output: String::new(),
+            depth: 0,
+            in_string: false,
+            escaped: false,
+        };
+
+        if write!(writer, "{:0?}", value).is_err() {
+            writer.output.push_str(TRUNCATION_MARKER);
+        }
+
+        writer.output
+    }
+}
+
+/// Writer of a Debug representation that tracks its nesting, outside of string literals
+struct LimitedWriter {
+    limits: DebugLimits,
+    output: String,
+    depth: usize,
+    in_string: bool,
+    escaped: bool,
+}
+
+impl fmt::Write for LimitedWriter {
+    fn write_str(&mut self, string: &str) -> fmt::Result {
+        for char in string.chars() {
+            let elided = self.depth > self.limits.max_depth;
+
+            if self.in_string {
+                self.in_string = self.escaped || char != '"';
+                self.escaped = !self.escaped && char == '\\';
+            } else {
+                match char {
+                    '"' => self.in_string = true,
+                    '{' | '[' | '(' => self.depth += 1,
+                    '}' | ']' | ')' => self.depth = self.depth.saturating_sub(1),
+                    _ => (),
+                }
+            }
+
+            let visible = match (elided, self.depth > self.limits.max_depth) {
+                (false, false) => Some(char),
+                // the delimiters of an elided value are kept around a single ellipsis
+                (false, true) => {
+                    self.push(char)?;
+                    Some('…')
+                }
+                (true, false) => Some(char),
+                (true, true) => None,
+            };
+
+            if let Some(char) = visible {
+                self.push(char)?;
+            }
+        }
+
+        Ok(())
+    }
+}
+
+impl LimitedWriter {
+    fn push(&mut self, char: char) -> fmt::Result {
+        if self.output.len() + char.len_utf8() > self.limits.max_length {
+            return Err(fmt::Error);
+        }
+
+        self.output.push(char);
+        Ok(())
+    }
+}
+
+/// How fields holding errors are written to entries
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum ErrorFormat {
+    /// As recorded: with Display for errors recorded as `&dyn Error`, and with Debug for errors
+    /// recorded with `?`
+    #[default]
+    AsRecorded,
+    /// With Display for errors recorded as `&dyn Error` or with [`as_error`], e.g.
+    /// `error = &error as &dyn Error` or `error = %as_error(&error)`, with their Debug
+    /// representation in a separate `<field>_debug` field of DEBUG and TRACE entries only.
+    ///
+    /// Errors recorded with a plain `?`, e.g. `error = ?error`, only reach the formatter as Debug,
+    /// so as a fallback, the Debug representation of fields named `error` or `err`, or ending in
+    /// `_error`, is truncated to its first 256 bytes above DEBUG.
+    Display,
+}
+
+/// How fields with dots in their names, e.g. `user.id`, are written to entries. Fields under
+/// `http_request.` and `labels.` are always written to `httpRequest` and labels.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum DottedKeys {
+    /// Nested objects per segment, e.g. `{ "user": { "id": 1 } }`
+    Nest,
+    /// A single camelCase key without dots, e.g. `"userId": 1`
+    #[default]
+    Escape,
+    /// A literal dotted key of camelCase segments, e.g. `"user.id": 1`, as expected by existing
+    /// queries and dashboards
+    Flat,
+}
+
+impl DottedKeys {
+    /// Key of a field written to entries, before any nesting
+    pub(crate) fn key(self, key: &str) -> String {
+        match self {
+            Self::Escape => key.to_camel_case(),
+            Self::Nest | Self::Flat => key
+                .split('.')
+                .map(|segment| segment.to_camel_case())
+                .collect::<Vec<_>>()
+                .join("."),
+        }
+    }
+
+    /// Insert a field under its key, nesting its segments if configured to
+    pub(crate) fn insert(self, fields: &mut Map<String, Value>, key: String, value: Value) {
+        if self != Self::Nest {
+            fields.insert(key, value);
+            return;
+        }
+
+        let mut segments: Vec<&str> = key.split('.').collect();
+        let last = segments.pop().unwrap_or_default();
+        let mut parent = &mut *fields;
+
+        for segment in segments {
+            let child = parent
+                .entry(segment)
+                .or_insert_with(|| Value::Object(Map::new()));
+
+            match child {
+                Value::Object(child) => parent = child,
+                // a value already recorded under this segment is kept, along with the flat key
+                _ => {
+                    fields.insert(key, value);
+                    return;
+                }
+            }
+        }
+
+        parent.insert(last.to_string(), value);
+    }
+}
+
+/// A struct that can be recorded as a single field, written to entries as a nested object of
+/// its fields instead of a Debug string. Implement it with `#[derive(StackdriverFields)]`, which
+/// requires the `derive` feature.
+pub trait StackdriverFields {
+    /// Fields written to the nested object
+    fn to_fields(&self) -> Map<String, Value>;
+
+    /// Wrap the struct for recording, as in `tracing::info!(order = %order.as_field())`
+    fn as_field(&self) -> AsField<'_, Self> {
+        AsField(self)
+    }
+}
+
+/// Recordable wrapper of a [`StackdriverFields`] struct, formatted as JSON by other layers
+pub struct AsField<'a, T: ?Sized>(&'a T);
+
+impl<'a, T> fmt::Display for AsField<'a, T>
+where
+    T: StackdriverFields + ?Sized,
+{
+    fn fmt(&self, formatter: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write_json(formatter, &Value::Object(self.0.to_fields()))
+    }
+}
+
+impl<'a, T> fmt::Debug for AsField<'a, T>
+where
+    T: StackdriverFields + ?Sized,
+{
+    fn fmt(&self, formatter: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Display::fmt(self, formatter)
+    }
+}
+
+/// Wrap any serializable value for recording as a single field, written to entries as its
+/// serialized structure instead of a Debug string, as in
+/// `tracing::info!(payload = %as_json(&payload))`
+pub fn as_json<T>(value: &T) -> AsJson<'_, T>
+where
+    T: Serialize + ?Sized,
+{
+    AsJson(value)
+}
+
+/// Recordable wrapper of a serializable value, formatted as JSON by other layers
+pub struct AsJson<'a, T: ?Sized>(&'a T);
+
+impl<'a, T> fmt::Display for AsJson<'a, T>
+where
+    T: Serialize + ?Sized,
+{
+    fn fmt(&self, formatter: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let value = serde_json::to_value(self.0).map_err(|_| fmt::Error)?;
+        write_json(formatter, &value)
+    }
+}
+
+impl<'a, T> fmt::Debug for AsJson<'a, T>
+where
+    T: Serialize + ?Sized,
+{
+    fn fmt(&self, formatter: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Display::fmt(self, formatter)
+    }
+}
+
+/// Wrap a duration for recording as a single field, written to entries in the configured
+/// [`DurationFormat`] instead of a Debug string, as in
+/// `tracing::info!(elapsed = %as_duration(started.elapsed()))`. Durations recorded as
+/// `http_request.latency` are always written as fractional seconds.
+pub fn as_duration(duration: Duration) -> AsDuration {
+    AsDuration(duration)
+}
+
+/// Recordable wrapper of a duration, formatted as fractional seconds by other layers
+#[derive(Clone, Copy)]
+pub struct AsDuration(Duration);
+
+impl fmt::Display for AsDuration {
+    fn fmt(&self, formatter: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let (seconds, nanos) = (self.0.as_secs(), self.0.subsec_nanos());
+
+        if formatter.sign_aware_zero_pad() {
+            write!(formatter, "{}{}:{}", DURATION_MARKER, seconds, nanos)
+        } else {
+            formatter.write_str(&fractional_seconds(seconds, nanos))
+        }
+    }
+}
+
+impl fmt::Debug for AsDuration {
+    fn fmt(&self, formatter: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Display::fmt(self, formatter)
+    }
+}
+
+/// Wrap an error for recording as a single field, written to entries like errors recorded as
+/// `&dyn Error`: with Display, and with their Debug representation kept as described by
+/// [`ErrorFormat::Display`], as in `tracing::warn!(error = %as_error(&error))`
+pub fn as_error<E>(error: &E) -> AsError<'_, E>
+where
+    E: Error + ?Sized,
+{
+    AsError(error)
+}
+
+/// Recordable wrapper of an error, formatted with Display by other layers
+pub struct AsError<'a, E: ?Sized>(&'a E);
+
+impl<'a, E> fmt::Display for AsError<'a, E>
+where
+    E: Error + ?Sized,
+{
+    fn fmt(&self, formatter: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if !formatter.sign_aware_zero_pad() {
+            return fmt::Display::fmt(self.0, formatter);
+        }
+
+        let chain: Vec<_> = iter::successors(self.0.source(), |error| (*error).source())
+            .map(ToString::to_string)
+            .collect();
+        let error = serde_json::json!({
+            "display": self.0.to_string(),
+            "debug": format!("{:?}", self.0),
+            "chain": chain,
+        });
+
+        write!(formatter, "{}{}", ERROR_MARKER, error)
+    }
+}
+
+impl<'a, E> fmt::Debug for AsError<'a, E>
+where
+    E: Error + ?Sized,
+{
+    fn fmt(&self, formatter: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Display::fmt(self, formatter)
+    }
+}
+
+/// Display and Debug representations and source chain of an error written with [`as_error`]
+pub(crate) struct RecordedError {
+    pub(crate) display: String,
+    pub(crate) debug: String,
+    pub(crate) chain: Vec<String>,
+}
+
+impl RecordedError {
+    /// Parse an error written with [`as_error`] from a formatted Debug value
+    pub(crate) fn from_value(value: &Value) -> Option<Self> {
+        let error: Value =
+            serde_json::from_str(value.as_str()?.strip_prefix(ERROR_MARKER)?).ok()?;
+
+        Some(Self {
+            display: error["display"].as_str()?.to_string(),
+            debug: error["debug"].as_str()?.to_string(),
+            chain: error["chain"]
+                .as_array()?
+                .iter()
+                .filter_map(|link| link.as_str().map(str::to_string))
+                .collect(),
+        })
+    }
+}
+
+/// A duration as fractional seconds with an `s` suffix, as in `httpRequest.latency`
+pub(crate) fn seconds(duration: Duration) -> String {
+    fractional_seconds(duration.as_secs(), duration.subsec_nanos())
+}
+
+fn fractional_seconds(seconds: u64, nanos: u32) -> String {
+    if nanos == 0 {
+        return format!("{}s", seconds);
+    }
+
+    let fraction = format!("{:09}", nanos);
+    format!("{}.{}s", seconds, fraction.trim_end_matches('0'))
+}
+
+/// Write a value as JSON, marked for the visitor when it requests it
+pub(crate) fn write_json(formatter: &mut fmt::Formatter<'_>, value: &Value) -> fmt::Result {
+    if formatter.sign_aware_zero_pad() {
+        formatter.write_str(JSON_MARKER)?;
+    }
+
+    write!(formatter, "{}", value)
+}
+
+/// Formatted Debug output without the quotes that string values are wrapped in
+pub(crate) fn unquote(debug: &str) -> &str {
+    debug
+        .strip_prefix('"')
+        .and_then(|debug| debug.strip_suffix('"'))
+        .unwrap_or(debug)
+}
+
+/// Format a recorded Debug value, embedding values written with [`write_json`] as is and
+/// durations in the given format, within any limits
+pub(crate) fn debug_value(
+    value: &dyn fmt::Debug,
+    duration_format: DurationFormat,
+    limits: Option<DebugLimits>,
+) -> Value {
+    let formatted = match limits {
+        // values written with markers are already structured, so they aren't limited
+        Some(limits) => match limits.format(value) {
+            formatted if formatted.starts_with('\u{0}') => format!("{:0?}", value),
+            formatted => formatted,
+        },
+        None => format!("{:0?}", value),
+    };
+
+    if let Some(duration) = formatted.strip_prefix(DURATION_MARKER) {
+        if let Some((seconds, nanos)) = duration.split_once(':') {
+            if let (Ok(seconds), Ok(nanos)) = (seconds.parse(), nanos.parse()) {
+                return duration_format.format(seconds, nanos);
+            }
+        }
+    }
+
+    formatted
+        .strip_prefix(JSON_MARKER)
+        .and_then(|json| serde_json::from_str(json).ok())
+        .unwrap_or(Value::String(formatted))
+}
+
+/// Items used by the code that `#[derive(StackdriverFields)]` generates
+#[doc(hidden)]
+pub mod __private {
+    pub use serde_json::Value;
+
+    /// Fields of a nested object
+    pub type Map = serde_json::Map<String, Value>;
+
+    /// Serialize a field, writing `null` for values that can't be serialized
+    pub fn to_value<T: super::Serialize + ?Sized>(value: &T) -> Value {
+        serde_json::to_value(value).unwrap_or(Value::Null)
+    }
+
+    /// Write a redacted field, reading it so that it doesn't look unused
+    pub fn redacted<T: ?Sized>(_: &T) -> Value {
+        Value::from("[REDACTED]")
+    }
+}
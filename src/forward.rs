@@ -0,0 +1,202 @@
+use crate::line_buffer::LineBuffer;
+use serde_json::{Map, Value};
+use std::{
+    fmt, io,
+    time::{SystemTime, UNIX_EPOCH},
+};
+use time::{format_description::well_known::Rfc3339, OffsetDateTime};
+
+/// Writer that transcodes formatted entries into MessagePack events of the
+/// [fluent forward protocol](https://github.com/fluent/fluentd/wiki/Forward-Protocol-Specification-v1),
+/// for shipping entries to Vector or fluent-bit sidecars with less overhead than JSON lines.
+/// Each entry is written in message mode as a `[tag, time, record]` array.
+pub struct ForwardWriter<W> {
+    writer: W,
+    tag: String,
+    lines: LineBuffer,
+}
+
+impl<W> ForwardWriter<W>
+where
+    W: io::Write,
+{
+    /// Create a writer of events with the given tag, which sidecars use to route them
+    pub fn new(tag: impl Into<String>, writer: W) -> Self {
+        Self {
+            writer,
+            tag: tag.into(),
+            lines: LineBuffer::default(),
+        }
+    }
+
+    /// Transcode a single formatted entry
+    fn write_line(&mut self, line: &[u8]) -> io::Result<()> {
+        let record = match serde_json::from_slice::<Value>(line) {
+            Ok(Value::Object(record)) => record,
+            // blank or malformed lines can't be transcoded into events
+            _ => return Ok(()),
+        };
+
+        let (seconds, nanos) = event_time(&record);
+        let mut event = MessagePack::default();
+        event.array(3);
+        event.string(&self.tag);
+        event.event_time(seconds, nanos);
+        event.map(&record);
+
+        self.writer.write_all(&event.0)
+    }
+}
+
+impl<W> io::Write for ForwardWriter<W>
+where
+    W: io::Write,
+{
+    fn write(&mut self, buffer: &[u8]) -> io::Result<usize> {
+        for line in self.lines.push(buffer) {
+            self.write_line(&line)?;
+        }
+
+        Ok(buffer.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.writer.flush()
+    }
+}
+
+impl<W> fmt::Debug for ForwardWriter<W> {
+    fn fmt(&self, formatter: &mut fmt::Formatter<'_>) -> fmt::Result {
+        formatter
+            .debug_struct("ForwardWriter")
+            .field("tag", &self.tag)
+            .finish_non_exhaustive()
+    }
+}
+
+/// Time of an entry from either its `time` or legacy timestamp fields, falling back to now
+fn event_time(record: &Map<String, Value>) -> (u32, u32) {
+    if let Some(time) = record
+        .get("time")
+        .and_then(Value::as_str)
+        .and_then(|time| OffsetDateTime::parse(time, &Rfc3339).ok())
+    {
+        return (time.unix_timestamp() as u32, time.nanosecond());
+    }
+
+    if let Some(seconds) = record.get("timestampSeconds").and_then(Value::as_u64) {
+        let nanos = record
+            .get("timestampNanos")
+            .and_then(Value::as_u64)
+            .unwrap_or_default();
+
+        return (seconds as u32, nanos as u32);
+    }
+
+    let now = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default();
+
+    (now.as_secs() as u32, now.subsec_nanos())
+}
+
+/// Encoded MessagePack values
+#[derive(Default)]
+struct MessagePack(Vec<u8>);
+
+impl MessagePack {
+    fn value(&mut self, value: &Value) {
+        match value {
+            Value::Null => self.0.push(0xc0),
+            Value::Bool(false) => self.0.push(0xc2),
+            Value::Bool(true) => self.0.push(0xc3),
+            Value::Number(number) => {
+                if let Some(number) = number.as_u64() {
+                    self.unsigned(number);
+                } else if let Some(number) = number.as_i64() {
+                    self.signed(number);
+                } else {
+                    self.0.push(0xcb);
+                    let number = number.as_f64().unwrap_or_default();
+                    self.0.extend_from_slice(&number.to_be_bytes());
+                }
+            }
+            Value::String(string) => self.string(string),
+            Value::Array(values) => {
+                self.array(values.len());
+
+                for value in values {
+                    self.value(value);
+                }
+            }
+            Value::Object(fields) => self.map(fields),
+        }
+    }
+
+    fn unsigned(&mut self, value: u64) {
+        match value {
+            0..=0x7f => self.0.push(value as u8),
+            0x80..=0xff => self.0.extend_from_slice(&[0xcc, value as u8]),
+            0x100..=0xffff => self.header(0xcd, 2, value),
+            0x1_0000..=0xffff_ffff => self.header(0xce, 4, value),
+            _ => self.header(0xcf, 8, value),
+        }
+    }
+
+    fn signed(&mut self, value: i64) {
+        match value {
+            -32..=-1 => self.0.push(value as u8),
+            -0x80..=-33 => self.0.extend_from_slice(&[0xd0, value as u8]),
+            -0x8000..=-0x81 => self.header(0xd1, 2, value as u64),
+            -0x8000_0000..=-0x8001 => self.header(0xd2, 4, value as u64),
+            _ => self.header(0xd3, 8, value as u64),
+        }
+    }
+
+    fn string(&mut self, value: &str) {
+        let length = value.len();
+
+        match length {
+            0..=31 => self.0.push(0xa0 | length as u8),
+            32..=0xff => self.0.extend_from_slice(&[0xd9, length as u8]),
+            0x100..=0xffff => self.header(0xda, 2, length as u64),
+            _ => self.header(0xdb, 4, length as u64),
+        }
+
+        self.0.extend_from_slice(value.as_bytes());
+    }
+
+    fn array(&mut self, length: usize) {
+        match length {
+            0..=15 => self.0.push(0x90 | length as u8),
+            16..=0xffff => self.header(0xdc, 2, length as u64),
+            _ => self.header(0xdd, 4, length as u64),
+        }
+    }
+
+    fn map(&mut self, fields: &Map<String, Value>) {
+        match fields.len() {
+            0..=15 => self.0.push(0x80 | fields.len() as u8),
+            16..=0xffff => self.header(0xde, 2, fields.len() as u64),
+            length => self.header(0xdf, 4, length as u64),
+        }
+
+        for (key, value) in fields {
+            self.string(key);
+            self.value(value);
+        }
+    }
+
+    /// Encode an `EventTime`, the forward protocol's extension type 0
+    fn event_time(&mut self, seconds: u32, nanos: u32) {
+        self.0.extend_from_slice(&[0xd7, 0x00]);
+        self.0.extend_from_slice(&seconds.to_be_bytes());
+        self.0.extend_from_slice(&nanos.to_be_bytes());
+    }
+
+    /// Encode a type marker followed by the trailing `bytes` of a big-endian value
+    fn header(&mut self, marker: u8, bytes: usize, value: u64) {
+        self.0.push(marker);
+        self.0.extend_from_slice(&value.to_be_bytes()[8 - bytes..]);
+    }
+}
@@ -1,4 +1,5 @@
-use serde::Serialize;
+use crate::fields::{self, DurationFormat};
+use serde::{Deserialize, Serialize};
 use std::{convert::Infallible, fmt, str::FromStr};
 use tracing_core::Level;
 
@@ -8,8 +9,8 @@ use tracing_core::Level;
     all(tracing_unstable, feature = "valuable"),
     derive(valuable::Valuable)
 )]
-#[derive(Debug, Default, Serialize)]
-#[serde(rename_all = "SCREAMING_SNAKE_CASE")]
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(rename_all = "SCREAMING_SNAKE_CASE", from = "serde_json::Value")]
 pub enum LogSeverity {
     /// Log entry has no assigned severity level
     #[default]
@@ -99,6 +100,38 @@ impl From<serde_json::Value> for LogSeverity {
     }
 }
 
+impl LogSeverity {
+    /// The severity recorded by a field as its Debug representation, as in `severity = ?value`,
+    /// which quotes strings
+    pub(crate) fn from_debug(value: &dyn fmt::Debug) -> Self {
+        match fields::debug_value(value, DurationFormat::default(), None) {
+            serde_json::Value::String(debug) => {
+                Self::from_str(fields::unquote(&debug)).unwrap_or(Self::Default)
+            }
+            json => Self::from(json),
+        }
+    }
+}
+
+/// Configuration for correlating log entries with [Cloud Trace](https://cloud.google.com/trace) traces.
+/// [See Google's trace correlation docs here](https://cloud.google.com/trace/docs/trace-log-integration).
+#[derive(Clone, Debug)]
+pub struct CloudTraceConfiguration {
+    /// Google Cloud project ID that owns the correlated traces
+    pub project_id: String,
+}
+
+impl CloudTraceConfiguration {
+    /// Configuration for the current project, named by the `GOOGLE_CLOUD_PROJECT`,
+    /// `GCLOUD_PROJECT` or `GCP_PROJECT` environment variables or by the metadata server,
+    /// returning `None` when it can't be discovered
+    #[cfg_attr(docsrs, doc(cfg(feature = "metadata")))]
+    #[cfg(feature = "metadata")]
+    pub fn detect() -> Option<Self> {
+        crate::metadata::project_id().map(|project_id| Self { project_id })
+    }
+}
+
 /// Structure for stucturally logging information about a request.
 /// [See Google's HttpRequest docs here](https://cloud.google.com/logging/docs/reference/v2/rest/v2/LogEntry#HttpRequest).
 #[cfg_attr(docsrs, doc(cfg(feature = "valuable")))]
@@ -0,0 +1,114 @@
+use std::{
+    fmt,
+    sync::atomic::{AtomicU64, Ordering},
+};
+use tracing_core::{Event, Field, Level};
+use tracing_subscriber::field::Visit;
+
+/// Configuration for dropping or downsampling the DEBUG and INFO entries of health checks, which
+/// dominate the log volume of GKE and Cloud Run services. Entries are matched by the path of the
+/// URL recorded in a field, ignoring its query string. WARN and ERROR entries are always kept.
+#[derive(Clone, Debug)]
+pub struct HealthCheckFilter {
+    /// Field recording the requested URL or path
+    pub field: String,
+    /// Paths of health check requests
+    pub paths: Vec<String>,
+    /// Keep 1 in every `sample_rate` health check entries, or none when `0`
+    pub sample_rate: u64,
+}
+
+impl Default for HealthCheckFilter {
+    fn default() -> Self {
+        Self {
+            field: "http_request.request_url".to_string(),
+            paths: ["/healthz", "/readyz", "/livez", "/_ah/health"]
+                .map(str::to_string)
+                .to_vec(),
+            sample_rate: 0,
+        }
+    }
+}
+
+/// Filter counting the health check entries of a [`HealthCheckFilter`]
+#[derive(Debug)]
+pub(crate) struct HealthChecks {
+    configuration: HealthCheckFilter,
+    matched: AtomicU64,
+}
+
+impl HealthChecks {
+    pub(crate) fn new(configuration: HealthCheckFilter) -> Self {
+        Self {
+            configuration,
+            matched: AtomicU64::new(0),
+        }
+    }
+
+    /// Whether an event is kept
+    pub(crate) fn keep(&self, event: &Event) -> bool {
+        if *event.metadata().level() < Level::INFO
+            || event
+                .metadata()
+                .fields()
+                .field(&self.configuration.field)
+                .is_none()
+        {
+            return true;
+        }
+
+        let mut url = Url {
+            field: &self.configuration.field,
+            value: None,
+        };
+        event.record(&mut url);
+
+        let is_health_check = url.value.is_some_and(|url| {
+            self.configuration
+                .paths
+                .iter()
+                .any(|path| *path == self::path(&url))
+        });
+
+        if !is_health_check {
+            return true;
+        }
+
+        let matched = self.matched.fetch_add(1, Ordering::Relaxed);
+
+        match self.configuration.sample_rate {
+            0 => false,
+            sample_rate => matched.is_multiple_of(sample_rate),
+        }
+    }
+}
+
+/// Path of a URL, or of a path with a query string
+fn path(url: &str) -> &str {
+    let path = match url.split_once("://") {
+        Some((_, rest)) => rest.find('/').map_or("/", |start| &rest[start..]),
+        None => url,
+    };
+
+    path.split(['?', '#']).next().unwrap_or(path)
+}
+
+/// Visitor extracting the URL recorded in a field
+struct Url<'a> {
+    field: &'a str,
+    value: Option<String>,
+}
+
+impl<'a> Visit for Url<'a> {
+    fn record_str(&mut self, field: &Field, value: &str) {
+        if field.name() == self.field {
+            self.value = Some(value.to_string());
+        }
+    }
+
+    fn record_debug(&mut self, field: &Field, value: &dyn fmt::Debug) {
+        if field.name() == self.field {
+            self.value = Some(format!("{:?}", value));
+        }
+    }
+}
@@ -0,0 +1,117 @@
+use crate::{
+    counts::EntryCounts,
+    entry::{self, LogEntry},
+    google::LogSeverity,
+};
+use serde_json::{Map, Value};
+use std::{
+    collections::BTreeMap,
+    fmt,
+    io::{self, Write},
+    sync::mpsc::{self, RecvTimeoutError},
+    thread,
+    time::{Duration, Instant},
+};
+use tracing_subscriber::fmt::MakeWriter;
+
+/// Configuration for periodic heartbeat entries, written by [`spawn_heartbeat`] to detect
+/// silently wedged workers purely from their logs
+#[derive(Clone, Debug)]
+pub struct Heartbeat {
+    /// Name of the service written with every heartbeat
+    pub service: String,
+    /// Version of the service written with every heartbeat, e.g. a release or revision
+    pub version: Option<String>,
+    /// Interval between heartbeats
+    pub interval: Duration,
+    /// Counters of the entries written by a Layer, whose totals by severity are written with
+    /// every heartbeat
+    pub entry_counts: Option<EntryCounts>,
+}
+
+impl Default for Heartbeat {
+    fn default() -> Self {
+        Self {
+            service: String::new(),
+            version: None,
+            interval: Duration::from_secs(60),
+            entry_counts: None,
+        }
+    }
+}
+
+/// Spawns a background thread writing an INFO heartbeat entry to `make_writer` once every
+/// interval, with the service's name, version, uptime, and number of heartbeats since it
+/// started. Heartbeats stop once the returned guard is dropped.
+pub fn spawn_heartbeat<W>(heartbeat: Heartbeat, make_writer: W) -> HeartbeatGuard
+where
+    W: for<'writer> MakeWriter<'writer> + Send + 'static,
+{
+    let (stop, stopped) = mpsc::channel::<()>();
+    let started = Instant::now();
+
+    thread::Builder::new()
+        .name("tracing-stackdriver-heartbeat".to_string())
+        .spawn(move || {
+            let mut sequence = 0;
+
+            while let Err(RecvTimeoutError::Timeout) = stopped.recv_timeout(heartbeat.interval) {
+                sequence += 1;
+
+                let mut writer = make_writer.make_writer();
+                let _ = write_heartbeat(&heartbeat, started, sequence, &mut writer);
+                let _ = writer.flush();
+            }
+        })
+        .expect("failed to spawn the heartbeat thread");
+
+    HeartbeatGuard { _stop: stop }
+}
+
+/// Guard that stops the heartbeats of [`spawn_heartbeat`] when dropped
+#[must_use = "dropping the guard immediately stops the heartbeats"]
+pub struct HeartbeatGuard {
+    _stop: mpsc::Sender<()>,
+}
+
+impl fmt::Debug for HeartbeatGuard {
+    fn fmt(&self, formatter: &mut fmt::Formatter<'_>) -> fmt::Result {
+        formatter.pad("HeartbeatGuard { .. }")
+    }
+}
+
+/// Write a single heartbeat entry
+fn write_heartbeat(
+    heartbeat: &Heartbeat,
+    started: Instant,
+    sequence: u64,
+    writer: &mut dyn io::Write,
+) -> io::Result<()> {
+    let mut entry = LogEntry::report(module_path!(), LogSeverity::Info, "heartbeat")
+        .with_field("service", &heartbeat.service)
+        .with_field("uptimeSeconds", started.elapsed().as_secs())
+        .with_field("sequence", sequence);
+
+    if let Some(version) = &heartbeat.version {
+        entry = entry.with_field("version", version);
+    }
+
+    if let Some(entry_counts) = &heartbeat.entry_counts {
+        let mut totals = BTreeMap::<LogSeverity, u64>::new();
+
+        for counts in entry_counts.snapshot().into_values() {
+            for (severity, count) in counts {
+                *totals.entry(severity).or_default() += count;
+            }
+        }
+
+        let entries: Map<String, Value> = totals
+            .into_iter()
+            .map(|(severity, count)| (severity.to_string(), count.into()))
+            .collect();
+
+        entry = entry.with_field("entries", entries);
+    }
+
+    entry::write_report(writer, entry)
+}
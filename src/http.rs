@@ -0,0 +1,76 @@
+use crate::{http_request::HttpRequest, trace::WithTraceContext};
+use serde_json::Value;
+use std::collections::BTreeMap;
+
+/// The method, URL, protocol, and headers of a request as `httpRequest` fields
+fn summary(parts: &http::request::Parts) -> BTreeMap<&'static str, Value> {
+    let mut values = BTreeMap::new();
+    let header = |name| {
+        parts
+            .headers
+            .get(name)
+            .and_then(|value| value.to_str().ok())
+            .map(|value| Value::String(value.to_string()))
+    };
+
+    values.insert(
+        "http_request.request_method",
+        Value::String(parts.method.to_string()),
+    );
+    values.insert(
+        "http_request.request_url",
+        Value::String(parts.uri.to_string()),
+    );
+    values.insert(
+        "http_request.protocol",
+        Value::String(format!("{:?}", parts.version)),
+    );
+
+    if let Some(user_agent) = header(http::header::USER_AGENT) {
+        values.insert("http_request.user_agent", user_agent);
+    }
+
+    if let Some(referer) = header(http::header::REFERER) {
+        values.insert("http_request.referer", referer);
+    }
+
+    // the client of a proxied request is the first address it was forwarded for
+    let remote_ip = parts
+        .headers
+        .get("x-forwarded-for")
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.split(',').next())
+        .map(|remote_ip| Value::String(remote_ip.trim().to_string()));
+
+    if let Some(remote_ip) = remote_ip {
+        values.insert("http_request.remote_ip", remote_ip);
+    }
+
+    values
+}
+
+/// Extension of [`tracing::Span`] for recording the HTTP request that a span handles
+pub trait SpanExt {
+    /// Records the method, URL, protocol, user agent, referer, and remote IP (from its
+    /// `X-Forwarded-For` header) of a request on the span, which the `httpRequest` of its entries
+    /// and those of its descendants are populated with. Fields recorded on the entries' events
+    /// take precedence. Requires a stackdriver [`Layer`](crate::Layer) in the span's subscriber.
+    fn record_http_request(&self, parts: &http::request::Parts);
+}
+
+impl SpanExt for tracing::Span {
+    fn record_http_request(&self, parts: &http::request::Parts) {
+        let values = summary(parts);
+
+        WithTraceContext::with_extensions(self, |extensions| {
+            match extensions.get_mut::<HttpRequest>() {
+                Some(http_request) => http_request.values.extend(values),
+                None => {
+                    let mut http_request = HttpRequest::new();
+                    http_request.values = values;
+                    extensions.insert(http_request);
+                }
+            }
+        });
+    }
+}
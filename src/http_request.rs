@@ -0,0 +1,83 @@
+use crate::fields;
+use serde_json::Value;
+use std::{collections::BTreeMap, fmt, time::Instant};
+use tracing_core::{Field, Metadata};
+use tracing_subscriber::{
+    field::Visit,
+    registry::{LookupSpan, SpanRef},
+};
+
+/// Prefix of the fields written to `httpRequest`
+const HTTP_REQUEST_PREFIX: &str = "http_request.";
+
+/// `httpRequest` fields of the request that a span handles, cached in that span's extensions so
+/// that the entries within the span, and its close entry, are populated with them
+#[derive(Clone, Debug)]
+pub(crate) struct HttpRequest {
+    pub(crate) values: BTreeMap<&'static str, Value>,
+    started: Instant,
+}
+
+impl HttpRequest {
+    pub(crate) fn new() -> Self {
+        Self {
+            values: BTreeMap::new(),
+            started: Instant::now(),
+        }
+    }
+
+    /// Whether a span handles a request, by declaring `http_request.` fields
+    pub(crate) fn is_request_span(metadata: &Metadata<'_>) -> bool {
+        metadata
+            .fields()
+            .iter()
+            .any(|field| field.name().starts_with(HTTP_REQUEST_PREFIX))
+    }
+
+    /// Find the request of the nearest request span in the scope
+    pub(crate) fn from_scope<S>(span: &SpanRef<S>) -> Option<Self>
+    where
+        S: for<'lookup> LookupSpan<'lookup>,
+    {
+        span.scope()
+            .find_map(|span| span.extensions().get::<Self>().cloned())
+    }
+
+    /// Complete the request as its span closes, with the span's lifetime as its latency unless
+    /// one was recorded
+    pub(crate) fn finish(&mut self) {
+        let latency = fields::seconds(self.started.elapsed());
+
+        self.values
+            .entry("http_request.latency")
+            .or_insert(Value::String(latency));
+    }
+
+    fn record(&mut self, field: &Field, value: Value) {
+        if field.name().starts_with(HTTP_REQUEST_PREFIX) {
+            self.values.insert(field.name(), value);
+        }
+    }
+}
+
+impl Visit for HttpRequest {
+    fn record_i64(&mut self, field: &Field, value: i64) {
+        self.record(field, Value::from(value));
+    }
+
+    fn record_u64(&mut self, field: &Field, value: u64) {
+        self.record(field, Value::from(value));
+    }
+
+    fn record_bool(&mut self, field: &Field, value: bool) {
+        self.record(field, Value::from(value));
+    }
+
+    fn record_str(&mut self, field: &Field, value: &str) {
+        self.record(field, Value::from(value));
+    }
+
+    fn record_debug(&mut self, field: &Field, value: &dyn fmt::Debug) {
+        self.record(field, Value::from(format!("{:?}", value)));
+    }
+}
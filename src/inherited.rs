@@ -0,0 +1,53 @@
+use serde_json::Value;
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    fmt,
+    sync::Arc,
+};
+use tracing_core::Field;
+use tracing_subscriber::field::Visit;
+
+/// Values of inherited fields recorded on a span or any of its ancestors, cached in that span's
+/// extensions so that events can be stamped with them without walking their scope
+#[derive(Clone, Debug)]
+pub(crate) struct InheritedFields {
+    names: Arc<BTreeSet<String>>,
+    pub(crate) values: BTreeMap<&'static str, Value>,
+}
+
+impl InheritedFields {
+    pub(crate) fn new(names: Arc<BTreeSet<String>>) -> Self {
+        Self {
+            names,
+            values: BTreeMap::new(),
+        }
+    }
+
+    fn record(&mut self, field: &Field, value: Value) {
+        if self.names.contains(field.name()) {
+            self.values.insert(field.name(), value);
+        }
+    }
+}
+
+impl Visit for InheritedFields {
+    fn record_i64(&mut self, field: &Field, value: i64) {
+        self.record(field, Value::from(value));
+    }
+
+    fn record_u64(&mut self, field: &Field, value: u64) {
+        self.record(field, Value::from(value));
+    }
+
+    fn record_bool(&mut self, field: &Field, value: bool) {
+        self.record(field, Value::from(value));
+    }
+
+    fn record_str(&mut self, field: &Field, value: &str) {
+        self.record(field, Value::from(value));
+    }
+
+    fn record_debug(&mut self, field: &Field, value: &dyn fmt::Debug) {
+        self.record(field, Value::from(format!("{:?}", value)));
+    }
+}
@@ -0,0 +1,39 @@
+use tracing_core::Metadata;
+
+/// Configuration for the events generated by `#[instrument(err)]` and `#[instrument(ret)]`
+#[derive(Clone, Debug)]
+pub struct InstrumentConfiguration {
+    /// Key of the structured `error` of `#[instrument(err)]` events
+    pub error_key: String,
+    /// Key of the returned value of `#[instrument(ret)]` events
+    pub return_key: String,
+}
+
+impl Default for InstrumentConfiguration {
+    fn default() -> Self {
+        Self {
+            error_key: "error".to_string(),
+            return_key: "returnValue".to_string(),
+        }
+    }
+}
+
+/// Fields recorded by the events that `#[instrument]` generates for errors and return values
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub(crate) enum InstrumentField {
+    Error,
+    Return,
+}
+
+impl InstrumentField {
+    /// Recognize an `#[instrument]`-generated event, which records nothing but its single field
+    pub(crate) fn from_metadata(metadata: &Metadata) -> Option<Self> {
+        let mut fields = metadata.fields().iter();
+
+        match (fields.next(), fields.next()) {
+            (Some(field), None) if field.name() == "error" => Some(Self::Error),
+            (Some(field), None) if field.name() == "return" => Some(Self::Return),
+            _ => None,
+        }
+    }
+}
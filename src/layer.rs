@@ -1,8 +1,42 @@
-use crate::event_formatter::EventFormatter;
-use std::{fmt, io, ops::Deref};
-use tracing_core::{Event, Subscriber};
+use crate::{
+    audit::{AuditLog, AuditTrail},
+    banner,
+    build_info::BuildInfo,
+    canonical::{Canonical, CanonicalEntries, CanonicalFields},
+    cloud_functions::ExecutionId,
+    cloud_run::cloud_run_labels,
+    counts::EntryCounts,
+    database::QueryLogging,
+    decorator::EntryDecorator,
+    entry::{self, EntryContext, LogEntry},
+    escalation::SeverityEscalation,
+    event_formatter::EventFormatter,
+    fields::{DebugLimits, DottedKeys, DurationFormat, ErrorFormat},
+    google::{CloudTraceConfiguration, LogSeverity},
+    health_check::{HealthCheckFilter, HealthChecks},
+    http_request::HttpRequest,
+    inherited::InheritedFields,
+    instrument::InstrumentConfiguration,
+    line_limit::LineLimit,
+    non_blocking::{non_blocking, NonBlocking, WorkerGuard},
+    processor::{Decorate, EntryProcessor},
+    recent::RecentEntries,
+    sampling::{AdaptiveSampler, AdaptiveSampling},
+    span_events::{SpanEvents, SPAN_ID_FIELD},
+    stack_trace::StackTraces,
+    summary::{ErrorSummary, EventCounts, Message},
+    tail::{self, TailBuffer, TailBuffering},
+    timings::Timings,
+    trace::{SpanIdEncoding, TraceFields, TraceResolution, WithTraceContext},
+    transform::FieldTransformer,
+    volume::{Outcome, VolumeSummaries},
+};
+use serde_json::Value;
+use std::{any::TypeId, collections::BTreeSet, fmt, io, sync::Arc, time::Duration};
+use tracing_core::{field::FieldSet, Event, Level, Metadata, Subscriber};
 use tracing_subscriber::{
-    fmt::{format::JsonFields, MakeWriter},
+    field::RecordFields,
+    fmt::{format::JsonFields, time::FormatTime, MakeWriter, TestWriter},
     registry::LookupSpan,
 };
 
@@ -24,24 +58,114 @@ impl From<Error> for fmt::Error {
     }
 }
 
+/// Invalid or conflicting configuration of a Layer, as returned by [`Layer::build`]
+#[derive(Debug, thiserror::Error, PartialEq, Eq)]
+pub enum BuildError {
+    /// The Cloud Trace project ID isn't a valid Google Cloud project ID
+    #[error("invalid project ID `{0}`, expected 6 to 30 lowercase letters, digits, or hyphens")]
+    ProjectId(String),
+    /// Two options were set that can't be used together
+    #[error("`{0}` can't be combined with `{1}`")]
+    Conflict(&'static str, &'static str),
+    /// An option was set without another option it depends on, so it would have no effect
+    #[error("`{0}` requires `{1}`")]
+    Requires(&'static str, &'static str),
+    /// Strict validation is enabled along with raw entries, whose records are parsed before
+    /// they're emitted, so that their duplicate keys are silently coerced where it can't see them
+    #[error(
+        "strict validation requires raw entries to be disabled with `with_raw_entries(false)`"
+    )]
+    StrictRawEntries,
+    /// The field namespace is empty, which would write fields under an empty key
+    #[error("the field namespace must not be empty")]
+    EmptyFieldNamespace,
+    /// The reserved key prefix is empty, which would let user fields clobber reserved keys
+    #[error("the reserved key prefix must not be empty")]
+    EmptyReservedKeyPrefix,
+}
+
 /// Create a configurable stackdriver-specific Layer and event formatter
 pub fn layer<S>() -> Layer<S>
 where
     S: Subscriber + for<'span> LookupSpan<'span>,
 {
-    Layer(
-        tracing_subscriber::fmt::layer()
+    Layer {
+        inner: tracing_subscriber::fmt::layer()
             .json()
             .event_format(EventFormatter::default()),
-    )
+        event_formatter: EventFormatter::default(),
+        inherited_fields: Arc::default(),
+        trace_context: WithTraceContext::new::<S>(),
+        sampler: None,
+        health_checks: None,
+        tail_buffering: None,
+        volume_summaries: None,
+        canonical: None,
+        recent_entries: false,
+        span_close_entries: false,
+        span_events: SpanEvents::NONE,
+        error_summaries: false,
+        event_counts: false,
+        startup_banner: false,
+        #[cfg(feature = "uuid")]
+        uuid_trace_ids: false,
+        #[cfg(feature = "trace-export")]
+        span_exporter: None,
+    }
+}
+
+/// Create a Layer preset for App Engine request log correlation, nesting entries under their
+/// request log by emitting Cloud Trace fields with decimal-encoded span IDs
+pub fn app_engine<S>(project_id: impl Into<String>) -> Layer<S>
+where
+    S: Subscriber + for<'span> LookupSpan<'span>,
+{
+    layer()
+        .with_cloud_trace(CloudTraceConfiguration {
+            project_id: project_id.into(),
+        })
+        .with_span_id_encoding(SpanIdEncoding::Decimal)
+}
+
+/// Create a Layer preset for tests that validates every entry, panicking in debug builds (or
+/// dropping the entry in release builds) on malformed output that would otherwise be silently
+/// coerced, such as duplicate keys, non-string or oversized labels, oversized entries, and
+/// invalid trace IDs. Raw entries are written like other events, with their record in an `entry`
+/// field.
+pub fn strict_test<S>() -> Layer<S>
+where
+    S: Subscriber + for<'span> LookupSpan<'span>,
+{
+    layer().with_strict_validation(true).with_raw_entries(false)
 }
 
 /// A tracing-compatible Layer implementation for Stackdriver
-pub struct Layer<S, W = fn() -> io::Stdout>(
-    tracing_subscriber::fmt::Layer<S, JsonFields, EventFormatter, W>,
-)
+pub struct Layer<S, W = fn() -> io::Stdout>
 where
-    S: Subscriber + for<'span> LookupSpan<'span>;
+    S: Subscriber + for<'span> LookupSpan<'span>,
+{
+    inner: tracing_subscriber::fmt::Layer<S, JsonFields, EventFormatter, W>,
+    // events are formatted with this formatter rather than that of the fmt layer, which only
+    // formats the fields of spans
+    event_formatter: EventFormatter,
+    inherited_fields: Arc<BTreeSet<String>>,
+    trace_context: WithTraceContext,
+    sampler: Option<AdaptiveSampler>,
+    health_checks: Option<HealthChecks>,
+    tail_buffering: Option<TailBuffering>,
+    volume_summaries: Option<VolumeSummaries>,
+    canonical: Option<Canonical>,
+    recent_entries: bool,
+    span_close_entries: bool,
+    span_events: SpanEvents,
+    error_summaries: bool,
+    event_counts: bool,
+    startup_banner: bool,
+    #[cfg(feature = "uuid")]
+    uuid_trace_ids: bool,
+    #[cfg(feature = "trace-export")]
+    span_exporter: Option<NonBlocking>,
+}
 
 impl<S, W> Layer<S, W>
 where
@@ -53,15 +177,839 @@ where
     where
         M: for<'writer> MakeWriter<'writer> + 'static,
     {
-        Layer(self.0.with_writer(make_writer))
+        self.map_writer(|_| make_writer)
+    }
+
+    /// The Layer with its MakeWriter replaced by one derived from it, keeping every other option
+    fn map_writer<M>(self, map: impl FnOnce(W) -> M) -> Layer<S, M>
+    where
+        M: for<'writer> MakeWriter<'writer> + 'static,
+    {
+        Layer {
+            inner: self.inner.map_writer(map),
+            event_formatter: self.event_formatter,
+            inherited_fields: self.inherited_fields,
+            trace_context: self.trace_context,
+            sampler: self.sampler,
+            health_checks: self.health_checks,
+            tail_buffering: self.tail_buffering,
+            volume_summaries: self.volume_summaries,
+            canonical: self.canonical,
+            recent_entries: self.recent_entries,
+            span_close_entries: self.span_close_entries,
+            span_events: self.span_events,
+            error_summaries: self.error_summaries,
+            event_counts: self.event_counts,
+            startup_banner: self.startup_banner,
+            #[cfg(feature = "uuid")]
+            uuid_trace_ids: self.uuid_trace_ids,
+            #[cfg(feature = "trace-export")]
+            span_exporter: self.span_exporter,
+        }
+    }
+
+    /// Validates the configuration of the Layer, returning it unless any of its options are
+    /// invalid or conflict with each other, rather than silently writing unexpected entries
+    pub fn build(self) -> Result<Self, BuildError> {
+        self.event_formatter.validate()?;
+
+        if self.error_summaries && !self.span_close_entries {
+            return Err(BuildError::Requires(
+                "with_span_error_summaries",
+                "with_span_close_entries",
+            ));
+        }
+
+        if self.event_counts && !self.span_close_entries {
+            return Err(BuildError::Requires(
+                "with_span_event_counts",
+                "with_span_close_entries",
+            ));
+        }
+
+        Ok(self)
+    }
+
+    /// The Layer with its formatter replaced by one derived from it
+    fn map_event_format(self, map: impl FnOnce(EventFormatter) -> EventFormatter) -> Self {
+        Self {
+            event_formatter: map(self.event_formatter),
+            ..self
+        }
+    }
+
+    /// Formats the fields of an event or span as a Stackdriver entry with the options of this
+    /// Layer, like [`format_entry`](crate::format_entry) does for a default Layer
+    pub fn format_entry(
+        &self,
+        metadata: &Metadata<'_>,
+        fields: &impl RecordFields,
+        context: &EntryContext,
+    ) -> Value {
+        self.event_formatter()
+            .format_entry(metadata, fields, context)
+    }
+
+    /// The formatter of the entries of this Layer
+    fn event_formatter(&self) -> &EventFormatter {
+        &self.event_formatter
+    }
+
+    /// Writes events to standard output, which is the default
+    pub fn with_stdout(self) -> Layer<S> {
+        self.with_writer(io::stdout)
+    }
+
+    /// Writes events to standard error
+    pub fn with_stderr(self) -> Layer<S, fn() -> io::Stderr> {
+        self.with_writer(io::stderr)
+    }
+
+    /// Writes events through the output capturing of `cargo test`, so that entries are only
+    /// printed for failing tests
+    pub fn with_test_writer(self) -> Layer<S, TestWriter> {
+        self.with_writer(TestWriter::new())
+    }
+
+    /// Writes events to `writer` from a background thread, returning the Layer along with a
+    /// [`WorkerGuard`] that flushes buffered events when dropped.
+    pub fn with_non_blocking<N>(self, writer: N) -> (Layer<S, NonBlocking>, WorkerGuard)
+    where
+        N: io::Write + Send + 'static,
+    {
+        let (non_blocking, guard) = non_blocking(writer);
+        (self.with_writer(non_blocking), guard)
+    }
+
+    /// Sends events directly to the Cloud Logging API from a background thread, returning the
+    /// Layer along with a [`WorkerGuard`] that flushes buffered events when dropped.
+    #[cfg_attr(docsrs, doc(cfg(feature = "exporter")))]
+    #[cfg(feature = "exporter")]
+    pub fn with_exporter(
+        self,
+        exporter: crate::exporter::Exporter,
+    ) -> (Layer<S, NonBlocking>, WorkerGuard) {
+        self.with_non_blocking(exporter)
+    }
+
+    /// Sends events directly to the Cloud Logging API from a background thread in addition to the
+    /// current writer, returning the Layer along with a [`WorkerGuard`] that flushes buffered
+    /// events when dropped. Failures of either destination don't affect the other.
+    #[cfg_attr(docsrs, doc(cfg(feature = "exporter")))]
+    #[cfg(feature = "exporter")]
+    pub fn with_tee_exporter(
+        self,
+        exporter: crate::exporter::Exporter,
+    ) -> (Layer<S, crate::writer::Tee<W, NonBlocking>>, WorkerGuard) {
+        let (non_blocking, guard) = non_blocking(exporter);
+        let layer =
+            self.map_writer(|make_writer| crate::writer::Tee::new(make_writer, non_blocking));
+
+        (layer, guard)
+    }
+
+    /// Reports ERROR and more severe events to the Error Reporting API from a background thread
+    /// in addition to the current writer, returning the Layer along with a [`WorkerGuard`] that
+    /// flushes queued events when dropped
+    #[cfg_attr(docsrs, doc(cfg(feature = "error-reporting")))]
+    #[cfg(feature = "error-reporting")]
+    pub fn with_error_reporter(
+        self,
+        error_reporter: crate::error_reporting::ErrorReporter,
+    ) -> (Layer<S, crate::writer::Tee<W, NonBlocking>>, WorkerGuard) {
+        let (non_blocking, guard) = non_blocking(error_reporter);
+        let layer =
+            self.map_writer(|make_writer| crate::writer::Tee::new(make_writer, non_blocking));
+
+        (layer, guard)
+    }
+
+    /// Exports closed spans to the Cloud Trace API from a background thread, within the trace of
+    /// the nearest span in their scope with a `trace_id`, returning the Layer along with a
+    /// [`WorkerGuard`] that flushes queued spans when dropped
+    #[cfg_attr(docsrs, doc(cfg(feature = "trace-export")))]
+    #[cfg(feature = "trace-export")]
+    pub fn with_trace_exporter(
+        self,
+        trace_exporter: crate::trace_export::TraceExporter,
+    ) -> (Self, WorkerGuard) {
+        let (span_exporter, guard) = crate::non_blocking::NonBlockingBuilder::default()
+            .with_drop_report_interval(None)
+            .finish(trace_exporter);
+
+        let layer = Self {
+            span_exporter: Some(span_exporter),
+            ..self
+        };
+
+        (layer, guard)
     }
 
     /// Configures whether or not Events will include source locations in a special LogEntry field
     pub fn with_source_location(self, include_source_location: bool) -> Self {
-        Self(self.0.map_event_format(|mut event_formatter| {
+        self.map_event_format(|mut event_formatter| {
             event_formatter.include_source_location = include_source_location;
             event_formatter
-        }))
+        })
+    }
+
+    /// Sets the format of durations recorded with [`as_duration`](crate::as_duration)
+    pub fn with_duration_format(self, duration_format: DurationFormat) -> Self {
+        self.map_event_format(|mut event_formatter| {
+            event_formatter.duration_format = duration_format;
+            event_formatter
+        })
+    }
+
+    /// Configures whether or not every entry is validated, as in [`strict_test`]. Strict
+    /// validation requires raw entries to be disabled with [`Layer::with_raw_entries`].
+    pub fn with_strict_validation(self, strict: bool) -> Self {
+        self.map_event_format(|mut event_formatter| {
+            event_formatter.strict = strict;
+            event_formatter
+        })
+    }
+
+    /// Nests every field recorded on Events, other than their message and special fields like
+    /// `labels` and `http_request`, under an object with the given key. This prevents collisions
+    /// with the formatter's own keys and keeps the schemas of BigQuery sinks stable.
+    pub fn with_field_namespace(self, field_namespace: impl Into<String>) -> Self {
+        let field_namespace = field_namespace.into();
+
+        self.map_event_format(|mut event_formatter| {
+            event_formatter.field_namespace = Some(field_namespace);
+            event_formatter
+        })
+    }
+
+    /// Adds a `consoleUrl` to ERROR and more severe entries within a trace, linking to the Logs
+    /// Explorer filtered to the entries of the trace within the given window either side of the
+    /// entry. Requires Cloud Trace support, as in [`Layer::with_cloud_trace`].
+    pub fn with_console_urls(self, window: Duration) -> Self {
+        self.map_event_format(|mut event_formatter| {
+            event_formatter.console_url_window = Some(window);
+            event_formatter
+        })
+    }
+
+    /// Sets how fields with dots in their names, e.g. `user.id`, are written to entries: as
+    /// nested objects, as a single camelCase key (the default), or as literal dotted keys
+    pub fn with_dotted_keys(self, dotted_keys: DottedKeys) -> Self {
+        self.map_event_format(|mut event_formatter| {
+            event_formatter.dotted_keys = dotted_keys;
+            event_formatter
+        })
+    }
+
+    /// Lifts `key=value` pairs within messages, e.g. `user=42 path="/a b"`, into fields of their
+    /// entries, easing the migration of plain-text call sites to structured logging. Fields
+    /// recorded on events take precedence over those lifted from their messages.
+    pub fn with_logfmt_messages(self, logfmt_messages: bool) -> Self {
+        self.map_event_format(|mut event_formatter| {
+            event_formatter.logfmt_messages = logfmt_messages;
+            event_formatter
+        })
+    }
+
+    /// Gathers the `metric.` fields of events into a `metrics` object, e.g. `metric.order_value =
+    /// 12.5` as `metrics.orderValue`, so that log-based metrics count or extract the distribution
+    /// of `jsonPayload.metrics.` fields consistently across services
+    pub fn with_metric_fields(self, metric_fields: bool) -> Self {
+        self.map_event_format(|mut event_formatter| {
+            event_formatter.metric_fields = metric_fields;
+            event_formatter
+        })
+    }
+
+    /// Writes a `callsite` fingerprint with each entry, a hash of the target, file, line, and
+    /// name of the statement it comes from, so that entries can be grouped by log statement
+    /// regardless of the values interpolated into their messages
+    pub fn with_callsite_fingerprints(self, callsite_fingerprints: bool) -> Self {
+        self.map_event_format(|mut event_formatter| {
+            event_formatter.callsite_fingerprints = callsite_fingerprints;
+            event_formatter
+        })
+    }
+
+    /// Writes the template of each message as its `messageTemplate`, e.g. `user {} logged in from
+    /// {}`, so that entries can be grouped by the shape of their message and counted by log-based
+    /// metrics without a distinct value for each interpolated value. Format strings aren't
+    /// retained by `tracing`, so templates replace the words of messages that look interpolated,
+    /// those with digits or within quotes, with `{}`.
+    pub fn with_message_templates(self, message_templates: bool) -> Self {
+        self.map_event_format(|mut event_formatter| {
+            event_formatter.message_templates = message_templates;
+            event_formatter
+        })
+    }
+
+    /// Limits the Debug representation of fields recorded with `?` by length and nesting depth,
+    /// so that an accidental `?huge_state_struct` can't produce megabyte entries
+    pub fn with_debug_limits(self, debug_limits: DebugLimits) -> Self {
+        self.map_event_format(|mut event_formatter| {
+            event_formatter.debug_limits = Some(debug_limits);
+            event_formatter
+        })
+    }
+
+    /// Sets how fields holding errors are written to entries: as recorded (the default), or with
+    /// Display, keeping their Debug representation for DEBUG and TRACE entries so that production
+    /// entries aren't flooded with Debug dumps of deeply nested error types. Errors recorded with
+    /// `?` are written with Display when wrapped with [`as_error`](crate::as_error), as described
+    /// by [`ErrorFormat::Display`].
+    pub fn with_error_format(self, error_format: ErrorFormat) -> Self {
+        self.map_event_format(|mut event_formatter| {
+            event_formatter.error_format = error_format;
+            event_formatter
+        })
+    }
+
+    /// Sets the prefix that user fields named after keys reserved by Cloud Logging or written by
+    /// this layer, i.e. `severity`, `time`, `target`, `span`, `httpRequest` and
+    /// `logging.googleapis.com/*`, are
+    /// renamed with so that they can't spoof or clobber them. Defaults to `user.`.
+    pub fn with_reserved_key_prefix(self, reserved_key_prefix: impl Into<String>) -> Self {
+        let reserved_key_prefix = reserved_key_prefix.into();
+
+        self.map_event_format(|mut event_formatter| {
+            event_formatter.reserved_key_prefix = reserved_key_prefix;
+            event_formatter
+        })
+    }
+
+    /// Configures whether or not the current Span of each Event includes its registry `id` and
+    /// its parent's `parentId`, so that the Span tree can be reconstructed from entries without
+    /// distributed tracing, e.g. for batch jobs
+    pub fn with_span_ids(self, include_span_ids: bool) -> Self {
+        self.map_event_format(|mut event_formatter| {
+            event_formatter.include_span_ids = include_span_ids;
+            event_formatter
+        })
+    }
+
+    /// Configures whether or not Events use the split `timestampSeconds` and `timestampNanos`
+    /// fields expected by the legacy google-fluentd logging agent instead of an RFC 3339 `time`
+    pub fn with_legacy_agent_fields(self, legacy_agent_fields: bool) -> Self {
+        self.map_event_format(|mut event_formatter| {
+            event_formatter.legacy_agent_fields = legacy_agent_fields;
+            event_formatter
+        })
+    }
+
+    /// Sets the maximum length in bytes of string field values, including the message. Longer
+    /// values are truncated and end with a `…[truncated N bytes]` marker.
+    pub fn with_max_value_length(self, max_value_length: usize) -> Self {
+        self.map_event_format(|mut event_formatter| {
+            event_formatter.max_value_length = Some(max_value_length);
+            event_formatter
+        })
+    }
+
+    /// Configures whether or not Events within Spans are grouped into operations, using the
+    /// root Span's ID and name as the special LogEntry operation's `id` and `producer`
+    pub fn with_span_operations(self, span_operations: bool) -> Self {
+        self.map_event_format(|mut event_formatter| {
+            event_formatter.span_operations = span_operations;
+            event_formatter
+        })
+    }
+
+    /// Enables structured output for the events generated by `#[instrument(err)]`, which are
+    /// written with ERROR severity and their error as the message, and `#[instrument(ret)]`
+    pub fn with_instrument_fields(self, configuration: InstrumentConfiguration) -> Self {
+        self.map_event_format(|mut event_formatter| {
+            event_formatter.instrument = Some(configuration);
+            event_formatter
+        })
+    }
+
+    /// Caps the severity of Events from `target` and its submodules, e.g. to write a dependency's
+    /// warnings as DEBUG without dropping them. The most specific matching target applies.
+    pub fn with_severity_cap(self, target: impl Into<String>, max_severity: LogSeverity) -> Self {
+        let target = target.into();
+
+        self.map_event_format(|mut event_formatter| {
+            event_formatter.severity_caps.insert(target, max_severity);
+            event_formatter
+        })
+    }
+
+    /// Adds a rule raising the severity of Events whose field values reach a threshold, keeping
+    /// alerting thresholds in logging configuration rather than at call sites. Severity caps
+    /// still apply to escalated Events.
+    pub fn with_severity_escalation(self, escalation: SeverityEscalation) -> Self {
+        self.map_event_format(|mut event_formatter| {
+            event_formatter.severity_escalations.push(escalation);
+            event_formatter
+        })
+    }
+
+    /// Samples DEBUG and INFO Events while the entry rate exceeds a threshold, writing a NOTICE
+    /// entry whenever the effective sampling rate changes
+    pub fn with_adaptive_sampling(self, configuration: AdaptiveSampling) -> Self {
+        Self {
+            sampler: Some(AdaptiveSampler::new(configuration)),
+            ..self
+        }
+    }
+
+    /// Moves multi-line Rust backtraces and panics within the messages of ERROR and more severe
+    /// Events, e.g. from `error!("{err:?}")`, into their `stack_trace` field, and types their entries
+    /// as `ReportedErrorEvent`s so that Error Reporting groups them
+    pub fn with_stack_trace_detection(self, stack_trace_detection: bool) -> Self {
+        self.map_event_format(|mut event_formatter| {
+            event_formatter.stack_trace_detection = stack_trace_detection;
+            event_formatter
+        })
+    }
+
+    /// Deduplicates the `stack_trace` fields of ERROR and more severe Events within a window:
+    /// the first occurrence of a trace is written in full, later ones as a `stackTraceRef` hash
+    /// and a `stackTraceCount` of the occurrences within the window
+    pub fn with_stack_trace_deduplication(self, window: Duration) -> Self {
+        self.map_event_format(|mut event_formatter| {
+            event_formatter.stack_traces = Some(StackTraces::new(window));
+            event_formatter
+        })
+    }
+
+    /// Configures whether or not an entry with a `close` message is written for each Span when it
+    /// closes, with the Span's metadata, severity, and fields, along with the `busyNs` spent
+    /// within the Span and the `idleNs` spent outside of it, e.g. awaiting in an async task.
+    /// The close entries of request Spans, which declare `http_request.` fields, carry a complete
+    /// `httpRequest` with the Span's lifetime as its `latency`, as an access log entry.
+    pub fn with_span_close_entries(self, span_close_entries: bool) -> Self {
+        Self {
+            span_close_entries,
+            ..self
+        }
+    }
+
+    /// Writes a lightweight DEBUG entry with an `enter` or `exit` message, along with the Span's
+    /// name and `spanId`, each time a Span is entered or exited, as selected by `span_events`
+    pub fn with_span_events(self, span_events: SpanEvents) -> Self {
+        Self {
+            span_events,
+            ..self
+        }
+    }
+
+    /// Folds the INFO events within request Spans, which declare `http_request.` fields, into one
+    /// canonical entry per request, written as the Span's close entry with the fields of the
+    /// folded events and their `canonicalEvents` count
+    pub fn with_canonical_entries(self, configuration: CanonicalEntries) -> Self {
+        Self {
+            canonical: Some(Canonical::new(configuration)),
+            ..self
+        }
+    }
+
+    /// Configures whether or not Span close entries include an `errorCount` of the ERROR and WARN
+    /// Events within the Span, along with the `firstError` and `lastError` messages. Requires
+    /// close entries to be enabled with [`with_span_close_entries`](Self::with_span_close_entries).
+    pub fn with_span_error_summaries(self, error_summaries: bool) -> Self {
+        Self {
+            error_summaries,
+            ..self
+        }
+    }
+
+    /// Configures whether or not Span close entries include the `eventCounts` of each severity of the
+    /// Events within the Span, e.g. to query requests with warnings. Requires close entries to be
+    /// enabled with [`with_span_close_entries`](Self::with_span_close_entries).
+    pub fn with_span_event_counts(self, event_counts: bool) -> Self {
+        Self {
+            event_counts,
+            ..self
+        }
+    }
+
+    /// Counts the entries written by top-level target and severity in `entry_counts`, a handle
+    /// that stays usable after the Layer is installed
+    pub fn with_entry_counts(self, entry_counts: EntryCounts) -> Self {
+        self.map_event_format(|mut event_formatter| {
+            event_formatter.entry_counts = Some(entry_counts);
+            event_formatter
+        })
+    }
+
+    /// Keeps the last entries formatted in `recent_entries`, a handle that stays usable after the
+    /// Layer is installed. Entries dropped by adaptive sampling are formatted and kept as well.
+    pub fn with_recent_entries(self, recent_entries: RecentEntries) -> Self {
+        Self {
+            recent_entries: true,
+            ..self.map_event_format(|mut event_formatter| {
+                event_formatter.recent_entries = Some(recent_entries);
+                event_formatter
+            })
+        }
+    }
+
+    /// Reshapes the query logs of `sqlx` and `diesel_logger` into structured `db.statement`,
+    /// `db.rows`, and `db.elapsed_ms` fields, truncating and redacting statements as configured
+    pub fn with_query_logging(self, query_logging: QueryLogging) -> Self {
+        self.map_event_format(|mut event_formatter| {
+            event_formatter.query_logging = Some(query_logging);
+            event_formatter
+        })
+    }
+
+    /// Shapes audit events, from the configured targets or recording `audit = true`, into an
+    /// `audit` record of their `actor`, `action`, `resource`, and `outcome`, numbered by `sequence`
+    /// with a unique `insertId` and written to the configured log
+    pub fn with_audit_log(self, audit_log: AuditLog) -> Self {
+        self.map_event_format(|mut event_formatter| {
+            event_formatter.audit_trail = Some(AuditTrail::new(audit_log));
+            event_formatter
+        })
+    }
+
+    /// Configures whether or not audit entries are chained by hash, with the SHA-256 hash of the
+    /// previous audit entry as their `audit.prevHash` and that of their own canonical JSON as their
+    /// `audit.entryHash`, so that deleted or altered entries are detected by
+    /// [`verify_audit_chain`](crate::verify_audit_chain). Requires audit logs to be enabled with
+    /// [`with_audit_log`](Self::with_audit_log).
+    ///
+    /// Entries are linked as they're written, once split by a line limit and flushed by tail
+    /// buffering, and in the order they're written in. The EventFormatter of another fmt Layer
+    /// links them as it formats them instead, so entries it writes concurrently may be out of
+    /// order.
+    #[cfg_attr(docsrs, doc(cfg(feature = "audit-chain")))]
+    #[cfg(feature = "audit-chain")]
+    pub fn with_audit_hash_chain(self, audit_hash_chain: bool) -> Self {
+        self.map_event_format(|mut event_formatter| {
+            event_formatter.audit_chain = audit_hash_chain.then(crate::audit::HashChain::new);
+            event_formatter
+        })
+    }
+
+    /// Scans the string values of fields, including messages, for sensitive data such as emails,
+    /// credit card numbers, and bearer tokens, masking, hashing, or dropping the fields holding
+    /// what matches before entries are written, and redacts fields by name with the configured
+    /// [`Redactor`](crate::Redactor) strategies
+    #[cfg_attr(docsrs, doc(cfg(feature = "scrubbing")))]
+    #[cfg(feature = "scrubbing")]
+    pub fn with_value_scrubbing(self, scrubbing: crate::ValueScrubbing) -> Self {
+        self.map_event_format(|mut event_formatter| {
+            event_formatter.scrubbing = Some(scrubbing);
+            event_formatter
+        })
+    }
+
+    /// Marks Span fields as inherited, stamping their values on every Event within the Span, at
+    /// any depth. Event fields take precedence over inherited fields of the same name.
+    pub fn with_inherited_fields<I>(self, fields: I) -> Self
+    where
+        I: IntoIterator,
+        I::Item: Into<String>,
+    {
+        let mut inherited_fields = (*self.inherited_fields).clone();
+        inherited_fields.extend(fields.into_iter().map(Into::into));
+
+        Self {
+            inherited_fields: Arc::new(inherited_fields),
+            ..self
+        }
+    }
+
+    /// Enables Cloud Trace correlation for Events within Spans that record `trace_id`, `span_id`,
+    /// and `trace_sampled` fields, writing them to their special LogEntry fields. Spans recording a
+    /// `gcp_project` field correlate the Events within them with traces of that project instead of
+    /// the configured one, e.g. for gateways proxying requests from other projects.
+    pub fn with_cloud_trace(self, configuration: CloudTraceConfiguration) -> Self {
+        self.map_event_format(|mut event_formatter| {
+            event_formatter.cloud_trace = Some(configuration);
+            event_formatter
+        })
+    }
+
+    /// Sets the encoding of the `logging.googleapis.com/spanId` field, which defaults to the
+    /// 16-character hex expected by Cloud Logging. App Engine request log correlation expects
+    /// decimal span IDs instead, as configured by the [`app_engine`] preset.
+    pub fn with_span_id_encoding(self, span_id_encoding: SpanIdEncoding) -> Self {
+        self.map_event_format(|mut event_formatter| {
+            event_formatter.span_id_encoding = span_id_encoding;
+            event_formatter
+        })
+    }
+
+    /// Sets the policy choosing the trace that Events are correlated with when several Spans in
+    /// their scope record a `trace_id`, which defaults to the nearest Span's trace
+    pub fn with_trace_resolution(self, trace_resolution: TraceResolution) -> Self {
+        self.map_event_format(|mut event_formatter| {
+            event_formatter.trace_resolution = trace_resolution;
+            event_formatter
+        })
+    }
+
+    /// Configures whether or not Events are only correlated with sampled traces: within Spans
+    /// recording `trace_sampled = false`, the `logging.googleapis.com/trace` and `spanId` fields
+    /// are omitted and the trace ID is written as a plain `traceId` instead, so that Cloud Logging
+    /// doesn't link entries to traces that were never recorded
+    pub fn with_sampled_traces_only(self, sampled_traces_only: bool) -> Self {
+        self.map_event_format(|mut event_formatter| {
+            event_formatter.sampled_traces_only = sampled_traces_only;
+            event_formatter
+        })
+    }
+
+    /// Configures whether or not a UUID recorded as a Span's `trace_id` is used as the trace ID,
+    /// by stripping its hyphens
+    #[cfg_attr(docsrs, doc(cfg(feature = "uuid")))]
+    #[cfg(feature = "uuid")]
+    pub fn with_uuid_trace_ids(self, uuid_trace_ids: bool) -> Self {
+        Self {
+            uuid_trace_ids,
+            ..self
+        }
+    }
+
+    /// Limits the length of the lines of entries, truncating or splitting oversized entries so
+    /// that container runtimes don't split their lines and corrupt their JSON
+    pub fn with_line_limit(self, line_limit: LineLimit) -> Self {
+        self.map_event_format(|mut event_formatter| {
+            event_formatter.line_limit = Some(line_limit);
+            event_formatter
+        })
+    }
+
+    /// Configures whether or not Events can set the time of their entry with a `timestamp` field,
+    /// as an RFC 3339 string or nanoseconds since the Unix epoch, e.g. when re-emitting buffered
+    /// or replayed records. Unparseable timestamps are written as regular fields.
+    pub fn with_timestamp_overrides(self, timestamp_overrides: bool) -> Self {
+        self.map_event_format(|mut event_formatter| {
+            event_formatter.timestamp_overrides = timestamp_overrides;
+            event_formatter
+        })
+    }
+
+    /// Configures whether or not the records of [`emit_raw_entry`](crate::emit_raw_entry) are
+    /// written as-is, which is the default. Otherwise, they're written like other events, with
+    /// their record in an `entry` field.
+    pub fn with_raw_entries(self, raw_entries: bool) -> Self {
+        self.map_event_format(|mut event_formatter| {
+            event_formatter.raw_entries = raw_entries;
+            event_formatter
+        })
+    }
+
+    /// Configures whether or not entry timestamps are computed as a wall-clock time read once, on
+    /// the first entry, plus the monotonic time elapsed since, so that they never decrease even if
+    /// the system clock steps backwards, at the cost of ignoring clock adjustments made since
+    pub fn with_monotonic_timestamps(self, monotonic_time: bool) -> Self {
+        self.map_event_format(|mut event_formatter| {
+            event_formatter.monotonic_time = monotonic_time;
+            event_formatter
+        })
+    }
+
+    /// Sets the timer that writes the `time` of entries, e.g. with a UTC offset, a coarse clock,
+    /// or a fixed time in tests, instead of the current time in RFC 3339 format. Timestamps
+    /// overridden by events, and the split timestamps of the legacy logging agent, are unaffected.
+    pub fn with_timer<T>(self, timer: T) -> Self
+    where
+        T: FormatTime + Send + Sync + 'static,
+    {
+        self.map_event_format(|mut event_formatter| {
+            event_formatter.timer = Some(Box::new(timer));
+            event_formatter
+        })
+    }
+
+    /// Sets a hook that every field of an entry goes through before it's written, renaming,
+    /// coercing, or dropping fields where the built-in options fall short, e.g.
+    /// `with_field_transformer(|key: &str, value| Some((key.replace("ctx_", ""), value)))`
+    pub fn with_field_transformer<T>(self, field_transformer: T) -> Self
+    where
+        T: FieldTransformer + 'static,
+    {
+        self.map_event_format(|mut event_formatter| {
+            event_formatter.field_transformer = Some(Box::new(field_transformer));
+            event_formatter
+        })
+    }
+
+    /// Sets a hook that every entry goes through once it's assembled, right before it's written,
+    /// as a [`LogEntry`](crate::LogEntry) to mutate, e.g. to derive labels from its target and
+    /// severity. Entries are decorated after strict validation, as a step of the processor
+    /// pipeline after the processors added before it.
+    pub fn with_entry_decorator<D>(self, entry_decorator: D) -> Self
+    where
+        D: EntryDecorator + 'static,
+    {
+        self.with_processor(Decorate(entry_decorator))
+    }
+
+    /// Adds a step to the pipeline that every entry goes through once it's assembled, right before
+    /// it's written, after the steps added before it. Processors mutate entries as a
+    /// [`LogEntry`](crate::LogEntry) and can drop them, skipping the steps after them, e.g. to
+    /// sample entries, redact them, or route them away.
+    pub fn with_processor<P>(self, processor: P) -> Self
+    where
+        P: EntryProcessor + 'static,
+    {
+        self.map_event_format(|mut event_formatter| {
+            event_formatter.processors.push(Box::new(processor));
+            event_formatter
+        })
+    }
+
+    /// Drops or downsamples the DEBUG and INFO entries of health check requests, matched by the
+    /// path of the URL recorded in a field, e.g. `/healthz` or `/_ah/health`
+    pub fn with_health_check_filter(self, configuration: HealthCheckFilter) -> Self {
+        Self {
+            health_checks: Some(HealthChecks::new(configuration)),
+            ..self
+        }
+    }
+
+    /// Buffers the DEBUG and TRACE entries of each root span, writing them just before the span
+    /// closes only if it recorded an ERROR event or exceeded a latency threshold. DEBUG events
+    /// must still be enabled by the subscriber's filter to be buffered.
+    pub fn with_tail_buffering(self, configuration: TailBuffering) -> Self {
+        Self {
+            tail_buffering: Some(configuration),
+            ..self
+        }
+    }
+
+    /// Writes a NOTICE entry summarizing how many entries of each severity were written, sampled
+    /// away by adaptive sampling, rate-limited by the health check filter, or dropped by tail
+    /// buffering, along with the first entry after each interval
+    pub fn with_volume_summaries(self, interval: Duration) -> Self {
+        Self {
+            volume_summaries: Some(VolumeSummaries::new(interval)),
+            ..self
+        }
+    }
+
+    /// Configures whether or not a NOTICE entry summarizing the effective configuration, such as
+    /// the output format, sampling, project ID, and detected monitored resource, is written when
+    /// the Layer is added to a subscriber. The banner doesn't wait for the metadata server: the
+    /// project ID and resource are detected from the environment and metadata already fetched.
+    pub fn with_startup_banner(self, startup_banner: bool) -> Self {
+        Self {
+            startup_banner,
+            ..self
+        }
+    }
+
+    /// Configures whether or not Events will be labeled with the Cloud Run service, revision,
+    /// configuration, and location that emitted them. Labels are detected from the `K_SERVICE`,
+    /// `K_REVISION`, and `K_CONFIGURATION` environment variables and the metadata server when
+    /// this method is called, and are omitted when not running on Cloud Run.
+    pub fn with_cloud_run_labels(self, include_cloud_run_labels: bool) -> Self {
+        let labels = include_cloud_run_labels.then(cloud_run_labels).flatten();
+
+        self.map_event_format(|mut event_formatter| {
+            event_formatter
+                .labels
+                .retain(|key, _| !key.starts_with("run.googleapis.com/"));
+            event_formatter.labels.extend(labels.into_iter().flatten());
+            event_formatter
+        })
+    }
+
+    /// Labels Events with the build information of the binary that emitted them, e.g. as captured
+    /// by [`build_info!`](crate::build_info), as `build_version`, `build_sha`, `build_timestamp`, and
+    /// `build_rustc_version`. Unknown build information is omitted.
+    pub fn with_build_info(self, build_info: BuildInfo) -> Self {
+        let labels = build_info.labels();
+
+        self.map_event_format(|mut event_formatter| {
+            event_formatter
+                .labels
+                .retain(|key, _| !BuildInfo::is_label(key));
+            event_formatter.labels.extend(labels);
+            event_formatter
+        })
+    }
+
+    /// Convert a UUID recorded as a trace ID into a Cloud Trace ID, when enabled
+    fn normalize_trace_id(&self, trace_fields: &mut TraceFields) {
+        #[cfg(feature = "uuid")]
+        if self.uuid_trace_ids {
+            if let Some(trace_id) = trace_fields
+                .trace_id
+                .as_deref()
+                .and_then(crate::uuid::uuid_trace_id)
+            {
+                trace_fields.trace_id = Some(trace_id);
+            }
+        }
+
+        #[cfg(not(feature = "uuid"))]
+        let _ = trace_fields;
+    }
+
+    /// Count entries towards volume summaries, writing the summary of the last interval once it
+    /// has passed
+    fn record_volume(&self, severity: &LogSeverity, outcome: Outcome, entries: u64) {
+        let summary = self
+            .volume_summaries
+            .as_ref()
+            .filter(|_| entries > 0)
+            .and_then(|volume_summaries| volume_summaries.record(*severity, outcome, entries));
+
+        if let Some(summary) = summary {
+            self.write_report(summary.entry());
+        }
+    }
+
+    /// Format an event and write its entry straight to the writer of this Layer, in a single write
+    fn write_event(&self, event: &Event<'_>, context: &tracing_subscriber::layer::Context<'_, S>) {
+        let span = event
+            .parent()
+            .and_then(|id| context.span(id))
+            .or_else(|| context.lookup_current());
+        let mut writer = self.inner.writer().make_writer_for(event.metadata());
+
+        let _ = self
+            .event_formatter()
+            .write_event::<S, JsonFields>(span, &mut writer, event);
+    }
+
+    /// Write an entry reported by this Layer itself, such as a change of its sampling rate
+    fn write_report(&self, entry: LogEntry) {
+        let _ = self
+            .event_formatter()
+            .write_report(&mut self.inner.writer().make_writer(), entry);
+    }
+
+    /// Write the lifecycle entry of a Span, as an event with the Span's own metadata
+    fn emit_span_event(
+        &self,
+        id: &tracing_core::span::Id,
+        message: &'static str,
+        context: tracing_subscriber::layer::Context<'_, S>,
+    ) {
+        let Some(metadata) = context.span(id).map(|span| span.metadata()) else {
+            return;
+        };
+
+        let fields = FieldSet::new(&["message", "severity", SPAN_ID_FIELD], metadata.callsite());
+        let span_id = id.into_u64();
+        let field = |name| fields.field(name).expect("field set has the field");
+        let (message_field, severity_field, span_id_field) =
+            (field("message"), field("severity"), field(SPAN_ID_FIELD));
+        let values = [
+            (
+                &message_field,
+                Some(&message as &dyn tracing_core::field::Value),
+            ),
+            (
+                &severity_field,
+                Some(&"DEBUG" as &dyn tracing_core::field::Value),
+            ),
+            (
+                &span_id_field,
+                Some(&span_id as &dyn tracing_core::field::Value),
+            ),
+        ];
+        let values = fields.value_set(&values);
+        let event = Event::new_child_of(id.clone(), metadata, &values);
+
+        self.write_event(&event, &context);
     }
 }
 
@@ -71,13 +1019,78 @@ where
     S: Subscriber + for<'span> LookupSpan<'span>,
     W: for<'writer> MakeWriter<'writer> + 'static,
 {
+    fn on_layer(&mut self, subscriber: &mut S) {
+        self.inner.on_layer(subscriber);
+
+        if self.startup_banner {
+            banner::report(
+                &mut self.inner.writer().make_writer(),
+                self.event_formatter(),
+                self.sampler.as_ref().map(AdaptiveSampler::configuration),
+            );
+        }
+
+        let _ = entry::write_pending(
+            self.event_formatter(),
+            &mut self.inner.writer().make_writer(),
+        );
+    }
+
     fn on_new_span(
         &self,
         attrs: &tracing_core::span::Attributes<'_>,
         id: &tracing_core::span::Id,
         context: tracing_subscriber::layer::Context<'_, S>,
     ) {
-        self.0.on_new_span(attrs, id, context)
+        if let Some(span) = context.span(id) {
+            let mut trace_fields = TraceFields::default();
+            attrs.record(&mut trace_fields);
+            self.normalize_trace_id(&mut trace_fields);
+            span.extensions_mut().replace(trace_fields);
+
+            if HttpRequest::is_request_span(attrs.metadata()) {
+                let mut http_request = HttpRequest::new();
+                attrs.record(&mut http_request);
+                span.extensions_mut().replace(http_request);
+
+                if self.canonical.is_some() {
+                    span.extensions_mut().replace(CanonicalFields::default());
+                }
+            }
+
+            if ExecutionId::is_execution_span(attrs.metadata()) {
+                let mut execution_id = ExecutionId::default();
+                attrs.record(&mut execution_id);
+                span.extensions_mut().replace(execution_id);
+            }
+
+            if !self.inherited_fields.is_empty() {
+                let mut inherited_fields = span
+                    .parent()
+                    .and_then(|parent| parent.extensions().get::<InheritedFields>().cloned())
+                    .unwrap_or_else(|| InheritedFields::new(self.inherited_fields.clone()));
+
+                attrs.record(&mut inherited_fields);
+                span.extensions_mut().replace(inherited_fields);
+            }
+
+            if self.span_close_entries {
+                span.extensions_mut().replace(Timings::new());
+            }
+
+            if self.tail_buffering.is_some() && span.parent().is_none() {
+                span.extensions_mut().replace(TailBuffer::new());
+            }
+
+            #[cfg(feature = "trace-export")]
+            if self.span_exporter.is_some() {
+                let mut record = crate::trace_export::SpanRecord::new();
+                attrs.record(&mut record);
+                span.extensions_mut().replace(record);
+            }
+        }
+
+        self.inner.on_new_span(attrs, id, context)
     }
 
     fn on_record(
@@ -86,7 +1099,35 @@ where
         values: &tracing_core::span::Record<'_>,
         context: tracing_subscriber::layer::Context<'_, S>,
     ) {
-        self.0.on_record(span, values, context)
+        if let Some(span) = context.span(span) {
+            // fields declared as Empty and recorded later, e.g. a trace_id extracted from headers
+            if let Some(trace_fields) = span
+                .extensions_mut()
+                .get_mut::<TraceFields>()
+                .filter(|trace_fields| !trace_fields.typed)
+            {
+                values.record(trace_fields);
+                self.normalize_trace_id(trace_fields);
+            }
+
+            if let Some(inherited_fields) = span.extensions_mut().get_mut::<InheritedFields>() {
+                values.record(inherited_fields);
+            }
+
+            if let Some(http_request) = span.extensions_mut().get_mut::<HttpRequest>() {
+                values.record(http_request);
+            }
+
+            #[cfg(feature = "trace-export")]
+            if let Some(record) = span
+                .extensions_mut()
+                .get_mut::<crate::trace_export::SpanRecord>()
+            {
+                values.record(record);
+            }
+        }
+
+        self.inner.on_record(span, values, context)
     }
 
     fn on_enter(
@@ -94,7 +1135,17 @@ where
         id: &tracing_core::span::Id,
         context: tracing_subscriber::layer::Context<'_, S>,
     ) {
-        self.0.on_enter(id, context)
+        if let Some(span) = context.span(id) {
+            if let Some(timings) = span.extensions_mut().get_mut::<Timings>() {
+                timings.idle();
+            }
+        }
+
+        if self.span_events.is_emitted(SpanEvents::ENTER) {
+            self.emit_span_event(id, "enter", context.clone());
+        }
+
+        self.inner.on_enter(id, context)
     }
 
     fn on_exit(
@@ -102,7 +1153,17 @@ where
         id: &tracing_core::span::Id,
         context: tracing_subscriber::layer::Context<'_, S>,
     ) {
-        self.0.on_exit(id, context)
+        if let Some(span) = context.span(id) {
+            if let Some(timings) = span.extensions_mut().get_mut::<Timings>() {
+                timings.busy();
+            }
+        }
+
+        if self.span_events.is_emitted(SpanEvents::EXIT) {
+            self.emit_span_event(id, "exit", context.clone());
+        }
+
+        self.inner.on_exit(id, context)
     }
 
     fn on_close(
@@ -110,25 +1171,225 @@ where
         id: tracing_core::span::Id,
         context: tracing_subscriber::layer::Context<'_, S>,
     ) {
-        self.0.on_close(id, context)
+        #[cfg(feature = "trace-export")]
+        if let Some(span_exporter) = &self.span_exporter {
+            if let Some(span) = context.span(&id) {
+                let record = span
+                    .extensions_mut()
+                    .remove::<crate::trace_export::SpanRecord>();
+                let own = span.extensions().get::<TraceFields>().cloned();
+
+                // spans starting a trace are children of the remote span they continue, if any
+                let parent_span_id = match own {
+                    Some(TraceFields {
+                        trace_id: Some(_),
+                        span_id,
+                        ..
+                    }) => span_id,
+                    _ => span.parent().and_then(|parent| {
+                        parent
+                            .extensions()
+                            .get::<crate::trace_export::SpanRecord>()
+                            .map(|record| record.span_id)
+                    }),
+                };
+
+                let line =
+                    record
+                        .zip(TraceFields::from_scope(&span))
+                        .and_then(|(record, trace)| {
+                            record.to_line(span.name(), &trace, parent_span_id)
+                        });
+
+                if let Some(line) = line {
+                    let _ = io::Write::write_all(
+                        &mut span_exporter.clone(),
+                        format!("{}\n", line).as_bytes(),
+                    );
+                }
+            }
+        }
+
+        if let Some(tail_buffering) = &self.tail_buffering {
+            let buffer = context
+                .span(&id)
+                .and_then(|span| span.extensions_mut().remove::<TailBuffer>());
+
+            if let Some(buffer) = buffer {
+                let (buffered, evicted) = (buffer.len() as u64, buffer.evicted());
+
+                match buffer.flush(tail_buffering) {
+                    Some(entries) => {
+                        // each line is written on its own, by the writer for its own event
+                        for (metadata, entry) in entries {
+                            for line in entry.lines() {
+                                let _ = self.event_formatter().write_line(
+                                    &mut self.inner.writer().make_writer_for(metadata),
+                                    line.to_string(),
+                                );
+                            }
+                        }
+
+                        self.record_volume(&LogSeverity::Debug, Outcome::Written, buffered);
+                    }
+                    None => self.record_volume(&LogSeverity::Debug, Outcome::Dropped, buffered),
+                }
+
+                self.record_volume(&LogSeverity::Debug, Outcome::Dropped, evicted);
+            }
+        }
+
+        if let Some(span) = context.span(&id) {
+            let is_canonical = span.extensions().get::<CanonicalFields>().is_some();
+
+            if self.span_close_entries || is_canonical {
+                if let Some(timings) = span.extensions_mut().get_mut::<Timings>() {
+                    timings.idle();
+                }
+
+                if let Some(http_request) = span.extensions_mut().get_mut::<HttpRequest>() {
+                    http_request.finish();
+                }
+
+                // an event with the span's own metadata, as written by fmt's span events
+                let metadata = span.metadata();
+                let fields = FieldSet::new(&["message"], metadata.callsite());
+                let message = fields.field("message");
+                let values = [(
+                    message.as_ref().expect("field set has a message"),
+                    Some(&"close" as &dyn tracing_core::field::Value),
+                )];
+                let values = fields.value_set(&values);
+                let event = Event::new_child_of(id.clone(), metadata, &values);
+
+                self.write_event(&event, &context);
+            }
+        }
+
+        self.inner.on_close(id, context)
     }
 
     fn on_event(&self, event: &Event<'_>, context: tracing_subscriber::layer::Context<'_, S>) {
-        self.0.on_event(event, context)
-    }
+        if self.error_summaries && ErrorSummary::is_summarized(event) {
+            if let Some(scope) = context.event_scope(event) {
+                let mut message = Message::default();
+                event.record(&mut message);
+
+                for span in scope {
+                    let mut extensions = span.extensions_mut();
+
+                    match extensions.get_mut::<ErrorSummary>() {
+                        Some(summary) => summary.record(message.0.clone()),
+                        None => {
+                            let mut summary = ErrorSummary::default();
+                            summary.record(message.0.clone());
+                            extensions.insert(summary);
+                        }
+                    }
+                }
+            }
+        }
+
+        let severity = LogSeverity::from(event.metadata().level());
+
+        if self.event_counts && EventCounts::is_counted(event) {
+            if let Some(scope) = context.event_scope(event) {
+                for span in scope {
+                    let mut extensions = span.extensions_mut();
 
-    unsafe fn downcast_raw(&self, id: std::any::TypeId) -> Option<*const ()> {
-        self.0.downcast_raw(id)
+                    match extensions.get_mut::<EventCounts>() {
+                        Some(counts) => counts.record(severity),
+                        None => {
+                            let mut counts = EventCounts::default();
+                            counts.record(severity);
+                            extensions.insert(counts);
+                        }
+                    }
+                }
+            }
+        }
+
+        if let Some(canonical) = &self.canonical {
+            if Canonical::is_folded(event) {
+                let folded = context.event_scope(event).is_some_and(|mut scope| {
+                    scope.any(
+                        |span| match span.extensions_mut().get_mut::<CanonicalFields>() {
+                            Some(fields) => {
+                                fields.fold(event);
+                                true
+                            }
+                            None => false,
+                        },
+                    )
+                });
+
+                if folded && !canonical.keep() {
+                    return;
+                }
+            }
+        }
+
+        if let Some(health_checks) = &self.health_checks {
+            if !health_checks.keep(event) {
+                self.record_volume(&severity, Outcome::RateLimited, 1);
+                return;
+            }
+        }
+
+        if let Some(tail_buffering) = &self.tail_buffering {
+            let root = context
+                .event_scope(event)
+                .and_then(|scope| scope.from_root().next());
+
+            if let Some(root) = root {
+                let level = *event.metadata().level();
+
+                if level == Level::ERROR {
+                    if let Some(buffer) = root.extensions_mut().get_mut::<TailBuffer>() {
+                        buffer.fail();
+                    }
+                } else if level > Level::INFO && root.extensions().get::<TailBuffer>().is_some() {
+                    let entry = tail::capture(|| self.write_event(event, &context));
+
+                    if let Some(buffer) = root.extensions_mut().get_mut::<TailBuffer>() {
+                        buffer.push(event.metadata(), entry, tail_buffering);
+                    }
+
+                    return;
+                }
+            }
+        }
+
+        if let Some(sampler) = &self.sampler {
+            let (keep, change) = sampler.sample(event.metadata().level());
+
+            if let Some(change) = change {
+                self.write_report(change.entry());
+            }
+
+            if !keep {
+                if self.recent_entries {
+                    tail::capture(|| self.write_event(event, &context));
+                }
+
+                self.record_volume(&severity, Outcome::Sampled, 1);
+                return;
+            }
+        }
+
+        self.write_event(event, &context);
+        self.record_volume(&severity, Outcome::Written, 1);
     }
-}
 
-impl<S, W> Deref for Layer<S, W>
-where
-    S: Subscriber + for<'span> LookupSpan<'span>,
-{
-    type Target = tracing_subscriber::fmt::Layer<S, JsonFields, EventFormatter, W>;
+    unsafe fn downcast_raw(&self, id: TypeId) -> Option<*const ()> {
+        if id == TypeId::of::<WithTraceContext>() {
+            return Some(&self.trace_context as *const WithTraceContext as *const ());
+        }
+
+        if id == TypeId::of::<EventFormatter>() {
+            return Some(&self.event_formatter as *const EventFormatter as *const ());
+        }
 
-    fn deref(&self) -> &Self::Target {
-        &self.0
+        self.inner.downcast_raw(id)
     }
 }
@@ -4,12 +4,193 @@
 #![allow(clippy::needless_doctest_main)]
 #![doc = include_str!("../README.md")]
 
+mod audit;
+#[cfg(feature = "exporter")]
+mod auth;
+mod auto;
+mod banner;
+mod build_info;
+mod callsite;
+mod canonical;
+#[cfg(feature = "exporter")]
+mod client;
+mod clock;
+mod cloud_functions;
+mod cloud_run;
+mod console;
+mod counts;
+mod crash;
+mod database;
+mod decorator;
+mod entry;
+#[cfg(feature = "error-reporting")]
+mod error_reporting;
+mod escalation;
 mod event_formatter;
+#[cfg(feature = "exporter")]
+mod exporter;
+mod fields;
+#[cfg(feature = "forward")]
+mod forward;
 mod google;
+mod health_check;
+mod heartbeat;
+#[cfg(feature = "http")]
+mod http;
+mod http_request;
+mod inherited;
+mod instrument;
 mod layer;
+#[cfg(any(
+    feature = "exporter",
+    feature = "protobuf",
+    feature = "forward",
+    feature = "network"
+))]
+mod line_buffer;
+mod line_limit;
+#[cfg(any(feature = "exporter", feature = "protobuf"))]
+mod log_entry;
+mod logfmt;
+#[cfg(feature = "metadata")]
+mod metadata;
+#[cfg(feature = "exporter")]
+mod metrics;
+#[cfg(feature = "network")]
+mod network;
+mod non_blocking;
+#[cfg(feature = "opentelemetry")]
+mod otel;
+mod pretty;
+mod processor;
+#[cfg(feature = "reqwest")]
+mod propagation;
+#[cfg(feature = "protobuf")]
+mod protobuf;
+mod raw;
+mod recent;
+#[cfg(any(feature = "exporter", feature = "protobuf"))]
+mod resource;
+mod sampling;
+#[cfg(feature = "scrubbing")]
+mod scrubbing;
 mod serializers;
+mod severity_filter;
+#[cfg(feature = "slog")]
+mod slog_drain;
+mod span_events;
+#[cfg(feature = "exporter")]
+mod spool;
+mod stack_trace;
+mod strict;
+mod summary;
+mod tail;
+mod template;
+mod timestamp;
+mod timings;
+mod trace;
+#[cfg(feature = "trace-export")]
+mod trace_export;
+mod transform;
+#[cfg(feature = "uuid")]
+mod uuid;
+mod verbosity;
 mod visitor;
+mod volume;
 mod writer;
 
+pub use self::audit::AuditLog;
+#[cfg_attr(docsrs, doc(cfg(feature = "audit-chain")))]
+#[cfg(feature = "audit-chain")]
+pub use self::audit::{verify_audit_chain, AuditChainError};
+#[cfg_attr(docsrs, doc(cfg(feature = "exporter")))]
+#[cfg(feature = "exporter")]
+pub use self::auth::*;
+pub use self::auto::*;
+pub use self::build_info::BuildInfo;
+pub use self::canonical::CanonicalEntries;
+#[cfg_attr(docsrs, doc(cfg(feature = "exporter")))]
+#[cfg(feature = "exporter")]
+pub use self::client::ApiClientBuilder;
+pub use self::cloud_functions::*;
+pub use self::counts::EntryCounts;
+pub use self::crash::*;
+pub use self::database::QueryLogging;
+pub use self::decorator::EntryDecorator;
+pub use self::entry::{
+    emit, format_entry, EntryContext, EntryOperation, EntrySourceLocation, LogEntry,
+};
+#[cfg_attr(docsrs, doc(cfg(feature = "error-reporting")))]
+#[cfg(feature = "error-reporting")]
+pub use self::error_reporting::*;
+pub use self::escalation::SeverityEscalation;
+pub use self::event_formatter::EventFormatter;
+#[cfg_attr(docsrs, doc(cfg(feature = "exporter")))]
+#[cfg(feature = "exporter")]
+pub use self::exporter::*;
+#[doc(hidden)]
+pub use self::fields::__private;
+pub use self::fields::{
+    as_duration, as_error, as_json, AsDuration, AsError, AsField, AsJson, DebugLimits, DottedKeys,
+    DurationFormat, ErrorFormat, StackdriverFields,
+};
+#[cfg_attr(docsrs, doc(cfg(feature = "forward")))]
+#[cfg(feature = "forward")]
+pub use self::forward::*;
 pub use self::google::*;
+pub use self::health_check::HealthCheckFilter;
+pub use self::heartbeat::{spawn_heartbeat, Heartbeat, HeartbeatGuard};
+#[cfg_attr(docsrs, doc(cfg(feature = "http")))]
+#[cfg(feature = "http")]
+pub use self::http::SpanExt;
+pub use self::instrument::InstrumentConfiguration;
 pub use self::layer::*;
+pub use self::line_limit::LineLimit;
+#[cfg_attr(docsrs, doc(cfg(feature = "exporter")))]
+#[cfg(feature = "exporter")]
+pub use self::metrics::*;
+#[cfg_attr(docsrs, doc(cfg(feature = "network")))]
+#[cfg(feature = "network")]
+pub use self::network::*;
+pub use self::non_blocking::*;
+#[cfg_attr(docsrs, doc(cfg(feature = "opentelemetry")))]
+#[cfg(feature = "opentelemetry")]
+pub use self::otel::*;
+pub use self::pretty::PrettyFormatter;
+pub use self::processor::{EntryProcessor, Verdict};
+#[cfg_attr(docsrs, doc(cfg(feature = "reqwest")))]
+#[cfg(feature = "reqwest")]
+pub use self::propagation::*;
+#[cfg_attr(docsrs, doc(cfg(feature = "protobuf")))]
+#[cfg(feature = "protobuf")]
+pub use self::protobuf::*;
+pub use self::raw::emit_raw_entry;
+pub use self::recent::RecentEntries;
+#[cfg_attr(docsrs, doc(cfg(any(feature = "exporter", feature = "protobuf"))))]
+#[cfg(any(feature = "exporter", feature = "protobuf"))]
+pub use self::resource::*;
+pub use self::sampling::AdaptiveSampling;
+#[cfg_attr(docsrs, doc(cfg(feature = "scrubbing")))]
+#[cfg(feature = "scrubbing")]
+pub use self::scrubbing::{
+    HashKey, KeyRedaction, Redactor, ScrubAction, ScrubPattern, Tokenizer, ValueScrubbing,
+};
+pub use self::severity_filter::SeverityFilter;
+#[cfg_attr(docsrs, doc(cfg(feature = "slog")))]
+#[cfg(feature = "slog")]
+pub use self::slog_drain::*;
+pub use self::span_events::SpanEvents;
+pub use self::tail::TailBuffering;
+pub use self::trace::{SpanIdEncoding, SpanTraceContext, TraceContext, TraceResolution};
+#[cfg_attr(docsrs, doc(cfg(feature = "trace-export")))]
+#[cfg(feature = "trace-export")]
+pub use self::trace_export::*;
+pub use self::transform::FieldTransformer;
+#[cfg_attr(docsrs, doc(cfg(feature = "uuid")))]
+#[cfg(feature = "uuid")]
+pub use self::uuid::*;
+pub use self::verbosity::RequestVerbosity;
+pub use self::writer::{Fanout, FanoutWriter, Tee};
+#[cfg_attr(docsrs, doc(cfg(feature = "derive")))]
+#[cfg(feature = "derive")]
+pub use tracing_stackdriver_cw_derive::StackdriverFields;
@@ -0,0 +1,24 @@
+use std::mem;
+
+/// Partial line of the newline-delimited entries written to a writer, which formatters may split
+/// across writes or join into one
+#[derive(Debug, Default)]
+pub(crate) struct LineBuffer {
+    line: Vec<u8>,
+}
+
+impl LineBuffer {
+    /// Append the bytes of a write, returning the lines they complete without their newlines
+    pub(crate) fn push(&mut self, buffer: &[u8]) -> Vec<Vec<u8>> {
+        let mut segments = buffer.split(|byte| *byte == b'\n');
+
+        if let Some(first) = segments.next() {
+            self.line.extend_from_slice(first);
+        }
+
+        // every remaining segment follows a newline that completes the line before it
+        segments
+            .map(|segment| mem::replace(&mut self.line, segment.to_vec()))
+            .collect()
+    }
+}
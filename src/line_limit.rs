@@ -0,0 +1,246 @@
+use serde_json::{Map, Value};
+use std::{
+    mem,
+    sync::atomic::{AtomicU64, Ordering},
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+/// Key of the metadata relating the entries an oversized entry is split into
+const SPLIT_KEY: &str = "logging.googleapis.com/split";
+
+/// Upper bound of the length of the split metadata's values, used to reserve space for them
+const SPLIT_PLACEHOLDER: u64 = u32::MAX as u64;
+
+/// Smallest share of a line left for the split value, below which entries are truncated instead
+const MIN_SPLIT_LENGTH: usize = 64;
+
+/// Attempts at truncating the longest string values of an entry until it fits
+const TRUNCATION_ATTEMPTS: usize = 8;
+
+/// Reserved length of the markers appended to truncated strings
+const TRUNCATION_MARKER_LENGTH: usize = 32;
+
+/// Sequence of the groups of split entries, distinguishing the groups of a process
+static SPLIT_SEQUENCE: AtomicU64 = AtomicU64::new(0);
+
+/// Upper bound of the length in bytes of an entry's line, excluding its newline, for container
+/// runtimes like Docker and containerd that split longer lines, corrupting their JSON.
+/// `16_000` keeps lines below their 16 KiB limit.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum LineLimit {
+    /// Truncate the longest string values of oversized entries, marking them with the number of
+    /// bytes removed
+    Truncate(usize),
+    /// Split the longest string value of oversized entries, usually the message, across several
+    /// entries carrying `logging.googleapis.com/split` metadata with a shared `uid`, their
+    /// `index`, and the number of `totalSplits`
+    Split(usize),
+}
+
+impl LineLimit {
+    /// The limit with room left on each line for content added once the entry is limited
+    pub(crate) fn reserving(self, length: usize) -> Self {
+        match self {
+            Self::Truncate(max_length) => Self::Truncate(max_length.saturating_sub(length)),
+            Self::Split(max_length) => Self::Split(max_length.saturating_sub(length)),
+        }
+    }
+
+    /// Lines of a formatted entry within the limit
+    pub(crate) fn apply(self, entry: String) -> Vec<String> {
+        let max_length = match self {
+            Self::Truncate(max_length) | Self::Split(max_length) => max_length,
+        };
+
+        if entry.len() <= max_length {
+            return vec![entry];
+        }
+
+        let fields = match serde_json::from_str(&entry) {
+            Ok(Value::Object(fields)) => fields,
+            _ => return vec![entry],
+        };
+
+        match self {
+            Self::Truncate(_) => vec![truncate(Value::Object(fields), max_length)],
+            Self::Split(_) => split(fields, max_length),
+        }
+    }
+}
+
+/// Truncate the longest string values of an entry until it fits, or no string is left to cut
+fn truncate(mut entry: Value, max_length: usize) -> String {
+    let mut line = entry.to_string();
+
+    for _ in 0..TRUNCATION_ATTEMPTS {
+        if line.len() <= max_length {
+            break;
+        }
+
+        let excess = line.len() - max_length + TRUNCATION_MARKER_LENGTH;
+        let string = match longest_string(&entry).and_then(|pointer| entry.pointer_mut(&pointer)) {
+            Some(Value::String(string)) if !string.is_empty() => string,
+            _ => break,
+        };
+
+        // removing bytes removes at least as many bytes of their escaped form
+        let mut end = string.len().saturating_sub(excess);
+
+        while !string.is_char_boundary(end) {
+            end -= 1;
+        }
+
+        let truncated = string.len() - end;
+        string.truncate(end);
+        string.push_str(&format!("…[truncated {} bytes]", truncated));
+        line = entry.to_string();
+    }
+
+    line
+}
+
+/// Split the longest string value of an entry across as many entries as it takes to fit
+fn split(fields: Map<String, Value>, max_length: usize) -> Vec<String> {
+    let mut template = Value::Object(fields);
+
+    let pointer = match longest_string(&template) {
+        Some(pointer) => pointer,
+        None => return vec![truncate(template, max_length)],
+    };
+
+    let value = match template.pointer_mut(&pointer) {
+        Some(Value::String(value)) => mem::take(value),
+        _ => return vec![truncate(template, max_length)],
+    };
+
+    // the entry without the value, along with split metadata of the largest possible length
+    let uid = uid();
+    let placeholder = split_metadata(&uid, SPLIT_PLACEHOLDER, SPLIT_PLACEHOLDER);
+    set(&mut template, SPLIT_KEY, placeholder);
+    let budget = max_length.saturating_sub(template.to_string().len());
+
+    if budget < MIN_SPLIT_LENGTH {
+        if let Value::Object(fields) = &mut template {
+            fields.remove(SPLIT_KEY);
+        }
+
+        if let Some(string) = template.pointer_mut(&pointer) {
+            *string = Value::String(value);
+        }
+
+        return vec![truncate(template, max_length)];
+    }
+
+    let chunks = chunks(&value, budget);
+    let total = chunks.len() as u64;
+
+    chunks
+        .into_iter()
+        .enumerate()
+        .map(|(index, chunk)| {
+            if let Some(string) = template.pointer_mut(&pointer) {
+                *string = Value::String(chunk.to_string());
+            }
+
+            set(
+                &mut template,
+                SPLIT_KEY,
+                split_metadata(&uid, index as u64, total),
+            );
+            template.to_string()
+        })
+        .collect()
+}
+
+/// Set a top-level field of an entry
+fn set(entry: &mut Value, key: &str, value: Value) {
+    if let Value::Object(fields) = entry {
+        fields.insert(key.to_string(), value);
+    }
+}
+
+fn split_metadata(uid: &str, index: u64, total_splits: u64) -> Value {
+    serde_json::json!({ "uid": uid, "index": index, "totalSplits": total_splits })
+}
+
+/// Identifier shared by the entries an entry is split into
+fn uid() -> String {
+    let nanos = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_nanos() as u64;
+
+    format!(
+        "{:016x}{:08x}",
+        nanos,
+        SPLIT_SEQUENCE.fetch_add(1, Ordering::Relaxed) as u32
+    )
+}
+
+/// Pieces of a string whose escaped forms are at most `budget` bytes long
+fn chunks(value: &str, budget: usize) -> Vec<&str> {
+    let mut chunks = vec![];
+    let mut start = 0;
+    let mut length = 0;
+
+    for (index, character) in value.char_indices() {
+        let escaped_length = escaped_length(character);
+
+        if length + escaped_length > budget && index > start {
+            chunks.push(&value[start..index]);
+            start = index;
+            length = 0;
+        }
+
+        length += escaped_length;
+    }
+
+    chunks.push(&value[start..]);
+    chunks
+}
+
+/// Length of a character escaped within a JSON string
+fn escaped_length(character: char) -> usize {
+    match character {
+        '"' | '\\' | '\n' | '\r' | '\t' | '\u{8}' | '\u{c}' => 2,
+        '\u{0}'..='\u{1f}' => 6,
+        _ => character.len_utf8(),
+    }
+}
+
+/// JSON pointer to the longest string within a value
+fn longest_string(value: &Value) -> Option<String> {
+    fn visit(value: &Value, pointer: &mut String, longest: &mut Option<(usize, String)>) {
+        match value {
+            Value::String(string)
+                if longest
+                    .as_ref()
+                    .is_none_or(|(length, _)| string.len() > *length) =>
+            {
+                *longest = Some((string.len(), pointer.clone()));
+            }
+            Value::Array(values) => {
+                for (index, value) in values.iter().enumerate() {
+                    let length = pointer.len();
+                    pointer.push_str(&format!("/{}", index));
+                    visit(value, pointer, longest);
+                    pointer.truncate(length);
+                }
+            }
+            Value::Object(fields) => {
+                for (key, value) in fields {
+                    let length = pointer.len();
+                    pointer.push('/');
+                    pointer.push_str(&key.replace('~', "~0").replace('/', "~1"));
+                    visit(value, pointer, longest);
+                    pointer.truncate(length);
+                }
+            }
+            _ => (),
+        }
+    }
+
+    let mut longest = None;
+    visit(value, &mut String::new(), &mut longest);
+    longest.map(|(_, pointer)| pointer)
+}
@@ -0,0 +1,91 @@
+use serde_json::{Map, Value};
+use time::{format_description::well_known::Rfc3339, OffsetDateTime};
+
+/// Log ID used when none is configured
+pub(crate) const DEFAULT_LOG_ID: &str = "tracing-stackdriver";
+
+/// Convert a structured logging entry into a Cloud Logging API `LogEntry`, lifting special
+/// fields to their `LogEntry` counterparts and leaving the rest as the `jsonPayload`
+pub(crate) fn api_entry(mut structured: Map<String, Value>, default_log_name: &str) -> Value {
+    let mut entry = Map::new();
+
+    if let Some(Value::String(log_name)) = structured.remove("logName") {
+        entry.insert(
+            "logName".to_string(),
+            Value::String(resolve_log_name(&log_name, default_log_name)),
+        );
+    }
+
+    for (structured_key, entry_key) in [
+        ("time", "timestamp"),
+        ("severity", "severity"),
+        ("httpRequest", "httpRequest"),
+        ("logging.googleapis.com/insertId", "insertId"),
+        ("logging.googleapis.com/labels", "labels"),
+        ("logging.googleapis.com/operation", "operation"),
+        ("logging.googleapis.com/sourceLocation", "sourceLocation"),
+        ("logging.googleapis.com/spanId", "spanId"),
+        ("logging.googleapis.com/trace", "trace"),
+        ("logging.googleapis.com/trace_sampled", "traceSampled"),
+    ] {
+        if let Some(value) = structured.remove(structured_key) {
+            entry.insert(entry_key.to_string(), value);
+        }
+    }
+
+    if !entry.contains_key("timestamp") {
+        if let Some(timestamp) = split_timestamp(&mut structured) {
+            entry.insert("timestamp".to_string(), Value::String(timestamp));
+        }
+    }
+
+    entry.insert("jsonPayload".to_string(), Value::Object(structured));
+    Value::Object(entry)
+}
+
+/// Convert the split timestamp fields written for legacy agents into an RFC 3339 timestamp
+fn split_timestamp(structured: &mut Map<String, Value>) -> Option<String> {
+    let seconds = structured.get("timestampSeconds")?.as_i64()?;
+    let nanos = structured.get("timestampNanos")?.as_i64()?;
+    let timestamp = OffsetDateTime::from_unix_timestamp_nanos(
+        i128::from(seconds) * 1_000_000_000 + i128::from(nanos),
+    )
+    .ok()?
+    .format(&Rfc3339)
+    .ok()?;
+
+    structured.remove("timestampSeconds");
+    structured.remove("timestampNanos");
+    Some(timestamp)
+}
+
+/// Resource name prefixes of the parents that logs can belong to
+const LOG_PARENTS: [&str; 4] = [
+    "projects/",
+    "organizations/",
+    "folders/",
+    "billingAccounts/",
+];
+
+/// Resolve an entry's log name override, which is either a full log name or a log ID under the
+/// parent of the default log
+fn resolve_log_name(log_name: &str, default_log_name: &str) -> String {
+    if LOG_PARENTS
+        .iter()
+        .any(|parent| log_name.starts_with(parent))
+    {
+        return log_name.to_string();
+    }
+
+    let parent = default_log_name
+        .split_once("/logs/")
+        .map(|(parent, _)| parent)
+        .unwrap_or(default_log_name);
+
+    format!("{}/logs/{}", parent, encode_log_id(log_name))
+}
+
+/// URL-encode the slashes of a log ID, as required in log names
+pub(crate) fn encode_log_id(log_id: &str) -> String {
+    log_id.replace('/', "%2F")
+}
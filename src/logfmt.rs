@@ -0,0 +1,90 @@
+use serde_json::Value;
+
+/// Parse the `key=value` pairs within a message, e.g. `user=42 path="/a b"`, where values are
+/// either quoted with `"` or end at the next whitespace. Numbers and booleans are written as such.
+pub(crate) fn key_values(message: &str) -> Vec<(String, Value)> {
+    let mut pairs = Vec::new();
+    let mut rest = message;
+
+    while let Some(start) = rest.find(|char: char| char.is_ascii_alphabetic() || char == '_') {
+        // keys only start at the beginning of a word
+        if rest[..start]
+            .chars()
+            .next_back()
+            .is_some_and(|char| !char.is_whitespace())
+        {
+            rest = skip_word(&rest[start..]);
+            continue;
+        }
+
+        let word = &rest[start..];
+        let key_length = word
+            .find(|char: char| !(char.is_ascii_alphanumeric() || matches!(char, '_' | '.')))
+            .unwrap_or(word.len());
+
+        let Some(value) = word[key_length..].strip_prefix('=') else {
+            rest = skip_word(word);
+            continue;
+        };
+
+        let key = &word[..key_length];
+        let (value, remainder) = match value.strip_prefix('"') {
+            Some(quoted) => match parse_quoted(quoted) {
+                Some((value, remainder)) => (Value::String(value), remainder),
+                None => break,
+            },
+            None => {
+                let end = value.find(char::is_whitespace).unwrap_or(value.len());
+                let bare = value[..end].trim_end_matches([',', ';']);
+
+                (parse_bare(bare), &value[end..])
+            }
+        };
+
+        pairs.push((key.to_string(), value));
+        rest = remainder;
+    }
+
+    pairs
+}
+
+/// Skip to the whitespace after the current word
+fn skip_word(rest: &str) -> &str {
+    let end = rest.find(char::is_whitespace).unwrap_or(rest.len());
+    &rest[end..]
+}
+
+/// Unescape a quoted value, returning it along with the text after its closing quote
+fn parse_quoted(quoted: &str) -> Option<(String, &str)> {
+    let mut value = String::new();
+    let mut chars = quoted.char_indices();
+
+    while let Some((index, char)) = chars.next() {
+        match char {
+            '"' => return Some((value, &quoted[index + 1..])),
+            '\\' => value.push(chars.next()?.1),
+            char => value.push(char),
+        }
+    }
+
+    None
+}
+
+/// A bare value as a number or boolean where it reads as one, or a string otherwise
+fn parse_bare(bare: &str) -> Value {
+    if let Ok(value) = bare.parse::<i64>() {
+        return value.into();
+    }
+
+    if let Ok(value) = bare.parse::<f64>() {
+        if value.is_finite() {
+            return value.into();
+        }
+    }
+
+    match bare {
+        "true" => Value::Bool(true),
+        "false" => Value::Bool(false),
+        bare => Value::String(bare.to_string()),
+    }
+}
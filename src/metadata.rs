@@ -0,0 +1,132 @@
+use std::{
+    cell::Cell,
+    collections::HashMap,
+    env,
+    io::{Read, Write},
+    net::{SocketAddr, TcpStream},
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc, Mutex, OnceLock,
+    },
+    time::Duration,
+};
+
+/// Address of the metadata server, avoiding a DNS lookup for `metadata.google.internal`
+const METADATA_SERVER: ([u8; 4], u16) = ([169, 254, 169, 254], 80);
+
+/// Upper bound for connecting to and reading from the metadata server
+const METADATA_TIMEOUT: Duration = Duration::from_secs(1);
+
+/// Environment variables naming the current project, in order of precedence
+const PROJECT_ID_VARIABLES: [&str; 3] = ["GOOGLE_CLOUD_PROJECT", "GCLOUD_PROJECT", "GCP_PROJECT"];
+
+/// Value fetched from the metadata server, set once by the first lookup of its path
+type CachedValue = Arc<OnceLock<Option<String>>>;
+
+/// Values fetched from the metadata server for the lifetime of the process, including missing ones
+static CACHE: OnceLock<Mutex<HashMap<String, CachedValue>>> = OnceLock::new();
+
+/// Set once the metadata server can't be reached, i.e. when not running on Google Cloud, so that
+/// later lookups don't wait for the timeout again
+static UNREACHABLE: AtomicBool = AtomicBool::new(false);
+
+thread_local! {
+    /// Set while values are looked up without fetching them, e.g. while a layer is installed
+    static CACHED_ONLY: Cell<bool> = const { Cell::new(false) };
+}
+
+/// Fetch a value from the metadata server, given its path relative to `/computeMetadata/v1/`,
+/// returning `None` when not running on Google Cloud, the value isn't set, or the response doesn't
+/// come from the metadata server, lacking its `Metadata-Flavor: Google` header. Each value is
+/// fetched at most once per process.
+pub(crate) fn get(path: &str) -> Option<String> {
+    if UNREACHABLE.load(Ordering::Relaxed) {
+        return None;
+    }
+
+    let mut cache = CACHE
+        .get_or_init(Default::default)
+        .lock()
+        .unwrap_or_else(|poisoned| poisoned.into_inner());
+
+    if CACHED_ONLY.get() {
+        return cache.get(path)?.get().cloned().flatten();
+    }
+
+    // the cache is only locked to find the value's cell, so that lookups of other values don't
+    // wait for this one to be fetched, while concurrent lookups of the same value share a request
+    let value = cache.entry(path.to_string()).or_default().clone();
+    drop(cache);
+
+    value.get_or_init(|| fetch(path)).clone()
+}
+
+/// Run a callback whose lookups only return the values already fetched, rather than waiting for
+/// the metadata server
+pub(crate) fn cached_only<T>(callback: impl FnOnce() -> T) -> T {
+    let previous = CACHED_ONLY.replace(true);
+    let result = callback();
+    CACHED_ONLY.set(previous);
+    result
+}
+
+/// ID of the current project, from the environment or the metadata server
+pub(crate) fn project_id() -> Option<String> {
+    PROJECT_ID_VARIABLES
+        .iter()
+        .find_map(|variable| env::var(variable).ok().filter(|value| !value.is_empty()))
+        .or_else(|| get("project/project-id"))
+}
+
+fn fetch(path: &str) -> Option<String> {
+    let address = SocketAddr::from(METADATA_SERVER);
+    let mut stream = match TcpStream::connect_timeout(&address, METADATA_TIMEOUT) {
+        Ok(stream) => stream,
+        Err(_) => {
+            UNREACHABLE.store(true, Ordering::Relaxed);
+            return None;
+        }
+    };
+    stream.set_read_timeout(Some(METADATA_TIMEOUT)).ok()?;
+    stream.set_write_timeout(Some(METADATA_TIMEOUT)).ok()?;
+
+    write!(
+        stream,
+        "GET /computeMetadata/v1/{path} HTTP/1.0\r\nHost: metadata.google.internal\r\nMetadata-Flavor: Google\r\n\r\n"
+    )
+    .ok()?;
+
+    let mut response = String::new();
+    stream.read_to_string(&mut response).ok()?;
+
+    let (head, body) = response.split_once("\r\n\r\n")?;
+    let mut lines = head.lines();
+    let status = lines.next()?.split_whitespace().nth(1)?;
+
+    if status != "200" {
+        return None;
+    }
+
+    // anything else listening on the address isn't the metadata server
+    let from_metadata_server = lines.any(|line| {
+        line.split_once(':').is_some_and(|(name, value)| {
+            name.trim().eq_ignore_ascii_case("metadata-flavor") && value.trim() == "Google"
+        })
+    });
+
+    if !from_metadata_server {
+        return None;
+    }
+
+    Some(body.trim().to_string()).filter(|value| !value.is_empty())
+}
+
+/// Last segment of a fully-qualified metadata value, e.g. the region of
+/// `projects/PROJECT_NUMBER/regions/REGION`
+pub(crate) fn last_segment(value: &str) -> Option<String> {
+    value
+        .rsplit('/')
+        .next()
+        .filter(|segment| !segment.is_empty())
+        .map(str::to_string)
+}
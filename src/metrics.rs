@@ -0,0 +1,172 @@
+use serde::Serialize;
+use std::{
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc,
+    },
+    time::Duration,
+};
+
+/// Upper bounds in milliseconds of the export latency histogram's buckets
+const LATENCY_BOUNDS: [u64; 9] = [10, 50, 100, 250, 500, 1_000, 2_500, 5_000, 10_000];
+
+/// Upper bounds in entries of the batch size histogram's buckets
+const BATCH_SIZE_BOUNDS: [u64; 6] = [1, 10, 50, 100, 500, 1_000];
+
+/// Handle for inspecting the health of an [`Exporter`](crate::Exporter), which stays usable after
+/// the exporter is moved into a writer
+#[derive(Clone, Debug, Default)]
+pub struct ExporterMetrics {
+    inner: Arc<Metrics>,
+}
+
+impl ExporterMetrics {
+    /// Current values of the exporter's counters and histograms
+    pub fn snapshot(&self) -> ExporterMetricsSnapshot {
+        let inner = &self.inner;
+
+        ExporterMetricsSnapshot {
+            requests: inner.requests.load(Ordering::Relaxed),
+            failed_requests: inner.failed_requests.load(Ordering::Relaxed),
+            retries: inner.retries.load(Ordering::Relaxed),
+            exported_entries: inner.exported_entries.load(Ordering::Relaxed),
+            failed_entries: inner.failed_entries.load(Ordering::Relaxed),
+            spooled_batches: inner.spooled_batches.load(Ordering::Relaxed),
+            latency_ms: inner.latency_ms.snapshot(),
+            batch_size: inner.batch_size.snapshot(),
+        }
+    }
+
+    pub(crate) fn record_request(&self, entries: usize, latency: Duration, succeeded: bool) {
+        self.inner.requests.fetch_add(1, Ordering::Relaxed);
+        self.inner
+            .latency_ms
+            .record(latency.as_millis().try_into().unwrap_or(u64::MAX));
+        self.inner.batch_size.record(entries as u64);
+
+        if !succeeded {
+            self.inner.failed_requests.fetch_add(1, Ordering::Relaxed);
+        }
+    }
+
+    pub(crate) fn record_retry(&self) {
+        self.inner.retries.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub(crate) fn record_exported(&self, entries: usize) {
+        self.inner
+            .exported_entries
+            .fetch_add(entries as u64, Ordering::Relaxed);
+    }
+
+    pub(crate) fn record_failed(&self, entries: usize) {
+        self.inner
+            .failed_entries
+            .fetch_add(entries as u64, Ordering::Relaxed);
+    }
+
+    pub(crate) fn record_spooled(&self) {
+        self.inner.spooled_batches.fetch_add(1, Ordering::Relaxed);
+    }
+}
+
+#[derive(Debug)]
+struct Metrics {
+    requests: AtomicU64,
+    failed_requests: AtomicU64,
+    retries: AtomicU64,
+    exported_entries: AtomicU64,
+    failed_entries: AtomicU64,
+    spooled_batches: AtomicU64,
+    latency_ms: Histogram,
+    batch_size: Histogram,
+}
+
+impl Default for Metrics {
+    fn default() -> Self {
+        Self {
+            requests: AtomicU64::new(0),
+            failed_requests: AtomicU64::new(0),
+            retries: AtomicU64::new(0),
+            exported_entries: AtomicU64::new(0),
+            failed_entries: AtomicU64::new(0),
+            spooled_batches: AtomicU64::new(0),
+            latency_ms: Histogram::new(&LATENCY_BOUNDS),
+            batch_size: Histogram::new(&BATCH_SIZE_BOUNDS),
+        }
+    }
+}
+
+/// Point-in-time values of an exporter's metrics
+#[derive(Clone, Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ExporterMetricsSnapshot {
+    /// WriteLogEntries requests sent, including retries
+    pub requests: u64,
+    /// Requests that failed, whether entirely or partially
+    pub failed_requests: u64,
+    /// Retries of failed requests
+    pub retries: u64,
+    /// Entries written to Cloud Logging
+    pub exported_entries: u64,
+    /// Entries that could not be exported and were dropped
+    pub failed_entries: u64,
+    /// Batches written to the spool
+    pub spooled_batches: u64,
+    /// Latency of requests in milliseconds
+    pub latency_ms: HistogramSnapshot,
+    /// Number of entries per request
+    pub batch_size: HistogramSnapshot,
+}
+
+/// Point-in-time values of a histogram with fixed buckets
+#[derive(Clone, Debug, Serialize)]
+pub struct HistogramSnapshot {
+    /// Inclusive upper bound of each bucket but the last, which holds every larger value
+    pub bounds: Vec<u64>,
+    /// Number of values in each bucket
+    pub counts: Vec<u64>,
+    /// Total number of values
+    pub count: u64,
+    /// Sum of all values
+    pub sum: u64,
+}
+
+#[derive(Debug)]
+struct Histogram {
+    bounds: &'static [u64],
+    counts: Vec<AtomicU64>,
+    count: AtomicU64,
+    sum: AtomicU64,
+}
+
+impl Histogram {
+    fn new(bounds: &'static [u64]) -> Self {
+        Self {
+            bounds,
+            counts: (0..=bounds.len()).map(|_| AtomicU64::new(0)).collect(),
+            count: AtomicU64::new(0),
+            sum: AtomicU64::new(0),
+        }
+    }
+
+    fn record(&self, value: u64) {
+        let bucket = self.bounds.partition_point(|bound| *bound < value);
+        self.counts[bucket].fetch_add(1, Ordering::Relaxed);
+        self.count.fetch_add(1, Ordering::Relaxed);
+        self.sum.fetch_add(value, Ordering::Relaxed);
+    }
+
+    fn snapshot(&self) -> HistogramSnapshot {
+        HistogramSnapshot {
+            bounds: self.bounds.to_vec(),
+            counts: self
+                .counts
+                .iter()
+                .map(|count| count.load(Ordering::Relaxed))
+                .collect(),
+            count: self.count.load(Ordering::Relaxed),
+            sum: self.sum.load(Ordering::Relaxed),
+        }
+    }
+}
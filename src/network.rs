@@ -0,0 +1,284 @@
+use crate::line_buffer::LineBuffer;
+use std::{
+    collections::VecDeque,
+    fmt,
+    io::{self, Write},
+    net::{TcpStream, ToSocketAddrs, UdpSocket},
+    time::{Duration, Instant},
+};
+
+/// Default upper bound of the entries buffered while the collector is unreachable
+const DEFAULT_MAX_BUFFERED_BYTES: usize = 8 * 1024 * 1024;
+
+/// Default interval between attempts to reconnect to the collector
+const DEFAULT_RECONNECT_INTERVAL: Duration = Duration::from_secs(1);
+
+/// Upper bound for connecting to and writing to the collector
+const NETWORK_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Writer that ships entries to a central collector as newline-delimited JSON over TCP (optionally
+/// with TLS) or UDP, one datagram per entry. Entries are buffered while the collector is
+/// unreachable, dropping the oldest beyond a bound, and the connection is re-established
+/// automatically. Writes never fail, so that logging never blocks on the collector for longer
+/// than its timeouts.
+pub struct NetworkWriter {
+    address: String,
+    transport: Transport,
+    connection: Option<Connection>,
+    buffer: VecDeque<Vec<u8>>,
+    buffered_bytes: usize,
+    max_buffered_bytes: usize,
+    reconnect_interval: Duration,
+    next_attempt: Instant,
+    lines: LineBuffer,
+}
+
+enum Transport {
+    Tcp,
+    Udp,
+    #[cfg(feature = "network-tls")]
+    Tls(std::sync::Arc<rustls::ClientConfig>, String),
+}
+
+enum Connection {
+    Tcp(TcpStream),
+    Udp(UdpSocket),
+    #[cfg(feature = "network-tls")]
+    Tls(Box<rustls::StreamOwned<rustls::ClientConnection, TcpStream>>),
+}
+
+impl NetworkWriter {
+    /// Create a writer of entries over TCP to a collector at `host:port`
+    pub fn tcp(address: impl Into<String>) -> Self {
+        Self::new(address.into(), Transport::Tcp)
+    }
+
+    /// Create a writer of entries over UDP to a collector at `host:port`
+    pub fn udp(address: impl Into<String>) -> Self {
+        Self::new(address.into(), Transport::Udp)
+    }
+
+    /// Create a writer of entries over TLS to a collector at `host:port`, verifying its
+    /// certificate against the Mozilla root certificates
+    #[cfg_attr(docsrs, doc(cfg(feature = "network-tls")))]
+    #[cfg(feature = "network-tls")]
+    pub fn tls(address: impl Into<String>) -> Self {
+        let roots =
+            rustls::RootCertStore::from_iter(webpki_roots::TLS_SERVER_ROOTS.iter().cloned());
+        let config = rustls::ClientConfig::builder()
+            .with_root_certificates(roots)
+            .with_no_client_auth();
+
+        Self::tls_with_config(address, std::sync::Arc::new(config))
+    }
+
+    /// Create a writer of entries over TLS to a collector at `host:port`, with a custom client
+    /// configuration, e.g. for private certificate authorities or client certificates
+    #[cfg_attr(docsrs, doc(cfg(feature = "network-tls")))]
+    #[cfg(feature = "network-tls")]
+    pub fn tls_with_config(
+        address: impl Into<String>,
+        config: std::sync::Arc<rustls::ClientConfig>,
+    ) -> Self {
+        let address = address.into();
+        let host = address
+            .rsplit_once(':')
+            .map(|(host, _)| host.trim_start_matches('[').trim_end_matches(']'))
+            .unwrap_or(&address)
+            .to_string();
+
+        Self::new(address, Transport::Tls(config, host))
+    }
+
+    fn new(address: String, transport: Transport) -> Self {
+        Self {
+            address,
+            transport,
+            connection: None,
+            buffer: VecDeque::new(),
+            buffered_bytes: 0,
+            max_buffered_bytes: DEFAULT_MAX_BUFFERED_BYTES,
+            reconnect_interval: DEFAULT_RECONNECT_INTERVAL,
+            next_attempt: Instant::now(),
+            lines: LineBuffer::default(),
+        }
+    }
+
+    /// Sets the upper bound in bytes of the entries buffered while the collector is unreachable,
+    /// beyond which the oldest entries are dropped
+    pub fn with_max_buffered_bytes(self, max_buffered_bytes: usize) -> Self {
+        Self {
+            max_buffered_bytes,
+            ..self
+        }
+    }
+
+    /// Sets the interval between attempts to reconnect to the collector
+    pub fn with_reconnect_interval(self, reconnect_interval: Duration) -> Self {
+        Self {
+            reconnect_interval,
+            ..self
+        }
+    }
+
+    /// Buffer a complete entry, dropping the oldest entries beyond the bound
+    fn push(&mut self, mut line: Vec<u8>) {
+        if line.is_empty() {
+            return;
+        }
+
+        line.push(b'\n');
+        self.buffered_bytes += line.len();
+        self.buffer.push_back(line);
+
+        while self.buffered_bytes > self.max_buffered_bytes {
+            match self.buffer.pop_front() {
+                Some(dropped) => self.buffered_bytes -= dropped.len(),
+                None => break,
+            }
+        }
+    }
+
+    /// Send buffered entries, connecting first if needed and due
+    fn send(&mut self) {
+        while let Some(line) = self.buffer.front() {
+            let connection = match &mut self.connection {
+                Some(connection) => connection,
+                None if Instant::now() < self.next_attempt => return,
+                None => match self.transport.connect(&self.address) {
+                    Ok(connection) => self.connection.insert(connection),
+                    Err(_) => {
+                        self.next_attempt = Instant::now() + self.reconnect_interval;
+                        return;
+                    }
+                },
+            };
+
+            if connection.send(line).is_err() {
+                // the entry is kept, to be sent again once reconnected
+                self.connection = None;
+                self.next_attempt = Instant::now() + self.reconnect_interval;
+                return;
+            }
+
+            if let Some(sent) = self.buffer.pop_front() {
+                self.buffered_bytes -= sent.len();
+            }
+        }
+    }
+}
+
+impl Transport {
+    fn connect(&self, address: &str) -> io::Result<Connection> {
+        let mut last_error = None;
+
+        for socket_address in address.to_socket_addrs()? {
+            let connection = match self {
+                Self::Udp => {
+                    let local = if socket_address.is_ipv4() {
+                        "0.0.0.0:0"
+                    } else {
+                        "[::]:0"
+                    };
+                    let socket = UdpSocket::bind(local)?;
+                    socket
+                        .connect(socket_address)
+                        .map(|_| Connection::Udp(socket))
+                }
+                _ => TcpStream::connect_timeout(&socket_address, NETWORK_TIMEOUT).and_then(
+                    |stream| {
+                        stream.set_write_timeout(Some(NETWORK_TIMEOUT))?;
+                        self.wrap(stream)
+                    },
+                ),
+            };
+
+            match connection {
+                Ok(connection) => return Ok(connection),
+                Err(error) => last_error = Some(error),
+            }
+        }
+
+        Err(last_error.unwrap_or_else(|| io::Error::from(io::ErrorKind::AddrNotAvailable)))
+    }
+
+    /// Wrap a connected stream for the transport
+    fn wrap(&self, stream: TcpStream) -> io::Result<Connection> {
+        match self {
+            #[cfg(feature = "network-tls")]
+            Self::Tls(config, host) => {
+                let server_name = rustls::pki_types::ServerName::try_from(host.clone())
+                    .map_err(|error| io::Error::new(io::ErrorKind::InvalidInput, error))?;
+                let connection = rustls::ClientConnection::new(config.clone(), server_name)
+                    .map_err(io::Error::other)?;
+
+                Ok(Connection::Tls(Box::new(rustls::StreamOwned::new(
+                    connection, stream,
+                ))))
+            }
+            _ => Ok(Connection::Tcp(stream)),
+        }
+    }
+}
+
+impl Connection {
+    fn send(&mut self, line: &[u8]) -> io::Result<()> {
+        match self {
+            Self::Tcp(stream) => stream.write_all(line),
+            Self::Udp(socket) => socket.send(line).map(|_| ()),
+            #[cfg(feature = "network-tls")]
+            Self::Tls(stream) => stream.write_all(line).and_then(|_| stream.flush()),
+        }
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        match self {
+            Self::Tcp(stream) => stream.flush(),
+            Self::Udp(_) => Ok(()),
+            #[cfg(feature = "network-tls")]
+            Self::Tls(stream) => stream.flush(),
+        }
+    }
+}
+
+impl io::Write for NetworkWriter {
+    fn write(&mut self, buffer: &[u8]) -> io::Result<usize> {
+        for line in self.lines.push(buffer) {
+            self.push(line);
+        }
+
+        self.send();
+        Ok(buffer.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.send();
+
+        if let Some(connection) = &mut self.connection {
+            if connection.flush().is_err() {
+                self.connection = None;
+            }
+        }
+
+        Ok(())
+    }
+}
+
+impl fmt::Debug for NetworkWriter {
+    fn fmt(&self, formatter: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let transport = match self.transport {
+            Transport::Tcp => "tcp",
+            Transport::Udp => "udp",
+            #[cfg(feature = "network-tls")]
+            Transport::Tls(..) => "tls",
+        };
+
+        formatter
+            .debug_struct("NetworkWriter")
+            .field("address", &self.address)
+            .field("transport", &transport)
+            .field("connected", &self.connection.is_some())
+            .field("buffered_bytes", &self.buffered_bytes)
+            .finish_non_exhaustive()
+    }
+}
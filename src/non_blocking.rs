@@ -0,0 +1,665 @@
+use crate::{
+    entry::{self, LogEntry},
+    google::LogSeverity,
+};
+use std::{
+    collections::VecDeque,
+    fmt, io,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc, Condvar, Mutex, MutexGuard,
+    },
+    thread,
+    time::{Duration, Instant},
+};
+use tracing_subscriber::fmt::MakeWriter;
+
+/// Default upper bound for draining buffered entries when a [`WorkerGuard`] is dropped
+const DEFAULT_SHUTDOWN_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Default number of entries buffered before applying [`Backpressure`]
+const DEFAULT_CAPACITY: usize = 128_000;
+
+/// Default interval between WARNING entries reporting dropped entries
+const DEFAULT_DROP_REPORT_INTERVAL: Duration = Duration::from_secs(60);
+
+/// Behavior of a [`NonBlocking`] writer when its buffer is full
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Backpressure {
+    /// Block the emitting thread until the worker frees up space. With a `timeout`, the entry is
+    /// dropped once that much time has passed without space becoming available.
+    Block {
+        /// Maximum time to block the emitting thread, or `None` to wait indefinitely
+        timeout: Option<Duration>,
+    },
+    /// Drop the oldest buffered entry to make room for the new one
+    DropOldest,
+    /// Drop the new entry, keeping the buffered entries intact
+    #[default]
+    DropNewest,
+}
+
+/// Create a [`NonBlocking`] writer that hands entries off to a background thread writing to
+/// `writer`, along with the [`WorkerGuard`] that drains it on drop.
+pub fn non_blocking<W>(writer: W) -> (NonBlocking, WorkerGuard)
+where
+    W: io::Write + Send + 'static,
+{
+    NonBlockingBuilder::default().finish(writer)
+}
+
+/// Builder for configuring [`NonBlocking`] writers and their background workers
+#[derive(Debug)]
+pub struct NonBlockingBuilder {
+    shutdown_timeout: Duration,
+    capacity: usize,
+    backpressure: Backpressure,
+    drop_report_interval: Option<Duration>,
+}
+
+impl NonBlockingBuilder {
+    /// Sets the maximum time a dropped [`WorkerGuard`] waits for buffered entries to be written
+    pub fn with_shutdown_timeout(self, shutdown_timeout: Duration) -> Self {
+        Self {
+            shutdown_timeout,
+            ..self
+        }
+    }
+
+    /// Sets the maximum number of entries buffered before applying [`Backpressure`]
+    pub fn with_capacity(self, capacity: usize) -> Self {
+        Self {
+            capacity: capacity.max(1),
+            ..self
+        }
+    }
+
+    /// Sets the behavior of the writer when its buffer is full
+    pub fn with_backpressure(self, backpressure: Backpressure) -> Self {
+        Self {
+            backpressure,
+            ..self
+        }
+    }
+
+    /// Sets how often the worker writes a WARNING entry stating how many entries were dropped since
+    /// the previous report, or disables these reports with `None`. Reports are only written when
+    /// entries were dropped, and a final report is written on shutdown.
+    pub fn with_drop_report_interval(self, drop_report_interval: Option<Duration>) -> Self {
+        Self {
+            drop_report_interval,
+            ..self
+        }
+    }
+
+    /// Spawn the background worker writing to `writer`
+    pub fn finish<W>(self, mut writer: W) -> (NonBlocking, WorkerGuard)
+    where
+        W: io::Write + Send + 'static,
+    {
+        let shared = self.shared(Queue::Thread);
+        let worker = shared.clone();
+
+        thread::Builder::new()
+            .name("tracing-stackdriver-worker".to_string())
+            .spawn(move || worker.run(&mut writer))
+            .expect("Unable to spawn tracing-stackdriver worker thread");
+
+        self.guard(shared)
+    }
+
+    /// Spawn the background worker as a task of the current Tokio runtime, writing to an async
+    /// `writer` the entries sent over a bounded `tokio::sync::mpsc` channel of the configured
+    /// capacity. Panics when called outside of a runtime, like `tokio::spawn`.
+    ///
+    /// Emitting threads are blocked by [`Backpressure::Block`] and by a dropped [`WorkerGuard`]
+    /// without yielding to the runtime, so the guard should be dropped outside of it, and blocking
+    /// backpressure requires a multi-threaded runtime to make progress. Drop reports require the
+    /// runtime's time driver.
+    #[cfg_attr(docsrs, doc(cfg(feature = "tokio")))]
+    #[cfg(feature = "tokio")]
+    pub fn spawn<W>(self, mut writer: W) -> (NonBlocking, WorkerGuard)
+    where
+        W: tokio::io::AsyncWrite + Send + Unpin + 'static,
+    {
+        let (sender, receiver) = tokio::sync::mpsc::channel(self.capacity);
+        let shared = self.shared(Queue::Channel {
+            sender,
+            receiver: Mutex::new(receiver),
+            shutdown: tokio::sync::Notify::new(),
+        });
+        let worker = shared.clone();
+
+        tokio::spawn(async move { worker.run_async(&mut writer).await });
+
+        self.guard(shared)
+    }
+
+    /// State shared with a worker draining the given queue
+    fn shared(&self, queue: Queue) -> Arc<Shared> {
+        Arc::new(Shared {
+            capacity: self.capacity,
+            backpressure: self.backpressure,
+            drop_report_interval: self.drop_report_interval,
+            queue,
+            state: Mutex::default(),
+            available: Condvar::new(),
+            space: Condvar::new(),
+            drained: Condvar::new(),
+            dropped: AtomicU64::new(0),
+        })
+    }
+
+    /// The writer and guard of a spawned worker
+    fn guard(self, shared: Arc<Shared>) -> (NonBlocking, WorkerGuard) {
+        let guard = WorkerGuard {
+            shared: shared.clone(),
+            shutdown_timeout: self.shutdown_timeout,
+        };
+
+        (NonBlocking { shared }, guard)
+    }
+}
+
+impl Default for NonBlockingBuilder {
+    fn default() -> Self {
+        Self {
+            shutdown_timeout: DEFAULT_SHUTDOWN_TIMEOUT,
+            capacity: DEFAULT_CAPACITY,
+            backpressure: Backpressure::default(),
+            drop_report_interval: Some(DEFAULT_DROP_REPORT_INTERVAL),
+        }
+    }
+}
+
+/// A writer that buffers entries for a background worker thread, keeping slow destinations
+/// from blocking the threads that emit events
+#[derive(Clone)]
+pub struct NonBlocking {
+    shared: Arc<Shared>,
+}
+
+impl NonBlocking {
+    /// Total number of entries dropped because the buffer was full, or because they were written
+    /// after the [`WorkerGuard`] was dropped
+    pub fn dropped_entries(&self) -> u64 {
+        self.shared.dropped.load(Ordering::Relaxed)
+    }
+
+    /// Waits up to `timeout` for the entries buffered so far to be written and the destination
+    /// flushed, e.g. before a serverless instance is frozen, returning whether they were
+    pub fn flush(&self, timeout: Duration) -> bool {
+        self.shared.flush(timeout)
+    }
+}
+
+impl io::Write for NonBlocking {
+    fn write(&mut self, buffer: &[u8]) -> io::Result<usize> {
+        self.shared.push(buffer.to_vec());
+        Ok(buffer.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+impl<'a> MakeWriter<'a> for NonBlocking {
+    type Writer = NonBlocking;
+
+    fn make_writer(&'a self) -> Self::Writer {
+        self.clone()
+    }
+}
+
+impl fmt::Debug for NonBlocking {
+    fn fmt(&self, formatter: &mut fmt::Formatter<'_>) -> fmt::Result {
+        formatter.pad("NonBlocking { .. }")
+    }
+}
+
+/// Guard that flushes buffered entries when dropped, waiting up to its shutdown timeout for the
+/// background worker to drain. Hold this guard until the end of `main` so that the final entries
+/// of an exiting process are not lost.
+#[must_use = "dropping the guard immediately shuts down the background worker"]
+pub struct WorkerGuard {
+    shared: Arc<Shared>,
+    shutdown_timeout: Duration,
+}
+
+impl WorkerGuard {
+    /// Waits up to `timeout` for the entries buffered so far to be written and the destination
+    /// flushed, without shutting down the background worker, returning whether they were
+    pub fn flush(&self, timeout: Duration) -> bool {
+        self.shared.flush(timeout)
+    }
+}
+
+impl Drop for WorkerGuard {
+    fn drop(&mut self) {
+        let deadline = Instant::now() + self.shutdown_timeout;
+        let mut state = self.shared.lock();
+        state.shutdown = true;
+        self.shared.available.notify_all();
+        self.shared.space.notify_all();
+
+        // the worker task drains the entries sent so far, and later ones are dropped
+        #[cfg(feature = "tokio")]
+        if let Queue::Channel {
+            receiver, shutdown, ..
+        } = &self.shared.queue
+        {
+            lock(receiver).close();
+            shutdown.notify_one();
+        }
+
+        while !state.finished {
+            let remaining = deadline.saturating_duration_since(Instant::now());
+
+            if remaining.is_zero() {
+                break;
+            }
+
+            state = match self.shared.drained.wait_timeout(state, remaining) {
+                Ok((state, _)) => state,
+                Err(_) => break,
+            };
+        }
+    }
+}
+
+impl fmt::Debug for WorkerGuard {
+    fn fmt(&self, formatter: &mut fmt::Formatter<'_>) -> fmt::Result {
+        formatter
+            .debug_struct("WorkerGuard")
+            .field("shutdown_timeout", &self.shutdown_timeout)
+            .finish()
+    }
+}
+
+/// Buffer of the entries handed off to the worker
+enum Queue {
+    /// The queue of the shared state, drained by a worker thread
+    Thread,
+    /// A bounded channel, drained by a worker task
+    #[cfg(feature = "tokio")]
+    Channel {
+        sender: tokio::sync::mpsc::Sender<Vec<u8>>,
+        // only locked while polled, so that writers can evict the oldest entries
+        receiver: Mutex<tokio::sync::mpsc::Receiver<Vec<u8>>>,
+        // closing the receiver doesn't wake the worker waiting on it
+        shutdown: tokio::sync::Notify,
+    },
+}
+
+/// State shared between writers, the worker, and the guard
+struct Shared {
+    capacity: usize,
+    backpressure: Backpressure,
+    drop_report_interval: Option<Duration>,
+    queue: Queue,
+    state: Mutex<State>,
+    available: Condvar,
+    space: Condvar,
+    drained: Condvar,
+    dropped: AtomicU64,
+}
+
+#[derive(Default)]
+struct State {
+    queue: VecDeque<Vec<u8>>,
+    /// Number of entries ever buffered
+    queued: u64,
+    /// Number of buffered entries since written and flushed, or evicted
+    settled: u64,
+    shutdown: bool,
+    finished: bool,
+}
+
+/// Lock a mutex, even if a thread panicked while holding it
+fn lock<T>(mutex: &Mutex<T>) -> MutexGuard<'_, T> {
+    mutex
+        .lock()
+        .unwrap_or_else(|poisoned| poisoned.into_inner())
+}
+
+impl Shared {
+    fn lock(&self) -> MutexGuard<'_, State> {
+        lock(&self.state)
+    }
+
+    /// Buffer an entry for the worker, applying backpressure when full
+    fn push(&self, entry: Vec<u8>) {
+        match &self.queue {
+            Queue::Thread => {}
+            #[cfg(feature = "tokio")]
+            Queue::Channel {
+                sender, receiver, ..
+            } => return self.send(sender, receiver, entry),
+        }
+
+        let mut state = self.lock();
+
+        if state.queue.len() >= self.capacity {
+            match self.backpressure {
+                Backpressure::Block { timeout } => {
+                    let deadline = timeout.map(|timeout| Instant::now() + timeout);
+
+                    while state.queue.len() >= self.capacity && !state.shutdown {
+                        state = match deadline {
+                            None => self
+                                .space
+                                .wait(state)
+                                .unwrap_or_else(|poisoned| poisoned.into_inner()),
+                            Some(deadline) => {
+                                let remaining = deadline.saturating_duration_since(Instant::now());
+
+                                if remaining.is_zero() {
+                                    self.dropped.fetch_add(1, Ordering::Relaxed);
+                                    return;
+                                }
+
+                                self.space
+                                    .wait_timeout(state, remaining)
+                                    .unwrap_or_else(|poisoned| poisoned.into_inner())
+                                    .0
+                            }
+                        };
+                    }
+                }
+                Backpressure::DropOldest => {
+                    state.queue.pop_front();
+                    state.settled += 1;
+                    self.dropped.fetch_add(1, Ordering::Relaxed);
+                }
+                Backpressure::DropNewest => {
+                    self.dropped.fetch_add(1, Ordering::Relaxed);
+                    return;
+                }
+            }
+        }
+
+        // the worker no longer drains the buffer once shut down
+        if state.shutdown {
+            self.dropped.fetch_add(1, Ordering::Relaxed);
+            return;
+        }
+
+        state.queue.push_back(entry);
+        state.queued += 1;
+        self.available.notify_one();
+    }
+
+    /// Send an entry to the worker task, applying backpressure when the channel is full. Entries
+    /// are sent with the state locked, so that the channel only has room freed up while full.
+    #[cfg(feature = "tokio")]
+    fn send(
+        &self,
+        sender: &tokio::sync::mpsc::Sender<Vec<u8>>,
+        receiver: &Mutex<tokio::sync::mpsc::Receiver<Vec<u8>>>,
+        mut entry: Vec<u8>,
+    ) {
+        use tokio::sync::mpsc::error::TrySendError;
+
+        let mut state = self.lock();
+        let deadline = match self.backpressure {
+            Backpressure::Block { timeout } => timeout.map(|timeout| Instant::now() + timeout),
+            _ => None,
+        };
+
+        loop {
+            if state.shutdown {
+                self.dropped.fetch_add(1, Ordering::Relaxed);
+                return;
+            }
+
+            entry = match sender.try_send(entry) {
+                Ok(()) => {
+                    state.queued += 1;
+                    return;
+                }
+                Err(TrySendError::Full(entry)) => entry,
+                Err(TrySendError::Closed(_)) => {
+                    self.dropped.fetch_add(1, Ordering::Relaxed);
+                    return;
+                }
+            };
+
+            match self.backpressure {
+                Backpressure::Block { .. } => {
+                    state = match deadline {
+                        None => self
+                            .space
+                            .wait(state)
+                            .unwrap_or_else(|poisoned| poisoned.into_inner()),
+                        Some(deadline) => {
+                            let remaining = deadline.saturating_duration_since(Instant::now());
+
+                            if remaining.is_zero() {
+                                self.dropped.fetch_add(1, Ordering::Relaxed);
+                                return;
+                            }
+
+                            self.space
+                                .wait_timeout(state, remaining)
+                                .unwrap_or_else(|poisoned| poisoned.into_inner())
+                                .0
+                        }
+                    };
+                }
+                Backpressure::DropOldest => {
+                    // the worker may have received every entry since, leaving room for this one
+                    if lock(receiver).try_recv().is_ok() {
+                        state.settled += 1;
+                        self.dropped.fetch_add(1, Ordering::Relaxed);
+                    }
+                }
+                Backpressure::DropNewest => {
+                    self.dropped.fetch_add(1, Ordering::Relaxed);
+                    return;
+                }
+            }
+        }
+    }
+
+    /// Wait for the entries buffered so far to settle, returning whether they did in time
+    fn flush(&self, timeout: Duration) -> bool {
+        let deadline = Instant::now() + timeout;
+        let mut state = self.lock();
+        let queued = state.queued;
+
+        while state.settled < queued && !state.finished {
+            let remaining = deadline.saturating_duration_since(Instant::now());
+
+            if remaining.is_zero() {
+                return false;
+            }
+
+            state = match self.drained.wait_timeout(state, remaining) {
+                Ok((state, _)) => state,
+                Err(_) => return false,
+            };
+        }
+
+        state.settled >= queued
+    }
+
+    /// Write buffered entries until shut down and drained
+    fn run(&self, writer: &mut dyn io::Write) {
+        let mut reporter = self.drop_report_interval.map(DropReporter::new);
+        let mut state = self.lock();
+
+        loop {
+            if state.queue.is_empty() && !state.shutdown {
+                state = match &reporter {
+                    None => self
+                        .available
+                        .wait(state)
+                        .unwrap_or_else(|poisoned| poisoned.into_inner()),
+                    Some(reporter) => {
+                        self.available
+                            .wait_timeout(state, reporter.remaining())
+                            .unwrap_or_else(|poisoned| poisoned.into_inner())
+                            .0
+                    }
+                };
+            }
+
+            let entries: Vec<_> = state.queue.drain(..).collect();
+            let written = entries.len() as u64;
+            let shutdown = state.shutdown;
+            self.space.notify_all();
+            drop(state);
+
+            for entry in entries {
+                // there's nowhere left to report write errors, so the entry is skipped
+                let _ = writer.write_all(&entry);
+            }
+
+            if let Some(reporter) = &mut reporter {
+                let dropped = self.dropped.load(Ordering::Relaxed);
+                reporter.report(writer, dropped, shutdown);
+            }
+
+            let _ = writer.flush();
+            state = self.lock();
+            state.settled += written;
+            self.drained.notify_all();
+
+            if shutdown && state.queue.is_empty() {
+                break;
+            }
+        }
+
+        state.finished = true;
+        self.drained.notify_all();
+    }
+
+    /// Write the entries sent over the channel until it's closed and drained
+    #[cfg(feature = "tokio")]
+    async fn run_async<W>(&self, writer: &mut W)
+    where
+        W: tokio::io::AsyncWrite + Unpin,
+    {
+        use std::task::Poll;
+        use tokio::io::AsyncWriteExt;
+
+        use std::future::Future;
+
+        let Queue::Channel {
+            receiver, shutdown, ..
+        } = &self.queue
+        else {
+            return;
+        };
+
+        let mut reporter = self.drop_report_interval.map(DropReporter::new);
+        let mut entries = Vec::new();
+        let mut shutdown = std::pin::pin!(shutdown.notified());
+
+        loop {
+            let receive = std::future::poll_fn(|context| {
+                let mut receiver = lock(receiver);
+
+                match receiver.poll_recv_many(context, &mut entries, self.capacity) {
+                    // the channel is closed by the time the worker is notified of the shutdown,
+                    // so receiving the rest of its entries is ready
+                    Poll::Pending if shutdown.as_mut().poll(context).is_ready() => {
+                        receiver.poll_recv_many(context, &mut entries, self.capacity)
+                    }
+                    received => received,
+                }
+            });
+
+            // no entries are received once the channel is closed and drained
+            let closed = match &reporter {
+                None => receive.await == 0,
+                Some(reporter) => {
+                    matches!(
+                        tokio::time::timeout(reporter.remaining(), receive).await,
+                        Ok(0)
+                    )
+                }
+            };
+
+            let written = entries.len() as u64;
+
+            // writers blocked on the full channel wait for space with the state locked
+            {
+                let _state = self.lock();
+                self.space.notify_all();
+            }
+
+            for entry in entries.drain(..) {
+                // there's nowhere left to report write errors, so the entry is skipped
+                let _ = writer.write_all(&entry).await;
+            }
+
+            if let Some(reporter) = &mut reporter {
+                let mut report = Vec::new();
+                let dropped = self.dropped.load(Ordering::Relaxed);
+                reporter.report(&mut report, dropped, closed);
+                let _ = writer.write_all(&report).await;
+            }
+
+            let _ = writer.flush().await;
+            self.lock().settled += written;
+            self.drained.notify_all();
+
+            if closed {
+                break;
+            }
+        }
+
+        self.lock().finished = true;
+        self.drained.notify_all();
+    }
+}
+
+/// Periodic reporting of dropped entries, so that gaps in the logs are visible in the logs
+struct DropReporter {
+    interval: Duration,
+    since: Instant,
+    reported: u64,
+}
+
+impl DropReporter {
+    fn new(interval: Duration) -> Self {
+        Self {
+            interval,
+            since: Instant::now(),
+            reported: 0,
+        }
+    }
+
+    /// Time left until the next report is due
+    fn remaining(&self) -> Duration {
+        (self.since + self.interval).saturating_duration_since(Instant::now())
+    }
+
+    /// Write a WARNING entry for entries dropped since the last report, if one is due
+    fn report(&mut self, writer: &mut dyn io::Write, dropped: u64, force: bool) {
+        if !force && !self.remaining().is_zero() {
+            return;
+        }
+
+        let count = dropped.saturating_sub(self.reported);
+        let elapsed = self.since.elapsed();
+
+        if count > 0 {
+            let message = format!(
+                "dropped {} log entries in the last {:.3}s",
+                count,
+                elapsed.as_secs_f64()
+            );
+            let entry = LogEntry::report(module_path!(), LogSeverity::Warning, message)
+                .with_field("droppedEntries", count)
+                .with_field("intervalSeconds", elapsed.as_secs_f64());
+
+            let _ = entry::write_report(writer, entry);
+        }
+
+        self.reported = dropped;
+        self.since = Instant::now();
+    }
+}
@@ -0,0 +1,117 @@
+use crate::trace::TraceContext;
+use opentelemetry::{
+    propagation::{Extractor, Injector},
+    trace::{SpanContext, SpanId, TraceFlags, TraceId, TraceState},
+};
+
+/// Header carrying the W3C trace context
+const TRACEPARENT_HEADER: &str = "traceparent";
+
+/// Header carrying Google Cloud's legacy trace context
+const X_CLOUD_TRACE_CONTEXT_HEADER: &str = "x-cloud-trace-context";
+
+impl TraceContext {
+    /// Write the `traceparent` and `X-Cloud-Trace-Context` headers to an OpenTelemetry carrier
+    pub fn inject(&self, injector: &mut dyn Injector) {
+        injector.set(TRACEPARENT_HEADER, self.traceparent());
+        injector.set(X_CLOUD_TRACE_CONTEXT_HEADER, self.x_cloud_trace_context());
+    }
+
+    /// Read a trace context from an OpenTelemetry carrier, preferring the `traceparent` header
+    /// over the `X-Cloud-Trace-Context` header
+    pub fn extract(extractor: &dyn Extractor) -> Option<Self> {
+        extractor
+            .get(TRACEPARENT_HEADER)
+            .and_then(Self::from_traceparent)
+            .or_else(|| {
+                extractor
+                    .get(X_CLOUD_TRACE_CONTEXT_HEADER)
+                    .and_then(Self::from_x_cloud_trace_context)
+            })
+    }
+
+    /// Convert an OpenTelemetry span context, returning `None` for invalid contexts
+    pub fn from_span_context(span_context: &SpanContext) -> Option<Self> {
+        span_context.is_valid().then(|| Self {
+            trace_id: span_context.trace_id().to_string(),
+            span_id: u64::from_be_bytes(span_context.span_id().to_bytes()),
+            sampled: span_context.is_sampled(),
+            project_id: None,
+        })
+    }
+
+    /// Convert into a remote OpenTelemetry span context, e.g. for use as a parent context
+    pub fn to_span_context(&self) -> SpanContext {
+        let flags = if self.sampled {
+            TraceFlags::SAMPLED
+        } else {
+            TraceFlags::default()
+        };
+
+        SpanContext::new(
+            TraceId::from_hex(&self.trace_id).unwrap_or(TraceId::INVALID),
+            SpanId::from_bytes(self.span_id.to_be_bytes()),
+            flags,
+            true,
+            TraceState::default(),
+        )
+    }
+}
+
+/// Extractor exposing a [`TraceContext`] to OpenTelemetry propagators as the headers that carry it
+#[derive(Clone, Debug)]
+pub struct TraceContextExtractor {
+    traceparent: String,
+    x_cloud_trace_context: String,
+}
+
+impl From<&TraceContext> for TraceContextExtractor {
+    fn from(context: &TraceContext) -> Self {
+        Self {
+            traceparent: context.traceparent(),
+            x_cloud_trace_context: context.x_cloud_trace_context(),
+        }
+    }
+}
+
+impl Extractor for TraceContextExtractor {
+    fn get(&self, key: &str) -> Option<&str> {
+        if key.eq_ignore_ascii_case(TRACEPARENT_HEADER) {
+            Some(&self.traceparent)
+        } else if key.eq_ignore_ascii_case(X_CLOUD_TRACE_CONTEXT_HEADER) {
+            Some(&self.x_cloud_trace_context)
+        } else {
+            None
+        }
+    }
+
+    fn keys(&self) -> Vec<&str> {
+        vec![TRACEPARENT_HEADER, X_CLOUD_TRACE_CONTEXT_HEADER]
+    }
+}
+
+/// Injector collecting the headers written by OpenTelemetry propagators into a [`TraceContext`]
+#[derive(Clone, Debug, Default)]
+pub struct TraceContextInjector {
+    context: Option<TraceContext>,
+}
+
+impl TraceContextInjector {
+    /// The trace context injected so far, if any
+    pub fn into_trace_context(self) -> Option<TraceContext> {
+        self.context
+    }
+}
+
+impl Injector for TraceContextInjector {
+    fn set(&mut self, key: &str, value: String) {
+        if key.eq_ignore_ascii_case(TRACEPARENT_HEADER) {
+            // the W3C trace context takes precedence over the legacy header
+            if let Some(context) = TraceContext::from_traceparent(&value) {
+                self.context = Some(context);
+            }
+        } else if key.eq_ignore_ascii_case(X_CLOUD_TRACE_CONTEXT_HEADER) && self.context.is_none() {
+            self.context = TraceContext::from_x_cloud_trace_context(&value);
+        }
+    }
+}
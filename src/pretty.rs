@@ -0,0 +1,220 @@
+use crate::event_formatter::EventFormatter;
+use serde_json::{Map, Value};
+use std::fmt;
+use tracing_core::{Event, Subscriber};
+use tracing_subscriber::{
+    fmt::{
+        format::{self, FormatEvent, FormatFields},
+        FmtContext, FormattedFields,
+    },
+    registry::LookupSpan,
+};
+
+/// Fields of entries rendered on their own rather than with the other fields
+const RENDERED_FIELDS: [&str; 6] = [
+    "time",
+    "severity",
+    "target",
+    "message",
+    "span",
+    "httpRequest",
+];
+
+/// ANSI escape that resets the style
+const RESET: &str = "\x1b[0m";
+
+/// ANSI escape of dimmed text
+const DIMMED: &str = "\x1b[2m";
+
+/// Event formatter rendering Stackdriver entries for local development rather than as JSON: the
+/// time, severity colored by level, target, message, and other fields on one line, followed by a
+/// compact line of the entry's `httpRequest` summary and labels and by its span context, one
+/// indented line per span from the root. Colors are only written when the fmt layer enables ANSI.
+#[derive(Default)]
+pub struct PrettyFormatter {
+    event_formatter: EventFormatter,
+}
+
+impl PrettyFormatter {
+    /// Create a pretty formatter rendering the entries of an event formatter
+    pub fn new(event_formatter: EventFormatter) -> Self {
+        Self { event_formatter }
+    }
+}
+
+impl<S, N> FormatEvent<S, N> for PrettyFormatter
+where
+    S: Subscriber + for<'span> LookupSpan<'span>,
+    N: for<'writer> FormatFields<'writer> + 'static,
+{
+    fn format_event(
+        &self,
+        context: &FmtContext<S, N>,
+        mut writer: format::Writer,
+        event: &Event,
+    ) -> fmt::Result {
+        let mut entries = String::new();
+        FormatEvent::format_event(
+            &self.event_formatter,
+            context,
+            format::Writer::new(&mut entries),
+            event,
+        )?;
+
+        let ansi = writer.has_ansi_escapes();
+        let mut spans = Vec::new();
+
+        if let Some(scope) = context.event_scope() {
+            for span in scope.from_root() {
+                let extensions = span.extensions();
+                let fields = extensions
+                    .get::<FormattedFields<N>>()
+                    .map(|fields| fields.fields.clone())
+                    .unwrap_or_default();
+
+                spans.push((span.name(), fields));
+            }
+        }
+
+        for line in entries.lines() {
+            match serde_json::from_str::<Value>(line) {
+                Ok(Value::Object(entry)) => render(&mut writer, &entry, &spans, ansi)?,
+                _ => writeln!(writer, "{}", line)?,
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// Render an entry with the context of its spans
+fn render(
+    writer: &mut format::Writer,
+    entry: &Map<String, Value>,
+    spans: &[(&str, String)],
+    ansi: bool,
+) -> fmt::Result {
+    let severity = entry
+        .get("severity")
+        .and_then(Value::as_str)
+        .unwrap_or("DEFAULT");
+
+    if let Some(time) = entry.get("time").and_then(Value::as_str) {
+        let time = time.split_once('T').map_or(time, |(_, time)| time);
+        let time = time.get(..12).unwrap_or(time);
+        paint(writer, ansi, DIMMED, time)?;
+        writer.write_char(' ')?;
+    }
+
+    paint(writer, ansi, color(severity), &format!("{:>8}", severity))?;
+
+    if let Some(target) = entry.get("target").and_then(Value::as_str) {
+        writer.write_char(' ')?;
+        paint(writer, ansi, DIMMED, &format!("{}:", target))?;
+    }
+
+    if let Some(message) = entry.get("message").and_then(Value::as_str) {
+        write!(writer, " {}", message)?;
+    }
+
+    for (key, value) in entry {
+        if RENDERED_FIELDS.contains(&key.as_str()) || key.starts_with("logging.googleapis.com/") {
+            continue;
+        }
+
+        writer.write_char(' ')?;
+        paint(writer, ansi, DIMMED, &format!("{}=", key))?;
+        write_value(writer, value)?;
+    }
+
+    writeln!(writer)?;
+
+    let summary = [
+        entry.get("httpRequest").and_then(http_summary),
+        entry
+            .get("logging.googleapis.com/labels")
+            .and_then(Value::as_object)
+            .filter(|labels| !labels.is_empty())
+            .map(|labels| {
+                let labels: Vec<_> = labels
+                    .iter()
+                    .map(|(key, value)| format!("{}={}", key, value.as_str().unwrap_or_default()))
+                    .collect();
+
+                format!("[{}]", labels.join(" "))
+            }),
+    ];
+    let summary: Vec<_> = summary.into_iter().flatten().collect();
+
+    if !summary.is_empty() {
+        writeln!(writer, "    {}", summary.join(" "))?;
+    }
+
+    for (depth, (name, fields)) in spans.iter().enumerate() {
+        write!(writer, "{:indent$}", "", indent = 4 + depth * 2)?;
+        paint(writer, ansi, DIMMED, "in ")?;
+        write!(writer, "{}", name)?;
+
+        if !fields.is_empty() {
+            write!(writer, " {}", fields)?;
+        }
+
+        writeln!(writer)?;
+    }
+
+    Ok(())
+}
+
+/// One-line summary of an `httpRequest`, e.g. `GET /orders 200 0.012s 512B`
+fn http_summary(request: &Value) -> Option<String> {
+    let request = request.as_object()?;
+    let summary: Vec<_> = [
+        "requestMethod",
+        "requestUrl",
+        "status",
+        "latency",
+        "responseSize",
+    ]
+    .iter()
+    .filter_map(|key| match request.get(*key)? {
+        Value::String(value) if *key == "responseSize" => Some(format!("{}B", value)),
+        Value::String(value) => Some(value.clone()),
+        Value::Null => None,
+        value => Some(value.to_string()),
+    })
+    .collect();
+
+    (!summary.is_empty()).then(|| summary.join(" "))
+}
+
+/// Write a field value, with strings unquoted unless they're empty or contain whitespace
+fn write_value(writer: &mut format::Writer, value: &Value) -> fmt::Result {
+    match value {
+        Value::String(value) if !value.is_empty() && !value.contains(char::is_whitespace) => {
+            writer.write_str(value)
+        }
+        value => write!(writer, "{}", value),
+    }
+}
+
+/// ANSI escape of the color of a severity
+fn color(severity: &str) -> &'static str {
+    match severity {
+        "DEBUG" => "\x1b[34m",
+        "INFO" => "\x1b[32m",
+        "NOTICE" => "\x1b[36m",
+        "WARNING" => "\x1b[33m",
+        "ERROR" => "\x1b[31m",
+        "CRITICAL" | "ALERT" | "EMERGENCY" => "\x1b[1;31m",
+        _ => DIMMED,
+    }
+}
+
+/// Write text in a style when ANSI escapes are enabled
+fn paint(writer: &mut format::Writer, ansi: bool, style: &str, text: &str) -> fmt::Result {
+    if ansi {
+        write!(writer, "{}{}{}", style, text, RESET)
+    } else {
+        writer.write_str(text)
+    }
+}
@@ -0,0 +1,58 @@
+use crate::{decorator::EntryDecorator, entry::LogEntry};
+
+/// What becomes of an entry once a processor has run
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Verdict {
+    /// Pass the entry on to the next processor, or write it after the last one
+    Keep,
+    /// Drop the entry, skipping the processors after this one
+    Drop,
+}
+
+/// Step of the processor pipeline that every entry goes through once it's assembled, right before
+/// it's written, in the order processors were added with
+/// [`with_processor`](crate::Layer::with_processor). Closures taking a mutable [`LogEntry`] and
+/// returning a [`Verdict`] are processors.
+pub trait EntryProcessor: Send + Sync {
+    /// Processes an entry, returning whether it's kept
+    fn process(&self, entry: &mut LogEntry) -> Verdict;
+}
+
+impl<F> EntryProcessor for F
+where
+    F: Fn(&mut LogEntry) -> Verdict + Send + Sync,
+{
+    fn process(&self, entry: &mut LogEntry) -> Verdict {
+        self(entry)
+    }
+}
+
+/// Processor decorating every entry, as added by
+/// [`with_entry_decorator`](crate::Layer::with_entry_decorator)
+pub(crate) struct Decorate<D>(pub(crate) D);
+
+impl<D> EntryProcessor for Decorate<D>
+where
+    D: EntryDecorator,
+{
+    fn process(&self, entry: &mut LogEntry) -> Verdict {
+        self.0.decorate(entry);
+        Verdict::Keep
+    }
+}
+
+/// Run a formatted entry through the processors, returning `None` when one drops it. Entries that
+/// aren't JSON objects are kept as they are.
+pub(crate) fn process(processors: &[Box<dyn EntryProcessor>], entry: String) -> Option<String> {
+    let Ok(mut log_entry) = serde_json::from_str::<LogEntry>(&entry) else {
+        return Some(entry);
+    };
+
+    for processor in processors {
+        if processor.process(&mut log_entry) == Verdict::Drop {
+            return None;
+        }
+    }
+
+    Some(serde_json::to_string(&log_entry).unwrap_or(entry))
+}
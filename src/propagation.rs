@@ -0,0 +1,35 @@
+use crate::trace::TraceContext;
+use http::{header::HeaderValue, Extensions};
+use reqwest_middleware::{
+    reqwest::{Request, Response},
+    Middleware, Next, Result,
+};
+
+/// Middleware for [`reqwest_middleware`] clients that injects the current [`TraceContext`] into
+/// outgoing requests through the `traceparent` and `X-Cloud-Trace-Context` headers
+#[derive(Clone, Copy, Debug, Default)]
+pub struct TraceContextMiddleware;
+
+#[async_trait::async_trait]
+impl Middleware for TraceContextMiddleware {
+    async fn handle(
+        &self,
+        mut request: Request,
+        extensions: &mut Extensions,
+        next: Next<'_>,
+    ) -> Result<Response> {
+        if let Some(context) = TraceContext::current() {
+            let headers = request.headers_mut();
+
+            if let Ok(value) = HeaderValue::from_str(&context.traceparent()) {
+                headers.insert("traceparent", value);
+            }
+
+            if let Ok(value) = HeaderValue::from_str(&context.x_cloud_trace_context()) {
+                headers.insert("x-cloud-trace-context", value);
+            }
+        }
+
+        next.run(request, extensions).await
+    }
+}
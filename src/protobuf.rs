@@ -0,0 +1,365 @@
+use crate::{
+    line_buffer::LineBuffer,
+    log_entry::{api_entry, encode_log_id, DEFAULT_LOG_ID},
+    resource::MonitoredResource,
+};
+use serde_json::{Map, Value};
+use std::{fmt, io};
+use time::{format_description::well_known::Rfc3339, OffsetDateTime};
+
+/// Writer that transcodes formatted entries into length-delimited `google.logging.v2.LogEntry`
+/// protobuf messages, for pipelines that ingest protobuf directly. Each message is preceded by
+/// its varint-encoded length, as written by protobuf's `writeDelimitedTo`.
+pub struct ProtobufWriter<W> {
+    writer: W,
+    log_name: String,
+    resource: MonitoredResource,
+    lines: LineBuffer,
+}
+
+impl<W> ProtobufWriter<W>
+where
+    W: io::Write,
+{
+    /// Create a writer of entries for the logs of the given project
+    pub fn new(project_id: impl Into<String>, writer: W) -> Self {
+        let project_id = project_id.into();
+
+        Self {
+            writer,
+            log_name: format!("projects/{}/logs/{}", project_id, DEFAULT_LOG_ID),
+            resource: MonitoredResource::global(project_id),
+            lines: LineBuffer::default(),
+        }
+    }
+
+    /// Sets the ID of the log that entries are written to, as in `projects/PROJECT_ID/logs/LOG_ID`
+    pub fn with_log_id(self, log_id: &str) -> Self {
+        let parent = self
+            .log_name
+            .split_once("/logs/")
+            .map(|(parent, _)| parent.to_string())
+            .unwrap_or_default();
+
+        Self {
+            log_name: format!("{}/logs/{}", parent, encode_log_id(log_id)),
+            ..self
+        }
+    }
+
+    /// Sets the full resource name of the log that entries are written to
+    pub fn with_log_name(self, log_name: impl Into<String>) -> Self {
+        Self {
+            log_name: log_name.into(),
+            ..self
+        }
+    }
+
+    /// Sets the monitored resource that entries are attributed to
+    pub fn with_resource(self, resource: MonitoredResource) -> Self {
+        Self { resource, ..self }
+    }
+
+    /// Transcode a single formatted entry
+    fn write_line(&mut self, line: &[u8]) -> io::Result<()> {
+        let structured = match serde_json::from_slice::<Value>(line) {
+            Ok(Value::Object(structured)) => structured,
+            // blank or malformed lines can't be transcoded into entries
+            _ => return Ok(()),
+        };
+
+        let mut entry = match api_entry(structured, &self.log_name) {
+            Value::Object(entry) => entry,
+            _ => return Ok(()),
+        };
+
+        entry
+            .entry("logName")
+            .or_insert_with(|| Value::String(self.log_name.clone()));
+        entry
+            .entry("resource")
+            .or_insert_with(|| serde_json::to_value(&self.resource).unwrap_or_default());
+
+        let message = log_entry(&entry);
+        let mut length = Message::default();
+        length.varint(message.0.len() as u64);
+
+        self.writer.write_all(&length.0)?;
+        self.writer.write_all(&message.0)
+    }
+}
+
+impl<W> io::Write for ProtobufWriter<W>
+where
+    W: io::Write,
+{
+    fn write(&mut self, buffer: &[u8]) -> io::Result<usize> {
+        for line in self.lines.push(buffer) {
+            self.write_line(&line)?;
+        }
+
+        Ok(buffer.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.writer.flush()
+    }
+}
+
+impl<W> fmt::Debug for ProtobufWriter<W> {
+    fn fmt(&self, formatter: &mut fmt::Formatter<'_>) -> fmt::Result {
+        formatter
+            .debug_struct("ProtobufWriter")
+            .field("log_name", &self.log_name)
+            .field("resource", &self.resource)
+            .finish_non_exhaustive()
+    }
+}
+
+/// Encode the REST representation of a `LogEntry` as its protobuf message
+fn log_entry(entry: &Map<String, Value>) -> Message {
+    let mut message = Message::default();
+
+    for (key, value) in entry {
+        match key.as_str() {
+            "insertId" => message.string(4, value.as_str()),
+            "jsonPayload" => {
+                if let Value::Object(payload) = value {
+                    message.message(6, &structure(payload));
+                }
+            }
+            "httpRequest" => {
+                if let Value::Object(http_request) = value {
+                    message.message(7, &http_request_message(http_request));
+                }
+            }
+            "resource" => {
+                let mut resource = Message::default();
+                resource.string(1, value["type"].as_str());
+
+                if let Value::Object(labels) = &value["labels"] {
+                    resource.labels(2, labels);
+                }
+
+                message.message(8, &resource);
+            }
+            "timestamp" => {
+                if let Some(timestamp) = value.as_str().and_then(timestamp) {
+                    message.message(9, &timestamp);
+                }
+            }
+            "severity" => message.enumeration(10, value.as_str().map(severity).unwrap_or(0)),
+            "labels" => {
+                if let Value::Object(labels) = value {
+                    message.labels(11, labels);
+                }
+            }
+            "logName" => message.string(12, value.as_str()),
+            "operation" => {
+                let mut operation = Message::default();
+                operation.string(1, value["id"].as_str());
+                operation.string(2, value["producer"].as_str());
+                operation.boolean(3, value["first"].as_bool());
+                operation.boolean(4, value["last"].as_bool());
+                message.message(15, &operation);
+            }
+            "trace" => message.string(22, value.as_str()),
+            "sourceLocation" => {
+                let mut source_location = Message::default();
+                source_location.string(1, value["file"].as_str());
+                source_location.int64(2, integer(&value["line"]));
+                source_location.string(3, value["function"].as_str());
+                message.message(23, &source_location);
+            }
+            "spanId" => message.string(27, value.as_str()),
+            "traceSampled" => message.boolean(30, value.as_bool()),
+            _ => (),
+        }
+    }
+
+    message
+}
+
+/// Encode a `google.logging.type.HttpRequest`
+fn http_request_message(http_request: &Map<String, Value>) -> Message {
+    let mut message = Message::default();
+
+    for (key, value) in http_request {
+        match key.as_str() {
+            "requestMethod" => message.string(1, value.as_str()),
+            "requestUrl" => message.string(2, value.as_str()),
+            "requestSize" => message.int64(3, integer(value)),
+            "status" => message.int64(4, integer(value)),
+            "responseSize" => message.int64(5, integer(value)),
+            "userAgent" => message.string(6, value.as_str()),
+            "remoteIp" => message.string(7, value.as_str()),
+            "referer" => message.string(8, value.as_str()),
+            "cacheHit" => message.boolean(9, value.as_bool()),
+            "cacheValidatedWithOriginServer" => message.boolean(10, value.as_bool()),
+            "cacheLookup" => message.boolean(11, value.as_bool()),
+            "cacheFillBytes" => message.int64(12, integer(value)),
+            "serverIp" => message.string(13, value.as_str()),
+            "latency" => {
+                if let Some(latency) = value.as_str().and_then(duration) {
+                    message.message(14, &latency);
+                }
+            }
+            "protocol" => message.string(15, value.as_str()),
+            _ => (),
+        }
+    }
+
+    message
+}
+
+/// Encode a `google.protobuf.Struct`
+fn structure(fields: &Map<String, Value>) -> Message {
+    let mut message = Message::default();
+
+    for (key, value) in fields {
+        let mut field = Message::default();
+        field.string(1, Some(key));
+        field.message(2, &struct_value(value));
+        message.message(1, &field);
+    }
+
+    message
+}
+
+/// Encode a `google.protobuf.Value`
+fn struct_value(value: &Value) -> Message {
+    let mut message = Message::default();
+
+    match value {
+        Value::Null => message.enumeration(1, 0),
+        Value::Number(number) => message.double(2, number.as_f64().unwrap_or_default()),
+        Value::String(string) => message.string(3, Some(string)),
+        Value::Bool(boolean) => message.boolean(4, Some(*boolean)),
+        Value::Object(fields) => message.message(5, &structure(fields)),
+        Value::Array(values) => {
+            let mut list = Message::default();
+
+            for value in values {
+                list.message(1, &struct_value(value));
+            }
+
+            message.message(6, &list);
+        }
+    }
+
+    message
+}
+
+/// Encode an RFC 3339 timestamp as a `google.protobuf.Timestamp`
+fn timestamp(timestamp: &str) -> Option<Message> {
+    let timestamp = OffsetDateTime::parse(timestamp, &Rfc3339).ok()?;
+    let mut message = Message::default();
+    message.int64(1, Some(timestamp.unix_timestamp()));
+    message.int64(2, Some(timestamp.nanosecond().into()));
+    Some(message)
+}
+
+/// Encode a duration in seconds with an `s` suffix, e.g. `0.23s`, as a `google.protobuf.Duration`
+fn duration(duration: &str) -> Option<Message> {
+    let seconds: f64 = duration.strip_suffix('s')?.parse().ok()?;
+    let mut message = Message::default();
+    message.int64(1, Some(seconds.trunc() as i64));
+    message.int64(2, Some((seconds.fract() * 1e9).round() as i64));
+    Some(message)
+}
+
+/// Protobuf value of a `google.logging.type.LogSeverity`
+fn severity(severity: &str) -> i32 {
+    match severity {
+        "DEBUG" => 100,
+        "INFO" => 200,
+        "NOTICE" => 300,
+        "WARNING" => 400,
+        "ERROR" => 500,
+        "CRITICAL" => 600,
+        "ALERT" => 700,
+        "EMERGENCY" => 800,
+        _ => 0,
+    }
+}
+
+/// Read an integer that may be written as a JSON number or string
+fn integer(value: &Value) -> Option<i64> {
+    match value {
+        Value::Number(number) => number.as_i64(),
+        Value::String(string) => string.parse().ok(),
+        _ => None,
+    }
+}
+
+/// Encoded protobuf message, omitting unset fields
+#[derive(Default)]
+struct Message(Vec<u8>);
+
+impl Message {
+    fn varint(&mut self, mut value: u64) {
+        while value >= 0x80 {
+            self.0.push((value as u8) | 0x80);
+            value >>= 7;
+        }
+
+        self.0.push(value as u8);
+    }
+
+    fn key(&mut self, field: u32, wire_type: u8) {
+        self.varint(u64::from(field) << 3 | u64::from(wire_type));
+    }
+
+    fn string(&mut self, field: u32, value: Option<&str>) {
+        if let Some(value) = value {
+            self.key(field, 2);
+            self.varint(value.len() as u64);
+            self.0.extend_from_slice(value.as_bytes());
+        }
+    }
+
+    fn message(&mut self, field: u32, message: &Message) {
+        self.key(field, 2);
+        self.varint(message.0.len() as u64);
+        self.0.extend_from_slice(&message.0);
+    }
+
+    fn int64(&mut self, field: u32, value: Option<i64>) {
+        if let Some(value) = value {
+            self.key(field, 0);
+            self.varint(value as u64);
+        }
+    }
+
+    fn enumeration(&mut self, field: u32, value: i32) {
+        self.key(field, 0);
+        self.varint(i64::from(value) as u64);
+    }
+
+    fn boolean(&mut self, field: u32, value: Option<bool>) {
+        if let Some(value) = value {
+            self.key(field, 0);
+            self.varint(u64::from(value));
+        }
+    }
+
+    fn double(&mut self, field: u32, value: f64) {
+        self.key(field, 1);
+        self.0.extend_from_slice(&value.to_le_bytes());
+    }
+
+    /// Encode a `map<string, string>` of labels
+    fn labels(&mut self, field: u32, labels: &Map<String, Value>) {
+        for (key, value) in labels {
+            let value = match value {
+                Value::String(value) => value.clone(),
+                value => value.to_string(),
+            };
+
+            let mut entry = Message::default();
+            entry.string(1, Some(key));
+            entry.string(2, Some(&value));
+            self.message(field, &entry);
+        }
+    }
+}
@@ -0,0 +1,32 @@
+use crate::fields::{self, as_json, DurationFormat};
+use serde_json::Value;
+use std::fmt;
+use tracing_core::Field;
+use tracing_subscriber::field::Visit;
+
+/// Target of the events carrying raw entries
+pub(crate) const RAW_ENTRY_TARGET: &str = module_path!();
+
+/// Name of the field carrying a raw entry
+const RAW_ENTRY_FIELD: &str = "entry";
+
+/// Write an externally produced JSON object, e.g. a structured record received from a subprocess
+/// or over FFI, as a single entry. The object is written as-is, merged with the `time`,
+/// `severity`, Cloud Trace fields, and labels of the current context wherever it doesn't set
+/// them itself. Raw entries are dispatched as INFO events with their own target, so they're
+/// subject to the subscriber's filters, and are only written by this crate's layers.
+pub fn emit_raw_entry(entry: &Value) {
+    tracing::info!(target: RAW_ENTRY_TARGET, entry = %as_json(entry));
+}
+
+/// Visitor extracting the raw entry of an event
+#[derive(Debug, Default)]
+pub(crate) struct RawEntry(pub(crate) Option<Value>);
+
+impl Visit for RawEntry {
+    fn record_debug(&mut self, field: &Field, value: &dyn fmt::Debug) {
+        if field.name() == RAW_ENTRY_FIELD {
+            self.0 = Some(fields::debug_value(value, DurationFormat::default(), None));
+        }
+    }
+}
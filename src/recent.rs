@@ -0,0 +1,111 @@
+use std::{
+    fmt, ptr,
+    sync::{
+        atomic::{AtomicPtr, AtomicUsize, Ordering},
+        Arc,
+    },
+};
+
+/// Handle for reading the last entries a Layer formatted, including those dropped by sampling,
+/// e.g. to dump recent history from a panic handler, shutdown hook, or debug endpoint. The
+/// entries are kept in a lock-free ring buffer that stays usable after the Layer is installed.
+#[derive(Clone)]
+pub struct RecentEntries {
+    inner: Arc<Ring>,
+}
+
+impl RecentEntries {
+    /// Create a handle keeping the last `capacity` entries
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            inner: Arc::new(Ring {
+                slots: (0..capacity)
+                    .map(|_| AtomicPtr::new(ptr::null_mut()))
+                    .collect(),
+                next: AtomicUsize::new(0),
+            }),
+        }
+    }
+
+    /// The recent entries as JSON lines, oldest first
+    pub fn entries(&self) -> Vec<String> {
+        let slots = &self.inner.slots;
+        let next = self.inner.next.load(Ordering::Acquire);
+
+        (0..slots.len())
+            .filter_map(|offset| self.inner.read(&slots[(next + offset) % slots.len()]))
+            .collect()
+    }
+
+    pub(crate) fn push(&self, entry: &str) {
+        let slots = &self.inner.slots;
+
+        if slots.is_empty() {
+            return;
+        }
+
+        let index = self.inner.next.fetch_add(1, Ordering::AcqRel) % slots.len();
+        let entry = Box::into_raw(Box::new(entry.to_string()));
+        let previous = slots[index].swap(entry, Ordering::AcqRel);
+
+        if !previous.is_null() {
+            // SAFETY: every non-null pointer in a slot is an owned box, now swapped out of it
+            drop(unsafe { Box::from_raw(previous) });
+        }
+    }
+}
+
+impl fmt::Debug for RecentEntries {
+    fn fmt(&self, formatter: &mut fmt::Formatter<'_>) -> fmt::Result {
+        formatter
+            .debug_struct("RecentEntries")
+            .field("capacity", &self.inner.slots.len())
+            .finish_non_exhaustive()
+    }
+}
+
+/// Fixed slots of boxed entries, overwritten in order
+struct Ring {
+    slots: Box<[AtomicPtr<String>]>,
+    next: AtomicUsize,
+}
+
+impl Ring {
+    /// Copy the entry of a slot, taking it out while it's read so that writers can't free it
+    fn read(&self, slot: &AtomicPtr<String>) -> Option<String> {
+        let taken = slot.swap(ptr::null_mut(), Ordering::AcqRel);
+
+        if taken.is_null() {
+            return None;
+        }
+
+        // SAFETY: the pointer was swapped out of its slot, so this is its only owner
+        let entry = unsafe { Box::from_raw(taken) };
+        let copy = entry.as_ref().clone();
+        let entry = Box::into_raw(entry);
+
+        // a newer entry written in the meantime takes precedence over the one that was read
+        if slot
+            .compare_exchange(ptr::null_mut(), entry, Ordering::AcqRel, Ordering::Acquire)
+            .is_err()
+        {
+            // SAFETY: the entry wasn't put back, so it's still only owned here
+            drop(unsafe { Box::from_raw(entry) });
+        }
+
+        Some(copy)
+    }
+}
+
+impl Drop for Ring {
+    fn drop(&mut self) {
+        for slot in self.slots.iter_mut() {
+            let entry = *slot.get_mut();
+
+            if !entry.is_null() {
+                // SAFETY: the ring is no longer shared, so entries are only owned here
+                drop(unsafe { Box::from_raw(entry) });
+            }
+        }
+    }
+}
@@ -0,0 +1,169 @@
+use crate::{cloud_run, metadata};
+use serde::Serialize;
+use std::{collections::BTreeMap, env, fs};
+
+/// Namespace of the pod's service account, mounted into every Kubernetes container
+const KUBERNETES_NAMESPACE_PATH: &str = "/var/run/secrets/kubernetes.io/serviceaccount/namespace";
+
+/// The [monitored resource](https://cloud.google.com/logging/docs/api/v2/resource-list) that
+/// exported entries are attributed to, which determines where they show up in the Logs Explorer
+#[derive(Clone, Debug, PartialEq, Eq, Serialize)]
+pub struct MonitoredResource {
+    /// Resource type, e.g. `gce_instance` or `k8s_container`
+    #[serde(rename = "type")]
+    pub kind: String,
+    /// Labels identifying the resource, as defined by its type
+    pub labels: BTreeMap<String, String>,
+}
+
+impl MonitoredResource {
+    /// Create a resource of any type from its labels
+    pub fn new<K, V>(kind: impl Into<String>, labels: impl IntoIterator<Item = (K, V)>) -> Self
+    where
+        K: Into<String>,
+        V: Into<String>,
+    {
+        Self {
+            kind: kind.into(),
+            labels: labels
+                .into_iter()
+                .map(|(key, value)| (key.into(), value.into()))
+                .collect(),
+        }
+    }
+
+    /// A `global` resource, not associated with any specific infrastructure
+    pub fn global(project_id: impl Into<String>) -> Self {
+        Self::new("global", [("project_id", project_id.into())])
+    }
+
+    /// A `gce_instance` resource for a Compute Engine VM
+    pub fn gce_instance(
+        project_id: impl Into<String>,
+        zone: impl Into<String>,
+        instance_id: impl Into<String>,
+    ) -> Self {
+        Self::new(
+            "gce_instance",
+            [
+                ("project_id", project_id.into()),
+                ("zone", zone.into()),
+                ("instance_id", instance_id.into()),
+            ],
+        )
+    }
+
+    /// A `k8s_container` resource for a container running on GKE
+    pub fn k8s_container(
+        project_id: impl Into<String>,
+        location: impl Into<String>,
+        cluster_name: impl Into<String>,
+        namespace_name: impl Into<String>,
+        pod_name: impl Into<String>,
+        container_name: impl Into<String>,
+    ) -> Self {
+        Self::new(
+            "k8s_container",
+            [
+                ("project_id", project_id.into()),
+                ("location", location.into()),
+                ("cluster_name", cluster_name.into()),
+                ("namespace_name", namespace_name.into()),
+                ("pod_name", pod_name.into()),
+                ("container_name", container_name.into()),
+            ],
+        )
+    }
+
+    /// A `cloud_run_revision` resource for a Cloud Run service revision
+    pub fn cloud_run_revision(
+        project_id: impl Into<String>,
+        location: impl Into<String>,
+        service_name: impl Into<String>,
+        revision_name: impl Into<String>,
+        configuration_name: impl Into<String>,
+    ) -> Self {
+        Self::new(
+            "cloud_run_revision",
+            [
+                ("project_id", project_id.into()),
+                ("location", location.into()),
+                ("service_name", service_name.into()),
+                ("revision_name", revision_name.into()),
+                ("configuration_name", configuration_name.into()),
+            ],
+        )
+    }
+
+    /// A `generic_node` resource for machines outside of Google Cloud
+    pub fn generic_node(
+        project_id: impl Into<String>,
+        location: impl Into<String>,
+        namespace: impl Into<String>,
+        node_id: impl Into<String>,
+    ) -> Self {
+        Self::new(
+            "generic_node",
+            [
+                ("project_id", project_id.into()),
+                ("location", location.into()),
+                ("namespace", namespace.into()),
+                ("node_id", node_id.into()),
+            ],
+        )
+    }
+
+    /// Detect the resource of the current process from its runtime environment and the metadata
+    /// server: `cloud_run_revision` on Cloud Run, `k8s_container` on GKE, `gce_instance` on
+    /// Compute Engine, and `global` everywhere else.
+    pub fn detect(project_id: impl Into<String>) -> Self {
+        let project_id = project_id.into();
+
+        Self::detect_cloud_run(&project_id)
+            .or_else(|| Self::detect_kubernetes(&project_id))
+            .or_else(|| Self::detect_gce(&project_id))
+            .unwrap_or_else(|| Self::global(project_id))
+    }
+
+    fn detect_cloud_run(project_id: &str) -> Option<Self> {
+        let service = env::var("K_SERVICE").ok()?;
+
+        Some(Self::cloud_run_revision(
+            project_id,
+            cloud_run::metadata_region().unwrap_or_default(),
+            service,
+            env::var("K_REVISION").unwrap_or_default(),
+            env::var("K_CONFIGURATION").unwrap_or_default(),
+        ))
+    }
+
+    fn detect_kubernetes(project_id: &str) -> Option<Self> {
+        env::var("KUBERNETES_SERVICE_HOST").ok()?;
+
+        let namespace = env::var("POD_NAMESPACE")
+            .ok()
+            .or_else(|| fs::read_to_string(KUBERNETES_NAMESPACE_PATH).ok())
+            .map(|namespace| namespace.trim().to_string())
+            .unwrap_or_default();
+
+        Some(Self::k8s_container(
+            project_id,
+            metadata::get("instance/attributes/cluster-location").unwrap_or_default(),
+            metadata::get("instance/attributes/cluster-name").unwrap_or_default(),
+            namespace,
+            env::var("POD_NAME")
+                .or_else(|_| env::var("HOSTNAME"))
+                .unwrap_or_default(),
+            env::var("CONTAINER_NAME").unwrap_or_default(),
+        ))
+    }
+
+    fn detect_gce(project_id: &str) -> Option<Self> {
+        let instance_id = metadata::get("instance/id")?;
+        let zone = metadata::get("instance/zone")
+            .and_then(|zone| metadata::last_segment(&zone))
+            .unwrap_or_default();
+
+        Some(Self::gce_instance(project_id, zone, instance_id))
+    }
+}
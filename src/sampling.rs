@@ -0,0 +1,143 @@
+use crate::{entry::LogEntry, google::LogSeverity};
+use std::{
+    sync::Mutex,
+    time::{Duration, Instant},
+};
+use tracing_core::Level;
+
+/// Configuration for sampling DEBUG and INFO events once the entry rate exceeds a threshold.
+/// Events are kept 1 in every N, with N a power of two that doubles as the rate grows and halves
+/// again as it drops, so WARN and ERROR events are never sampled.
+#[derive(Clone, Debug)]
+pub struct AdaptiveSampling {
+    /// Rate of entries per second above which DEBUG and INFO events are sampled
+    pub max_entries_per_second: u64,
+    /// Interval over which the entry rate is measured
+    pub interval: Duration,
+    /// Lowest fraction of DEBUG and INFO events kept, however high the entry rate
+    pub min_rate: f64,
+}
+
+impl Default for AdaptiveSampling {
+    fn default() -> Self {
+        Self {
+            max_entries_per_second: 1_000,
+            interval: Duration::from_secs(1),
+            min_rate: 0.01,
+        }
+    }
+}
+
+/// Sampler tracking the entry rate and effective sampling rate of an [`AdaptiveSampling`]
+#[derive(Debug)]
+pub(crate) struct AdaptiveSampler {
+    configuration: AdaptiveSampling,
+    state: Mutex<State>,
+}
+
+#[derive(Debug)]
+struct State {
+    since: Instant,
+    entries: u64,
+    ratio: u64,
+    sampled: u64,
+}
+
+/// Change of the effective sampling rate, reported as a NOTICE entry
+#[derive(Debug)]
+pub(crate) struct RateChange {
+    ratio: u64,
+    entries_per_second: f64,
+}
+
+impl AdaptiveSampler {
+    pub(crate) fn new(configuration: AdaptiveSampling) -> Self {
+        Self {
+            configuration,
+            state: Mutex::new(State {
+                since: Instant::now(),
+                entries: 0,
+                ratio: 1,
+                sampled: 0,
+            }),
+        }
+    }
+
+    /// Configuration that the sampler was created with
+    pub(crate) fn configuration(&self) -> &AdaptiveSampling {
+        &self.configuration
+    }
+
+    /// Count an event, returning whether it's kept along with any change of the sampling rate
+    pub(crate) fn sample(&self, level: &Level) -> (bool, Option<RateChange>) {
+        let mut state = match self.state.lock() {
+            Ok(state) => state,
+            Err(poisoned) => poisoned.into_inner(),
+        };
+
+        state.entries += 1;
+
+        let elapsed = state.since.elapsed();
+        let mut change = None;
+
+        if elapsed >= self.configuration.interval {
+            let entries_per_second = state.entries as f64 / elapsed.as_secs_f64();
+            let ratio = self.ratio(entries_per_second);
+
+            if ratio != state.ratio {
+                state.ratio = ratio;
+                change = Some(RateChange {
+                    ratio,
+                    entries_per_second,
+                });
+            }
+
+            state.since = Instant::now();
+            state.entries = 0;
+        }
+
+        // WARN and ERROR events are always kept
+        if *level < Level::INFO {
+            return (true, change);
+        }
+
+        let keep = state.sampled % state.ratio == 0;
+        state.sampled = state.sampled.wrapping_add(1);
+        (keep, change)
+    }
+
+    /// Smallest power of two ratio that brings the entry rate within its threshold
+    fn ratio(&self, entries_per_second: f64) -> u64 {
+        let max_ratio = (1.0 / self.configuration.min_rate.clamp(f64::EPSILON, 1.0)) as u64;
+        let max_entries_per_second = self.configuration.max_entries_per_second.max(1) as f64;
+        let mut ratio = 1;
+
+        while entries_per_second / ratio as f64 > max_entries_per_second && ratio * 2 <= max_ratio {
+            ratio *= 2;
+        }
+
+        ratio
+    }
+}
+
+impl RateChange {
+    /// The NOTICE entry reporting the change
+    pub(crate) fn entry(&self) -> LogEntry {
+        let rate = 1.0 / self.ratio as f64;
+        let message = if self.ratio == 1 {
+            format!(
+                "stopped sampling DEBUG and INFO entries at {:.0} entries/s",
+                self.entries_per_second
+            )
+        } else {
+            format!(
+                "sampling 1 in {} DEBUG and INFO entries at {:.0} entries/s",
+                self.ratio, self.entries_per_second
+            )
+        };
+
+        LogEntry::report(module_path!(), LogSeverity::Notice, message)
+            .with_field("samplingRate", rate)
+            .with_field("entriesPerSecond", self.entries_per_second)
+    }
+}
@@ -0,0 +1,383 @@
+use crate::{
+    entry::LogEntry,
+    processor::{EntryProcessor, Verdict},
+};
+use regex::Regex;
+use serde_json::Value;
+use std::{
+    borrow::Cow,
+    collections::{BTreeMap, HashMap},
+    fmt,
+    sync::{Arc, Mutex},
+};
+
+/// Strategy for redacting sensitive values, e.g. encryption with a key from a key management
+/// service so that values remain recoverable by those allowed to read them
+pub trait Redactor: fmt::Debug + Send + Sync {
+    /// Returns the redacted replacement of a value, or `None` to drop the field holding it
+    fn redact(&self, value: &str) -> Option<String>;
+}
+
+/// What becomes of a value matching a [`ScrubPattern`] or [`KeyRedaction`]
+#[derive(Clone, Debug)]
+pub enum ScrubAction {
+    /// Replace each match with `[REDACTED]`
+    Mask,
+    /// Replace each match with a truncated HMAC-SHA256 of it under a secret key, e.g.
+    /// `[hmac:3b0c44298fc1c149]`, so that equal values can still be correlated. Values with little
+    /// entropy, such as card numbers and emails, can be recovered by brute force from their hashes
+    /// by anyone holding the key, so it must be kept as secret as the values themselves.
+    Hash(HashKey),
+    /// Drop the whole field holding the match
+    Drop,
+    /// Replace each match as a custom [`Redactor`] does
+    Redact(Arc<dyn Redactor>),
+}
+
+impl Redactor for ScrubAction {
+    fn redact(&self, value: &str) -> Option<String> {
+        match self {
+            Self::Mask => Some("[REDACTED]".to_string()),
+            Self::Hash(key) => Some(format!("[hmac:{}]", key.hash(value))),
+            Self::Drop => None,
+            Self::Redact(redactor) => redactor.redact(value),
+        }
+    }
+}
+
+/// Secret key of the hashes of [`ScrubAction::Hash`]
+#[derive(Clone)]
+pub struct HashKey(ring::hmac::Key);
+
+impl HashKey {
+    /// Creates a key from a secret, e.g. one loaded from a secret manager and rotated periodically
+    pub fn new(secret: impl AsRef<[u8]>) -> Self {
+        Self(ring::hmac::Key::new(
+            ring::hmac::HMAC_SHA256,
+            secret.as_ref(),
+        ))
+    }
+
+    /// First 16 hex digits of the HMAC-SHA256 of a match
+    fn hash(&self, value: &str) -> String {
+        ring::hmac::sign(&self.0, value.as_bytes())
+            .as_ref()
+            .iter()
+            .take(8)
+            .map(|byte| format!("{:02x}", byte))
+            .collect()
+    }
+}
+
+impl fmt::Debug for HashKey {
+    fn fmt(&self, formatter: &mut fmt::Formatter<'_>) -> fmt::Result {
+        formatter.pad("HashKey(..)")
+    }
+}
+
+/// Redactor replacing values with tokens, e.g. `tok_1`, that are the same for equal values over
+/// the lifetime of the process without being derived from them. Every distinct value is kept in
+/// memory to be tokenized consistently.
+#[derive(Debug, Default)]
+pub struct Tokenizer {
+    tokens: Mutex<HashMap<String, u64>>,
+}
+
+impl Redactor for Tokenizer {
+    fn redact(&self, value: &str) -> Option<String> {
+        let mut tokens = match self.tokens.lock() {
+            Ok(tokens) => tokens,
+            Err(poisoned) => poisoned.into_inner(),
+        };
+
+        let next = tokens.len() as u64 + 1;
+        let token = *tokens.entry(value.to_string()).or_insert(next);
+
+        Some(format!("tok_{}", token))
+    }
+}
+
+/// Redaction of the whole value of the fields whose names match a pattern, where `*` matches any
+/// characters, e.g. `*password*` or `user.*`
+#[derive(Clone, Debug)]
+pub struct KeyRedaction {
+    /// Pattern of the names of the redacted fields
+    pub pattern: String,
+    /// What becomes of the values of the fields
+    pub action: ScrubAction,
+}
+
+impl KeyRedaction {
+    /// Whether a field name matches the pattern
+    fn matches(&self, key: &str) -> bool {
+        let mut segments = self.pattern.split('*');
+        let first = segments.next().unwrap_or_default();
+
+        let Some(mut rest) = key.strip_prefix(first) else {
+            return false;
+        };
+
+        let segments: Vec<_> = segments.collect();
+
+        let Some((last, middle)) = segments.split_last() else {
+            return rest.is_empty();
+        };
+
+        for segment in middle {
+            match rest.find(segment) {
+                Some(index) => rest = &rest[index + segment.len()..],
+                None => return false,
+            }
+        }
+
+        rest.ends_with(last)
+    }
+}
+
+/// Pattern of sensitive data within string values, and what becomes of its matches
+#[derive(Clone, Debug)]
+pub struct ScrubPattern {
+    name: String,
+    regex: Regex,
+    action: ScrubAction,
+    validate: Option<fn(&str) -> bool>,
+}
+
+impl ScrubPattern {
+    /// Creates a named pattern from a regular expression
+    pub fn new(
+        name: impl Into<String>,
+        pattern: &str,
+        action: ScrubAction,
+    ) -> Result<Self, regex::Error> {
+        Ok(Self {
+            name: name.into(),
+            regex: Regex::new(pattern)?,
+            action,
+            validate: None,
+        })
+    }
+
+    /// Pattern of email addresses
+    pub fn email(action: ScrubAction) -> Self {
+        Self::builtin(
+            "email",
+            r"[A-Za-z0-9._%+-]+@[A-Za-z0-9.-]+\.[A-Za-z]{2,}",
+            action,
+        )
+    }
+
+    /// Pattern of credit card numbers, optionally grouped by spaces or hyphens, that pass the Luhn
+    /// checksum
+    pub fn credit_card(action: ScrubAction) -> Self {
+        Self {
+            validate: Some(luhn),
+            ..Self::builtin("credit_card", r"\b(?:\d[ -]?){12,18}\d\b", action)
+        }
+    }
+
+    /// Pattern of bearer tokens, e.g. in a recorded `Authorization` header
+    pub fn bearer_token(action: ScrubAction) -> Self {
+        Self::builtin(
+            "bearer_token",
+            r"(?i)\bbearer\s+[A-Za-z0-9\-._~+/]+=*",
+            action,
+        )
+    }
+
+    /// Name of the pattern
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn builtin(name: &str, pattern: &str, action: ScrubAction) -> Self {
+        Self::new(name, pattern, action).expect("built-in patterns are valid")
+    }
+
+    /// The matches of the pattern within a string, skipping those that fail validation
+    fn matches<'a>(&'a self, value: &'a str) -> impl Iterator<Item = regex::Match<'a>> + 'a {
+        self.regex.find_iter(value).filter(|found| {
+            self.validate
+                .is_none_or(|validate| validate(found.as_str()))
+        })
+    }
+}
+
+/// Configuration for scanning the string values of fields for sensitive data, such as emails,
+/// credit card numbers, and bearer tokens, replacing or dropping what matches before it's written.
+/// Values are scanned in nested objects and arrays as well, and patterns apply in order. The
+/// fields matching a key redaction are redacted as a whole instead.
+#[derive(Clone, Debug)]
+pub struct ValueScrubbing {
+    /// Patterns of sensitive data
+    pub patterns: Vec<ScrubPattern>,
+    /// Redactions of fields by name, of which the first matching a field applies
+    pub keys: Vec<KeyRedaction>,
+}
+
+impl Default for ValueScrubbing {
+    /// Masks emails, credit card numbers, and bearer tokens
+    fn default() -> Self {
+        Self {
+            patterns: vec![
+                ScrubPattern::email(ScrubAction::Mask),
+                ScrubPattern::credit_card(ScrubAction::Mask),
+                ScrubPattern::bearer_token(ScrubAction::Mask),
+            ],
+            keys: vec![],
+        }
+    }
+}
+
+impl ValueScrubbing {
+    /// Redact the values of an event's fields by the names they're recorded with, dropping those
+    /// matching a key redaction that drops fields
+    pub(crate) fn redact_keys(&self, values: &mut BTreeMap<&str, Value>) {
+        values.retain(|key, value| self.redact_key(key, value).unwrap_or(true));
+    }
+
+    /// Redact a string derived from a field, such as its Debug rendering or error chain, as the
+    /// field itself is redacted by name, returning whether it's kept
+    pub(crate) fn redact_key_string(&self, key: &str, string: &mut String) -> bool {
+        let mut value = Value::String(std::mem::take(string));
+        let kept = self.redact_key(key, &mut value).unwrap_or(true);
+
+        if let Value::String(redacted) = value {
+            *string = redacted;
+        }
+
+        kept
+    }
+
+    /// Scan the values of an assembled entry for sensitive data, including the fields of its span,
+    /// error chains, and Debug renderings, leaving the fields that the formatter writes itself,
+    /// such as its time and trace, as they are
+    pub(crate) fn scrub_entry(&self, entry: String) -> String {
+        let Ok(Value::Object(mut fields)) = serde_json::from_str::<Value>(&entry) else {
+            return entry;
+        };
+
+        fields.retain(|key, value| match key.as_str() {
+            "span" => match value {
+                Value::Object(span) => {
+                    span.retain(|key, value| self.scrub_field(key, value));
+                    true
+                }
+                _ => true,
+            },
+            key if is_formatter_field(key) => true,
+            _ => self.scrub_value(value),
+        });
+
+        serde_json::to_string(&fields).unwrap_or(entry)
+    }
+
+    /// Scrub the value of a field, returning whether it's kept
+    fn scrub_field(&self, key: &str, value: &mut Value) -> bool {
+        self.redact_key(key, value)
+            .unwrap_or_else(|| self.scrub_value(value))
+    }
+
+    /// Redact the value of a field by its name, returning whether it's kept, or `None` when no
+    /// key redaction matches it
+    fn redact_key(&self, key: &str, value: &mut Value) -> Option<bool> {
+        let redaction = self.keys.iter().find(|redaction| redaction.matches(key))?;
+        let redacted = match &*value {
+            Value::String(string) => redaction.action.redact(string),
+            other => redaction.action.redact(&other.to_string()),
+        };
+
+        Some(
+            redacted
+                .map(|redacted| *value = Value::String(redacted))
+                .is_some(),
+        )
+    }
+
+    /// Scrub a value in place, returning whether it's kept
+    fn scrub_value(&self, value: &mut Value) -> bool {
+        match value {
+            Value::String(string) => match self.scrub_string(string) {
+                Some(Cow::Owned(scrubbed)) => {
+                    *string = scrubbed;
+                    true
+                }
+                Some(Cow::Borrowed(_)) => true,
+                None => false,
+            },
+            Value::Array(values) => values.iter_mut().all(|value| self.scrub_value(value)),
+            Value::Object(fields) => fields.values_mut().all(|value| self.scrub_value(value)),
+            _ => true,
+        }
+    }
+
+    /// Scrub a string, or `None` when it must be dropped
+    fn scrub_string<'a>(&self, string: &'a str) -> Option<Cow<'a, str>> {
+        let mut scrubbed = Cow::Borrowed(string);
+
+        for pattern in &self.patterns {
+            let mut replaced = String::new();
+            let mut last = None;
+
+            for found in pattern.matches(&scrubbed) {
+                let replacement = pattern.action.redact(found.as_str())?;
+
+                replaced.push_str(&scrubbed[last.unwrap_or(0)..found.start()]);
+                replaced.push_str(&replacement);
+                last = Some(found.end());
+            }
+
+            if let Some(last) = last {
+                replaced.push_str(&scrubbed[last..]);
+                scrubbed = Cow::Owned(replaced);
+            }
+        }
+
+        Some(scrubbed)
+    }
+}
+
+/// Scrubbing as a step of the processor pipeline, applied to the message and other fields of
+/// entries as they're written, so that key redactions match written keys, e.g. `userEmail`
+impl EntryProcessor for ValueScrubbing {
+    fn process(&self, entry: &mut LogEntry) -> Verdict {
+        if let Some(message) = entry.message.take() {
+            let mut message = Value::String(message);
+
+            if self.scrub_field("message", &mut message) {
+                entry.message = message.as_str().map(str::to_string);
+            }
+        }
+
+        entry
+            .fields
+            .retain(|key, value| self.scrub_field(key, value));
+
+        Verdict::Keep
+    }
+}
+
+/// Whether a field of an entry is written by the formatter itself rather than recorded, such as
+/// its trace and span IDs, which may look like card numbers
+fn is_formatter_field(key: &str) -> bool {
+    matches!(
+        key,
+        "time" | "severity" | "target" | "callsite" | "traceId" | "timestampSeconds"
+    ) || (key.starts_with("logging.googleapis.com/") && key != "logging.googleapis.com/labels")
+}
+
+/// Whether the digits of a number pass the Luhn checksum
+fn luhn(number: &str) -> bool {
+    let digits = number.chars().filter_map(|char| char.to_digit(10));
+    let sum: u32 = digits
+        .rev()
+        .enumerate()
+        .map(|(index, digit)| match index % 2 {
+            0 => digit,
+            _ if digit > 4 => digit * 2 - 9,
+            _ => digit * 2,
+        })
+        .sum();
+
+    sum.is_multiple_of(10)
+}
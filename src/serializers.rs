@@ -1,56 +1,87 @@
+use crate::logfmt;
 use serde::ser::{Serialize, SerializeMap};
 use serde_json::Value;
+use std::marker::PhantomData;
 use tracing_subscriber::{
-    fmt::{format::JsonFields, FormattedFields},
+    fmt::FormattedFields,
     registry::{LookupSpan, SpanRef},
 };
 
-/// Serializable tracing span for nesting formatted event fields
-pub(crate) struct SerializableSpan<'a, 'b, S>(&'b SpanRef<'a, S>)
+/// Serializable tracing span for nesting formatted event fields, with the span's fields as
+/// formatted by `N`
+pub(crate) struct SerializableSpan<'a, 'b, S, N>
 where
-    S: for<'lookup> LookupSpan<'lookup>;
+    S: for<'lookup> LookupSpan<'lookup>,
+{
+    span: &'b SpanRef<'a, S>,
+    include_ids: bool,
+    fields: PhantomData<fn(N)>,
+}
 
-impl<'a, 'b, S> SerializableSpan<'a, 'b, S>
+impl<'a, 'b, S, N> SerializableSpan<'a, 'b, S, N>
 where
     S: for<'lookup> LookupSpan<'lookup>,
 {
     pub(crate) fn new(span: &'b SpanRef<'a, S>) -> Self {
-        Self(span)
+        Self {
+            span,
+            include_ids: false,
+            fields: PhantomData,
+        }
+    }
+
+    /// Configures whether or not the span's registry ID and its parent's are included
+    pub(crate) fn with_ids(self, include_ids: bool) -> Self {
+        Self {
+            include_ids,
+            ..self
+        }
     }
 }
 
-impl<'a, 'b, S> Serialize for SerializableSpan<'a, 'b, S>
+impl<'a, 'b, S, N> Serialize for SerializableSpan<'a, 'b, S, N>
 where
     S: for<'lookup> LookupSpan<'lookup>,
+    N: 'static,
 {
     fn serialize<R>(&self, serializer: R) -> Result<R::Ok, R::Error>
     where
         R: serde::Serializer,
     {
-        let name = self.0.name();
-        let extensions = self.0.extensions();
-
-        let formatted_fields = extensions
-            .get::<FormattedFields<JsonFields>>()
-            .expect("No fields!");
-
-        let span_length = formatted_fields.fields.len() + 1;
-        let mut map = serializer.serialize_map(Some(span_length))?;
-
-        match serde_json::from_str::<Value>(formatted_fields) { // TODO: this seems weird
-            // handle string escaping "properly" (this should be fixed upstream)
-            // https://github.com/tokio-rs/tracing/issues/391
-            Ok(Value::Object(fields)) => {
-                for (key, value) in fields {
-                    map.serialize_entry(&key, &value)?;
+        let name = self.span.name();
+        let extensions = self.span.extensions();
+
+        let fields = extensions
+            .get::<FormattedFields<N>>()
+            .map(|formatted_fields| {
+                // TODO: this seems weird
+                // handle string escaping "properly" (this should be fixed upstream)
+                // https://github.com/tokio-rs/tracing/issues/391
+                match serde_json::from_str::<Value>(formatted_fields) {
+                    Ok(Value::Object(fields)) => fields.into_iter().collect(),
+                    // fields formatted by other formatters, e.g. as `key=value` pairs
+                    _ => logfmt::key_values(formatted_fields),
                 }
-            }
-            // these two options should be impossible
-            Ok(value) => panic!("Invalid value: {}", value),
-            Err(error) => panic!("Error parsing logs: {}", error),
-        };
+            })
+            .unwrap_or_default();
+
+        let mut map = serializer.serialize_map(Some(fields.len() + 1))?;
+
+        for (key, value) in fields {
+            map.serialize_entry(&key, &value)?;
+        }
 
         map.serialize_entry("name", &name)?;
+
+        // IDs are only unique among the spans open at the same time within the process
+        if self.include_ids {
+            map.serialize_entry("id", &self.span.id().into_u64().to_string())?;
+
+            if let Some(parent) = self.span.parent() {
+                map.serialize_entry("parentId", &parent.id().into_u64().to_string())?;
+            }
+        }
+
         map.end()
     }
 }
@@ -0,0 +1,120 @@
+use crate::google::LogSeverity;
+use std::{fmt, str::FromStr};
+use tracing_core::{subscriber::Interest, Event, Field, Level, Metadata, Subscriber};
+use tracing_subscriber::{
+    field::Visit,
+    filter::LevelFilter,
+    layer::{Context, Filter},
+};
+
+/// Name of the event field that overrides the severity of its entry
+const SEVERITY_FIELD: &str = "severity";
+
+/// Per-layer [`Filter`] enabling events whose entries are at least as severe as a minimum
+/// [`LogSeverity`], so that minimum severities configured in GCP terms, e.g. `NOTICE`, match the
+/// written entries. Events with a `severity` field are filtered on the severity it overrides
+/// theirs with, e.g. `info!(severity = "NOTICE", ...)` passes a `NOTICE` minimum while
+/// `error!(severity = "DEBUG", ...)` doesn't.
+#[derive(Clone, Copy, Debug)]
+pub struct SeverityFilter {
+    min_severity: LogSeverity,
+    overrides_from: LevelFilter,
+}
+
+impl SeverityFilter {
+    /// Create a filter enabling events at least as severe as `min_severity`
+    pub fn new(min_severity: LogSeverity) -> Self {
+        Self {
+            min_severity,
+            overrides_from: LevelFilter::INFO,
+        }
+    }
+
+    /// Set the most verbose level of events whose `severity` field is taken into account, `INFO`
+    /// by default. More verbose events are filtered on their level alone, so that `tracing` can
+    /// disable them statically through the filter's max level hint.
+    pub fn with_overrides_from(self, overrides_from: LevelFilter) -> Self {
+        Self {
+            overrides_from,
+            ..self
+        }
+    }
+
+    /// Whether a callsite is enabled regardless of any `severity` field
+    fn enabled_by_level(&self, metadata: &Metadata<'_>) -> bool {
+        metadata.is_span() || LogSeverity::from(metadata.level()) >= self.min_severity
+    }
+
+    /// Whether a callsite's `severity` field may override its level
+    fn overrides_severity(&self, metadata: &Metadata<'_>) -> bool {
+        metadata.fields().field(SEVERITY_FIELD).is_some()
+            && *metadata.level() <= self.overrides_from
+    }
+}
+
+impl<S> Filter<S> for SeverityFilter
+where
+    S: Subscriber,
+{
+    fn enabled(&self, metadata: &Metadata<'_>, _: &Context<'_, S>) -> bool {
+        self.enabled_by_level(metadata) || self.overrides_severity(metadata)
+    }
+
+    fn callsite_enabled(&self, metadata: &'static Metadata<'static>) -> Interest {
+        // events that override their severity are decided on their field values
+        if metadata.is_event() && self.overrides_severity(metadata) {
+            Interest::sometimes()
+        } else if self.enabled_by_level(metadata) {
+            Interest::always()
+        } else {
+            Interest::never()
+        }
+    }
+
+    fn event_enabled(&self, event: &Event<'_>, _: &Context<'_, S>) -> bool {
+        if !self.overrides_severity(event.metadata()) {
+            return true;
+        }
+
+        let mut visitor = SeverityVisitor(None);
+        event.record(&mut visitor);
+
+        let severity = visitor
+            .0
+            .unwrap_or_else(|| LogSeverity::from(event.metadata().level()));
+
+        severity >= self.min_severity
+    }
+
+    fn max_level_hint(&self) -> Option<LevelFilter> {
+        let by_level = [
+            Level::TRACE,
+            Level::DEBUG,
+            Level::INFO,
+            Level::WARN,
+            Level::ERROR,
+        ]
+        .into_iter()
+        .find(|level| LogSeverity::from(level) >= self.min_severity)
+        .map_or(LevelFilter::OFF, LevelFilter::from_level);
+
+        Some(by_level.max(self.overrides_from))
+    }
+}
+
+/// Visitor extracting the severity recorded in an event's `severity` field
+struct SeverityVisitor(Option<LogSeverity>);
+
+impl Visit for SeverityVisitor {
+    fn record_str(&mut self, field: &Field, value: &str) {
+        if field.name() == SEVERITY_FIELD {
+            self.0 = LogSeverity::from_str(value).ok();
+        }
+    }
+
+    fn record_debug(&mut self, field: &Field, value: &dyn fmt::Debug) {
+        if field.name() == SEVERITY_FIELD {
+            self.0 = Some(LogSeverity::from_debug(value));
+        }
+    }
+}
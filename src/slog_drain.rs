@@ -0,0 +1,161 @@
+use crate::{google::LogSeverity, serializers::SourceLocation, visitor::Visitor};
+use serde::ser::{SerializeMap, Serializer as _};
+use serde_json::Value;
+use slog::{Key, Level, OwnedKVList, Record, KV};
+use std::{collections::BTreeMap, fmt, io, sync::Mutex};
+use time::{format_description::well_known::Rfc3339, OffsetDateTime};
+use tracing_subscriber::field::VisitOutput;
+
+/// A [`slog::Drain`] writing records as the same Stackdriver-compatible JSON entries as the
+/// tracing [`Layer`](crate::Layer), for codebases migrating from slog to tracing. Key-value
+/// pairs are written like tracing fields, including `severity` overrides and `labels.` and
+/// `http_request.` prefixes.
+pub struct StackdriverDrain<W> {
+    writer: Mutex<W>,
+}
+
+impl<W> StackdriverDrain<W>
+where
+    W: io::Write,
+{
+    /// Create a drain writing entries to the given writer
+    pub fn new(writer: W) -> Self {
+        Self {
+            writer: Mutex::new(writer),
+        }
+    }
+
+    /// Format a record as a single line
+    fn format(&self, record: &Record, values: &OwnedKVList) -> io::Result<Vec<u8>> {
+        let mut fields = Fields::default();
+        values
+            .serialize(record, &mut fields)
+            .map_err(io::Error::other)?;
+        record
+            .kv()
+            .serialize(record, &mut fields)
+            .map_err(io::Error::other)?;
+
+        let mut values: BTreeMap<&str, Value> = fields
+            .0
+            .iter()
+            .map(|(key, value)| (key.as_str(), value.clone()))
+            .collect();
+        values.insert("message", Value::String(record.msg().to_string()));
+
+        let mut line = vec![];
+        let mut serializer = serde_json::Serializer::new(&mut line);
+        let mut map = serializer.serialize_map(None)?;
+
+        let now = OffsetDateTime::now_utc()
+            .format(&Rfc3339)
+            .map_err(io::Error::other)?;
+        map.serialize_entry("time", &now)?;
+        map.serialize_entry("target", record.module())?;
+        map.serialize_entry(
+            "logging.googleapis.com/sourceLocation",
+            &SourceLocation {
+                file: record.file(),
+                line: Some(record.line()),
+            },
+        )?;
+
+        Visitor::new(severity(record.level()), map)
+            .with_inherited_values(values)
+            .finish()
+            .map_err(io::Error::other)?;
+
+        line.push(b'\n');
+        Ok(line)
+    }
+}
+
+impl<W> slog::Drain for StackdriverDrain<W>
+where
+    W: io::Write,
+{
+    type Ok = ();
+    type Err = io::Error;
+
+    fn log(&self, record: &Record, values: &OwnedKVList) -> io::Result<()> {
+        let line = self.format(record, values)?;
+        let mut writer = match self.writer.lock() {
+            Ok(writer) => writer,
+            Err(poisoned) => poisoned.into_inner(),
+        };
+
+        writer.write_all(&line)
+    }
+}
+
+impl<W> fmt::Debug for StackdriverDrain<W> {
+    fn fmt(&self, formatter: &mut fmt::Formatter<'_>) -> fmt::Result {
+        formatter
+            .debug_struct("StackdriverDrain")
+            .finish_non_exhaustive()
+    }
+}
+
+/// Severity of a slog level
+fn severity(level: Level) -> LogSeverity {
+    match level {
+        Level::Critical => LogSeverity::Critical,
+        Level::Error => LogSeverity::Error,
+        Level::Warning => LogSeverity::Warning,
+        Level::Info => LogSeverity::Info,
+        Level::Debug | Level::Trace => LogSeverity::Debug,
+    }
+}
+
+/// Serializer collecting the key-value pairs of a record, later pairs overriding earlier ones
+#[derive(Debug, Default)]
+struct Fields(Vec<(String, Value)>);
+
+impl Fields {
+    fn insert(&mut self, key: Key, value: Value) -> slog::Result {
+        self.0.push((key.to_string(), value));
+        Ok(())
+    }
+}
+
+/// Implement emitting values of primitive types as JSON values of the same type
+macro_rules! emit_values {
+    ($($method:ident: $type:ty),* $(,)?) => {
+        $(
+            fn $method(&mut self, key: Key, value: $type) -> slog::Result {
+                self.insert(key, Value::from(value))
+            }
+        )*
+    };
+}
+
+impl slog::Serializer for Fields {
+    fn emit_arguments(&mut self, key: Key, value: &fmt::Arguments<'_>) -> slog::Result {
+        self.insert(key, Value::String(value.to_string()))
+    }
+
+    emit_values! {
+        emit_str: &str,
+        emit_bool: bool,
+        emit_u8: u8,
+        emit_u16: u16,
+        emit_u32: u32,
+        emit_u64: u64,
+        emit_usize: usize,
+        emit_i8: i8,
+        emit_i16: i16,
+        emit_i32: i32,
+        emit_i64: i64,
+        emit_isize: isize,
+        emit_f32: f32,
+        emit_f64: f64,
+    }
+
+    fn emit_unit(&mut self, key: Key) -> slog::Result {
+        self.insert(key, Value::Null)
+    }
+
+    fn emit_none(&mut self, key: Key) -> slog::Result {
+        self.insert(key, Value::Null)
+    }
+}
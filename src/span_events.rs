@@ -0,0 +1,49 @@
+use std::ops::{BitOr, BitOrAssign};
+use tracing_core::Event;
+
+/// Field holding the ID of the Span on lifecycle entries, which also tells them apart from the
+/// close entries sharing the Span's metadata
+pub(crate) const SPAN_ID_FIELD: &str = "span_id";
+
+/// Span lifecycle events written as lightweight DEBUG entries with the Span's name and ID, e.g. to
+/// diagnose lost or leaked Spans and how async tasks are scheduled. Combined with `|`.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct SpanEvents(u8);
+
+impl SpanEvents {
+    /// No lifecycle entries, which is the default
+    pub const NONE: Self = Self(0);
+    /// An `enter` entry each time a Span is entered
+    pub const ENTER: Self = Self(1);
+    /// An `exit` entry each time a Span is exited
+    pub const EXIT: Self = Self(1 << 1);
+
+    /// Whether every event of `other` is included
+    pub fn contains(self, other: Self) -> bool {
+        self.0 & other.0 == other.0
+    }
+
+    /// Whether an entry is written for a lifecycle event
+    pub(crate) fn is_emitted(self, event: Self) -> bool {
+        self.0 & event.0 != 0
+    }
+}
+
+impl BitOr for SpanEvents {
+    type Output = Self;
+
+    fn bitor(self, other: Self) -> Self {
+        Self(self.0 | other.0)
+    }
+}
+
+impl BitOrAssign for SpanEvents {
+    fn bitor_assign(&mut self, other: Self) {
+        self.0 |= other.0;
+    }
+}
+
+/// Whether an event is the lifecycle entry of a Span, rather than its close entry
+pub(crate) fn is_lifecycle_event(event: &Event) -> bool {
+    event.fields().any(|field| field.name() == SPAN_ID_FIELD)
+}
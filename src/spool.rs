@@ -0,0 +1,135 @@
+use std::{
+    collections::VecDeque,
+    fs, io,
+    path::{Path, PathBuf},
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+/// Extension of spooled batch files
+const SPOOL_EXTENSION: &str = "batch";
+
+/// Bounded on-disk queue of batches that couldn't be exported, replayed oldest first. Its files
+/// are indexed in memory, starting from the batches left in the directory when it's created.
+#[derive(Debug)]
+pub(crate) struct Spool {
+    directory: PathBuf,
+    max_bytes: u64,
+    sequence: u64,
+    /// Spooled batch files and their sizes, oldest first
+    files: VecDeque<(PathBuf, u64)>,
+}
+
+impl Spool {
+    pub(crate) fn new(directory: PathBuf, max_bytes: u64) -> Self {
+        let files = read_files(&directory).unwrap_or_default();
+
+        Self {
+            directory,
+            max_bytes,
+            sequence: 0,
+            files,
+        }
+    }
+
+    /// Whether no batches are spooled
+    pub(crate) fn is_empty(&self) -> bool {
+        self.files.is_empty()
+    }
+
+    /// Write a batch to the spool, then drop the oldest batches until the spool fits its bound,
+    /// returning the number of batches dropped
+    pub(crate) fn push(&mut self, batch: &[u8]) -> io::Result<usize> {
+        fs::create_dir_all(&self.directory)?;
+
+        let nanos = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_nanos();
+
+        // zero-padded so that lexical order is chronological order
+        let path = self.directory.join(format!(
+            "{:024}-{:08}.{}",
+            nanos, self.sequence, SPOOL_EXTENSION
+        ));
+        self.sequence += 1;
+        write_atomically(&path, batch)?;
+        self.files.push_back((path, batch.len() as u64));
+
+        let mut total: u64 = self.files.iter().map(|(_, size)| size).sum();
+        let mut dropped = 0;
+
+        while total > self.max_bytes {
+            let Some((path, size)) = self.files.pop_front() else {
+                break;
+            };
+
+            remove_file(&path)?;
+            total -= size;
+            dropped += 1;
+        }
+
+        Ok(dropped)
+    }
+
+    /// The oldest spooled batch, along with the path used to remove or replace it, forgetting
+    /// batches whose files can no longer be read
+    pub(crate) fn oldest(&mut self) -> Option<(PathBuf, Vec<u8>)> {
+        loop {
+            let (path, _) = self.files.front()?;
+
+            match fs::read(path) {
+                Ok(batch) => return Some((path.clone(), batch)),
+                Err(_) => self.files.pop_front(),
+            };
+        }
+    }
+
+    /// Replace a spooled batch, e.g. with the entries of a partially successful replay
+    pub(crate) fn replace(&mut self, path: &Path, batch: &[u8]) -> io::Result<()> {
+        write_atomically(path, batch)?;
+
+        if let Some((_, size)) = self.files.iter_mut().find(|(file, _)| file == path) {
+            *size = batch.len() as u64;
+        }
+
+        Ok(())
+    }
+
+    /// Remove a spooled batch
+    pub(crate) fn remove(&mut self, path: &Path) -> io::Result<()> {
+        self.files.retain(|(file, _)| file != path);
+        remove_file(path)
+    }
+}
+
+/// Spooled batch files in a directory and their sizes, oldest first
+fn read_files(directory: &Path) -> io::Result<VecDeque<(PathBuf, u64)>> {
+    let mut files = vec![];
+
+    for entry in fs::read_dir(directory)? {
+        let entry = entry?;
+        let path = entry.path();
+
+        if path.extension().and_then(|extension| extension.to_str()) == Some(SPOOL_EXTENSION) {
+            files.push((path, entry.metadata()?.len()));
+        }
+    }
+
+    files.sort();
+    Ok(files.into())
+}
+
+/// Remove a spooled batch file, unless it's already gone
+fn remove_file(path: &Path) -> io::Result<()> {
+    match fs::remove_file(path) {
+        Err(error) if error.kind() != io::ErrorKind::NotFound => Err(error),
+        _ => Ok(()),
+    }
+}
+
+/// Write a file through a temporary file, so that a crash never leaves a partial batch behind
+fn write_atomically(path: &Path, contents: &[u8]) -> io::Result<()> {
+    let temporary = path.with_extension("tmp");
+    fs::write(&temporary, contents)?;
+    fs::rename(temporary, path)
+}
@@ -0,0 +1,109 @@
+use std::{
+    collections::HashMap,
+    sync::Mutex,
+    time::{Duration, Instant},
+};
+
+/// Number of tracked stack traces beyond which expired ones are forgotten
+const MAX_TRACKED: usize = 1_024;
+
+/// Deduplicator of identical stack traces within a window, so that error storms write each
+/// trace in full once, and references to it afterwards
+#[derive(Debug)]
+pub(crate) struct StackTraces {
+    window: Duration,
+    seen: Mutex<HashMap<u64, (Instant, u64)>>,
+}
+
+impl StackTraces {
+    pub(crate) fn new(window: Duration) -> Self {
+        Self {
+            window,
+            seen: Mutex::default(),
+        }
+    }
+
+    /// Record an occurrence of a stack trace, returning its reference and the number of its
+    /// occurrences within the current window, the first of which is written in full
+    pub(crate) fn deduplicate(&self, stack_trace: &str) -> (String, u64) {
+        let hash = fnv1a(stack_trace.as_bytes());
+        let now = Instant::now();
+        let mut seen = match self.seen.lock() {
+            Ok(seen) => seen,
+            Err(poisoned) => poisoned.into_inner(),
+        };
+
+        if seen.len() >= MAX_TRACKED {
+            seen.retain(|_, (since, _)| now.duration_since(*since) < self.window);
+        }
+
+        let (since, count) = seen.entry(hash).or_insert((now, 0));
+
+        if now.duration_since(*since) >= self.window {
+            *since = now;
+            *count = 0;
+        }
+
+        *count += 1;
+        (format!("{:016x}", hash), *count)
+    }
+}
+
+/// 64-bit FNV-1a hash, which is stable across processes unlike std's default hasher
+pub(crate) fn fnv1a(bytes: &[u8]) -> u64 {
+    bytes.iter().fold(0xcbf2_9ce4_8422_2325, |hash, byte| {
+        (hash ^ u64::from(*byte)).wrapping_mul(0x0100_0000_01b3)
+    })
+}
+
+/// `@type` of entries that Error Reporting ingests as error events, even without a stack trace in
+/// a format it recognizes
+pub(crate) const REPORTED_ERROR_EVENT_TYPE: &str =
+    "type.googleapis.com/google.devtools.clouderrorreporting.v1beta1.ReportedErrorEvent";
+
+/// Split a message containing a multi-line Rust backtrace or panic into the message before it and
+/// the stack trace itself, e.g. from `error!("{err:?}")` with an `anyhow` error
+pub(crate) fn detect(message: &str) -> Option<(String, String)> {
+    let lines: Vec<&str> = message.lines().collect();
+
+    if lines.len() < 2 {
+        return None;
+    }
+
+    let panic = lines.iter().position(|line| is_panic(line));
+    // lone numbered lines are more likely lists than backtraces
+    let frames = lines
+        .iter()
+        .position(|line| is_backtrace_header(line) || is_frame(line))
+        .filter(|start| lines[*start..].iter().filter(|line| is_frame(line)).count() >= 2);
+    let start = panic.into_iter().chain(frames).min()?;
+
+    let head = lines[..start].join("\n");
+    let head = match head.trim_end() {
+        "" => lines[start].trim().to_string(),
+        head => head.to_string(),
+    };
+
+    Some((head, lines[start..].join("\n")))
+}
+
+fn is_panic(line: &str) -> bool {
+    line.starts_with("panicked at ")
+        || (line.starts_with("thread '") && line.contains("' panicked at "))
+}
+
+fn is_backtrace_header(line: &str) -> bool {
+    let line = line.trim();
+    line.eq_ignore_ascii_case("stack backtrace:") || line.eq_ignore_ascii_case("backtrace:")
+}
+
+/// Whether a line is a numbered frame of a backtrace, e.g. `  3: my_crate::handler`
+fn is_frame(line: &str) -> bool {
+    let line = line.trim_start();
+    let digits = line.len()
+        - line
+            .trim_start_matches(|char: char| char.is_ascii_digit())
+            .len();
+
+    digits > 0 && line[digits..].starts_with(": ") && line.len() > digits + 2
+}
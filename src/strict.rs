@@ -0,0 +1,175 @@
+use crate::trace::is_hex;
+use serde::de::{self, Deserialize, Deserializer, MapAccess, SeqAccess, Visitor};
+use std::{collections::HashSet, fmt};
+use tracing_core::Field;
+use tracing_subscriber::field::Visit;
+
+/// Maximum size in bytes of a single entry accepted by Cloud Logging
+const MAX_ENTRY_BYTES: usize = 256 * 1024;
+
+/// Maximum size in bytes of a label key
+const MAX_LABEL_KEY_BYTES: usize = 512;
+
+/// Maximum size in bytes of a label value
+const MAX_LABEL_VALUE_BYTES: usize = 64 * 1024;
+
+/// Malformed output caught by strict validation, which would otherwise be silently coerced
+#[derive(Debug, thiserror::Error)]
+pub(crate) enum Violation {
+    #[error("entry is not a JSON object: {0}")]
+    Malformed(#[from] serde_json::Error),
+    #[error("duplicate key `{0}`")]
+    DuplicateKey(String),
+    #[error("label `{0}` has a non-string value")]
+    LabelValue(String),
+    #[error("label `{0}` exceeds the size limits of label keys and values")]
+    LabelSize(String),
+    #[error("entry of {0} bytes exceeds the 256 KiB entry size limit")]
+    Oversized(usize),
+    #[error("invalid trace `{0}`, expected a 32 character hexadecimal trace ID")]
+    TraceId(String),
+}
+
+/// Validate a formatted entry
+pub(crate) fn validate(entry: &str) -> Result<(), Violation> {
+    if entry.len() > MAX_ENTRY_BYTES {
+        return Err(Violation::Oversized(entry.len()));
+    }
+
+    // duplicate keys are only visible while deserializing, as maps keep the last value
+    if let Err(error) = serde_json::from_str::<NoDuplicates>(entry) {
+        let message = error.to_string();
+
+        return Err(match message.strip_prefix("duplicate key ") {
+            Some(key) => Violation::DuplicateKey(key.split('`').nth(1).unwrap_or(key).to_string()),
+            None => Violation::Malformed(error),
+        });
+    }
+
+    let entry: serde_json::Map<String, serde_json::Value> = serde_json::from_str(entry)?;
+
+    if let Some(serde_json::Value::Object(labels)) = entry.get("logging.googleapis.com/labels") {
+        for (key, value) in labels {
+            let value = value.as_str().unwrap_or_default();
+
+            if key.len() > MAX_LABEL_KEY_BYTES || value.len() > MAX_LABEL_VALUE_BYTES {
+                return Err(Violation::LabelSize(key.clone()));
+            }
+        }
+    }
+
+    if let Some(trace) = entry.get("logging.googleapis.com/trace") {
+        let trace = trace.as_str().unwrap_or_default();
+        let trace_id = trace.rsplit('/').next().unwrap_or_default();
+
+        if !is_hex(trace_id, 32) || trace_id.bytes().all(|byte| byte == b'0') {
+            return Err(Violation::TraceId(trace.to_string()));
+        }
+    }
+
+    Ok(())
+}
+
+/// Visitor of event fields that catches label values that would be coerced into strings
+#[derive(Debug, Default)]
+pub(crate) struct StrictFields(pub(crate) Option<Violation>);
+
+impl StrictFields {
+    fn record_non_string(&mut self, field: &Field) {
+        if let Some(label) = field.name().strip_prefix("labels.") {
+            self.0
+                .get_or_insert(Violation::LabelValue(label.to_string()));
+        }
+    }
+}
+
+impl Visit for StrictFields {
+    fn record_i64(&mut self, field: &Field, _: i64) {
+        self.record_non_string(field);
+    }
+
+    fn record_u64(&mut self, field: &Field, _: u64) {
+        self.record_non_string(field);
+    }
+
+    fn record_f64(&mut self, field: &Field, _: f64) {
+        self.record_non_string(field);
+    }
+
+    fn record_bool(&mut self, field: &Field, _: bool) {
+        self.record_non_string(field);
+    }
+
+    fn record_debug(&mut self, _: &Field, _: &dyn fmt::Debug) {}
+}
+
+/// Any JSON value, failing to deserialize objects with duplicate keys at any depth
+struct NoDuplicates;
+
+impl<'de> Deserialize<'de> for NoDuplicates {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        deserializer.deserialize_any(NoDuplicatesVisitor)
+    }
+}
+
+struct NoDuplicatesVisitor;
+
+impl<'de> Visitor<'de> for NoDuplicatesVisitor {
+    type Value = NoDuplicates;
+
+    fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        formatter.write_str("any JSON value")
+    }
+
+    fn visit_bool<E>(self, _: bool) -> Result<Self::Value, E> {
+        Ok(NoDuplicates)
+    }
+
+    fn visit_i64<E>(self, _: i64) -> Result<Self::Value, E> {
+        Ok(NoDuplicates)
+    }
+
+    fn visit_u64<E>(self, _: u64) -> Result<Self::Value, E> {
+        Ok(NoDuplicates)
+    }
+
+    fn visit_f64<E>(self, _: f64) -> Result<Self::Value, E> {
+        Ok(NoDuplicates)
+    }
+
+    fn visit_str<E>(self, _: &str) -> Result<Self::Value, E> {
+        Ok(NoDuplicates)
+    }
+
+    fn visit_unit<E>(self) -> Result<Self::Value, E> {
+        Ok(NoDuplicates)
+    }
+
+    fn visit_seq<A>(self, mut seq: A) -> Result<Self::Value, A::Error>
+    where
+        A: SeqAccess<'de>,
+    {
+        while seq.next_element::<NoDuplicates>()?.is_some() {}
+        Ok(NoDuplicates)
+    }
+
+    fn visit_map<A>(self, mut map: A) -> Result<Self::Value, A::Error>
+    where
+        A: MapAccess<'de>,
+    {
+        let mut keys = HashSet::new();
+
+        while let Some(key) = map.next_key::<String>()? {
+            map.next_value::<NoDuplicates>()?;
+
+            if !keys.insert(key.clone()) {
+                return Err(de::Error::custom(format!("duplicate key `{}`", key)));
+            }
+        }
+
+        Ok(NoDuplicates)
+    }
+}
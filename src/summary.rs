@@ -0,0 +1,66 @@
+use crate::google::LogSeverity;
+use std::{collections::BTreeMap, fmt};
+use tracing_core::{Event, Field, Level};
+use tracing_subscriber::field::Visit;
+
+/// Summary of the ERROR and WARN events that occurred within a span or its descendants, cached
+/// in that span's extensions and attached to its close entry
+#[derive(Clone, Debug, Default)]
+pub(crate) struct ErrorSummary {
+    pub(crate) error_count: u64,
+    pub(crate) first_error: Option<String>,
+    pub(crate) last_error: Option<String>,
+}
+
+impl ErrorSummary {
+    /// Whether an event is summarized
+    pub(crate) fn is_summarized(event: &Event) -> bool {
+        *event.metadata().level() <= Level::WARN && !event.metadata().is_span()
+    }
+
+    /// Count an event, keeping its message as the first and last errors
+    pub(crate) fn record(&mut self, message: Option<String>) {
+        self.error_count += 1;
+
+        if self.first_error.is_none() {
+            self.first_error.clone_from(&message);
+        }
+
+        self.last_error = message;
+    }
+}
+
+/// Number of events of each severity that occurred within a span or its descendants, cached in
+/// that span's extensions and attached to its close entry
+#[derive(Clone, Debug, Default)]
+pub(crate) struct EventCounts(pub(crate) BTreeMap<LogSeverity, u64>);
+
+impl EventCounts {
+    /// Whether an event is counted
+    pub(crate) fn is_counted(event: &Event) -> bool {
+        !event.metadata().is_span()
+    }
+
+    /// Count an event of a severity
+    pub(crate) fn record(&mut self, severity: LogSeverity) {
+        *self.0.entry(severity).or_default() += 1;
+    }
+}
+
+/// Visitor extracting the message of an event
+#[derive(Debug, Default)]
+pub(crate) struct Message(pub(crate) Option<String>);
+
+impl Visit for Message {
+    fn record_str(&mut self, field: &Field, value: &str) {
+        if field.name() == "message" {
+            self.0 = Some(value.to_string());
+        }
+    }
+
+    fn record_debug(&mut self, field: &Field, value: &dyn fmt::Debug) {
+        if field.name() == "message" {
+            self.0 = Some(format!("{:?}", value));
+        }
+    }
+}
@@ -0,0 +1,128 @@
+use std::{
+    cell::RefCell,
+    collections::VecDeque,
+    time::{Duration, Instant},
+};
+use tracing_core::Metadata;
+
+/// Configuration for buffering the DEBUG and TRACE entries of each root span in memory, writing
+/// them just before the span closes only if it failed, by recording an ERROR event, or took
+/// longer than a latency threshold. The entries of every other span are discarded.
+#[derive(Clone, Debug)]
+pub struct TailBuffering {
+    /// Duration beyond which the buffered entries of a span are written, or `None` to only write
+    /// them for failed spans
+    pub latency_threshold: Option<Duration>,
+    /// Maximum number of entries buffered per span, dropping the oldest ones beyond it
+    pub max_entries: usize,
+}
+
+impl Default for TailBuffering {
+    fn default() -> Self {
+        Self {
+            latency_threshold: None,
+            max_entries: 1_000,
+        }
+    }
+}
+
+/// Formatted DEBUG and TRACE entries of a root span, along with the metadata of their events
+#[derive(Debug)]
+pub(crate) struct TailBuffer {
+    started: Instant,
+    failed: bool,
+    entries: VecDeque<(&'static Metadata<'static>, String)>,
+    evicted: u64,
+}
+
+impl TailBuffer {
+    pub(crate) fn new() -> Self {
+        Self {
+            started: Instant::now(),
+            failed: false,
+            entries: VecDeque::new(),
+            evicted: 0,
+        }
+    }
+
+    pub(crate) fn fail(&mut self) {
+        self.failed = true;
+    }
+
+    pub(crate) fn push(
+        &mut self,
+        metadata: &'static Metadata<'static>,
+        entry: String,
+        configuration: &TailBuffering,
+    ) {
+        if configuration.max_entries == 0 {
+            self.evicted += 1;
+            return;
+        }
+
+        while self.entries.len() >= configuration.max_entries {
+            self.entries.pop_front();
+            self.evicted += 1;
+        }
+
+        self.entries.push_back((metadata, entry));
+    }
+
+    /// Number of entries currently buffered
+    pub(crate) fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    /// Number of entries dropped to stay within the maximum number of buffered entries
+    pub(crate) fn evicted(&self) -> u64 {
+        self.evicted
+    }
+
+    /// The buffered entries and the metadata of their events, if the span failed or exceeded the
+    /// latency threshold
+    pub(crate) fn flush(
+        self,
+        configuration: &TailBuffering,
+    ) -> Option<VecDeque<(&'static Metadata<'static>, String)>> {
+        let slow = configuration
+            .latency_threshold
+            .is_some_and(|threshold| self.started.elapsed() > threshold);
+
+        if !self.failed && !slow {
+            return None;
+        }
+
+        Some(self.entries)
+    }
+}
+
+thread_local! {
+    static CAPTURED: RefCell<Option<String>> = const { RefCell::new(None) };
+}
+
+/// Run a callback while capturing the entries formatted on this thread instead of writing them
+pub(crate) fn capture(callback: impl FnOnce()) -> String {
+    let previous = CAPTURED.with(|captured| captured.replace(Some(String::new())));
+    callback();
+
+    CAPTURED
+        .with(|captured| captured.replace(previous))
+        .unwrap_or_default()
+}
+
+/// Whether entries formatted on this thread are being captured
+pub(crate) fn is_capturing() -> bool {
+    CAPTURED.with(|captured| captured.borrow().is_some())
+}
+
+/// Capture a formatted line, returning whether it was captured rather than left to be written
+pub(crate) fn capture_line(line: &str) -> bool {
+    CAPTURED.with(|captured| match captured.borrow_mut().as_mut() {
+        Some(captured) => {
+            captured.push_str(line);
+            captured.push('\n');
+            true
+        }
+        None => false,
+    })
+}
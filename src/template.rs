@@ -0,0 +1,61 @@
+/// Characters trimmed from either side of a word before deciding whether it's interpolated
+const PUNCTUATION: [char; 8] = [',', '.', ';', ':', '(', ')', '[', ']'];
+
+/// Quotes around interpolated strings, e.g. `'jane'`
+const QUOTES: [char; 3] = ['"', '\'', '`'];
+
+/// Derive the template of a message, e.g. `user {} logged in from {}` for
+/// `user 42 logged in from 10.0.0.1`, by replacing the words that look interpolated, those with
+/// digits or within quotes, with `{}`. Values of `key=value` pairs are replaced, keeping their
+/// keys. Format strings aren't retained by `tracing`, so the template is derived from the message.
+pub(crate) fn template(message: &str) -> String {
+    let mut template = String::with_capacity(message.len());
+    let mut rest = message;
+
+    while !rest.is_empty() {
+        let start = rest
+            .find(|char: char| !char.is_whitespace())
+            .unwrap_or(rest.len());
+        template.push_str(&rest[..start]);
+        rest = &rest[start..];
+
+        let end = rest.find(char::is_whitespace).unwrap_or(rest.len());
+        push_word(&mut template, &rest[..end]);
+        rest = &rest[end..];
+    }
+
+    template
+}
+
+/// Push a word, replacing its interpolated part
+fn push_word(template: &mut String, word: &str) {
+    let (key, value) = match word.split_once('=') {
+        Some((key, value)) if !key.is_empty() => (&word[..=key.len()], value),
+        _ => ("", word),
+    };
+
+    let core = value.trim_start_matches(PUNCTUATION);
+    let prefix = &value[..value.len() - core.len()];
+    let core = core.trim_end_matches(PUNCTUATION);
+    let suffix = &value[prefix.len() + core.len()..];
+
+    template.push_str(key);
+    template.push_str(prefix);
+
+    if is_interpolated(core) {
+        template.push_str("{}");
+    } else {
+        template.push_str(core);
+    }
+
+    template.push_str(suffix);
+}
+
+/// Whether a word looks interpolated rather than part of the template
+fn is_interpolated(word: &str) -> bool {
+    let quoted = QUOTES
+        .iter()
+        .any(|quote| word.len() >= 2 && word.starts_with(*quote) && word.ends_with(*quote));
+
+    quoted || word.chars().any(|char| char.is_ascii_digit())
+}
@@ -0,0 +1,61 @@
+use std::fmt;
+use time::{format_description::well_known::Rfc3339, OffsetDateTime};
+use tracing_core::{Event, Field};
+use tracing_subscriber::field::Visit;
+
+/// Name of the field that overrides the time of an event's entry
+const TIMESTAMP_FIELD: &str = "timestamp";
+
+/// Visitor extracting the time of an event from its `timestamp` field, as an RFC 3339 string or
+/// nanoseconds since the Unix epoch
+#[derive(Debug, Default)]
+pub(crate) struct Timestamp(pub(crate) Option<OffsetDateTime>);
+
+impl Timestamp {
+    /// Time recorded on an event, if any
+    pub(crate) fn from_event(event: &Event) -> Option<OffsetDateTime> {
+        event.metadata().fields().field(TIMESTAMP_FIELD)?;
+
+        let mut timestamp = Self::default();
+        event.record(&mut timestamp);
+        timestamp.0
+    }
+
+    fn record_nanos(&mut self, field: &Field, nanos: i128) {
+        if field.name() == TIMESTAMP_FIELD {
+            self.0 = OffsetDateTime::from_unix_timestamp_nanos(nanos).ok();
+        }
+    }
+}
+
+impl Visit for Timestamp {
+    fn record_i64(&mut self, field: &Field, value: i64) {
+        self.record_nanos(field, value.into());
+    }
+
+    fn record_u64(&mut self, field: &Field, value: u64) {
+        self.record_nanos(field, value.into());
+    }
+
+    fn record_i128(&mut self, field: &Field, value: i128) {
+        self.record_nanos(field, value);
+    }
+
+    fn record_u128(&mut self, field: &Field, value: u128) {
+        if let Ok(value) = value.try_into() {
+            self.record_nanos(field, value);
+        }
+    }
+
+    fn record_str(&mut self, field: &Field, value: &str) {
+        if field.name() == TIMESTAMP_FIELD {
+            self.0 = OffsetDateTime::parse(value, &Rfc3339).ok();
+        }
+    }
+
+    fn record_debug(&mut self, field: &Field, value: &dyn fmt::Debug) {
+        if field.name() == TIMESTAMP_FIELD {
+            self.0 = OffsetDateTime::parse(&format!("{:?}", value), &Rfc3339).ok();
+        }
+    }
+}
@@ -0,0 +1,36 @@
+use std::time::Instant;
+
+/// Time a span spent entered and exited, tracked in its extensions for its close entry
+#[derive(Clone, Debug)]
+pub(crate) struct Timings {
+    pub(crate) busy_ns: u64,
+    pub(crate) idle_ns: u64,
+    last: Instant,
+}
+
+impl Timings {
+    pub(crate) fn new() -> Self {
+        Self {
+            busy_ns: 0,
+            idle_ns: 0,
+            last: Instant::now(),
+        }
+    }
+
+    /// Count the time since the last transition as idle, as when a span is entered or closed
+    pub(crate) fn idle(&mut self) {
+        self.idle_ns = self.idle_ns.saturating_add(self.elapsed());
+    }
+
+    /// Count the time since the last transition as busy, as when a span is exited
+    pub(crate) fn busy(&mut self) {
+        self.busy_ns = self.busy_ns.saturating_add(self.elapsed());
+    }
+
+    fn elapsed(&mut self) -> u64 {
+        let now = Instant::now();
+        let elapsed = now.duration_since(self.last);
+        self.last = now;
+        elapsed.as_nanos().try_into().unwrap_or(u64::MAX)
+    }
+}
@@ -0,0 +1,410 @@
+use crate::fields;
+use std::fmt;
+use tracing_core::{span::Id, Dispatch, Field, Subscriber};
+use tracing_subscriber::{
+    field::Visit,
+    registry::{ExtensionsMut, LookupSpan, SpanRef},
+};
+
+/// Trace correlation fields recorded on a span, cached in that span's extensions
+#[derive(Clone, Debug, Default)]
+pub(crate) struct TraceFields {
+    pub(crate) trace_id: Option<String>,
+    pub(crate) span_id: Option<u64>,
+    pub(crate) sampled: Option<bool>,
+    pub(crate) project_id: Option<String>,
+    /// Whether the fields were set from a [`TraceContext`], which recorded fields don't override
+    pub(crate) typed: bool,
+}
+
+impl From<TraceContext> for TraceFields {
+    fn from(context: TraceContext) -> Self {
+        Self {
+            trace_id: Some(context.trace_id),
+            span_id: Some(context.span_id),
+            sampled: Some(context.sampled),
+            project_id: context.project_id,
+            typed: true,
+        }
+    }
+}
+
+impl TraceFields {
+    /// Find the trace fields of the nearest span in the scope that carries a `trace_id`
+    pub(crate) fn from_scope<S>(span: &SpanRef<S>) -> Option<Self>
+    where
+        S: for<'lookup> LookupSpan<'lookup>,
+    {
+        span.scope()
+            .find_map(|span| {
+                span.extensions()
+                    .get::<Self>()
+                    .filter(|fields| fields.trace_id.is_some())
+                    .cloned()
+            })
+            .map(|fields| fields.with_scope_project(span))
+    }
+
+    /// Take the project of the trace from the nearest span in the scope recording a
+    /// `gcp_project`, unless the trace names its own
+    fn with_scope_project<S>(mut self, span: &SpanRef<S>) -> Self
+    where
+        S: for<'lookup> LookupSpan<'lookup>,
+    {
+        if self.project_id.is_none() {
+            self.project_id = span.scope().find_map(|span| {
+                span.extensions()
+                    .get::<Self>()
+                    .and_then(|fields| fields.project_id.clone())
+            });
+        }
+
+        self
+    }
+}
+
+/// Policy choosing the trace that entries are correlated with when several spans in their scope
+/// carry a `trace_id`, e.g. nested sub-requests
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum TraceResolution {
+    /// The trace of the nearest span with a `trace_id`
+    #[default]
+    Nearest,
+    /// The trace of the outermost span with a `trace_id`
+    Root,
+    /// Spans with different trace IDs are an error: entries are written without trace fields,
+    /// and with the conflicting IDs as their `traceConflict`
+    Strict,
+}
+
+impl TraceFields {
+    /// Find the trace fields of the scope according to a resolution policy, or the conflicting
+    /// trace IDs of the scope under [`TraceResolution::Strict`]
+    pub(crate) fn resolve<S>(
+        span: &SpanRef<S>,
+        resolution: TraceResolution,
+    ) -> Result<Option<Self>, Vec<String>>
+    where
+        S: for<'lookup> LookupSpan<'lookup>,
+    {
+        let traced = |span: SpanRef<S>| {
+            span.extensions()
+                .get::<Self>()
+                .filter(|fields| fields.trace_id.is_some())
+                .cloned()
+        };
+
+        match resolution {
+            TraceResolution::Nearest => Ok(Self::from_scope(span)),
+            TraceResolution::Root => Ok(span
+                .scope()
+                .from_root()
+                .find_map(traced)
+                .map(|fields| fields.with_scope_project(span))),
+            TraceResolution::Strict => {
+                let mut traces = span.scope().filter_map(traced);
+                let nearest = traces.next();
+                let mut trace_ids: Vec<String> = nearest
+                    .iter()
+                    .flat_map(|fields| fields.trace_id.clone())
+                    .collect();
+
+                for fields in traces {
+                    if let Some(trace_id) = fields.trace_id {
+                        if !trace_ids.contains(&trace_id) {
+                            trace_ids.push(trace_id);
+                        }
+                    }
+                }
+
+                match trace_ids.len() {
+                    0 | 1 => Ok(nearest.map(|fields| fields.with_scope_project(span))),
+                    _ => Err(trace_ids),
+                }
+            }
+        }
+    }
+}
+
+impl Visit for TraceFields {
+    fn record_u64(&mut self, field: &Field, value: u64) {
+        match field.name() {
+            "trace_id" => self.trace_id = Some(value.to_string()),
+            "span_id" => self.span_id = Some(value),
+            _ => (),
+        }
+    }
+
+    fn record_bool(&mut self, field: &Field, value: bool) {
+        if field.name() == "trace_sampled" {
+            self.sampled = Some(value);
+        }
+    }
+
+    fn record_str(&mut self, field: &Field, value: &str) {
+        match field.name() {
+            "trace_id" => self.trace_id = Some(value.to_string()),
+            "span_id" => self.span_id = parse_span_id(value),
+            "trace_sampled" => self.sampled = value.parse().ok(),
+            "gcp_project" => self.project_id = Some(value.to_string()),
+            "traceparent" => self.record_context(TraceContext::from_traceparent(value)),
+            "x_cloud_trace_context" => {
+                self.record_context(TraceContext::from_x_cloud_trace_context(value))
+            }
+            _ => (),
+        }
+    }
+
+    fn record_debug(&mut self, field: &Field, value: &dyn fmt::Debug) {
+        match field.name() {
+            "trace_id"
+            | "span_id"
+            | "trace_sampled"
+            | "gcp_project"
+            | "traceparent"
+            | "x_cloud_trace_context" => {
+                self.record_str(field, fields::unquote(&format!("{:?}", value)))
+            }
+            _ => (),
+        }
+    }
+}
+
+impl TraceFields {
+    /// Record the IDs and sampling decision of a trace context header, parsed by the header's own
+    /// format since its span ID is hex in `traceparent` and decimal in `X-Cloud-Trace-Context`
+    fn record_context(&mut self, context: Option<TraceContext>) {
+        if let Some(context) = context {
+            self.trace_id = Some(context.trace_id);
+            self.span_id = Some(context.span_id);
+            self.sampled = Some(context.sampled);
+        }
+    }
+}
+
+/// Parse a `span_id` field from either its 16-character hex form or its decimal form. Decimal
+/// span IDs of `X-Cloud-Trace-Context` headers may be 16 digits long too, so they're recorded
+/// unambiguously through the `x_cloud_trace_context` field or as integers.
+pub(crate) fn parse_span_id(value: &str) -> Option<u64> {
+    if value.len() == 16 && value.bytes().all(|byte| byte.is_ascii_hexdigit()) {
+        u64::from_str_radix(value, 16).ok()
+    } else {
+        value.parse().ok()
+    }
+}
+
+/// Encoding used when writing the `logging.googleapis.com/spanId` field
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum SpanIdEncoding {
+    /// 16-character, zero-padded lowercase hex
+    #[default]
+    Hex,
+    /// Unsigned decimal, as used by App Engine request log correlation
+    Decimal,
+}
+
+impl SpanIdEncoding {
+    pub(crate) fn encode(self, span_id: u64) -> String {
+        match self {
+            Self::Hex => format!("{:016x}", span_id),
+            Self::Decimal => span_id.to_string(),
+        }
+    }
+}
+
+/// Trace context of the current span, for propagating traces to downstream services so that their
+/// logs join the same trace
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct TraceContext {
+    /// The trace's 32-character hex ID
+    pub trace_id: String,
+    /// ID of the span making the outgoing request
+    pub span_id: u64,
+    /// Whether or not the trace is sampled
+    pub sampled: bool,
+    /// Google Cloud project ID that owns the trace, when it differs from the project of the
+    /// Layer's [`CloudTraceConfiguration`](crate::CloudTraceConfiguration)
+    pub project_id: Option<String>,
+}
+
+impl TraceContext {
+    /// Trace context of the nearest span that records a `trace_id` in the current span's scope,
+    /// using the ID of the current tracing span when no `span_id` was recorded. With a
+    /// [`TraceExporter`](crate::TraceExporter), the current span's exported ID is used instead,
+    /// so that downstream spans are nested under it. Requires a stackdriver
+    /// [`Layer`](crate::Layer) in the current subscriber.
+    pub fn current() -> Option<Self> {
+        tracing::Span::current()
+            .with_subscriber(|(id, dispatch)| {
+                let with_trace_context = dispatch.downcast_ref::<WithTraceContext>()?;
+                (with_trace_context.lookup)(dispatch, id)
+            })
+            .flatten()
+    }
+
+    /// Parse the value of a W3C `traceparent` header
+    pub fn from_traceparent(traceparent: &str) -> Option<Self> {
+        let mut parts = traceparent.trim().split('-');
+        let version = parts
+            .next()
+            .filter(|version| is_hex(version, 2) && *version != "ff")?;
+        let trace_id = parts.next().filter(|trace_id| is_hex(trace_id, 32))?;
+        let span_id = parts.next().filter(|span_id| is_hex(span_id, 16))?;
+        let flags = parts.next().filter(|flags| is_hex(flags, 2))?;
+
+        // later versions may append fields, but version 00 has exactly four
+        if version == "00" && parts.next().is_some() {
+            return None;
+        }
+
+        Some(Self {
+            trace_id: trace_id.to_ascii_lowercase(),
+            span_id: u64::from_str_radix(span_id, 16).ok()?,
+            sampled: u8::from_str_radix(flags, 16).ok()? & 1 == 1,
+            project_id: None,
+        })
+    }
+
+    /// Parse the value of an `X-Cloud-Trace-Context` header, formatted as
+    /// `TRACE_ID/SPAN_ID;o=OPTIONS`
+    pub fn from_x_cloud_trace_context(x_cloud_trace_context: &str) -> Option<Self> {
+        let (ids, options) = x_cloud_trace_context
+            .trim()
+            .split_once(";o=")
+            .unwrap_or((x_cloud_trace_context.trim(), "0"));
+        let (trace_id, span_id) = ids.split_once('/')?;
+
+        if !is_hex(trace_id, 32) {
+            return None;
+        }
+
+        Some(Self {
+            trace_id: trace_id.to_ascii_lowercase(),
+            span_id: span_id.parse().ok()?,
+            sampled: options == "1",
+            project_id: None,
+        })
+    }
+
+    /// Value of the W3C `traceparent` header
+    pub fn traceparent(&self) -> String {
+        format!(
+            "00-{}-{:016x}-{}",
+            self.trace_id,
+            self.span_id,
+            if self.sampled { "01" } else { "00" }
+        )
+    }
+
+    /// Value of the `X-Cloud-Trace-Context` header
+    pub fn x_cloud_trace_context(&self) -> String {
+        format!(
+            "{}/{};o={}",
+            self.trace_id,
+            self.span_id,
+            u8::from(self.sampled)
+        )
+    }
+}
+
+/// Whether a value is made up of exactly `length` hex digits
+pub(crate) fn is_hex(value: &str, length: usize) -> bool {
+    value.len() == length && value.bytes().all(|byte| byte.is_ascii_hexdigit())
+}
+
+/// Lookup of a span's [`TraceContext`], made available by the Layer through `downcast_raw` since
+/// the subscriber type is erased behind the current dispatcher
+#[derive(Clone, Copy)]
+pub(crate) struct WithTraceContext {
+    lookup: fn(&Dispatch, &Id) -> Option<TraceContext>,
+    extensions: WithExtensions,
+}
+
+/// Access to the extensions of a span, for setting span data from outside of the Layer
+type WithExtensions = fn(&Dispatch, &Id, &mut dyn FnMut(&mut ExtensionsMut<'_>));
+
+impl WithTraceContext {
+    pub(crate) fn new<S>() -> Self
+    where
+        S: Subscriber + for<'lookup> LookupSpan<'lookup>,
+    {
+        Self {
+            lookup: Self::lookup::<S>,
+            extensions: Self::extensions::<S>,
+        }
+    }
+
+    /// Modify the extensions of a span of the current subscriber
+    pub(crate) fn with_extensions(
+        span: &tracing::Span,
+        callback: impl FnOnce(&mut ExtensionsMut<'_>),
+    ) {
+        let mut callback = Some(callback);
+
+        span.with_subscriber(|(id, dispatch)| {
+            if let Some(with_trace_context) = dispatch.downcast_ref::<WithTraceContext>() {
+                (with_trace_context.extensions)(dispatch, id, &mut |extensions| {
+                    if let Some(callback) = callback.take() {
+                        callback(extensions);
+                    }
+                });
+            }
+        });
+    }
+
+    fn extensions<S>(dispatch: &Dispatch, id: &Id, callback: &mut dyn FnMut(&mut ExtensionsMut<'_>))
+    where
+        S: Subscriber + for<'lookup> LookupSpan<'lookup>,
+    {
+        if let Some(span) = dispatch
+            .downcast_ref::<S>()
+            .and_then(|subscriber| subscriber.span(id))
+        {
+            callback(&mut span.extensions_mut());
+        }
+    }
+
+    fn lookup<S>(dispatch: &Dispatch, id: &Id) -> Option<TraceContext>
+    where
+        S: Subscriber + for<'lookup> LookupSpan<'lookup>,
+    {
+        let subscriber = dispatch.downcast_ref::<S>()?;
+        let span = subscriber.span(id)?;
+        let fields = TraceFields::from_scope(&span)?;
+
+        // exported spans are the parents of the spans of downstream services
+        #[cfg(feature = "trace-export")]
+        if let Some(record) = span.extensions().get::<crate::trace_export::SpanRecord>() {
+            return Some(TraceContext {
+                trace_id: fields.trace_id?,
+                span_id: record.span_id,
+                sampled: fields.sampled.unwrap_or(true),
+                project_id: fields.project_id,
+            });
+        }
+
+        Some(TraceContext {
+            trace_id: fields.trace_id?,
+            span_id: fields.span_id.unwrap_or_else(|| id.into_u64()),
+            sampled: fields.sampled.unwrap_or_default(),
+            project_id: fields.project_id,
+        })
+    }
+}
+
+/// Extension of [`tracing::Span`] for correlating a span with a trace directly, rather than
+/// through its `trace_id`, `span_id`, and `trace_sampled` fields
+pub trait SpanTraceContext {
+    /// Sets the trace context of the span, which its events and those of its descendants are
+    /// correlated with, taking precedence over the trace fields recorded on it. Requires a
+    /// stackdriver [`Layer`](crate::Layer) in the span's subscriber.
+    fn set_trace_context(&self, context: TraceContext);
+}
+
+impl SpanTraceContext for tracing::Span {
+    fn set_trace_context(&self, context: TraceContext) {
+        WithTraceContext::with_extensions(self, |extensions| {
+            extensions.replace(TraceFields::from(context));
+        });
+    }
+}
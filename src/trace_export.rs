@@ -0,0 +1,290 @@
+use crate::{
+    auth::{TokenCache, TRACE_APPEND_SCOPE},
+    client::{ApiClientBuilder, ClientConfig},
+    exporter::{Diagnostics, ExportFailure},
+    line_buffer::LineBuffer,
+    trace::TraceFields,
+};
+use serde_json::{Map, Value};
+use std::{
+    collections::hash_map::RandomState,
+    fmt,
+    hash::{BuildHasher, Hasher},
+    io, mem,
+};
+use time::{format_description::well_known::Rfc3339, OffsetDateTime};
+use tracing_core::Field;
+use tracing_subscriber::field::Visit;
+
+/// Cloud Trace API endpoint, relative to which spans are written to a project
+const DEFAULT_ENDPOINT: &str = "https://cloudtrace.googleapis.com/v2";
+
+/// Default maximum number of spans per `batchWrite` request
+const DEFAULT_MAX_BATCH_SPANS: usize = 1_000;
+
+/// Builder for configuring a [`TraceExporter`]. Authorization, request timeouts, and the
+/// diagnostics hook, which is notified of spans whose request failed, are configured with the
+/// methods of [`ApiClientBuilder`].
+pub struct TraceExporterBuilder {
+    project_id: String,
+    endpoint: String,
+    client: ClientConfig,
+    max_batch_spans: usize,
+}
+
+impl TraceExporterBuilder {
+    /// Create a builder for an exporter writing to the traces of the given project
+    pub fn new(project_id: impl Into<String>) -> Self {
+        Self {
+            project_id: project_id.into(),
+            endpoint: DEFAULT_ENDPOINT.to_string(),
+            client: ClientConfig::new(TRACE_APPEND_SCOPE),
+            max_batch_spans: DEFAULT_MAX_BATCH_SPANS,
+        }
+    }
+
+    /// Sets the base URL of the Cloud Trace API, e.g. for a test server
+    pub fn with_endpoint(self, endpoint: impl Into<String>) -> Self {
+        Self {
+            endpoint: endpoint.into(),
+            ..self
+        }
+    }
+
+    /// Sets the maximum number of spans sent in a single request
+    pub fn with_max_batch_spans(self, max_batch_spans: usize) -> Self {
+        Self {
+            max_batch_spans: max_batch_spans.max(1),
+            ..self
+        }
+    }
+
+    /// Build the configured exporter
+    pub fn build(self) -> TraceExporter {
+        let agent = self.client.agent();
+
+        TraceExporter {
+            url: format!(
+                "{}/projects/{}/traces:batchWrite",
+                self.endpoint.trim_end_matches('/'),
+                self.project_id
+            ),
+            project_id: self.project_id,
+            tokens: TokenCache::new(self.client.token_provider),
+            max_batch_spans: self.max_batch_spans,
+            diagnostics: self.client.diagnostics,
+            agent,
+            lines: LineBuffer::default(),
+            batch: Vec::new(),
+        }
+    }
+}
+
+impl ApiClientBuilder for TraceExporterBuilder {
+    fn client_config(&mut self) -> &mut ClientConfig {
+        &mut self.client
+    }
+}
+
+impl fmt::Debug for TraceExporterBuilder {
+    fn fmt(&self, formatter: &mut fmt::Formatter<'_>) -> fmt::Result {
+        formatter
+            .debug_struct("TraceExporterBuilder")
+            .field("project_id", &self.project_id)
+            .field("endpoint", &self.endpoint)
+            .field("max_batch_spans", &self.max_batch_spans)
+            .field("client", &self.client)
+            .finish_non_exhaustive()
+    }
+}
+
+/// Exporter that sends the spans of traced requests to the Cloud Trace API's
+/// [`batchWrite`](https://cloud.google.com/trace/docs/reference/v2/rest/v2/projects.traces/batchWrite)
+/// method, through [`Layer::with_trace_exporter`](crate::Layer::with_trace_exporter). Spans are
+/// exported once they close, within the trace of the nearest span in their scope with a
+/// `trace_id`, and with their fields as attributes.
+pub struct TraceExporter {
+    project_id: String,
+    url: String,
+    tokens: TokenCache,
+    max_batch_spans: usize,
+    diagnostics: Diagnostics,
+    agent: ureq::Agent,
+    lines: LineBuffer,
+    batch: Vec<Value>,
+}
+
+impl TraceExporter {
+    /// Create a builder for an exporter writing to the traces of the given project
+    pub fn builder(project_id: impl Into<String>) -> TraceExporterBuilder {
+        TraceExporterBuilder::new(project_id)
+    }
+
+    /// Queue a single closed span, sending the current batch once it's full
+    fn push_line(&mut self, line: &[u8]) {
+        let Ok(Value::Object(mut span)) = serde_json::from_slice::<Value>(line) else {
+            return;
+        };
+
+        // lines other than closed spans, e.g. reports of dropped entries, aren't exported
+        let (Some(Value::String(trace_id)), Some(Value::String(span_id))) =
+            (span.remove("traceId"), span.get("spanId"))
+        else {
+            return;
+        };
+
+        let name = format!(
+            "projects/{}/traces/{}/spans/{}",
+            self.project_id, trace_id, span_id
+        );
+        span.insert("name".to_string(), Value::String(name));
+        self.batch.push(Value::Object(span));
+
+        if self.batch.len() >= self.max_batch_spans {
+            self.send_batch();
+        }
+    }
+
+    /// Send all queued spans in a single request
+    fn send_batch(&mut self) {
+        if self.batch.is_empty() {
+            return;
+        }
+
+        let spans = mem::take(&mut self.batch);
+
+        if let Err(message) = self.send_request(&spans) {
+            (self.diagnostics)(&ExportFailure {
+                entries: spans,
+                code: None,
+                message,
+            });
+        }
+    }
+
+    /// Send spans in a single `batchWrite` request
+    fn send_request(&mut self, spans: &[Value]) -> Result<(), String> {
+        let token = self.tokens.token().map_err(|error| error.to_string())?;
+
+        self.agent
+            .post(&self.url)
+            .set("Content-Type", "application/json")
+            .set("Authorization", &format!("Bearer {}", token))
+            .send_json(serde_json::json!({ "spans": spans }))
+            .map(|_| ())
+            .map_err(|error| match error {
+                ureq::Error::Status(status, _) => {
+                    format!("batchWrite failed with HTTP status {}", status)
+                }
+                error => error.to_string(),
+            })
+    }
+}
+
+impl io::Write for TraceExporter {
+    fn write(&mut self, buffer: &[u8]) -> io::Result<usize> {
+        for line in self.lines.push(buffer) {
+            self.push_line(&line);
+        }
+
+        Ok(buffer.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.send_batch();
+        Ok(())
+    }
+}
+
+impl fmt::Debug for TraceExporter {
+    fn fmt(&self, formatter: &mut fmt::Formatter<'_>) -> fmt::Result {
+        formatter
+            .debug_struct("TraceExporter")
+            .field("project_id", &self.project_id)
+            .field("url", &self.url)
+            .field("queued", &self.batch.len())
+            .finish()
+    }
+}
+
+/// Lifecycle and fields of a span being exported, cached in that span's extensions
+#[derive(Debug)]
+pub(crate) struct SpanRecord {
+    pub(crate) span_id: u64,
+    started: OffsetDateTime,
+    attributes: Map<String, Value>,
+}
+
+impl SpanRecord {
+    pub(crate) fn new() -> Self {
+        Self {
+            // span IDs are random, since the IDs of tracing spans are reused once they close
+            span_id: RandomState::new().build_hasher().finish().max(1),
+            started: OffsetDateTime::now_utc(),
+            attributes: Map::new(),
+        }
+    }
+
+    /// The closed span as a line for a [`TraceExporter`], if it's part of a sampled trace
+    pub(crate) fn to_line(
+        &self,
+        name: &str,
+        trace: &TraceFields,
+        parent_span_id: Option<u64>,
+    ) -> Option<String> {
+        let trace_id = trace.trace_id.as_ref()?;
+
+        if trace.sampled == Some(false) {
+            return None;
+        }
+
+        let mut span = serde_json::json!({
+            "traceId": trace_id,
+            "spanId": format!("{:016x}", self.span_id),
+            "displayName": { "value": name },
+            "startTime": self.started.format(&Rfc3339).ok()?,
+            "endTime": OffsetDateTime::now_utc().format(&Rfc3339).ok()?,
+            "attributes": { "attributeMap": self.attributes },
+        });
+
+        if let Some(parent_span_id) = parent_span_id {
+            span["parentSpanId"] = Value::String(format!("{:016x}", parent_span_id));
+        }
+
+        Some(span.to_string())
+    }
+
+    fn insert(&mut self, field: &Field, value: Value) {
+        if !matches!(
+            field.name(),
+            "trace_id" | "span_id" | "trace_sampled" | "traceparent" | "x_cloud_trace_context"
+        ) {
+            self.attributes.insert(field.name().to_string(), value);
+        }
+    }
+}
+
+impl Visit for SpanRecord {
+    fn record_i64(&mut self, field: &Field, value: i64) {
+        self.insert(field, serde_json::json!({ "intValue": value.to_string() }));
+    }
+
+    fn record_u64(&mut self, field: &Field, value: u64) {
+        self.insert(field, serde_json::json!({ "intValue": value.to_string() }));
+    }
+
+    fn record_bool(&mut self, field: &Field, value: bool) {
+        self.insert(field, serde_json::json!({ "boolValue": value }));
+    }
+
+    fn record_str(&mut self, field: &Field, value: &str) {
+        self.insert(
+            field,
+            serde_json::json!({ "stringValue": { "value": value } }),
+        );
+    }
+
+    fn record_debug(&mut self, field: &Field, value: &dyn fmt::Debug) {
+        self.record_str(field, &format!("{:?}", value))
+    }
+}
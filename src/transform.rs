@@ -0,0 +1,20 @@
+use serde_json::Value;
+
+/// Hook applied to every field of an entry before it's written, returning its key and value,
+/// renamed or coerced as needed, or `None` to drop it. Keys are the names of the fields as
+/// recorded, e.g. `http_request.status`, which are then written as usual, so that fields can be
+/// moved into the `httpRequest` or labels of entries as well. Closures taking a key and a value
+/// are transformers.
+pub trait FieldTransformer: Send + Sync {
+    /// Transforms a field, or drops it by returning `None`
+    fn transform(&self, key: &str, value: Value) -> Option<(String, Value)>;
+}
+
+impl<F> FieldTransformer for F
+where
+    F: Fn(&str, Value) -> Option<(String, Value)> + Send + Sync,
+{
+    fn transform(&self, key: &str, value: Value) -> Option<(String, Value)> {
+        self(key, value)
+    }
+}
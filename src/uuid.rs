@@ -0,0 +1,31 @@
+use std::fmt;
+use uuid::Uuid;
+
+/// Wrap a UUID for recording as a single field, written as its canonical hyphenated string, as
+/// in `tracing::info!(user_id = %as_uuid(&user_id))`
+pub fn as_uuid(uuid: &Uuid) -> AsUuid {
+    AsUuid(*uuid)
+}
+
+/// Recordable wrapper of a UUID, formatted as its canonical hyphenated string
+#[derive(Clone, Copy)]
+pub struct AsUuid(Uuid);
+
+impl fmt::Display for AsUuid {
+    fn fmt(&self, formatter: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Display::fmt(&self.0.hyphenated(), formatter)
+    }
+}
+
+impl fmt::Debug for AsUuid {
+    fn fmt(&self, formatter: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Display::fmt(self, formatter)
+    }
+}
+
+/// The trace ID of a UUID recorded as a `trace_id`, which is its 32 hex digits without hyphens
+pub(crate) fn uuid_trace_id(trace_id: &str) -> Option<String> {
+    Uuid::try_parse(trace_id)
+        .ok()
+        .map(|uuid| uuid.simple().to_string())
+}
@@ -0,0 +1,135 @@
+use std::{fmt, str::FromStr};
+use tracing_core::{
+    span::{Attributes, Id, Record},
+    subscriber::Interest,
+    Event, Field, Metadata, Subscriber,
+};
+use tracing_subscriber::{
+    field::Visit,
+    filter::LevelFilter,
+    layer::{Context, Filter},
+    registry::{LookupSpan, SpanRef},
+};
+
+/// Default name of the span field that raises the verbosity of a request
+const DEFAULT_VERBOSITY_FIELD: &str = "log_verbosity";
+
+/// Per-layer [`Filter`] enabling events up to a base level, or up to a more verbose level
+/// recorded in a span field, e.g. `log_verbosity = "debug"` set from a request header, for every
+/// event within that span, whether as their contextual or explicit parent. Individual requests
+/// can then be debugged in production without changing the level of every other request. Since
+/// this filter decides which callsites are enabled, it can't be combined with a less verbose
+/// global filter.
+#[derive(Clone, Debug)]
+pub struct RequestVerbosity {
+    base: LevelFilter,
+    field: &'static str,
+}
+
+impl RequestVerbosity {
+    /// Create a filter enabling events up to the base level outside of verbose spans
+    pub fn new(base: impl Into<LevelFilter>) -> Self {
+        Self {
+            base: base.into(),
+            field: DEFAULT_VERBOSITY_FIELD,
+        }
+    }
+
+    /// Sets the name of the span field that raises the verbosity of events within the span
+    pub fn with_field(self, field: &'static str) -> Self {
+        Self { field, ..self }
+    }
+
+    fn record<S>(&self, id: &Id, visit: impl FnOnce(&mut LevelVisitor), context: &Context<'_, S>)
+    where
+        S: Subscriber + for<'span> LookupSpan<'span>,
+    {
+        let mut visitor = LevelVisitor {
+            field: self.field,
+            level: None,
+        };
+        visit(&mut visitor);
+
+        if let (Some(level), Some(span)) = (visitor.level, context.span(id)) {
+            let mut extensions = span.extensions_mut();
+            extensions.replace(Verbosity(level));
+        }
+    }
+
+    /// Whether a level is enabled within a span, by its own verbosity or that of a span in its
+    /// scope
+    fn enabled_within<S>(&self, metadata: &Metadata<'_>, span: Option<SpanRef<'_, S>>) -> bool
+    where
+        S: Subscriber + for<'span> LookupSpan<'span>,
+    {
+        span.is_some_and(|span| {
+            span.scope().any(|span| {
+                span.extensions()
+                    .get::<Verbosity>()
+                    .is_some_and(|verbosity| verbosity.0 >= *metadata.level())
+            })
+        })
+    }
+}
+
+impl<S> Filter<S> for RequestVerbosity
+where
+    S: Subscriber + for<'span> LookupSpan<'span>,
+{
+    fn enabled(&self, metadata: &Metadata<'_>, context: &Context<'_, S>) -> bool {
+        // events may have an explicit parent, which is only known once they're dispatched
+        if self.base >= *metadata.level() || metadata.is_event() {
+            return true;
+        }
+
+        self.enabled_within(metadata, context.lookup_current())
+    }
+
+    fn event_enabled(&self, event: &Event<'_>, context: &Context<'_, S>) -> bool {
+        let metadata = event.metadata();
+        self.base >= *metadata.level() || self.enabled_within(metadata, context.event_span(event))
+    }
+
+    fn callsite_enabled(&self, metadata: &'static Metadata<'static>) -> Interest {
+        // more verbose callsites are enabled depending on the span they occur within
+        if self.base >= *metadata.level() {
+            Interest::always()
+        } else {
+            Interest::sometimes()
+        }
+    }
+
+    fn max_level_hint(&self) -> Option<LevelFilter> {
+        Some(LevelFilter::TRACE)
+    }
+
+    fn on_new_span(&self, attributes: &Attributes<'_>, id: &Id, context: Context<'_, S>) {
+        self.record(id, |visitor| attributes.record(visitor), &context);
+    }
+
+    fn on_record(&self, id: &Id, values: &Record<'_>, context: Context<'_, S>) {
+        self.record(id, |visitor| values.record(visitor), &context);
+    }
+}
+
+/// Verbosity recorded on a span, cached in its extensions
+#[derive(Debug)]
+struct Verbosity(LevelFilter);
+
+/// Visitor extracting the verbosity recorded in a field
+struct LevelVisitor {
+    field: &'static str,
+    level: Option<LevelFilter>,
+}
+
+impl Visit for LevelVisitor {
+    fn record_str(&mut self, field: &Field, value: &str) {
+        if field.name() == self.field {
+            self.level = LevelFilter::from_str(value.trim()).ok();
+        }
+    }
+
+    fn record_debug(&mut self, field: &Field, value: &dyn fmt::Debug) {
+        self.record_str(field, &format!("{:?}", value))
+    }
+}
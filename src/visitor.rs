@@ -1,10 +1,29 @@
-use crate::google::LogSeverity;
+use crate::{
+    audit::AuditTrail,
+    counts::EntryCounts,
+    database::QueryLogging,
+    escalation::SeverityEscalation,
+    fields::{self, DebugLimits, DottedKeys, DurationFormat, ErrorFormat, RecordedError},
+    google::LogSeverity,
+    instrument::{InstrumentConfiguration, InstrumentField},
+    logfmt,
+    stack_trace::{self, StackTraces},
+    template,
+    transform::FieldTransformer,
+};
 use inflector::Inflector;
 use serde::ser::SerializeMap;
-use std::{collections::BTreeMap, fmt};
+use std::{borrow::Cow, collections::BTreeMap, error::Error, fmt, iter};
 use tracing_core::Field;
 use tracing_subscriber::field::{Visit, VisitOutput};
 
+/// Prefix of user fields named after reserved keys, unless configured otherwise
+pub(crate) const DEFAULT_RESERVED_KEY_PREFIX: &str = "user.";
+
+/// Length in bytes beyond which errors recorded with a plain `?` are truncated above DEBUG, when
+/// they're written with [`ErrorFormat::Display`]
+const ERROR_DEBUG_LENGTH: usize = 256;
+
 /// Visitor for Stackdriver events that formats custom fields
 pub(crate) struct Visitor<'a, S>
 where
@@ -12,6 +31,33 @@ where
 {
     values: BTreeMap<&'a str, serde_json::Value>,
     severity: LogSeverity,
+    labels: BTreeMap<String, String>,
+    max_value_length: Option<usize>,
+    max_severity: Option<LogSeverity>,
+    escalations: &'a [SeverityEscalation],
+    stack_traces: Option<&'a StackTraces>,
+    stack_trace_detection: bool,
+    entry_counts: Option<(&'a EntryCounts, &'a str)>,
+    duration_format: DurationFormat,
+    debug_limits: Option<DebugLimits>,
+    field_namespace: Option<&'a str>,
+    dotted_keys: DottedKeys,
+    reserved_key_prefix: &'a str,
+    console_url: Option<String>,
+    timestamp_override: bool,
+    logfmt_messages: bool,
+    message_templates: bool,
+    metric_fields: bool,
+    error_chains: BTreeMap<&'a str, Vec<String>>,
+    error_format: ErrorFormat,
+    error_debugs: BTreeMap<&'a str, String>,
+    debug_errors: Vec<&'a str>,
+    instrument: Option<(InstrumentField, InstrumentConfiguration)>,
+    query_logging: Option<&'a QueryLogging>,
+    audit_trail: Option<(&'a AuditTrail, &'a str)>,
+    field_transformer: Option<&'a dyn FieldTransformer>,
+    #[cfg(feature = "scrubbing")]
+    scrubbing: Option<&'a crate::scrubbing::ValueScrubbing>,
     serializer: S,
 }
 
@@ -24,9 +70,212 @@ where
         Self {
             values: BTreeMap::new(),
             severity,
+            labels: BTreeMap::new(),
+            max_value_length: None,
+            max_severity: None,
+            escalations: &[],
+            stack_traces: None,
+            stack_trace_detection: false,
+            entry_counts: None,
+            duration_format: DurationFormat::default(),
+            debug_limits: None,
+            field_namespace: None,
+            dotted_keys: DottedKeys::default(),
+            reserved_key_prefix: DEFAULT_RESERVED_KEY_PREFIX,
+            console_url: None,
+            timestamp_override: false,
+            logfmt_messages: false,
+            message_templates: false,
+            metric_fields: false,
+            error_chains: BTreeMap::new(),
+            error_format: ErrorFormat::default(),
+            error_debugs: BTreeMap::new(),
+            debug_errors: Vec::new(),
+            instrument: None,
+            query_logging: None,
+            audit_trail: None,
+            field_transformer: None,
+            #[cfg(feature = "scrubbing")]
+            scrubbing: None,
             serializer,
         }
     }
+
+    /// Sets labels that are merged into the event's own labels, which take precedence
+    pub(crate) fn with_labels(mut self, labels: BTreeMap<String, String>) -> Self {
+        self.labels = labels;
+        self
+    }
+
+    /// Sets values inherited from the event's spans, which the event's own fields take precedence over
+    pub(crate) fn with_inherited_values(
+        mut self,
+        values: BTreeMap<&'a str, serde_json::Value>,
+    ) -> Self {
+        self.values = values;
+        self
+    }
+
+    /// Sets the maximum length in bytes of string values, which are truncated beyond it
+    pub(crate) fn with_max_value_length(mut self, max_value_length: Option<usize>) -> Self {
+        self.max_value_length = max_value_length;
+        self
+    }
+
+    /// Sets the maximum severity of the event, which any higher severity is lowered to
+    pub(crate) fn with_max_severity(mut self, max_severity: Option<LogSeverity>) -> Self {
+        self.max_severity = max_severity;
+        self
+    }
+
+    /// Sets rules that raise the severity of the event based on its field values
+    pub(crate) fn with_escalations(mut self, escalations: &'a [SeverityEscalation]) -> Self {
+        self.escalations = escalations;
+        self
+    }
+
+    /// Sets the deduplicator of the stack traces of ERROR and more severe events
+    pub(crate) fn with_stack_traces(mut self, stack_traces: Option<&'a StackTraces>) -> Self {
+        self.stack_traces = stack_traces;
+        self
+    }
+
+    /// Moves Rust backtraces and panics within the messages of ERROR and more severe events into
+    /// their `stack_trace` field
+    pub(crate) fn with_stack_trace_detection(mut self, stack_trace_detection: bool) -> Self {
+        self.stack_trace_detection = stack_trace_detection;
+        self
+    }
+
+    /// Sets the counts of entries that the event is counted in, under its target
+    pub(crate) fn with_entry_counts(
+        mut self,
+        entry_counts: Option<(&'a EntryCounts, &'a str)>,
+    ) -> Self {
+        self.entry_counts = entry_counts;
+        self
+    }
+
+    /// Sets the format of durations recorded with `as_duration`
+    pub(crate) fn with_duration_format(mut self, duration_format: DurationFormat) -> Self {
+        self.duration_format = duration_format;
+        self
+    }
+
+    /// Sets the limits on the Debug representation of fields recorded with `?`
+    pub(crate) fn with_debug_limits(mut self, debug_limits: Option<DebugLimits>) -> Self {
+        self.debug_limits = debug_limits;
+        self
+    }
+
+    /// Sets the key of the object that user fields other than the message are nested under
+    pub(crate) fn with_field_namespace(mut self, field_namespace: Option<&'a str>) -> Self {
+        self.field_namespace = field_namespace;
+        self
+    }
+
+    /// Sets how fields with dots in their names are written
+    pub(crate) fn with_dotted_keys(mut self, dotted_keys: DottedKeys) -> Self {
+        self.dotted_keys = dotted_keys;
+        self
+    }
+
+    /// Sets the prefix of user fields that would otherwise clobber a reserved key
+    pub(crate) fn with_reserved_key_prefix(mut self, reserved_key_prefix: &'a str) -> Self {
+        self.reserved_key_prefix = reserved_key_prefix;
+        self
+    }
+
+    /// Sets the Logs Explorer URL written to the event if it's an ERROR or more severe
+    pub(crate) fn with_console_url(mut self, console_url: Option<String>) -> Self {
+        self.console_url = console_url;
+        self
+    }
+
+    /// Marks the event's `timestamp` field as used for the time of its entry, omitting it
+    pub(crate) fn with_timestamp_override(mut self, timestamp_override: bool) -> Self {
+        self.timestamp_override = timestamp_override;
+        self
+    }
+
+    /// Sets how fields holding errors are written
+    pub(crate) fn with_error_format(mut self, error_format: ErrorFormat) -> Self {
+        self.error_format = error_format;
+        self
+    }
+
+    /// Lifts `key=value` pairs within the event's message into fields
+    pub(crate) fn with_logfmt_messages(mut self, logfmt_messages: bool) -> Self {
+        self.logfmt_messages = logfmt_messages;
+        self
+    }
+
+    /// Writes the template that the event's message was derived from as its `messageTemplate`
+    pub(crate) fn with_message_templates(mut self, message_templates: bool) -> Self {
+        self.message_templates = message_templates;
+        self
+    }
+
+    /// Gathers `metric.` fields into the `metrics` object of the entry
+    pub(crate) fn with_metric_fields(mut self, metric_fields: bool) -> Self {
+        self.metric_fields = metric_fields;
+        self
+    }
+
+    /// Marks the event as a query log of a database crate, reshaped into structured fields
+    pub(crate) fn with_query_logging(mut self, query_logging: Option<&'a QueryLogging>) -> Self {
+        self.query_logging = query_logging;
+        self
+    }
+
+    /// Shapes the event into an audit record if it's an audit event from the given target
+    pub(crate) fn with_audit_trail(
+        mut self,
+        audit_trail: Option<(&'a AuditTrail, &'a str)>,
+    ) -> Self {
+        self.audit_trail = audit_trail;
+        self
+    }
+
+    /// Scrubs sensitive data from the values of the event's fields
+    #[cfg(feature = "scrubbing")]
+    pub(crate) fn with_scrubbing(
+        mut self,
+        scrubbing: Option<&'a crate::scrubbing::ValueScrubbing>,
+    ) -> Self {
+        self.scrubbing = scrubbing;
+        self
+    }
+
+    /// Sets the hook transforming every field before it's written
+    pub(crate) fn with_field_transformer(
+        mut self,
+        field_transformer: Option<&'a dyn FieldTransformer>,
+    ) -> Self {
+        self.field_transformer = field_transformer;
+        self
+    }
+
+    /// Marks the event as generated by `#[instrument]` for the given field
+    pub(crate) fn with_instrument(
+        mut self,
+        instrument: Option<(InstrumentField, InstrumentConfiguration)>,
+    ) -> Self {
+        self.instrument = instrument;
+        self
+    }
+
+    /// Write an error with Display, keeping its source chain and, with [`ErrorFormat::Display`],
+    /// its Debug representation
+    fn record_error_parts(&mut self, name: &'a str, error: RecordedError) {
+        self.values
+            .insert(name, serde_json::Value::from(error.display));
+        self.error_chains.insert(name, error.chain);
+
+        if self.error_format == ErrorFormat::Display {
+            self.error_debugs.insert(name, error.debug);
+        }
+    }
 }
 
 impl<'a, S> VisitOutput<fmt::Result> for Visitor<'a, S>
@@ -35,24 +284,201 @@ where
 {
     fn finish(mut self) -> fmt::Result {
         let inner = || {
+            if let Some(query_logging) = self.query_logging {
+                query_logging.reshape(&mut self.values);
+            }
+
+            if let Some((audit_trail, target)) = self.audit_trail {
+                audit_trail.reshape(target, &mut self.values);
+            }
+
+            // values are redacted by the names of their fields here, and scanned for sensitive
+            // data once the whole entry is assembled
+            #[cfg(feature = "scrubbing")]
+            if let Some(scrubbing) = self.scrubbing {
+                scrubbing.redact_keys(&mut self.values);
+                self.error_debugs
+                    .retain(|key, debug| scrubbing.redact_key_string(key, debug));
+                self.error_chains.retain(|key, chain| {
+                    chain
+                        .iter_mut()
+                        .all(|link| scrubbing.redact_key_string(key, link))
+                });
+            }
+
+            let severity = match self.instrument {
+                Some((InstrumentField::Error, _)) => LogSeverity::Error,
+                _ => self.severity,
+            };
+
             let severity = self
                 .values
                 .remove("severity")
                 .map(LogSeverity::from)
-                .unwrap_or(self.severity);
+                .unwrap_or(severity);
+
+            let severity = self
+                .escalations
+                .iter()
+                .filter(|escalation| escalation.matches(&self.values))
+                .map(|escalation| escalation.severity)
+                .fold(severity, Ord::max);
+
+            let severity = match self.max_severity {
+                Some(max_severity) => severity.min(max_severity),
+                None => severity,
+            };
 
             self.serializer.serialize_entry("severity", &severity)?;
 
+            // errors are formatted for the final severity, once overrides and escalations apply
+            if self.error_format == ErrorFormat::Display && severity > LogSeverity::Debug {
+                for name in &self.debug_errors {
+                    if let Some(value) = self.values.get_mut(name) {
+                        truncate_strings(value, ERROR_DEBUG_LENGTH);
+                    }
+                }
+
+                self.error_debugs.clear();
+            }
+
+            if let Some(console_url) = self
+                .console_url
+                .as_ref()
+                .filter(|_| severity >= LogSeverity::Error)
+            {
+                self.serializer.serialize_entry("consoleUrl", console_url)?;
+            }
+
+            if let Some((entry_counts, target)) = self.entry_counts {
+                entry_counts.record(target, severity);
+            }
+
+            if self.stack_trace_detection
+                && severity >= LogSeverity::Error
+                && !self.values.contains_key("stack_trace")
+            {
+                if let Some((message, trace)) = self
+                    .values
+                    .get("message")
+                    .and_then(serde_json::Value::as_str)
+                    .and_then(stack_trace::detect)
+                {
+                    self.values.insert("message", message.into());
+                    self.values.insert("stack_trace", trace.into());
+                    self.serializer
+                        .serialize_entry("@type", stack_trace::REPORTED_ERROR_EVENT_TYPE)?;
+                }
+            }
+
+            // replace stack traces seen within the deduplication window with a reference
+            if let Some(stack_traces) = self.stack_traces.filter(|_| severity >= LogSeverity::Error)
+            {
+                if let Some(serde_json::Value::String(stack_trace)) = self.values.get("stack_trace")
+                {
+                    let (reference, count) = stack_traces.deduplicate(stack_trace);
+
+                    if count > 1 {
+                        self.values.remove("stack_trace");
+                        self.values
+                            .insert("stack_trace_count", serde_json::Value::from(count));
+                    }
+
+                    self.values
+                        .insert("stack_trace_ref", serde_json::Value::from(reference));
+                }
+            }
+
+            match &self.instrument {
+                Some((InstrumentField::Error, configuration)) => {
+                    if let Some(error) = self.values.remove("error") {
+                        let chain = self.error_chains.remove("error").unwrap_or_default();
+
+                        self.serializer.serialize_entry("message", &error)?;
+                        self.serializer.serialize_entry(
+                            &configuration.error_key,
+                            &serde_json::json!({ "message": error, "chain": chain }),
+                        )?;
+                    }
+                }
+                Some((InstrumentField::Return, configuration)) => {
+                    if let Some(value) = self.values.remove("return") {
+                        self.serializer
+                            .serialize_entry(&configuration.return_key, &value)?;
+                    }
+                }
+                None => (),
+            }
+
+            if self.timestamp_override {
+                self.values.remove("timestamp");
+            }
+
             let mut http_request = BTreeMap::new();
-            let mut labels = BTreeMap::new();
+            let mut metrics = serde_json::Map::new();
+            let mut namespaced = serde_json::Map::new();
+            let mut nested = serde_json::Map::new();
+            let mut labels = self.labels;
+
+            // fields recorded on the event take precedence over those lifted from its message
+            let mut lifted = BTreeMap::new();
+
+            if let Some(serde_json::Value::String(message)) =
+                self.values.get("message").filter(|_| self.logfmt_messages)
+            {
+                for (key, value) in logfmt::key_values(message) {
+                    if !self.values.contains_key(key.as_str()) {
+                        lifted.insert(Cow::Owned(key), value);
+                    }
+                }
+            }
+
+            if let Some(serde_json::Value::String(message)) = self
+                .values
+                .get("message")
+                .filter(|_| self.message_templates)
+            {
+                self.serializer
+                    .serialize_entry("messageTemplate", &template::template(message))?;
+            }
+
+            let error_debugs = self
+                .error_debugs
+                .into_iter()
+                .map(|(key, debug)| (Cow::Owned(format!("{}_debug", key)), debug.into()));
+
+            for (key, mut value) in self
+                .values
+                .into_iter()
+                .map(|(key, value)| (Cow::Borrowed(key), value))
+                .chain(error_debugs)
+                .chain(lifted)
+                .filter_map(|(key, value)| match self.field_transformer {
+                    Some(field_transformer) => field_transformer
+                        .transform(&key, value)
+                        .map(|(key, value)| (Cow::Owned(key), value)),
+                    None => Some((key, value)),
+                })
+            {
+                if let Some(max_value_length) = self.max_value_length {
+                    truncate_strings(&mut value, max_value_length);
+                }
 
-            for (key, value) in self.values {
                 let mut key_segments = key.splitn(2, '.');
 
                 match (key_segments.next(), key_segments.next()) {
                     (Some("http_request"), Some(request_key)) => {
                         http_request.insert(request_key.to_camel_case(), value);
                     }
+                    // structured requests, e.g. recorded through `valuable`
+                    (Some("http_request"), None) if value.is_object() => {
+                        if let serde_json::Value::Object(request) = value {
+                            http_request.extend(request);
+                        }
+                    }
+                    (Some("metric"), Some(metric_key)) if self.metric_fields => {
+                        metrics.insert(metric_key.to_camel_case(), value);
+                    }
                     (Some("labels"), Some(label_key)) => {
                         let value = match value {
                             serde_json::Value::String(value) => value,
@@ -70,12 +496,41 @@ where
                         self.serializer
                             .serialize_entry("logging.googleapis.com/insertId", &value)?;
                     }
-                    (Some(key), None) => self
-                        .serializer
-                        .serialize_entry(&key.to_camel_case(), &value)?,
-                    _ => self
-                        .serializer
-                        .serialize_entry(&key.to_camel_case(), &value)?,
+                    // raw names are checked before dotted keys are transformed, which would mangle
+                    // `logging.googleapis.com/*` keys past the check
+                    _ if self.field_namespace.is_none() && is_reserved(&key) => {
+                        self.serializer.serialize_entry(
+                            &format!("{}{}", self.reserved_key_prefix, key),
+                            &value,
+                        )?;
+                    }
+                    _ => {
+                        let key = self.dotted_keys.key(&key);
+
+                        if self.field_namespace.is_some() && key != "message" {
+                            self.dotted_keys.insert(&mut namespaced, key, value);
+                        } else if is_reserved(&key) {
+                            self.serializer.serialize_entry(
+                                &format!("{}{}", self.reserved_key_prefix, key),
+                                &value,
+                            )?;
+                        } else if self.dotted_keys == DottedKeys::Nest {
+                            self.dotted_keys.insert(&mut nested, key, value);
+                        } else {
+                            self.serializer.serialize_entry(&key, &value)?;
+                        }
+                    }
+                }
+            }
+
+            for (key, value) in nested {
+                self.serializer.serialize_entry(&key, &value)?;
+            }
+
+            if let Some(field_namespace) = self.field_namespace {
+                if !namespaced.is_empty() {
+                    self.serializer
+                        .serialize_entry(field_namespace, &namespaced)?;
                 }
             }
 
@@ -84,6 +539,10 @@ where
                     .serialize_entry("httpRequest", &http_request)?;
             }
 
+            if !metrics.is_empty() {
+                self.serializer.serialize_entry("metrics", &metrics)?;
+            }
+
             if !labels.is_empty() {
                 self.serializer
                     .serialize_entry("logging.googleapis.com/labels", &labels)?;
@@ -104,6 +563,17 @@ impl<'a, S> Visit for Visitor<'a, S>
 where
     S: SerializeMap,
 {
+    fn record_f64(&mut self, field: &Field, value: f64) {
+        // floats are written as their Debug representation, except for the metric fields that
+        // distributions extract numbers from
+        if self.metric_fields && field.name().starts_with("metric.") {
+            self.values
+                .insert(field.name(), serde_json::Value::from(value));
+        } else {
+            self.record_debug(field, &value);
+        }
+    }
+
     fn record_i64(&mut self, field: &Field, value: i64) {
         self.values
             .insert(field.name(), serde_json::Value::from(value));
@@ -125,10 +595,45 @@ where
     }
 
     fn record_debug(&mut self, field: &Field, value: &dyn fmt::Debug) {
-        self.values.insert(
-            field.name(),
-            serde_json::Value::from(format!("{:?}", value)),
-        );
+        // severities are parsed as the severity filter parses them, without their Debug quoting
+        if field.name() == "severity" {
+            let severity = LogSeverity::from_debug(value).to_string();
+            self.values
+                .insert(field.name(), serde_json::Value::from(severity));
+            return;
+        }
+
+        // httpRequest.latency must be written as fractional seconds
+        let duration_format = match field.name() {
+            "http_request.latency" => DurationFormat::Seconds,
+            _ => self.duration_format,
+        };
+
+        let value = fields::debug_value(value, duration_format, self.debug_limits);
+
+        if let Some(error) = RecordedError::from_value(&value) {
+            return self.record_error_parts(field.name(), error);
+        }
+
+        if self.error_format == ErrorFormat::Display && is_error_field(field.name()) {
+            self.debug_errors.push(field.name());
+        }
+
+        self.values.insert(field.name(), value);
+    }
+
+    fn record_error(&mut self, field: &Field, value: &(dyn Error + 'static)) {
+        let chain = iter::successors(value.source(), |error| (*error).source())
+            .map(ToString::to_string)
+            .collect();
+
+        let error = RecordedError {
+            display: value.to_string(),
+            debug: format!("{:?}", value),
+            chain,
+        };
+
+        self.record_error_parts(field.name(), error);
     }
 
     #[cfg(all(tracing_unstable, feature = "valuable"))]
@@ -139,6 +644,47 @@ where
     }
 }
 
+/// Whether a key is interpreted by Cloud Logging or written by the formatter itself, such that a
+/// user field of the same name could spoof or clobber it
+fn is_reserved(key: &str) -> bool {
+    key.starts_with("logging.googleapis.com/")
+        || matches!(key, "severity" | "time" | "target" | "span" | "httpRequest")
+}
+
+/// Whether a field recorded with `?` is named as holding an error
+fn is_error_field(name: &str) -> bool {
+    matches!(name, "error" | "err") || name.ends_with("_error")
+}
+
+/// Truncate every string within a value to at most `max_length` bytes, on a character boundary,
+/// marking each truncated string with the number of bytes removed
+fn truncate_strings(value: &mut serde_json::Value, max_length: usize) {
+    match value {
+        serde_json::Value::String(string) if string.len() > max_length => {
+            let mut end = max_length;
+
+            while !string.is_char_boundary(end) {
+                end -= 1;
+            }
+
+            let truncated = string.len() - end;
+            string.truncate(end);
+            string.push_str(&format!("…[truncated {} bytes]", truncated));
+        }
+        serde_json::Value::Array(values) => {
+            for value in values {
+                truncate_strings(value, max_length);
+            }
+        }
+        serde_json::Value::Object(values) => {
+            for value in values.values_mut() {
+                truncate_strings(value, max_length);
+            }
+        }
+        _ => (),
+    }
+}
+
 impl<'a, S> fmt::Debug for Visitor<'a, S>
 where
     S: SerializeMap,
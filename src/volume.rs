@@ -0,0 +1,110 @@
+use crate::{entry::LogEntry, google::LogSeverity};
+use serde::Serialize;
+use std::{
+    collections::BTreeMap,
+    sync::Mutex,
+    time::{Duration, Instant},
+};
+
+/// What became of an entry, as counted in volume summaries
+#[derive(Clone, Copy, Debug)]
+pub(crate) enum Outcome {
+    /// Written to the Layer's writer
+    Written,
+    /// Dropped by adaptive sampling
+    Sampled,
+    /// Dropped by the health check filter beyond its sample rate
+    RateLimited,
+    /// Buffered by tail buffering and then discarded
+    Dropped,
+}
+
+/// Counters of the entries of a single severity
+#[derive(Clone, Copy, Debug, Default, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct Counts {
+    written: u64,
+    sampled: u64,
+    rate_limited: u64,
+    dropped: u64,
+}
+
+/// Counters of entries by severity and outcome, summarized once per interval
+#[derive(Debug)]
+pub(crate) struct VolumeSummaries {
+    interval: Duration,
+    state: Mutex<(Instant, BTreeMap<LogSeverity, Counts>)>,
+}
+
+/// Entry counts over an interval, reported as a NOTICE entry
+#[derive(Debug)]
+pub(crate) struct Summary {
+    elapsed: Duration,
+    counts: BTreeMap<LogSeverity, Counts>,
+}
+
+impl VolumeSummaries {
+    pub(crate) fn new(interval: Duration) -> Self {
+        Self {
+            interval,
+            state: Mutex::new((Instant::now(), BTreeMap::new())),
+        }
+    }
+
+    /// Count entries, returning the summary of the last interval once it has passed
+    pub(crate) fn record(
+        &self,
+        severity: LogSeverity,
+        outcome: Outcome,
+        entries: u64,
+    ) -> Option<Summary> {
+        let mut state = match self.state.lock() {
+            Ok(state) => state,
+            Err(poisoned) => poisoned.into_inner(),
+        };
+
+        let (since, counts) = &mut *state;
+        let severity_counts = counts.entry(severity).or_default();
+
+        match outcome {
+            Outcome::Written => severity_counts.written += entries,
+            Outcome::Sampled => severity_counts.sampled += entries,
+            Outcome::RateLimited => severity_counts.rate_limited += entries,
+            Outcome::Dropped => severity_counts.dropped += entries,
+        }
+
+        let elapsed = since.elapsed();
+
+        if elapsed < self.interval {
+            return None;
+        }
+
+        *since = Instant::now();
+
+        Some(Summary {
+            elapsed,
+            counts: std::mem::take(counts),
+        })
+    }
+}
+
+impl Summary {
+    /// The NOTICE entry reporting the summary
+    pub(crate) fn entry(&self) -> LogEntry {
+        let total = self
+            .counts
+            .values()
+            .map(|counts| counts.written + counts.sampled + counts.rate_limited + counts.dropped)
+            .sum::<u64>();
+
+        let message = format!(
+            "{} entries in the last {:.1}s",
+            total,
+            self.elapsed.as_secs_f64()
+        );
+
+        LogEntry::report(module_path!(), LogSeverity::Notice, message)
+            .with_field("intervalSeconds", self.elapsed.as_secs_f64())
+            .with_field("entries", &self.counts)
+    }
+}
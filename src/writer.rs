@@ -1,39 +1,248 @@
 use std::{
-    fmt::{Formatter, Write},
+    cell::RefCell,
+    collections::BTreeMap,
+    fmt::Formatter,
     io,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc,
+    },
 };
+use tracing_core::Metadata;
+use tracing_subscriber::fmt::{writer::BoxMakeWriter, MakeWriter};
 
-/// Utility newtype for converting between fmt::Write and io::Write
-// https://docs.rs/tracing-subscriber/latest/src/tracing_subscriber/fmt/writer.rs.html
-pub(crate) struct WriteAdaptor<'a> {
-    fmt_write: &'a mut dyn Write,
+/// Capacity of the entry buffer kept on a thread between entries, so that an oversized entry
+/// doesn't pin its memory for the lifetime of the thread
+const MAX_RETAINED_CAPACITY: usize = 64 * 1024;
+
+thread_local! {
+    /// Buffer that entries are serialized into, reused across the entries of a thread
+    static ENTRY_BUFFER: RefCell<Vec<u8>> = const { RefCell::new(Vec::new()) };
+}
+
+/// Run `callback` with an empty buffer to serialize an entry into, reusing the thread's buffer
+/// unless it's already in use, e.g. by a formatter nested within another
+pub(crate) fn with_entry_buffer<T>(callback: impl FnOnce(&mut Vec<u8>) -> T) -> T {
+    ENTRY_BUFFER.with(|buffer| match buffer.try_borrow_mut() {
+        Ok(mut buffer) => {
+            buffer.clear();
+            let result = callback(&mut buffer);
+
+            if buffer.capacity() > MAX_RETAINED_CAPACITY {
+                buffer.clear();
+                buffer.shrink_to(MAX_RETAINED_CAPACITY);
+            }
+
+            result
+        }
+        Err(_) => callback(&mut Vec::new()),
+    })
+}
+
+/// Writer that duplicates every entry to two writers, such as stdout for the logging agent and
+/// an exporter. Each writer fails independently: an entry only fails once both fail to write it.
+#[derive(Clone, Debug)]
+pub struct Tee<A, B> {
+    first: A,
+    second: B,
 }
 
-impl<'a> WriteAdaptor<'a> {
-    pub(crate) fn new(fmt_write: &'a mut dyn Write) -> Self {
-        Self { fmt_write }
+impl<A, B> Tee<A, B> {
+    /// Create a writer duplicating entries to `first` and `second`
+    pub fn new(first: A, second: B) -> Self {
+        Self { first, second }
+    }
+}
+
+impl<'a, A, B> MakeWriter<'a> for Tee<A, B>
+where
+    A: MakeWriter<'a>,
+    B: MakeWriter<'a>,
+{
+    type Writer = Tee<A::Writer, B::Writer>;
+
+    fn make_writer(&'a self) -> Self::Writer {
+        Tee::new(self.first.make_writer(), self.second.make_writer())
+    }
+
+    fn make_writer_for(&'a self, meta: &Metadata<'_>) -> Self::Writer {
+        Tee::new(
+            self.first.make_writer_for(meta),
+            self.second.make_writer_for(meta),
+        )
     }
 }
 
-impl<'a> io::Write for WriteAdaptor<'a> {
+impl<A, B> io::Write for Tee<A, B>
+where
+    A: io::Write,
+    B: io::Write,
+{
     fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
-        let s =
-            std::str::from_utf8(buf).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+        // write whole entries to both, so that neither sees a partial entry the other doesn't
+        self.write_all(buf)?;
+        Ok(buf.len())
+    }
+
+    fn write_all(&mut self, buf: &[u8]) -> io::Result<()> {
+        let first = self.first.write_all(buf);
+        let second = self.second.write_all(buf);
+        first.or(second)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        let first = self.first.flush();
+        let second = self.second.flush();
+        first.or(second)
+    }
+}
+
+/// Writer that duplicates every entry to any number of named destinations, such as stdout, a
+/// file, and a socket. Each destination fails independently: entries one fails to write are
+/// counted as dropped for it alone, and an entry only fails once every destination fails to write
+/// it. Destinations that may stall, such as sockets, should be wrapped in a
+/// [`NonBlocking`](crate::NonBlocking) writer so that they don't hold up the others.
+#[derive(Clone, Debug, Default)]
+pub struct Fanout {
+    destinations: Vec<Arc<Destination>>,
+}
+
+#[derive(Debug)]
+struct Destination {
+    name: String,
+    make_writer: BoxMakeWriter,
+    dropped: AtomicU64,
+}
+
+impl Fanout {
+    /// Create a writer without any destinations
+    pub fn new() -> Self {
+        Self::default()
+    }
 
-        self.fmt_write
-            .write_str(s)
-            .map_err(|e| io::Error::new(io::ErrorKind::Other, e))?;
+    /// Adds a destination that entries are duplicated to, named for its drop counter
+    pub fn with_destination<M>(mut self, name: impl Into<String>, make_writer: M) -> Self
+    where
+        M: for<'writer> MakeWriter<'writer> + Send + Sync + 'static,
+    {
+        self.destinations.push(Arc::new(Destination {
+            name: name.into(),
+            make_writer: BoxMakeWriter::new(make_writer),
+            dropped: AtomicU64::new(0),
+        }));
+        self
+    }
+
+    /// Number of entries that each destination failed to write, by name, shared by every clone
+    /// of the writer
+    pub fn dropped_entries(&self) -> BTreeMap<String, u64> {
+        self.destinations
+            .iter()
+            .map(|destination| {
+                (
+                    destination.name.clone(),
+                    destination.dropped.load(Ordering::Relaxed),
+                )
+            })
+            .collect()
+    }
+}
+
+impl<'a> MakeWriter<'a> for Fanout {
+    type Writer = FanoutWriter<'a>;
+
+    fn make_writer(&'a self) -> Self::Writer {
+        FanoutWriter {
+            writers: self
+                .destinations
+                .iter()
+                .map(|destination| DestinationWriter {
+                    writer: destination.make_writer.make_writer(),
+                    dropped: &destination.dropped,
+                    failed: false,
+                })
+                .collect(),
+        }
+    }
+
+    fn make_writer_for(&'a self, meta: &Metadata<'_>) -> Self::Writer {
+        FanoutWriter {
+            writers: self
+                .destinations
+                .iter()
+                .map(|destination| DestinationWriter {
+                    writer: destination.make_writer.make_writer_for(meta),
+                    dropped: &destination.dropped,
+                    failed: false,
+                })
+                .collect(),
+        }
+    }
+}
+
+/// Writer of a single entry to every destination of a [`Fanout`]
+pub struct FanoutWriter<'a> {
+    writers: Vec<DestinationWriter<'a>>,
+}
+
+struct DestinationWriter<'a> {
+    writer: Box<dyn io::Write + 'a>,
+    dropped: &'a AtomicU64,
+    failed: bool,
+}
+
+impl DestinationWriter<'_> {
+    /// Run a write, counting the entry as dropped the first time one fails
+    fn attempt(
+        &mut self,
+        write: impl FnOnce(&mut dyn io::Write) -> io::Result<()>,
+    ) -> io::Result<()> {
+        let result = write(&mut self.writer);
+
+        if result.is_err() && !self.failed {
+            self.failed = true;
+            self.dropped.fetch_add(1, Ordering::Relaxed);
+        }
+
+        result
+    }
+}
+
+impl FanoutWriter<'_> {
+    /// Run a write on every destination, failing only when all of them fail
+    fn each(&mut self, write: impl Fn(&mut dyn io::Write) -> io::Result<()>) -> io::Result<()> {
+        self.writers
+            .iter_mut()
+            .map(|writer| writer.attempt(&write))
+            .fold(
+                None,
+                |result: Option<io::Result<()>>, attempt| match result {
+                    Some(Ok(())) => Some(Ok(())),
+                    _ => Some(attempt),
+                },
+            )
+            .unwrap_or(Ok(()))
+    }
+}
+
+impl io::Write for FanoutWriter<'_> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        // write whole entries to every destination, so that none sees a partial entry
+        self.write_all(buf)?;
+        Ok(buf.len())
+    }
 
-        Ok(s.as_bytes().len())
+    fn write_all(&mut self, buf: &[u8]) -> io::Result<()> {
+        self.each(|writer| writer.write_all(buf))
     }
 
     fn flush(&mut self) -> io::Result<()> {
-        Ok(())
+        self.each(|writer| writer.flush())
     }
 }
 
-impl<'a> std::fmt::Debug for WriteAdaptor<'a> {
+impl std::fmt::Debug for FanoutWriter<'_> {
     fn fmt(&self, formatter: &mut Formatter<'_>) -> std::fmt::Result {
-        formatter.pad("WriteAdaptor { .. }")
+        formatter.pad("FanoutWriter { .. }")
     }
 }
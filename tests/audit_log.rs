@@ -0,0 +1,206 @@
+use helpers::run_with_tracing_layer;
+use serde_json::{json, Value};
+use tracing_stackdriver_cw::AuditLog;
+
+mod helpers;
+
+#[test]
+fn shapes_audit_events_into_audit_records() {
+    let layer = tracing_stackdriver_cw::layer().with_audit_log(AuditLog::default());
+    let events = run_with_tracing_layer::<Value>(layer, || {
+        let span = tracing::info_span!("request", http_request.request_url = "/orders/7");
+        let _span = span.enter();
+
+        tracing::info!(
+            target: "audit::orders",
+            actor = "user:42",
+            action = "orders.cancel",
+            resource = "orders/7",
+            outcome = "success",
+            "order cancelled"
+        );
+        tracing::info!(
+            audit = true,
+            actor = "user:42",
+            action = "orders.view",
+            "order viewed"
+        );
+        tracing::info!(actor = "user:42", "not audited");
+    })
+    .expect("Error converting test buffer to JSON");
+
+    let cancelled = &events[0];
+    assert_eq!(cancelled["message"], "order cancelled");
+    assert_eq!(cancelled["logName"], "audit");
+    assert_eq!(
+        cancelled["audit"],
+        json!({
+            "actor": "user:42",
+            "action": "orders.cancel",
+            "resource": "orders/7",
+            "outcome": "success",
+            "sequence": 1,
+        })
+    );
+    assert_eq!(cancelled["httpRequest"]["requestUrl"], "/orders/7");
+    assert!(cancelled.get("actor").is_none());
+
+    let viewed = &events[1];
+    assert_eq!(viewed["audit"]["resource"], Value::Null);
+    assert_eq!(viewed["audit"]["outcome"], "unknown");
+    assert_eq!(viewed["audit"]["sequence"], 2);
+    assert_ne!(
+        viewed["logging.googleapis.com/insertId"],
+        cancelled["logging.googleapis.com/insertId"]
+    );
+
+    let unaudited = &events[2];
+    assert_eq!(unaudited["actor"], "user:42");
+    assert!(unaudited.get("audit").is_none());
+    assert!(unaudited.get("logName").is_none());
+}
+
+#[test]
+fn writes_audit_entries_to_the_configured_log() {
+    let layer = tracing_stackdriver_cw::layer().with_audit_log(AuditLog {
+        targets: vec!["security".to_string()],
+        log_name: "security-audit".to_string(),
+    });
+    let events = run_with_tracing_layer::<Value>(layer, || {
+        tracing::warn!(target: "security", actor = "user:7", action = "login", outcome = "denied");
+        tracing::info!(target: "security_scanner", "scanned");
+    })
+    .expect("Error converting test buffer to JSON");
+
+    assert_eq!(events[0]["logName"], "security-audit");
+    assert_eq!(events[0]["severity"], "WARNING");
+    assert_eq!(events[0]["audit"]["outcome"], "denied");
+    assert!(events[0]["logging.googleapis.com/insertId"]
+        .as_str()
+        .unwrap()
+        .starts_with("audit-"));
+    assert!(events[1].get("audit").is_none());
+}
+
+#[cfg(feature = "audit-chain")]
+#[test]
+fn chains_audit_entries_by_hash() {
+    use tracing_stackdriver_cw::{verify_audit_chain, AuditChainError};
+
+    let layer = tracing_stackdriver_cw::layer()
+        .with_audit_log(AuditLog::default())
+        .with_audit_hash_chain(true);
+    let events = run_with_tracing_layer::<Value>(layer, || {
+        for order in 0..3 {
+            tracing::info!(target: "audit", actor = "user:42", action = "orders.cancel", order);
+        }
+
+        tracing::info!("not audited");
+    })
+    .expect("Error converting test buffer to JSON");
+
+    assert_eq!(events[0]["audit"]["prevHash"], "0".repeat(64));
+    assert_eq!(
+        events[1]["audit"]["prevHash"],
+        events[0]["audit"]["entryHash"]
+    );
+    assert!(events[3].get("audit").is_none());
+
+    let lines: Vec<String> = events[..3].iter().map(Value::to_string).collect();
+    assert_eq!(verify_audit_chain(lines.iter().map(String::as_str)), Ok(()));
+
+    let altered = lines[1].replace("user:42", "user:43");
+    assert_eq!(
+        verify_audit_chain([lines[0].as_str(), altered.as_str(), lines[2].as_str()]),
+        Err(AuditChainError::Altered(1))
+    );
+    assert_eq!(
+        verify_audit_chain([lines[0].as_str(), lines[2].as_str()]),
+        Err(AuditChainError::Broken(1))
+    );
+}
+
+#[cfg(feature = "audit-chain")]
+#[test]
+fn chains_audit_entries_written_concurrently() {
+    use std::{
+        io,
+        sync::{Arc, Mutex},
+        thread,
+    };
+    use tracing_stackdriver_cw::verify_audit_chain;
+    use tracing_subscriber::{layer::SubscriberExt, Registry};
+
+    struct YieldingWriter(Arc<Mutex<Vec<u8>>>);
+
+    impl io::Write for YieldingWriter {
+        fn write(&mut self, buffer: &[u8]) -> io::Result<usize> {
+            thread::yield_now();
+            self.0.lock().unwrap().write(buffer)
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    let buffer = Arc::new(Mutex::new(vec![]));
+    let shared = buffer.clone();
+    let layer = tracing_stackdriver_cw::layer()
+        .with_audit_log(AuditLog::default())
+        .with_audit_hash_chain(true)
+        .with_writer(move || YieldingWriter(shared.clone()));
+    let dispatch = tracing::Dispatch::new(Registry::default().with(layer));
+
+    let threads: Vec<_> = (0..8)
+        .map(|worker| {
+            let dispatch = dispatch.clone();
+
+            thread::spawn(move || {
+                tracing::dispatcher::with_default(&dispatch, || {
+                    for order in 0..50 {
+                        tracing::info!(target: "audit", actor = worker, action = "orders.cancel", order);
+                    }
+                })
+            })
+        })
+        .collect();
+
+    threads
+        .into_iter()
+        .for_each(|thread| thread.join().unwrap());
+
+    let buffer = buffer.lock().unwrap();
+    let lines: Vec<&str> = std::str::from_utf8(&buffer).unwrap().lines().collect();
+
+    assert_eq!(lines.len(), 400);
+    assert_eq!(verify_audit_chain(lines), Ok(()));
+}
+
+#[cfg(feature = "audit-chain")]
+#[test]
+fn chains_the_written_lines_of_audit_entries() {
+    use tracing_stackdriver_cw::{verify_audit_chain, LineLimit, TailBuffering};
+
+    let layer = tracing_stackdriver_cw::layer()
+        .with_audit_log(AuditLog::default())
+        .with_audit_hash_chain(true)
+        .with_line_limit(LineLimit::Split(1_000))
+        .with_tail_buffering(TailBuffering::default());
+    let events = run_with_tracing_layer::<Value>(layer, || {
+        tracing::info!(target: "audit", action = "orders.cancel", "cancelled");
+
+        tracing::info_span!("request").in_scope(|| {
+            tracing::debug!(target: "audit", action = "orders.view", "buffered and dropped");
+        });
+
+        tracing::info!(target: "audit", action = "orders.export", "{}", "x".repeat(3_000));
+    })
+    .expect("Error converting test buffer to JSON");
+
+    assert!(events.len() > 2);
+    assert!(events.iter().all(|event| event.to_string().len() <= 1_000));
+
+    let lines: Vec<String> = events.iter().map(Value::to_string).collect();
+    assert_eq!(verify_audit_chain(lines.iter().map(String::as_str)), Ok(()));
+}
@@ -0,0 +1,23 @@
+use std::env;
+use tracing_stackdriver_cw::LogFormat;
+
+#[test]
+fn honors_forced_log_formats() {
+    env::set_var("LOG_FORMAT", "pretty");
+    assert_eq!(LogFormat::detect(), LogFormat::Pretty);
+
+    env::set_var("LOG_FORMAT", "Compact");
+    assert_eq!(LogFormat::detect(), LogFormat::Compact);
+
+    env::set_var("K_SERVICE", "my-service");
+    env::set_var("LOG_FORMAT", "compact");
+    assert_eq!(LogFormat::detect(), LogFormat::Compact);
+
+    // unknown formats fall back to detection
+    env::set_var("LOG_FORMAT", "yaml");
+    assert_eq!(LogFormat::detect(), LogFormat::Json);
+
+    env::remove_var("K_SERVICE");
+    env::set_var("LOG_FORMAT", "json");
+    assert_eq!(LogFormat::detect(), LogFormat::Json);
+}
@@ -0,0 +1,123 @@
+use std::time::Duration;
+use tracing_stackdriver_cw::{BuildError, CloudTraceConfiguration};
+use tracing_subscriber::{fmt::time::SystemTime, Registry};
+
+fn layer() -> tracing_stackdriver_cw::Layer<Registry> {
+    tracing_stackdriver_cw::layer()
+}
+
+#[test]
+fn accepts_valid_configurations() {
+    let layer = layer()
+        .with_cloud_trace(CloudTraceConfiguration {
+            project_id: "example.com:my-project".to_string(),
+        })
+        .with_timer(SystemTime)
+        .with_field_namespace("fields")
+        .with_console_urls(Duration::from_secs(3600))
+        .with_span_close_entries(true)
+        .with_span_error_summaries(true)
+        .with_span_event_counts(true)
+        .build();
+
+    assert!(layer.is_ok());
+    assert!(tracing_stackdriver_cw::strict_test::<Registry>()
+        .build()
+        .is_ok());
+}
+
+#[test]
+fn rejects_invalid_configurations() {
+    let project_id = |project_id: &str| {
+        layer()
+            .with_cloud_trace(CloudTraceConfiguration {
+                project_id: project_id.to_string(),
+            })
+            .build()
+            .err()
+    };
+
+    assert_eq!(
+        project_id("My_Project"),
+        Some(BuildError::ProjectId("My_Project".to_string()))
+    );
+    assert!(project_id("short").is_some());
+    assert!(project_id("my-project-").is_some());
+
+    assert_eq!(
+        layer()
+            .with_legacy_agent_fields(true)
+            .with_timer(SystemTime)
+            .build()
+            .err(),
+        Some(BuildError::Conflict(
+            "with_timer",
+            "with_legacy_agent_fields"
+        ))
+    );
+    assert_eq!(
+        layer().with_strict_validation(true).build().err(),
+        Some(BuildError::StrictRawEntries)
+    );
+    assert_eq!(
+        layer().with_field_namespace("").build().err(),
+        Some(BuildError::EmptyFieldNamespace)
+    );
+    assert_eq!(
+        layer().with_reserved_key_prefix("").build().err(),
+        Some(BuildError::EmptyReservedKeyPrefix)
+    );
+}
+
+#[test]
+fn rejects_span_error_summaries_without_close_entries() {
+    assert_eq!(
+        layer().with_span_error_summaries(true).build().err(),
+        Some(BuildError::Requires(
+            "with_span_error_summaries",
+            "with_span_close_entries"
+        ))
+    );
+}
+
+#[test]
+fn rejects_span_event_counts_without_close_entries() {
+    assert_eq!(
+        layer().with_span_event_counts(true).build().err(),
+        Some(BuildError::Requires(
+            "with_span_event_counts",
+            "with_span_close_entries"
+        ))
+    );
+}
+
+#[test]
+fn rejects_console_urls_without_cloud_trace() {
+    assert_eq!(
+        layer()
+            .with_console_urls(Duration::from_secs(3600))
+            .build()
+            .err(),
+        Some(BuildError::Requires(
+            "with_console_urls",
+            "with_cloud_trace"
+        ))
+    );
+}
+
+#[cfg(feature = "audit-chain")]
+#[test]
+fn rejects_audit_hash_chains_without_audit_logs() {
+    assert_eq!(
+        layer().with_audit_hash_chain(true).build().err(),
+        Some(BuildError::Requires(
+            "with_audit_hash_chain",
+            "with_audit_log"
+        ))
+    );
+    assert!(layer()
+        .with_audit_log(tracing_stackdriver_cw::AuditLog::default())
+        .with_audit_hash_chain(true)
+        .build()
+        .is_ok());
+}
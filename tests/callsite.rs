@@ -0,0 +1,33 @@
+use helpers::run_with_tracing_layer;
+use serde_json::Value;
+
+mod helpers;
+
+#[test]
+fn fingerprints_callsites() {
+    let layer = tracing_stackdriver_cw::layer().with_callsite_fingerprints(true);
+    let events = run_with_tracing_layer::<Value>(layer, || {
+        for user in [1, 2] {
+            tracing::info!("user {} logged in", user);
+        }
+
+        tracing::info!("user {} logged in", 3);
+    })
+    .expect("Error converting test buffer to JSON");
+
+    let callsite = events[0]["callsite"].as_str().unwrap();
+    assert_eq!(callsite.len(), 16);
+    assert!(callsite.chars().all(|char| char.is_ascii_hexdigit()));
+    assert_eq!(events[1]["callsite"], callsite);
+    assert_ne!(events[2]["callsite"], callsite);
+}
+
+#[test]
+fn omits_callsites_by_default() {
+    let events = run_with_tracing_layer::<Value>(tracing_stackdriver_cw::layer(), || {
+        tracing::info!("hello");
+    })
+    .expect("Error converting test buffer to JSON");
+
+    assert!(events[0].get("callsite").is_none());
+}
@@ -0,0 +1,75 @@
+use helpers::run_with_tracing_layer;
+use serde_json::Value;
+use tracing_stackdriver_cw::CanonicalEntries;
+
+mod helpers;
+
+#[test]
+fn folds_info_events_into_canonical_entries() {
+    let layer = tracing_stackdriver_cw::layer().with_canonical_entries(CanonicalEntries::default());
+    let events = run_with_tracing_layer::<Value>(layer, || {
+        let span = tracing::info_span!(
+            "request",
+            http_request.request_method = "GET",
+            http_request.status = tracing::field::Empty,
+        );
+        let _span = span.enter();
+
+        tracing::info!(user_id = 7, "authenticated");
+        tracing::info!(cache_hits = 2, cart_items = 3, "loaded cart");
+        tracing::info!(cache_hits = 5, "loaded prices");
+        tracing::warn!("slow upstream");
+        tracing::info_span!("inner").in_scope(|| tracing::info!(db_calls = 4, "queried"));
+
+        span.record("http_request.status", 200);
+    })
+    .expect("Error converting test buffer to JSON");
+
+    assert_eq!(events.len(), 2);
+    assert_eq!(events[0]["message"], "slow upstream");
+
+    let canonical = &events[1];
+    assert_eq!(canonical["message"], "close");
+    assert_eq!(canonical["canonicalEvents"], 4);
+    assert_eq!(canonical["userId"], 7);
+    assert_eq!(canonical["cacheHits"], 5);
+    assert_eq!(canonical["cartItems"], 3);
+    assert_eq!(canonical["dbCalls"], 4);
+    assert_eq!(canonical["httpRequest"]["requestMethod"], "GET");
+    assert_eq!(canonical["httpRequest"]["status"], 200);
+}
+
+#[test]
+fn keeps_events_outside_of_request_spans() {
+    let layer = tracing_stackdriver_cw::layer().with_canonical_entries(CanonicalEntries::default());
+    let events = run_with_tracing_layer::<Value>(layer, || {
+        let span = tracing::info_span!("startup");
+        let _span = span.enter();
+        tracing::info!(port = 8080, "listening");
+    })
+    .expect("Error converting test buffer to JSON");
+
+    assert_eq!(events.len(), 1);
+    assert_eq!(events[0]["message"], "listening");
+}
+
+#[test]
+fn samples_folded_events() {
+    let layer =
+        tracing_stackdriver_cw::layer().with_canonical_entries(CanonicalEntries { sample_rate: 2 });
+    let events = run_with_tracing_layer::<Value>(layer, || {
+        let span = tracing::info_span!("request", http_request.request_url = "/orders");
+        let _span = span.enter();
+
+        for step in 0..4 {
+            tracing::info!(step, "step");
+        }
+    })
+    .expect("Error converting test buffer to JSON");
+
+    assert_eq!(events.len(), 3);
+    assert_eq!(events[0]["step"], 0);
+    assert_eq!(events[1]["step"], 2);
+    assert_eq!(events[2]["canonicalEvents"], 4);
+    assert_eq!(events[2]["step"], 3);
+}
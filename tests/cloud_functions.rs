@@ -0,0 +1,174 @@
+use helpers::{run_with_tracing, MockWriter};
+use mocks::MockDefaultEvent;
+use serde_json::Value;
+use std::sync::{Arc, Mutex};
+use tracing_stackdriver_cw::{LogSeverity, SeverityFilter};
+use tracing_subscriber::{filter::LevelFilter, layer::SubscriberExt, Layer, Registry};
+
+mod helpers;
+mod mocks;
+
+#[test]
+fn finds_execution_id_header() {
+    let headers = [
+        ("content-type", "text/plain"),
+        ("function-execution-id", "abc123"),
+    ];
+    let execution_id = tracing_stackdriver_cw::function_execution_id(headers);
+    assert_eq!(execution_id.as_deref(), Some("abc123"));
+}
+
+#[test]
+fn labels_entries_with_execution_id() {
+    let headers = [("Function-Execution-Id", "abc123")];
+
+    let events = run_with_tracing::<MockDefaultEvent>(|| {
+        tracing_stackdriver_cw::instrument_function_execution(headers, || {
+            let span = tracing::info_span!("handler");
+            let _guard = span.enter();
+            tracing::info!(labels.foo = "bar", "handling request");
+        })
+    })
+    .expect("Error converting test buffer to JSON");
+
+    let event = events.first().expect("No event heard");
+    assert_eq!(
+        event.labels.get("execution_id"),
+        Some(&"abc123".to_string())
+    );
+    assert_eq!(event.labels.get("foo"), Some(&"bar".to_string()));
+}
+
+#[test]
+fn labels_entries_with_execution_id_under_level_filters() {
+    let buffer = Arc::new(Mutex::new(vec![]));
+    let shared = buffer.clone();
+    let stackdriver = tracing_stackdriver_cw::layer()
+        .with_writer(move || MockWriter(shared.clone()))
+        .with_filter(SeverityFilter::new(LogSeverity::Warning));
+    let subscriber = Registry::default().with(stackdriver);
+
+    tracing::subscriber::with_default(subscriber, || {
+        let headers = [("Function-Execution-Id", "abc123")];
+        tracing_stackdriver_cw::instrument_function_execution(headers, || {
+            tracing::warn!("handling request");
+        })
+    });
+
+    let buffer = buffer.lock().unwrap();
+    let event: MockDefaultEvent =
+        serde_json::from_slice(&buffer).expect("Error converting test buffer to JSON");
+    assert_eq!(
+        event.labels.get("execution_id"),
+        Some(&"abc123".to_string())
+    );
+}
+
+#[test]
+fn labels_entries_with_execution_id_when_filters_disable_the_span() {
+    let buffer = Arc::new(Mutex::new(vec![]));
+    let shared = buffer.clone();
+    let stackdriver = tracing_stackdriver_cw::layer()
+        .with_writer(move || MockWriter(shared.clone()))
+        .with_filter(LevelFilter::WARN);
+    let subscriber = Registry::default().with(stackdriver);
+
+    tracing::subscriber::with_default(subscriber, || {
+        let headers = [("Function-Execution-Id", "abc123")];
+        tracing_stackdriver_cw::instrument_function_execution(headers, || {
+            tracing::warn!("handling request");
+        });
+        tracing::warn!("between requests");
+    });
+
+    let buffer = buffer.lock().unwrap();
+    let events: Vec<MockDefaultEvent> = serde_json::Deserializer::from_slice(&buffer)
+        .into_iter()
+        .collect::<Result<_, _>>()
+        .expect("Error converting test buffer to JSON");
+    assert_eq!(
+        events[0].labels.get("execution_id"),
+        Some(&"abc123".to_string())
+    );
+    assert_eq!(events[1].labels.get("execution_id"), None);
+}
+
+#[test]
+fn labels_entries_of_asynchronous_executions() {
+    let buffer = Arc::new(Mutex::new(vec![]));
+    let shared = buffer.clone();
+    let stackdriver = tracing_stackdriver_cw::layer()
+        .with_writer(move || MockWriter(shared.clone()))
+        .with_filter(LevelFilter::WARN);
+    let subscriber = Registry::default().with(stackdriver);
+    let runtime = tokio::runtime::Builder::new_current_thread()
+        .enable_time()
+        .build()
+        .unwrap();
+
+    tracing::subscriber::with_default(subscriber, || {
+        runtime.block_on(tracing_stackdriver_cw::function_execution(
+            Some("abc123"),
+            async {
+                tokio::time::sleep(std::time::Duration::from_millis(1)).await;
+                tracing::warn!("handling request");
+            },
+        ))
+    });
+
+    let buffer = buffer.lock().unwrap();
+    let event: MockDefaultEvent =
+        serde_json::from_slice(&buffer).expect("Error converting test buffer to JSON");
+    assert_eq!(
+        event.labels.get("execution_id"),
+        Some(&"abc123".to_string())
+    );
+}
+
+#[test]
+fn labels_entries_with_execution_ids_recorded_with_debug() {
+    let execution_id = String::from("abc123");
+
+    let events = run_with_tracing::<MockDefaultEvent>(|| {
+        let span = tracing::info_span!(
+            target: "tracing_stackdriver_cw::cloud_functions",
+            "function_execution",
+            execution_id = ?execution_id
+        );
+        let _guard = span.enter();
+        tracing::info!("handling request");
+    })
+    .expect("Error converting test buffer to JSON");
+
+    let event = events.first().expect("No event heard");
+    assert_eq!(
+        event.labels.get("execution_id"),
+        Some(&"abc123".to_string())
+    );
+}
+
+#[test]
+fn writes_execution_span_close_entries_at_info() {
+    let buffer = Arc::new(Mutex::new(vec![]));
+    let shared = buffer.clone();
+    let stackdriver = tracing_stackdriver_cw::layer()
+        .with_span_close_entries(true)
+        .with_writer(move || MockWriter(shared.clone()));
+    let subscriber = Registry::default().with(stackdriver);
+
+    tracing::subscriber::with_default(subscriber, || {
+        let headers = [("Function-Execution-Id", "abc123")];
+        tracing_stackdriver_cw::instrument_function_execution(headers, || {
+            tracing::info!("handling request");
+        })
+    });
+
+    let buffer = buffer.lock().unwrap();
+    let close = serde_json::Deserializer::from_slice(&buffer)
+        .into_iter::<Value>()
+        .map(|entry| entry.expect("Error converting test buffer to JSON"))
+        .find(|entry| entry["message"] == "close")
+        .expect("no close entry was written");
+
+    assert_eq!(close["severity"], "INFO");
+}
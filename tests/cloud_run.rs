@@ -0,0 +1,37 @@
+use helpers::run_with_tracing_layer;
+use mocks::MockDefaultEvent;
+
+mod helpers;
+mod mocks;
+
+#[test]
+fn includes_cloud_run_labels() {
+    std::env::set_var("K_SERVICE", "my-service");
+    std::env::set_var("K_REVISION", "my-service-00001-abc");
+    std::env::set_var("K_CONFIGURATION", "my-service");
+
+    let layer = tracing_stackdriver_cw::layer().with_cloud_run_labels(true);
+    let events = run_with_tracing_layer::<MockDefaultEvent>(layer, || tracing::info!("hello!"))
+        .expect("Error converting test buffer to JSON");
+
+    let event = events.first().expect("No event heard");
+    assert_eq!(
+        event.labels.get("run.googleapis.com/service_name"),
+        Some(&"my-service".to_string())
+    );
+    assert_eq!(
+        event.labels.get("run.googleapis.com/revision_name"),
+        Some(&"my-service-00001-abc".to_string())
+    );
+    assert_eq!(
+        event.labels.get("run.googleapis.com/configuration_name"),
+        Some(&"my-service".to_string())
+    );
+
+    let layer = tracing_stackdriver_cw::layer().with_cloud_run_labels(false);
+    let events = run_with_tracing_layer::<MockDefaultEvent>(layer, || tracing::info!("hello!"))
+        .expect("Error converting test buffer to JSON");
+
+    let event = events.first().expect("No event heard");
+    assert!(event.labels.is_empty());
+}
@@ -0,0 +1,46 @@
+use helpers::run_with_tracing_layer;
+use serde_json::Value;
+use std::time::Duration;
+use tracing_stackdriver_cw::CloudTraceConfiguration;
+
+mod helpers;
+
+const TRACE_ID: &str = "4bf92f3577b34da6a3ce929d0e0e4736";
+
+#[test]
+fn links_errors_to_their_trace() {
+    let layer = tracing_stackdriver_cw::layer()
+        .with_cloud_trace(CloudTraceConfiguration {
+            project_id: "my-project".to_string(),
+        })
+        .with_console_urls(Duration::from_secs(3600));
+
+    let events = run_with_tracing_layer::<Value>(layer, || {
+        let span = tracing::info_span!("request", trace_id = TRACE_ID);
+        let _span = span.enter();
+        tracing::info!("fine");
+        tracing::error!("broken");
+    })
+    .expect("Error converting test buffer to JSON");
+
+    assert!(events[0].get("consoleUrl").is_none());
+
+    let console_url = events[1]["consoleUrl"].as_str().expect("No console URL");
+    assert!(console_url.starts_with("https://console.cloud.google.com/logs/query;query=trace%3D%22projects%2Fmy-project%2Ftraces%2F4bf92f3577b34da6a3ce929d0e0e4736%22;timeRange="));
+    assert!(console_url.contains(";cursorTimestamp="));
+    assert!(console_url.ends_with("?project=my-project"));
+}
+
+#[test]
+fn skips_errors_outside_of_traces() {
+    let layer = tracing_stackdriver_cw::layer()
+        .with_cloud_trace(CloudTraceConfiguration {
+            project_id: "my-project".to_string(),
+        })
+        .with_console_urls(Duration::from_secs(3600));
+
+    let events = run_with_tracing_layer::<Value>(layer, || tracing::error!("untraced"))
+        .expect("Error converting test buffer to JSON");
+
+    assert!(events[0].get("consoleUrl").is_none());
+}
@@ -0,0 +1,76 @@
+use helpers::{run_with_tracing_layer, MockWriter};
+use serde_json::Value;
+use std::{
+    panic,
+    sync::{Arc, Mutex},
+};
+use tracing_stackdriver_cw::{install_crash_hook, write_crash_context, RecentEntries};
+
+mod helpers;
+
+#[test]
+fn writes_crash_context() {
+    let recent_entries = RecentEntries::new(10);
+    let layer = tracing_stackdriver_cw::layer().with_recent_entries(recent_entries.clone());
+
+    run_with_tracing_layer::<Value>(layer, || {
+        tracing::debug!(labels.request = "abc", "lookup");
+    })
+    .expect("Error converting test buffer to JSON");
+
+    let mut buffer = vec![];
+    write_crash_context(&recent_entries, &mut buffer).expect("Error writing crash context");
+
+    let entries: Vec<Value> = serde_json::Deserializer::from_slice(&buffer)
+        .into_iter()
+        .collect::<Result<_, _>>()
+        .expect("Error converting test buffer to JSON");
+
+    assert_eq!(entries.len(), 1);
+    assert_eq!(entries[0]["message"], "lookup");
+    assert_eq!(
+        entries[0]["logging.googleapis.com/labels"],
+        serde_json::json!({ "request": "abc", "crash_context": "true" })
+    );
+}
+
+#[test]
+fn writes_recent_entries_before_panics() {
+    let recent_entries = RecentEntries::new(10);
+    let layer = tracing_stackdriver_cw::layer().with_recent_entries(recent_entries.clone());
+
+    run_with_tracing_layer::<Value>(layer, || {
+        tracing::info!("before the crash");
+    })
+    .expect("Error converting test buffer to JSON");
+
+    let buffer = Arc::new(Mutex::new(vec![]));
+    let shared = buffer.clone();
+    install_crash_hook(recent_entries, move || MockWriter(shared.clone()));
+
+    let _ = panic::catch_unwind(|| panic!("boom"));
+    let _ = panic::take_hook();
+
+    let buffer = buffer
+        .lock()
+        .expect("Couldn't get lock on test write target");
+    let entries: Vec<Value> = serde_json::Deserializer::from_slice(&buffer)
+        .into_iter()
+        .collect::<Result<_, _>>()
+        .expect("Error converting test buffer to JSON");
+
+    assert_eq!(entries.len(), 2);
+    assert_eq!(entries[0]["message"], "before the crash");
+    assert_eq!(
+        entries[0]["logging.googleapis.com/labels"]["crash_context"],
+        "true"
+    );
+    assert_eq!(entries[1]["severity"], "CRITICAL");
+    assert!(entries[1]["message"].as_str().is_some_and(|message| message
+        .starts_with("panicked at tests/crash.rs")
+        && message.contains("boom")));
+    assert_eq!(
+        entries[1]["logging.googleapis.com/sourceLocation"]["file"],
+        "tests/crash.rs"
+    );
+}
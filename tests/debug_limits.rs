@@ -0,0 +1,58 @@
+use helpers::run_with_tracing_layer;
+use serde_json::Value;
+use tracing_stackdriver_cw::{as_json, DebugLimits};
+
+mod helpers;
+
+#[derive(Debug)]
+#[allow(dead_code)]
+struct Node {
+    label: &'static str,
+    children: Vec<Node>,
+}
+
+#[test]
+fn elides_deeply_nested_values() {
+    let tree = Node {
+        label: "root {",
+        children: vec![Node {
+            label: "child",
+            children: vec![Node {
+                label: "leaf",
+                children: vec![],
+            }],
+        }],
+    };
+    let json = serde_json::json!({ "a": { "b": [1] } });
+    let layer = tracing_stackdriver_cw::layer().with_debug_limits(DebugLimits {
+        max_length: 1_024,
+        max_depth: 3,
+    });
+    let events = run_with_tracing_layer::<Value>(layer, || {
+        tracing::info!(tree = ?tree, json = ?as_json(&json));
+    })
+    .expect("Error converting test buffer to JSON");
+
+    assert_eq!(
+        events[0]["tree"],
+        r#"Node { label: "root {", children: [Node { label: "child", children: […] }] }"#
+    );
+    assert_eq!(events[0]["json"], json);
+}
+
+#[test]
+fn truncates_long_values() {
+    let values = vec![42_u64; 10_000];
+    let layer = tracing_stackdriver_cw::layer().with_debug_limits(DebugLimits {
+        max_length: 16,
+        ..DebugLimits::default()
+    });
+    let events = run_with_tracing_layer::<Value>(layer, || {
+        tracing::info!(values = ?values, short = ?[1, 2], "state");
+    })
+    .expect("Error converting test buffer to JSON");
+
+    assert_eq!(events[0]["values"], "[42, 42, 42, 42,…[truncated]");
+    assert_eq!(events[0]["short"], "[1, 2]");
+    assert_eq!(events[0]["message"], "state");
+}
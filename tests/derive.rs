@@ -0,0 +1,68 @@
+#![cfg(feature = "derive")]
+use helpers::run_with_tracing;
+use serde::Serialize;
+use serde_json::{json, Value};
+use tracing_stackdriver_cw::StackdriverFields;
+
+mod helpers;
+
+#[derive(Serialize)]
+struct Item {
+    sku: &'static str,
+}
+
+#[derive(StackdriverFields)]
+struct Order {
+    order_id: u64,
+    items: Vec<Item>,
+    #[stackdriver(rename = "buyer")]
+    customer_name: &'static str,
+    #[stackdriver(redact)]
+    card_number: &'static str,
+    #[stackdriver(skip)]
+    #[allow(dead_code)]
+    internal: bool,
+}
+
+#[test]
+fn records_structs_as_nested_objects() {
+    let order = Order {
+        order_id: 42,
+        items: vec![Item { sku: "abc" }],
+        customer_name: "Ada",
+        card_number: "4111111111111111",
+        internal: true,
+    };
+
+    let events = run_with_tracing::<Value>(|| {
+        tracing::info!(order = %order.as_field(), "order placed");
+        tracing::info!(order = ?order.as_field(), "order shipped");
+    })
+    .expect("Error converting test buffer to JSON");
+
+    let expected = json!({
+        "orderId": 42,
+        "items": [{ "sku": "abc" }],
+        "buyer": "Ada",
+        "cardNumber": "[REDACTED]",
+    });
+
+    assert_eq!(events[0]["order"], expected);
+    assert_eq!(events[1]["order"], expected);
+}
+
+#[test]
+fn formats_structs_as_json_for_other_layers() {
+    let order = Order {
+        order_id: 7,
+        items: vec![],
+        customer_name: "Grace",
+        card_number: "",
+        internal: false,
+    };
+
+    assert_eq!(
+        order.as_field().to_string(),
+        r#"{"buyer":"Grace","cardNumber":"[REDACTED]","items":[],"orderId":7}"#
+    );
+}
@@ -0,0 +1,45 @@
+use helpers::run_with_tracing_layer;
+use serde_json::{json, Value};
+use tracing_stackdriver_cw::DottedKeys;
+
+mod helpers;
+
+fn dotted_event(dotted_keys: DottedKeys) -> Value {
+    let layer = tracing_stackdriver_cw::layer().with_dotted_keys(dotted_keys);
+
+    run_with_tracing_layer::<Value>(layer, || {
+        tracing::info!(
+            user.id = 1,
+            user.first_name = "Ada",
+            labels.team = "core",
+            "dotted"
+        )
+    })
+    .expect("Error converting test buffer to JSON")
+    .remove(0)
+}
+
+#[test]
+fn escapes_dotted_keys_by_default() {
+    let event = dotted_event(DottedKeys::default());
+
+    assert_eq!(event["userId"], 1);
+    assert_eq!(event["userFirstName"], "Ada");
+}
+
+#[test]
+fn nests_dotted_keys() {
+    let event = dotted_event(DottedKeys::Nest);
+
+    assert_eq!(event["user"], json!({ "id": 1, "firstName": "Ada" }));
+    assert_eq!(event["logging.googleapis.com/labels"]["team"], "core");
+}
+
+#[test]
+fn keeps_dotted_keys_flat() {
+    let event = dotted_event(DottedKeys::Flat);
+
+    assert_eq!(event["user.id"], 1);
+    assert_eq!(event["user.firstName"], "Ada");
+    assert!(event.get("user").is_none());
+}
@@ -0,0 +1,49 @@
+use helpers::run_with_tracing_layer;
+use serde_json::{json, Value};
+use tracing_stackdriver_cw::{emit, BuildInfo, LogEntry, LogSeverity};
+
+mod helpers;
+
+// entries emitted before any layer exists are kept globally, so a single test covers emission
+// both before and within a subscriber
+#[test]
+fn emits_entries_before_and_within_subscribers() {
+    emit(LogEntry {
+        severity: LogSeverity::Notice,
+        message: Some("starting up".to_string()),
+        ..Default::default()
+    });
+
+    let layer = tracing_stackdriver_cw::layer().with_build_info(BuildInfo {
+        version: Some("1.2.3".to_string()),
+        ..Default::default()
+    });
+    let events = run_with_tracing_layer::<Value>(layer, || {
+        emit(LogEntry {
+            severity: LogSeverity::Warning,
+            message: Some("translated".to_string()),
+            insert_id: Some("record-7".to_string()),
+            fields: json!({ "source": "syslog" }).as_object().cloned().unwrap(),
+            ..Default::default()
+        })
+    })
+    .expect("Error converting test buffer to JSON");
+
+    assert_eq!(events.len(), 2);
+    assert_eq!(events[0]["severity"], "NOTICE");
+    assert_eq!(events[0]["message"], "starting up");
+    assert!(events[0]["time"].is_string());
+    assert_eq!(
+        events[0]["logging.googleapis.com/labels"],
+        json!({ "build_version": "1.2.3" })
+    );
+
+    assert_eq!(events[1]["severity"], "WARNING");
+    assert_eq!(events[1]["message"], "translated");
+    assert_eq!(events[1]["logging.googleapis.com/insertId"], "record-7");
+    assert_eq!(events[1]["source"], "syslog");
+    assert_eq!(
+        events[1]["logging.googleapis.com/labels"],
+        json!({ "build_version": "1.2.3" })
+    );
+}
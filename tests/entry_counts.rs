@@ -0,0 +1,28 @@
+use helpers::run_with_tracing_layer;
+use serde_json::Value;
+use std::collections::BTreeMap;
+use tracing_stackdriver_cw::{EntryCounts, LogSeverity};
+
+mod helpers;
+
+#[test]
+fn counts_entries_by_target_and_severity() {
+    let entry_counts = EntryCounts::default();
+    let layer = tracing_stackdriver_cw::layer().with_entry_counts(entry_counts.clone());
+
+    run_with_tracing_layer::<Value>(layer, || {
+        tracing::info!(target: "hyper::client", "connecting");
+        tracing::info!(target: "hyper::proto", "reading");
+        tracing::warn!(target: "hyper", "slow");
+        tracing::info!(target: "app", severity = "notice", "started");
+    })
+    .expect("Error converting test buffer to JSON");
+
+    let snapshot = entry_counts.snapshot();
+    assert_eq!(
+        snapshot["hyper"],
+        BTreeMap::from([(LogSeverity::Info, 2), (LogSeverity::Warning, 1)])
+    );
+    assert_eq!(snapshot["app"], BTreeMap::from([(LogSeverity::Notice, 1)]));
+    assert_eq!(snapshot.len(), 2);
+}
@@ -0,0 +1,34 @@
+use helpers::run_with_tracing_layer;
+use serde_json::Value;
+use tracing_stackdriver_cw::{LogEntry, LogSeverity};
+
+mod helpers;
+
+#[test]
+fn decorates_entries_before_they_are_written() {
+    let layer = tracing_stackdriver_cw::layer().with_entry_decorator(|entry: &mut LogEntry| {
+        let is_payments = entry.fields.get("target") == Some(&Value::from("payments"));
+
+        if is_payments && entry.severity >= LogSeverity::Error {
+            entry
+                .labels
+                .insert("alert_team".to_string(), "payments-oncall".to_string());
+        }
+
+        entry.fields.remove("internal");
+    });
+    let events = run_with_tracing_layer::<Value>(layer, || {
+        tracing::error!(target: "payments", internal = true, "charge failed");
+        tracing::info!(target: "payments", "charged");
+    })
+    .expect("Error converting test buffer to JSON");
+
+    assert_eq!(events[0]["message"], "charge failed");
+    assert_eq!(
+        events[0]["logging.googleapis.com/labels"]["alert_team"],
+        "payments-oncall"
+    );
+    assert!(events[0].get("internal").is_none());
+    assert!(events[1].get("logging.googleapis.com/labels").is_none());
+    assert_eq!(events[1]["severity"], "INFO");
+}
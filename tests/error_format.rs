@@ -0,0 +1,116 @@
+use helpers::run_with_tracing_layer;
+use serde_json::Value;
+use std::{error::Error, fmt};
+use tracing_stackdriver_cw::{as_error, ErrorFormat};
+
+mod helpers;
+
+#[derive(Debug)]
+struct NestedError {
+    context: Vec<&'static str>,
+}
+
+impl fmt::Display for NestedError {
+    fn fmt(&self, formatter: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(formatter, "{} failed", self.context[0])
+    }
+}
+
+impl Error for NestedError {}
+
+#[test]
+fn writes_error_debug_only_at_debug() {
+    let error = NestedError {
+        context: vec!["connect", "handshake"],
+    };
+    let layer = tracing_stackdriver_cw::layer().with_error_format(ErrorFormat::Display);
+    let events = run_with_tracing_layer::<Value>(layer, || {
+        tracing::error!(error = &error as &dyn Error, "failed");
+        tracing::debug!(error = &error as &dyn Error, "failed");
+    })
+    .expect("Error converting test buffer to JSON");
+
+    assert_eq!(events[0]["error"], "connect failed");
+    assert!(events[0].get("errorDebug").is_none());
+
+    assert_eq!(events[1]["error"], "connect failed");
+    assert_eq!(
+        events[1]["errorDebug"],
+        r#"NestedError { context: ["connect", "handshake"] }"#
+    );
+}
+
+#[test]
+fn writes_wrapped_errors_with_display_and_debug_only_at_debug() {
+    let error = NestedError {
+        context: vec!["connect", "handshake"],
+    };
+    let layer = tracing_stackdriver_cw::layer().with_error_format(ErrorFormat::Display);
+    let events = run_with_tracing_layer::<Value>(layer, || {
+        tracing::error!(error = ?as_error(&error), "failed");
+        tracing::debug!(request_error = %as_error(&error), "failed");
+    })
+    .expect("Error converting test buffer to JSON");
+
+    assert_eq!(events[0]["error"], "connect failed");
+    assert!(events[0].get("errorDebug").is_none());
+
+    assert_eq!(events[1]["requestError"], "connect failed");
+    assert_eq!(
+        events[1]["requestErrorDebug"],
+        r#"NestedError { context: ["connect", "handshake"] }"#
+    );
+}
+
+#[test]
+fn writes_wrapped_errors_with_display_as_recorded() {
+    let error = NestedError {
+        context: vec!["connect", "handshake"],
+    };
+    let events = run_with_tracing_layer::<Value>(tracing_stackdriver_cw::layer(), || {
+        tracing::debug!(error = %as_error(&error), "failed");
+    })
+    .expect("Error converting test buffer to JSON");
+
+    assert_eq!(events[0]["error"], "connect failed");
+    assert!(events[0].get("errorDebug").is_none());
+}
+
+#[test]
+fn truncates_debug_errors_above_debug() {
+    let error = NestedError {
+        context: vec!["a very long context"; 100],
+    };
+    let layer = tracing_stackdriver_cw::layer().with_error_format(ErrorFormat::Display);
+    let events = run_with_tracing_layer::<Value>(layer, || {
+        tracing::warn!(error = ?error, other = ?error, "failed");
+        tracing::debug!(error = ?error, "failed");
+    })
+    .expect("Error converting test buffer to JSON");
+
+    let truncated = events[0]["error"].as_str().unwrap();
+    assert!(truncated.starts_with("NestedError { context: ["));
+    assert!(truncated.contains("…[truncated"));
+    assert!(!events[0]["other"].as_str().unwrap().contains("…[truncated"));
+
+    assert_eq!(events[1]["error"], format!("{:?}", error));
+    assert!(events[1].get("errorDebug").is_none());
+}
+
+#[test]
+fn formats_errors_for_the_final_severity() {
+    let error = NestedError {
+        context: vec!["connect", "handshake"],
+    };
+    let layer = tracing_stackdriver_cw::layer().with_error_format(ErrorFormat::Display);
+    let events = run_with_tracing_layer::<Value>(layer, || {
+        tracing::debug!(severity = "ERROR", error = &error as &dyn Error, "failed");
+        tracing::error!(severity = "DEBUG", error = &error as &dyn Error, "failed");
+    })
+    .expect("Error converting test buffer to JSON");
+
+    assert_eq!(events[0]["severity"], "ERROR");
+    assert!(events[0].get("errorDebug").is_none());
+    assert_eq!(events[1]["severity"], "DEBUG");
+    assert!(events[1].get("errorDebug").is_some());
+}
@@ -0,0 +1,122 @@
+#![cfg(feature = "error-reporting")]
+use helpers::MockWriter;
+use mock_server::MockServer;
+use serde_json::Value;
+use std::sync::{Arc, Mutex};
+use tracing_stackdriver_cw::{ApiClientBuilder, ErrorReporter};
+use tracing_subscriber::{layer::SubscriberExt, Registry};
+
+mod helpers;
+mod mock_server;
+
+fn run_with_error_reporter(error_reporter: ErrorReporter, callback: impl FnOnce()) -> Vec<u8> {
+    let buffer = Arc::new(Mutex::new(vec![]));
+    let shared = buffer.clone();
+    let (stackdriver, guard) = tracing_stackdriver_cw::layer()
+        .with_writer(move || MockWriter(shared.clone()))
+        .with_error_reporter(error_reporter);
+    let subscriber = Registry::default().with(stackdriver);
+    tracing::subscriber::with_default(subscriber, callback);
+    drop(guard);
+
+    let buffer = buffer
+        .lock()
+        .expect("Couldn't get lock on test write target");
+    buffer.clone()
+}
+
+#[test]
+fn reports_errors_to_the_error_reporting_api() {
+    let server = MockServer::start(200, "{}");
+    let error_reporter = ErrorReporter::builder("my-project", "my-service")
+        .with_version("1.2.3")
+        .with_endpoint(&server.url)
+        .with_access_token("token")
+        .build();
+
+    let written = run_with_error_reporter(error_reporter, || {
+        tracing::info!("not an error");
+        tracing::error!(
+            stack_trace = "at main.rs:10",
+            http_request.request_method = "GET",
+            "request failed"
+        );
+    });
+
+    assert_eq!(written.iter().filter(|byte| **byte == b'\n').count(), 2);
+
+    let requests = server.requests();
+    assert_eq!(requests.len(), 1);
+
+    let request = &requests[0];
+    assert_eq!(request.path, "/projects/my-project/events:report");
+    assert_eq!(
+        request.headers.get("authorization").map(String::as_str),
+        Some("Bearer token")
+    );
+
+    let event: Value = serde_json::from_slice(&request.body).expect("Invalid JSON body");
+    assert_eq!(
+        event["serviceContext"],
+        serde_json::json!({ "service": "my-service", "version": "1.2.3" })
+    );
+    assert_eq!(event["message"], "request failed\nat main.rs:10");
+    assert_eq!(event["context"]["httpRequest"]["method"], "GET");
+    assert_eq!(
+        event["context"]["reportLocation"]["filePath"],
+        "tests/error_reporting.rs"
+    );
+    assert!(event["eventTime"].is_string());
+}
+
+#[test]
+fn rate_limits_reported_errors() {
+    let server = MockServer::start(200, "{}");
+    let dropped = Arc::new(Mutex::new(0));
+    let error_reporter = ErrorReporter::builder("my-project", "my-service")
+        .with_endpoint(&server.url)
+        .with_access_token("token")
+        .with_max_events_per_minute(3)
+        .with_diagnostics({
+            let dropped = dropped.clone();
+            move |failure| *dropped.lock().unwrap() += failure.entries.len()
+        })
+        .build();
+
+    run_with_error_reporter(error_reporter, || {
+        for _ in 0..10 {
+            tracing::error!("storm");
+        }
+    });
+
+    assert_eq!(server.requests().len(), 3);
+    assert_eq!(*dropped.lock().unwrap(), 7);
+}
+
+#[test]
+fn reports_failures_to_the_diagnostics_hook() {
+    let server = MockServer::start(500, "{}");
+    let failures = Arc::new(Mutex::new(vec![]));
+    let error_reporter = ErrorReporter::builder("my-project", "my-service")
+        .with_endpoint(&server.url)
+        .with_access_token("token")
+        .with_diagnostics({
+            let failures = failures.clone();
+            move |failure| {
+                failures
+                    .lock()
+                    .unwrap()
+                    .push((failure.entries.len(), failure.message.clone()))
+            }
+        })
+        .build();
+
+    run_with_error_reporter(error_reporter, || {
+        tracing::error!(stack_trace = "at main.rs:10", "request failed");
+    });
+
+    assert_eq!(
+        *failures.lock().unwrap(),
+        [(1, "report failed with HTTP status 500".to_string())]
+    );
+}
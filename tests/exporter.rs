@@ -0,0 +1,571 @@
+#![cfg(feature = "exporter")]
+use flate2::read::GzDecoder;
+use helpers::MockWriter;
+use mock_server::{MockRequest, MockServer};
+use serde_json::Value;
+use std::{
+    io::{Read, Write},
+    sync::{
+        atomic::{AtomicBool, AtomicUsize, Ordering},
+        Arc, Mutex,
+    },
+    time::Duration,
+};
+use tracing_stackdriver_cw::{ApiClientBuilder, Exporter, MonitoredResource, RetryPolicy};
+use tracing_subscriber::{layer::SubscriberExt, Registry};
+
+mod helpers;
+mod mock_server;
+
+fn run_with_exporter(exporter: Exporter, callback: impl FnOnce()) {
+    let (stackdriver, guard) = tracing_stackdriver_cw::layer().with_exporter(exporter);
+    let subscriber = Registry::default().with(stackdriver);
+    tracing::subscriber::with_default(subscriber, callback);
+    drop(guard);
+}
+
+fn request_body(request: &MockRequest) -> Value {
+    let body = if request.headers.get("content-encoding").map(String::as_str) == Some("gzip") {
+        let mut body = vec![];
+        GzDecoder::new(request.body.as_slice())
+            .read_to_end(&mut body)
+            .expect("Invalid gzip body");
+        body
+    } else {
+        request.body.clone()
+    };
+
+    serde_json::from_slice(&body).expect("Invalid JSON body")
+}
+
+#[test]
+fn exports_entries_to_the_logging_api() {
+    let server = MockServer::start(200, "{}");
+    let exporter = Exporter::builder("my-project")
+        .with_endpoint(format!("{}/v2/entries:write", server.url))
+        .with_access_token("token")
+        .build();
+
+    run_with_exporter(exporter, || {
+        tracing::info!(labels.foo = "bar", answer = 42, "exported message")
+    });
+
+    let requests = server.requests();
+    let request = requests.first().expect("No request heard");
+    assert_eq!(request.method, "POST");
+    assert_eq!(request.path, "/v2/entries:write");
+    assert_eq!(
+        request.headers.get("authorization").map(String::as_str),
+        Some("Bearer token")
+    );
+
+    let body = request_body(request);
+    assert_eq!(
+        body["logName"],
+        "projects/my-project/logs/tracing-stackdriver"
+    );
+
+    let entry = &body["entries"][0];
+    assert_eq!(entry["severity"], "INFO");
+    assert_eq!(entry["labels"]["foo"], "bar");
+    assert!(entry["timestamp"].is_string());
+    assert_eq!(entry["jsonPayload"]["message"], "exported message");
+    assert_eq!(entry["jsonPayload"]["answer"], 42);
+}
+
+#[test]
+fn compresses_requests_with_gzip() {
+    let server = MockServer::start(200, "{}");
+    let exporter = Exporter::builder("my-project")
+        .with_endpoint(&server.url)
+        .with_access_token("token")
+        .with_gzip(true)
+        .build();
+
+    run_with_exporter(exporter, || tracing::info!("compressed message"));
+
+    let requests = server.requests();
+    let request = requests.first().expect("No request heard");
+    assert_eq!(
+        request.headers.get("content-encoding").map(String::as_str),
+        Some("gzip")
+    );
+    assert_eq!(
+        request_body(request)["entries"][0]["jsonPayload"]["message"],
+        "compressed message"
+    );
+}
+
+#[test]
+fn splits_batches_by_entry_count() {
+    let server = MockServer::start(200, "{}");
+    let exporter = Exporter::builder("my-project")
+        .with_endpoint(&server.url)
+        .with_access_token("token")
+        .with_max_batch_entries(2)
+        .build();
+
+    run_with_exporter(exporter, || {
+        for index in 0..5 {
+            tracing::info!(index, "batched message");
+        }
+    });
+
+    let batch_sizes: Vec<_> = server
+        .requests()
+        .iter()
+        .map(|request| request_body(request)["entries"].as_array().unwrap().len())
+        .collect();
+
+    assert_eq!(batch_sizes.iter().sum::<usize>(), 5);
+    assert!(batch_sizes.iter().all(|size| *size <= 2));
+}
+
+#[test]
+fn attributes_entries_to_the_configured_resource() {
+    let server = MockServer::start(200, "{}");
+    let exporter = Exporter::builder("my-project")
+        .with_endpoint(&server.url)
+        .with_access_token("token")
+        .with_resource(MonitoredResource::gce_instance(
+            "my-project",
+            "us-central1-a",
+            "1234567890",
+        ))
+        .build();
+
+    run_with_exporter(exporter, || tracing::info!("attributed message"));
+
+    let requests = server.requests();
+    let body = request_body(requests.first().expect("No request heard"));
+    assert_eq!(
+        body["resource"],
+        serde_json::json!({
+            "type": "gce_instance",
+            "labels": {
+                "project_id": "my-project",
+                "zone": "us-central1-a",
+                "instance_id": "1234567890",
+            },
+        })
+    );
+}
+
+#[test]
+fn detects_cloud_run_resources() {
+    std::env::set_var("K_SERVICE", "my-service");
+    std::env::set_var("K_REVISION", "my-service-00001-abc");
+    std::env::set_var("K_CONFIGURATION", "my-service");
+
+    let resource = MonitoredResource::detect("my-project");
+    assert_eq!(resource.kind, "cloud_run_revision");
+    assert_eq!(resource.labels["project_id"], "my-project");
+    assert_eq!(resource.labels["service_name"], "my-service");
+    assert_eq!(resource.labels["revision_name"], "my-service-00001-abc");
+}
+
+#[test]
+fn writes_to_configured_and_overridden_log_names() {
+    let server = MockServer::start(200, "{}");
+    let exporter = Exporter::builder("my-project")
+        .with_endpoint(&server.url)
+        .with_access_token("token")
+        .with_log_name("folders/123/logs/app")
+        .build();
+
+    // written directly so that all entries end up in the same batch
+    let mut exporter = exporter;
+    for (index, log_name) in [
+        None,
+        Some("audit"),
+        Some("projects/other/logs/access"),
+        None,
+    ]
+    .into_iter()
+    .enumerate()
+    {
+        let entry = serde_json::json!({ "index": index, "logName": log_name });
+        writeln!(exporter, "{}", entry).unwrap();
+    }
+    exporter.flush().unwrap();
+
+    let requests: Vec<_> = server
+        .requests()
+        .iter()
+        .map(|request| {
+            let body = request_body(request);
+            let indices: Vec<_> = body["entries"]
+                .as_array()
+                .unwrap()
+                .iter()
+                .map(|entry| {
+                    assert!(entry.get("logName").is_none());
+                    assert!(entry["jsonPayload"].get("logName").is_none());
+                    entry["jsonPayload"]["index"].as_u64().unwrap()
+                })
+                .collect();
+
+            (body["logName"].as_str().unwrap().to_string(), indices)
+        })
+        .collect();
+
+    assert_eq!(
+        requests,
+        vec![
+            ("folders/123/logs/app".to_string(), vec![0, 3]),
+            ("folders/123/logs/audit".to_string(), vec![1]),
+            ("projects/other/logs/access".to_string(), vec![2]),
+        ]
+    );
+}
+
+fn fast_retries() -> RetryPolicy {
+    RetryPolicy {
+        max_attempts: 3,
+        initial_backoff: Duration::from_millis(1),
+        max_backoff: Duration::from_millis(5),
+    }
+}
+
+#[test]
+fn retries_unavailable_requests() {
+    let attempts = Arc::new(AtomicUsize::new(0));
+    let server = MockServer::start_with({
+        let attempts = attempts.clone();
+        move |_| match attempts.fetch_add(1, Ordering::SeqCst) {
+            0 => (
+                503,
+                r#"{"error":{"code":503,"status":"UNAVAILABLE"}}"#.to_string(),
+            ),
+            _ => (200, "{}".to_string()),
+        }
+    });
+    let failures = Arc::new(Mutex::new(vec![]));
+    let exporter = Exporter::builder("my-project")
+        .with_endpoint(&server.url)
+        .with_access_token("token")
+        .with_retry_policy(fast_retries())
+        .with_diagnostics({
+            let failures = failures.clone();
+            move |failure| failures.lock().unwrap().push(failure.message.clone())
+        })
+        .build();
+
+    run_with_exporter(exporter, || tracing::info!("retried message"));
+
+    let requests = server.requests();
+    assert_eq!(requests.len(), 2);
+    assert_eq!(request_body(&requests[1])["partialSuccess"], true);
+    assert_eq!(
+        request_body(&requests[1])["entries"][0]["jsonPayload"]["message"],
+        "retried message"
+    );
+    assert!(failures.lock().unwrap().is_empty());
+}
+
+#[test]
+fn retries_only_retryable_entries_of_partial_failures() {
+    let attempts = Arc::new(AtomicUsize::new(0));
+    let server = MockServer::start_with({
+        let attempts = attempts.clone();
+        move |_| {
+            match attempts.fetch_add(1, Ordering::SeqCst) {
+            0 => (
+                400,
+                serde_json::json!({
+                    "error": {
+                        "code": 400,
+                        "message": "Log entries with invalid fields",
+                        "status": "INVALID_ARGUMENT",
+                        "details": [{
+                            "@type": "type.googleapis.com/google.logging.v2.WriteLogEntriesPartialErrors",
+                            "logEntryErrors": {
+                                "0": { "code": 3, "message": "invalid entry" },
+                                "2": { "code": 14, "message": "try again" },
+                            },
+                        }],
+                    },
+                })
+                .to_string(),
+            ),
+            _ => (200, "{}".to_string()),
+        }
+        }
+    });
+    let failures = Arc::new(Mutex::new(vec![]));
+    let exporter = Exporter::builder("my-project")
+        .with_endpoint(&server.url)
+        .with_access_token("token")
+        .with_retry_policy(fast_retries())
+        .with_diagnostics({
+            let failures = failures.clone();
+            move |failure| {
+                failures
+                    .lock()
+                    .unwrap()
+                    .push((failure.code, failure.entries.len()))
+            }
+        })
+        .build();
+
+    // written directly so that all entries end up in the same batch
+    let mut exporter = exporter;
+    for index in 0..3 {
+        writeln!(
+            exporter,
+            r#"{{"index":{},"message":"partially failed"}}"#,
+            index
+        )
+        .unwrap();
+    }
+    exporter.flush().unwrap();
+
+    let requests = server.requests();
+    assert_eq!(requests.len(), 2);
+
+    let retried = request_body(&requests[1]);
+    let retried = retried["entries"].as_array().unwrap();
+    assert_eq!(retried.len(), 1);
+    assert_eq!(retried[0]["jsonPayload"]["index"], 2);
+
+    assert_eq!(*failures.lock().unwrap(), vec![(Some(3), 1)]);
+}
+
+#[test]
+fn spools_failed_batches_and_replays_them_on_recovery() {
+    let available = Arc::new(AtomicBool::new(false));
+    let server = MockServer::start_with({
+        let available = available.clone();
+        move |_| match available.load(Ordering::SeqCst) {
+            false => (503, "{}".to_string()),
+            true => (200, "{}".to_string()),
+        }
+    });
+
+    let directory =
+        std::env::temp_dir().join(format!("tracing-stackdriver-spool-{}", std::process::id()));
+    let _ = std::fs::remove_dir_all(&directory);
+
+    let mut exporter = Exporter::builder("my-project")
+        .with_endpoint(&server.url)
+        .with_access_token("token")
+        .with_retry_policy(RetryPolicy {
+            max_attempts: 1,
+            ..fast_retries()
+        })
+        .with_diagnostics(|failure| panic!("Unexpected failure: {}", failure.message))
+        .with_spool(&directory, 1024 * 1024)
+        .build();
+
+    writeln!(exporter, r#"{{"message":"during outage"}}"#).unwrap();
+    exporter.flush().unwrap();
+    assert_eq!(std::fs::read_dir(&directory).unwrap().count(), 1);
+
+    available.store(true, Ordering::SeqCst);
+    writeln!(exporter, r#"{{"message":"after recovery"}}"#).unwrap();
+    exporter.flush().unwrap();
+    assert_eq!(std::fs::read_dir(&directory).unwrap().count(), 0);
+
+    let messages: Vec<_> = server
+        .requests()
+        .iter()
+        .map(|request| request_body(request)["entries"][0]["jsonPayload"]["message"].clone())
+        .collect();
+
+    assert_eq!(
+        messages,
+        ["during outage", "after recovery", "during outage"]
+    );
+    let _ = std::fs::remove_dir_all(&directory);
+}
+
+#[test]
+fn spools_forbidden_batches_without_retrying_them() {
+    let authorized = Arc::new(AtomicBool::new(false));
+    let server = MockServer::start_with({
+        let authorized = authorized.clone();
+        move |_| match authorized.load(Ordering::SeqCst) {
+            false => (
+                403,
+                r#"{"error":{"code":7,"status":"PERMISSION_DENIED"}}"#.to_string(),
+            ),
+            true => (200, "{}".to_string()),
+        }
+    });
+
+    let directory = std::env::temp_dir().join(format!(
+        "tracing-stackdriver-spool-forbidden-{}",
+        std::process::id()
+    ));
+    let _ = std::fs::remove_dir_all(&directory);
+
+    let mut exporter = Exporter::builder("my-project")
+        .with_endpoint(&server.url)
+        .with_access_token("token")
+        .with_retry_policy(fast_retries())
+        .with_diagnostics(|failure| panic!("Unexpected failure: {}", failure.message))
+        .with_spool(&directory, 1024 * 1024)
+        .build();
+
+    writeln!(exporter, r#"{{"message":"while forbidden"}}"#).unwrap();
+    exporter.flush().unwrap();
+    assert_eq!(std::fs::read_dir(&directory).unwrap().count(), 1);
+
+    authorized.store(true, Ordering::SeqCst);
+    writeln!(exporter, r#"{{"message":"once authorized"}}"#).unwrap();
+    exporter.flush().unwrap();
+    assert_eq!(std::fs::read_dir(&directory).unwrap().count(), 0);
+
+    let messages: Vec<_> = server
+        .requests()
+        .iter()
+        .map(|request| request_body(request)["entries"][0]["jsonPayload"]["message"].clone())
+        .collect();
+
+    assert_eq!(
+        messages,
+        ["while forbidden", "once authorized", "while forbidden"]
+    );
+    let _ = std::fs::remove_dir_all(&directory);
+}
+
+#[test]
+fn replays_batches_spooled_by_earlier_exporters_on_their_first_successful_send() {
+    let available = Arc::new(AtomicBool::new(false));
+    let server = MockServer::start_with({
+        let available = available.clone();
+        move |_| match available.load(Ordering::SeqCst) {
+            false => (503, "{}".to_string()),
+            true => (200, "{}".to_string()),
+        }
+    });
+
+    let directory = std::env::temp_dir().join(format!(
+        "tracing-stackdriver-spool-earlier-{}",
+        std::process::id()
+    ));
+    let _ = std::fs::remove_dir_all(&directory);
+
+    let build = || {
+        Exporter::builder("my-project")
+            .with_endpoint(&server.url)
+            .with_access_token("token")
+            .with_retry_policy(RetryPolicy {
+                max_attempts: 1,
+                ..fast_retries()
+            })
+            .with_diagnostics(|failure| panic!("Unexpected failure: {}", failure.message))
+            .with_spool(&directory, 1024 * 1024)
+            .build()
+    };
+
+    let mut earlier = build();
+    writeln!(earlier, r#"{{"message":"spooled earlier"}}"#).unwrap();
+    earlier.flush().unwrap();
+    drop(earlier);
+    assert_eq!(std::fs::read_dir(&directory).unwrap().count(), 1);
+
+    available.store(true, Ordering::SeqCst);
+    let mut exporter = build();
+    writeln!(exporter, r#"{{"message":"first"}}"#).unwrap();
+    exporter.flush().unwrap();
+    assert_eq!(std::fs::read_dir(&directory).unwrap().count(), 0);
+
+    writeln!(exporter, r#"{{"message":"second"}}"#).unwrap();
+    exporter.flush().unwrap();
+
+    let messages: Vec<_> = server
+        .requests()
+        .iter()
+        .map(|request| request_body(request)["entries"][0]["jsonPayload"]["message"].clone())
+        .collect();
+
+    assert_eq!(
+        messages,
+        ["spooled earlier", "first", "spooled earlier", "second"]
+    );
+    let _ = std::fs::remove_dir_all(&directory);
+}
+
+#[test]
+fn records_and_reports_metrics() {
+    let attempts = Arc::new(AtomicUsize::new(0));
+    let server = MockServer::start_with({
+        let attempts = attempts.clone();
+        move |_| match attempts.fetch_add(1, Ordering::SeqCst) {
+            0 => (503, "{}".to_string()),
+            _ => (200, "{}".to_string()),
+        }
+    });
+    let builder = Exporter::builder("my-project")
+        .with_endpoint(&server.url)
+        .with_access_token("token")
+        .with_retry_policy(fast_retries());
+    let metrics = builder.metrics();
+    let mut exporter = builder.build();
+
+    writeln!(exporter, r#"{{"message":"measured"}}"#).unwrap();
+    exporter.flush().unwrap();
+
+    let snapshot = metrics.snapshot();
+    assert_eq!(snapshot.requests, 2);
+    assert_eq!(snapshot.failed_requests, 1);
+    assert_eq!(snapshot.retries, 1);
+    assert_eq!(snapshot.exported_entries, 1);
+    assert_eq!(snapshot.failed_entries, 0);
+    assert_eq!(snapshot.batch_size.count, 2);
+    assert_eq!(snapshot.batch_size.counts[0], 2);
+
+    let server = MockServer::start(200, "{}");
+    let mut exporter = Exporter::builder("my-project")
+        .with_endpoint(&server.url)
+        .with_access_token("token")
+        .with_metrics_report_interval(Some(Duration::ZERO))
+        .build();
+
+    exporter.flush().unwrap();
+
+    let requests = server.requests();
+    let entry = &request_body(requests.first().expect("No request heard"))["entries"][0];
+    assert_eq!(entry["severity"], "NOTICE");
+    assert_eq!(entry["jsonPayload"]["exporterMetrics"]["requests"], 0);
+}
+
+#[test]
+fn tees_entries_to_the_writer_and_the_exporter() {
+    let server = MockServer::start(503, "{}");
+    let exporter = Exporter::builder("my-project")
+        .with_endpoint(&server.url)
+        .with_access_token("token")
+        .with_retry_policy(RetryPolicy {
+            max_attempts: 1,
+            ..fast_retries()
+        })
+        .with_diagnostics(|_| ())
+        .build();
+
+    let buffer = Arc::new(Mutex::new(vec![]));
+    let make_writer = {
+        let buffer = buffer.clone();
+        move || MockWriter(buffer.clone())
+    };
+
+    let (stackdriver, guard) = tracing_stackdriver_cw::layer()
+        .with_writer(make_writer)
+        .with_tee_exporter(exporter);
+    let subscriber = Registry::default().with(stackdriver);
+    tracing::subscriber::with_default(subscriber, || tracing::info!("teed message"));
+    drop(guard);
+
+    // the failing exporter doesn't keep entries from the writer
+    let buffer = buffer.lock().unwrap();
+    let event: Value = serde_json::from_slice(&buffer).expect("Invalid JSON entry");
+    assert_eq!(event["message"], "teed message");
+
+    let requests = server.requests();
+    let request = requests.first().expect("No request heard");
+    assert_eq!(
+        request_body(request)["entries"][0]["jsonPayload"]["message"],
+        "teed message"
+    );
+}
@@ -0,0 +1,33 @@
+use helpers::MockWriter;
+use serde_json::Value;
+use std::sync::{Arc, Mutex};
+use tracing_stackdriver_cw::EventFormatter;
+use tracing_subscriber::{fmt::format::DefaultFields, layer::SubscriberExt, Registry};
+
+mod helpers;
+
+#[test]
+fn formats_events_with_custom_field_formatters() {
+    let buffer = Arc::new(Mutex::new(vec![]));
+    let shared = buffer.clone();
+    let layer = tracing_subscriber::fmt::layer()
+        .with_ansi(false)
+        .fmt_fields(DefaultFields::new())
+        .event_format(EventFormatter::default())
+        .with_writer(move || MockWriter(shared.clone()));
+    let subscriber = Registry::default().with(layer);
+
+    tracing::subscriber::with_default(subscriber, || {
+        let span = tracing::info_span!("request", user_id = 42, path = "/a b");
+        span.in_scope(|| tracing::info!(attempt = 1, "handled"));
+    });
+
+    let buffer = buffer.lock().unwrap();
+    let event: Value = serde_json::from_slice(&buffer).expect("Error parsing entry");
+
+    assert_eq!(event["message"], "handled");
+    assert_eq!(event["attempt"], 1);
+    assert_eq!(event["span"]["name"], "request");
+    assert_eq!(event["span"]["user_id"], 42);
+    assert_eq!(event["span"]["path"], "/a b");
+}
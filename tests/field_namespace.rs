@@ -0,0 +1,28 @@
+use helpers::run_with_tracing_layer;
+use serde_json::{json, Value};
+
+mod helpers;
+
+#[test]
+fn nests_user_fields_under_the_namespace() {
+    let layer = tracing_stackdriver_cw::layer().with_field_namespace("app");
+    let events = run_with_tracing_layer::<Value>(layer, || {
+        tracing::info!(
+            user_id = 1,
+            target = "spoofed",
+            labels.service = "api",
+            http_request.status = 200,
+            "namespaced"
+        )
+    })
+    .expect("Error converting test buffer to JSON");
+
+    let event = &events[0];
+    assert_eq!(event["app"], json!({ "userId": 1, "target": "spoofed" }));
+    assert_eq!(event["message"], "namespaced");
+    assert_eq!(event["severity"], "INFO");
+    assert_eq!(event["target"], "field_namespace");
+    assert_eq!(event["httpRequest"]["status"], 200);
+    assert_eq!(event["logging.googleapis.com/labels"]["service"], "api");
+    assert!(event.get("userId").is_none());
+}
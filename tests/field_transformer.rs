@@ -0,0 +1,33 @@
+use helpers::run_with_tracing_layer;
+use serde_json::Value;
+
+mod helpers;
+
+#[test]
+fn transforms_every_field() {
+    let layer = tracing_stackdriver_cw::layer().with_field_transformer(
+        |key: &str, value: Value| match key {
+            "internal" => None,
+            "status_code" => Some(("http_request.status".to_string(), value)),
+            "count" => Some((key.to_string(), Value::from(value.to_string()))),
+            _ => Some((key.trim_start_matches("ctx_").to_string(), value)),
+        },
+    );
+    let events = run_with_tracing_layer::<Value>(layer, || {
+        tracing::info!(
+            internal = true,
+            status_code = 404,
+            count = 3,
+            ctx_tenant = "acme",
+            "hello"
+        );
+    })
+    .expect("Error converting test buffer to JSON");
+
+    let event = &events[0];
+    assert!(event.get("internal").is_none());
+    assert_eq!(event["httpRequest"]["status"], 404);
+    assert_eq!(event["count"], "3");
+    assert_eq!(event["tenant"], "acme");
+    assert_eq!(event["message"], "hello");
+}
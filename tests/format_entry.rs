@@ -0,0 +1,127 @@
+use serde_json::{Map, Value};
+use std::{
+    sync::{Arc, Mutex},
+    time::{Duration, SystemTime},
+};
+use tracing::Subscriber;
+use tracing_stackdriver_cw::{
+    format_entry, CloudTraceConfiguration, EntryContext, SpanIdEncoding, TraceContext,
+};
+use tracing_subscriber::{layer::Context, layer::SubscriberExt, Layer, Registry};
+
+/// Harness layer formatting every event with a fixed context
+struct Harness {
+    context: EntryContext,
+    entries: Arc<Mutex<Vec<Value>>>,
+}
+
+impl<S: Subscriber> Layer<S> for Harness {
+    fn on_event(&self, event: &tracing::Event<'_>, _: Context<'_, S>) {
+        let entry = format_entry(event.metadata(), event, &self.context);
+        self.entries.lock().unwrap().push(entry);
+    }
+}
+
+/// Harness layer formatting every event with the options of a configured Layer
+struct ConfiguredHarness {
+    layer: tracing_stackdriver_cw::Layer<Registry>,
+    context: EntryContext,
+    entries: Arc<Mutex<Vec<Value>>>,
+}
+
+impl Layer<Registry> for ConfiguredHarness {
+    fn on_event(&self, event: &tracing::Event<'_>, _: Context<'_, Registry>) {
+        let entry = self
+            .layer
+            .format_entry(event.metadata(), event, &self.context);
+        self.entries.lock().unwrap().push(entry);
+    }
+}
+
+fn trace_context() -> (CloudTraceConfiguration, TraceContext) {
+    (
+        CloudTraceConfiguration {
+            project_id: "my-project".to_string(),
+        },
+        TraceContext {
+            trace_id: "4bf92f3577b34da6a3ce929d0e0e4736".to_string(),
+            span_id: 1,
+            sampled: true,
+            project_id: None,
+        },
+    )
+}
+
+#[test]
+fn formats_entries_outside_of_fmt_layers() {
+    let entries = Arc::new(Mutex::new(Vec::new()));
+    let harness = Harness {
+        context: EntryContext {
+            time: Some(SystemTime::UNIX_EPOCH + Duration::from_secs(1_714_566_600)),
+            span: Some((
+                "request".to_string(),
+                Map::from_iter([("user".to_string(), Value::from("ada"))]),
+            )),
+            trace: Some(trace_context()),
+            labels: [("service".to_string(), "api".to_string())].into(),
+        },
+        entries: entries.clone(),
+    };
+
+    tracing::subscriber::with_default(Registry::default().with(harness), || {
+        tracing::warn!(
+            http_request.status = 503,
+            labels.region = "eu",
+            "unavailable"
+        );
+    });
+
+    let entries = entries.lock().unwrap();
+    let entry = &entries[0];
+
+    assert_eq!(entry["time"], "2024-05-01T12:30:00Z");
+    assert_eq!(entry["severity"], "WARNING");
+    assert_eq!(entry["message"], "unavailable");
+    assert_eq!(entry["target"], "format_entry");
+    assert_eq!(
+        entry["span"],
+        serde_json::json!({ "name": "request", "user": "ada" })
+    );
+    assert_eq!(
+        entry["logging.googleapis.com/trace"],
+        "projects/my-project/traces/4bf92f3577b34da6a3ce929d0e0e4736"
+    );
+    assert_eq!(entry["logging.googleapis.com/spanId"], "0000000000000001");
+    assert_eq!(entry["httpRequest"]["status"], 503);
+    assert_eq!(
+        entry["logging.googleapis.com/labels"],
+        serde_json::json!({ "service": "api", "region": "eu" })
+    );
+    assert!(entry["logging.googleapis.com/sourceLocation"]["file"].is_string());
+}
+
+#[test]
+fn formats_entries_with_the_options_of_a_layer() {
+    let entries = Arc::new(Mutex::new(Vec::new()));
+    let harness = ConfiguredHarness {
+        layer: tracing_stackdriver_cw::layer()
+            .with_source_location(false)
+            .with_span_id_encoding(SpanIdEncoding::Decimal),
+        context: EntryContext {
+            trace: Some(trace_context()),
+            ..EntryContext::default()
+        },
+        entries: entries.clone(),
+    };
+
+    tracing::subscriber::with_default(Registry::default().with(harness), || {
+        tracing::info!("configured");
+    });
+
+    let entries = entries.lock().unwrap();
+    let entry = &entries[0];
+
+    assert_eq!(entry["message"], "configured");
+    assert_eq!(entry["logging.googleapis.com/spanId"], "1");
+    assert!(entry.get("logging.googleapis.com/sourceLocation").is_none());
+}
@@ -0,0 +1,43 @@
+#![cfg(feature = "forward")]
+use helpers::MockWriter;
+use std::{
+    sync::{Arc, Mutex},
+    time::{SystemTime, UNIX_EPOCH},
+};
+use tracing_stackdriver_cw::ForwardWriter;
+use tracing_subscriber::{layer::SubscriberExt, Registry};
+
+mod helpers;
+
+#[test]
+fn writes_forward_protocol_messages() {
+    let buffer = Arc::new(Mutex::new(vec![]));
+    let writer = Mutex::new(ForwardWriter::new("app", MockWriter(buffer.clone())));
+    let stackdriver = tracing_stackdriver_cw::layer().with_writer(writer);
+    let subscriber = Registry::default().with(stackdriver);
+
+    tracing::subscriber::with_default(subscriber, || {
+        tracing::info!(count = 300, "forwarded message");
+    });
+
+    let buffer = buffer.lock().unwrap();
+
+    // a [tag, time, record] array, tagged with a fixstr and timed with an EventTime
+    assert_eq!(&buffer[..6], &[0x93, 0xa3, b'a', b'p', b'p', 0xd7]);
+    assert_eq!(buffer[6], 0x00);
+
+    let seconds = u32::from_be_bytes(buffer[7..11].try_into().unwrap());
+    let now = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap()
+        .as_secs();
+    assert!(now - u64::from(seconds) < 60);
+
+    let record = &buffer[15..];
+    assert_eq!(record[0] & 0xf0, 0x80, "record should be a fixmap");
+
+    let contains = |bytes: &[u8]| record.windows(bytes.len()).any(|window| window == bytes);
+    assert!(contains(b"\xa7message\xb1forwarded message"));
+    assert!(contains(b"\xa8severity\xa4INFO"));
+    assert!(contains(b"\xa5count\xcd\x01\x2c"));
+}
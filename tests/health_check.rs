@@ -0,0 +1,45 @@
+use helpers::run_with_tracing_layer;
+use serde_json::Value;
+use tracing_stackdriver_cw::HealthCheckFilter;
+
+mod helpers;
+
+#[test]
+fn drops_health_check_entries() {
+    let layer =
+        tracing_stackdriver_cw::layer().with_health_check_filter(HealthCheckFilter::default());
+    let events = run_with_tracing_layer::<Value>(layer, || {
+        tracing::info!(
+            http_request.request_url = "http://10.0.0.1:8080/healthz?probe=1",
+            "probe"
+        );
+        tracing::info!(http_request.request_url = "/_ah/health", "probe");
+        tracing::info!(http_request.request_url = "/healthz/details", "details");
+        tracing::warn!(http_request.request_url = "/readyz", "not ready");
+        tracing::info!("unrelated");
+    })
+    .expect("Error converting test buffer to JSON");
+
+    let messages: Vec<_> = events
+        .iter()
+        .map(|event| event["message"].clone())
+        .collect();
+    assert_eq!(messages, ["details", "not ready", "unrelated"]);
+}
+
+#[test]
+fn downsamples_health_check_entries() {
+    let layer = tracing_stackdriver_cw::layer().with_health_check_filter(HealthCheckFilter {
+        field: "path".to_string(),
+        paths: vec!["/ping".to_string()],
+        sample_rate: 10,
+    });
+    let events = run_with_tracing_layer::<Value>(layer, || {
+        for _ in 0..100 {
+            tracing::info!(path = "/ping", "probe");
+        }
+    })
+    .expect("Error converting test buffer to JSON");
+
+    assert_eq!(events.len(), 10);
+}
@@ -0,0 +1,103 @@
+use helpers::MockWriter;
+use serde_json::Value;
+use std::{
+    sync::{Arc, Mutex},
+    thread,
+    time::Duration,
+};
+use tracing_stackdriver_cw::{spawn_heartbeat, EntryCounts, Heartbeat};
+use tracing_subscriber::{layer::SubscriberExt, Registry};
+
+mod helpers;
+
+#[test]
+fn writes_heartbeats_until_the_guard_is_dropped() {
+    let buffer = Arc::new(Mutex::new(vec![]));
+    let shared = buffer.clone();
+    let entry_counts = EntryCounts::default();
+
+    let guard = spawn_heartbeat(
+        Heartbeat {
+            service: "checkout".to_string(),
+            version: Some("1.4.2".to_string()),
+            interval: Duration::from_millis(20),
+            entry_counts: Some(entry_counts.clone()),
+        },
+        move || MockWriter(shared.clone()),
+    );
+
+    let stackdriver = tracing_stackdriver_cw::layer()
+        .with_entry_counts(entry_counts)
+        .with_writer(std::io::sink);
+    let subscriber = Registry::default().with(stackdriver);
+
+    tracing::subscriber::with_default(subscriber, || tracing::warn!("counted"));
+
+    thread::sleep(Duration::from_millis(70));
+    drop(guard);
+    thread::sleep(Duration::from_millis(30));
+
+    let written = buffer.lock().unwrap().len();
+    thread::sleep(Duration::from_millis(50));
+
+    let buffer = buffer.lock().unwrap();
+    assert_eq!(
+        buffer.len(),
+        written,
+        "heartbeats continued after the guard was dropped"
+    );
+
+    let heartbeats = serde_json::Deserializer::from_slice(&buffer)
+        .into_iter::<Value>()
+        .collect::<Result<Vec<_>, _>>()
+        .unwrap();
+
+    assert!(heartbeats.len() >= 2);
+    assert_eq!(heartbeats[0]["message"], "heartbeat");
+    assert_eq!(heartbeats[0]["severity"], "INFO");
+    assert_eq!(heartbeats[0]["service"], "checkout");
+    assert_eq!(heartbeats[0]["version"], "1.4.2");
+    assert_eq!(heartbeats[0]["sequence"], 1);
+    assert_eq!(heartbeats[1]["sequence"], 2);
+    assert_eq!(heartbeats[1]["entries"]["WARNING"], 1);
+}
+
+#[test]
+fn writes_each_heartbeat_with_its_newline_at_once() {
+    let writes = Arc::new(Mutex::new(Vec::<Vec<u8>>::new()));
+    let shared = writes.clone();
+
+    /// Writer keeping each write it receives separately
+    struct Writes(Arc<Mutex<Vec<Vec<u8>>>>);
+
+    impl std::io::Write for Writes {
+        fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+            self.0.lock().unwrap().push(buf.to_vec());
+            Ok(buf.len())
+        }
+
+        fn flush(&mut self) -> std::io::Result<()> {
+            Ok(())
+        }
+    }
+
+    let guard = spawn_heartbeat(
+        Heartbeat {
+            service: "checkout".to_string(),
+            interval: Duration::from_millis(20),
+            ..Heartbeat::default()
+        },
+        move || Writes(shared.clone()),
+    );
+
+    thread::sleep(Duration::from_millis(50));
+    drop(guard);
+
+    let writes = writes.lock().unwrap();
+    assert!(!writes.is_empty());
+
+    for write in writes.iter() {
+        assert!(write.ends_with(b"\n"));
+        serde_json::from_slice::<Value>(write).expect("a whole heartbeat");
+    }
+}
@@ -0,0 +1,38 @@
+#![cfg(feature = "http")]
+use helpers::run_with_tracing_layer;
+use serde_json::Value;
+use tracing_stackdriver_cw::SpanExt;
+
+mod helpers;
+
+#[test]
+fn populates_http_requests_from_request_parts() {
+    let (parts, _) = http::Request::get("https://example.com/orders?page=2")
+        .header("user-agent", "curl/8.0")
+        .header("referer", "https://example.com/")
+        .header("x-forwarded-for", "203.0.113.7, 10.0.0.1")
+        .body(())
+        .unwrap()
+        .into_parts();
+
+    let events = run_with_tracing_layer::<Value>(tracing_stackdriver_cw::layer(), || {
+        let span = tracing::info_span!("request");
+        span.record_http_request(&parts);
+        let _span = span.enter();
+
+        tracing::info!(http_request.status = 200, "handled");
+    })
+    .expect("Error converting test buffer to JSON");
+
+    let http_request = &events[0]["httpRequest"];
+    assert_eq!(http_request["requestMethod"], "GET");
+    assert_eq!(
+        http_request["requestUrl"],
+        "https://example.com/orders?page=2"
+    );
+    assert_eq!(http_request["protocol"], "HTTP/1.1");
+    assert_eq!(http_request["userAgent"], "curl/8.0");
+    assert_eq!(http_request["referer"], "https://example.com/");
+    assert_eq!(http_request["remoteIp"], "203.0.113.7");
+    assert_eq!(http_request["status"], 200);
+}
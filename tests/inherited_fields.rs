@@ -0,0 +1,33 @@
+use helpers::run_with_tracing_layer;
+use serde_json::Value;
+
+mod helpers;
+
+#[test]
+fn stamps_inherited_span_fields_on_descendant_events() {
+    let layer = tracing_stackdriver_cw::layer().with_inherited_fields(["request_id", "user_id"]);
+    let events = run_with_tracing_layer::<Value>(layer, || {
+        let request = tracing::info_span!(
+            "request",
+            request_id = "abc",
+            user_id = tracing::field::Empty,
+            path = "/"
+        );
+        let _request = request.enter();
+        request.record("user_id", 42);
+
+        let handler = tracing::info_span!("handler");
+        let _handler = handler.enter();
+        let query = tracing::info_span!("query");
+        let _query = query.enter();
+
+        tracing::info!("deeply nested");
+        tracing::info!(request_id = "override", "overridden");
+    })
+    .expect("Error converting test buffer to JSON");
+
+    assert_eq!(events[0]["requestId"], "abc");
+    assert_eq!(events[0]["userId"], 42);
+    assert!(events[0].get("path").is_none());
+    assert_eq!(events[1]["requestId"], "override");
+}
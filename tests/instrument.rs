@@ -0,0 +1,67 @@
+use helpers::run_with_tracing_layer;
+use serde_json::{json, Value};
+use std::{error::Error, fmt};
+use tracing_stackdriver_cw::InstrumentConfiguration;
+
+mod helpers;
+
+#[derive(Debug)]
+struct QueryError {
+    source: fmt::Error,
+}
+
+impl fmt::Display for QueryError {
+    fn fmt(&self, formatter: &mut fmt::Formatter<'_>) -> fmt::Result {
+        formatter.write_str("query failed")
+    }
+}
+
+impl Error for QueryError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        Some(&self.source)
+    }
+}
+
+#[tracing::instrument(err(level = "warn"))]
+fn failing() -> Result<(), QueryError> {
+    Err(QueryError { source: fmt::Error })
+}
+
+#[tracing::instrument(ret)]
+fn returning() -> Vec<u8> {
+    vec![1, 2]
+}
+
+#[test]
+fn structures_instrument_errors_and_return_values() {
+    let layer = tracing_stackdriver_cw::layer().with_instrument_fields(InstrumentConfiguration {
+        return_key: "result".to_string(),
+        ..Default::default()
+    });
+    let events = run_with_tracing_layer::<Value>(layer, || {
+        let _ = failing();
+        returning();
+
+        let error = QueryError { source: fmt::Error };
+        tracing::error!(error = &error as &dyn Error);
+    })
+    .expect("Error converting test buffer to JSON");
+
+    assert_eq!(events[0]["severity"], "ERROR");
+    assert_eq!(events[0]["message"], "query failed");
+    assert_eq!(
+        events[0]["error"],
+        json!({ "message": "query failed", "chain": [] })
+    );
+
+    assert_eq!(events[1]["result"], "[1, 2]");
+    assert!(events[1].get("return").is_none());
+
+    assert_eq!(
+        events[2]["error"],
+        json!({
+            "message": "query failed",
+            "chain": ["an error occurred when formatting an argument"],
+        })
+    );
+}
@@ -1,6 +1,8 @@
-use helpers::run_with_tracing;
+use helpers::{run_with_tracing, run_with_tracing_layer};
 use mocks::MockDefaultEvent;
+use serde_json::{json, Value};
 use std::collections::BTreeMap;
+use tracing_stackdriver_cw::BuildInfo;
 
 mod helpers;
 mod mocks;
@@ -56,3 +58,27 @@ fn omits_labels_by_default() {
     let event = events.first().expect("No event heard");
     assert!(event.labels.is_empty());
 }
+
+#[test]
+fn labels_entries_with_build_info() {
+    let layer = tracing_stackdriver_cw::layer().with_build_info(BuildInfo {
+        sha: Some("0ec4692".to_string()),
+        timestamp: None,
+        rustc_version: Some("1.80.0".to_string()),
+        ..tracing_stackdriver_cw::build_info!()
+    });
+    let events = run_with_tracing_layer::<Value>(layer, || {
+        tracing::info!(labels.team = "payments", "hello!");
+    })
+    .expect("Error converting test buffer to JSON");
+
+    assert_eq!(
+        events[0]["logging.googleapis.com/labels"],
+        json!({
+            "build_version": env!("CARGO_PKG_VERSION"),
+            "build_sha": "0ec4692",
+            "build_rustc_version": "1.80.0",
+            "team": "payments",
+        })
+    );
+}
@@ -0,0 +1,20 @@
+use helpers::run_with_tracing_layer;
+use mocks::MockLegacyEvent;
+use time::OffsetDateTime;
+
+mod helpers;
+mod mocks;
+
+#[test]
+fn splits_timestamps_for_legacy_agents() {
+    let before = OffsetDateTime::now_utc().unix_timestamp();
+    let layer = tracing_stackdriver_cw::layer().with_legacy_agent_fields(true);
+    let events = run_with_tracing_layer::<MockLegacyEvent>(layer, || tracing::warn!("hello!"))
+        .expect("Error converting test buffer to JSON");
+
+    let event = events.first().expect("No event heard");
+    assert!(event.timestamp_seconds >= before);
+    assert!(event.timestamp_nanos < 1_000_000_000);
+    assert!(event.time.is_none());
+    assert_eq!(event.severity, "WARNING");
+}
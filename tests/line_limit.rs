@@ -0,0 +1,56 @@
+use helpers::run_with_tracing_layer;
+use serde_json::Value;
+use tracing_stackdriver_cw::LineLimit;
+
+mod helpers;
+
+#[test]
+fn truncates_oversized_entries() {
+    let message = "a".repeat(20_000);
+    let layer = tracing_stackdriver_cw::layer().with_line_limit(LineLimit::Truncate(16_000));
+    let events = run_with_tracing_layer::<Value>(layer, || tracing::info!("{}", message))
+        .expect("Error converting test buffer to JSON");
+
+    assert_eq!(events.len(), 1);
+    assert!(events[0].to_string().len() <= 16_000);
+
+    let message = events[0]["message"].as_str().unwrap();
+    assert!(message.starts_with("aaa"));
+    assert!(message.contains("…[truncated "));
+}
+
+#[test]
+fn splits_oversized_entries() {
+    let message = "é\"".repeat(10_000);
+    let layer = tracing_stackdriver_cw::layer().with_line_limit(LineLimit::Split(16_000));
+    let events =
+        run_with_tracing_layer::<Value>(layer, || tracing::info!(user_id = 1, "{}", message))
+            .expect("Error converting test buffer to JSON");
+
+    assert!(events.len() > 1);
+
+    let uid = &events[0]["logging.googleapis.com/split"]["uid"];
+    let mut reassembled = String::new();
+
+    for (index, event) in events.iter().enumerate() {
+        let split = &event["logging.googleapis.com/split"];
+        assert!(event.to_string().len() <= 16_000);
+        assert_eq!(&split["uid"], uid);
+        assert_eq!(split["index"], index);
+        assert_eq!(split["totalSplits"], events.len());
+        assert_eq!(event["userId"], 1);
+        reassembled.push_str(event["message"].as_str().unwrap());
+    }
+
+    assert_eq!(reassembled, message);
+}
+
+#[test]
+fn keeps_entries_within_the_limit() {
+    let layer = tracing_stackdriver_cw::layer().with_line_limit(LineLimit::Split(16_000));
+    let events = run_with_tracing_layer::<Value>(layer, || tracing::info!("short"))
+        .expect("Error converting test buffer to JSON");
+
+    assert_eq!(events.len(), 1);
+    assert!(events[0].get("logging.googleapis.com/split").is_none());
+}
@@ -0,0 +1,55 @@
+use helpers::run_with_tracing_layer;
+use serde_json::json;
+use tracing_stackdriver_cw::{CloudTraceConfiguration, LogEntry, LogSeverity};
+
+mod helpers;
+
+#[test]
+fn deserializes_written_entries() {
+    let layer = tracing_stackdriver_cw::layer()
+        .with_cloud_trace(CloudTraceConfiguration {
+            project_id: "my-project".to_string(),
+        })
+        .with_span_operations(true);
+
+    let entries = run_with_tracing_layer::<LogEntry>(layer, || {
+        let span = tracing::info_span!("request", trace_id = "4bf92f3577b34da6a3ce929d0e0e4736");
+        let _span = span.enter();
+
+        tracing::warn!(labels.region = "us-east1", attempt = 2, "retrying");
+    })
+    .expect("Error converting test buffer to LogEntry");
+
+    let entry = &entries[0];
+    assert_eq!(entry.severity, LogSeverity::Warning);
+    assert_eq!(entry.message.as_deref(), Some("retrying"));
+    assert_eq!(entry.labels["region"], "us-east1");
+    assert_eq!(
+        entry.trace.as_deref(),
+        Some("projects/my-project/traces/4bf92f3577b34da6a3ce929d0e0e4736")
+    );
+    assert_eq!(entry.operation.as_ref().unwrap().producer, "request");
+    assert!(entry.source_location.as_ref().unwrap().line.is_some());
+    assert_eq!(entry.fields["attempt"], 2);
+    assert!(entry.time.is_some());
+}
+
+#[test]
+fn round_trips_entries() {
+    let entry: LogEntry = serde_json::from_value(json!({
+        "severity": "ERROR",
+        "message": "failed",
+        "logging.googleapis.com/insertId": "42",
+        "logging.googleapis.com/trace_sampled": true,
+        "code": 7,
+    }))
+    .unwrap();
+
+    assert_eq!(entry.severity, LogSeverity::Error);
+    assert_eq!(entry.insert_id.as_deref(), Some("42"));
+    assert_eq!(entry.trace_sampled, Some(true));
+    assert_eq!(
+        serde_json::from_value::<LogEntry>(serde_json::to_value(&entry).unwrap()).unwrap(),
+        entry
+    );
+}
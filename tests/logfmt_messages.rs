@@ -0,0 +1,45 @@
+use helpers::run_with_tracing_layer;
+use serde_json::Value;
+
+mod helpers;
+
+#[test]
+fn lifts_key_values_from_messages() {
+    let layer = tracing_stackdriver_cw::layer().with_logfmt_messages(true);
+    let events = run_with_tracing_layer::<Value>(layer, || {
+        tracing::info!(
+            user = "recorded",
+            "request done user_id=42 path=\"/a \\\"b\\\"\" ratio=0.5 cached=true status=ok, \
+             severity=error user=lifted a=b=c"
+        );
+        tracing::info!("nothing to lift, see https://example.com/?a=b");
+    })
+    .expect("Error converting test buffer to JSON");
+
+    let event = &events[0];
+    assert_eq!(event["userId"], 42);
+    assert_eq!(event["path"], "/a \"b\"");
+    assert_eq!(event["ratio"], 0.5);
+    assert_eq!(event["cached"], true);
+    assert_eq!(event["status"], "ok");
+    assert_eq!(event["a"], "b=c");
+    assert_eq!(event["user"], "recorded");
+    assert_eq!(event["user.severity"], "error");
+    assert_eq!(event["severity"], "INFO");
+    assert!(event["message"]
+        .as_str()
+        .unwrap()
+        .starts_with("request done user_id=42"));
+
+    assert_eq!(events[1].as_object().unwrap().len(), 5);
+}
+
+#[test]
+fn keeps_messages_as_is_by_default() {
+    let events = run_with_tracing_layer::<Value>(tracing_stackdriver_cw::layer(), || {
+        tracing::info!("request done user_id=42");
+    })
+    .expect("Error converting test buffer to JSON");
+
+    assert!(events[0].get("userId").is_none());
+}
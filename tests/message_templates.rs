@@ -0,0 +1,42 @@
+use helpers::run_with_tracing_layer;
+use serde_json::Value;
+
+mod helpers;
+
+#[test]
+fn writes_message_templates() {
+    let layer = tracing_stackdriver_cw::layer().with_message_templates(true);
+    let events = run_with_tracing_layer::<Value>(layer, || {
+        for (user, address) in [(42, "10.0.0.1"), (7, "10.0.0.2")] {
+            tracing::info!("user {} logged in from {}.", user, address);
+        }
+
+        tracing::info!("renamed '{}' to \"{}\" after {} attempts", "a", "b", 3);
+        tracing::info!("request done status=200 path=/health (took 12ms)");
+        tracing::info!("started");
+    })
+    .expect("Error converting test buffer to JSON");
+
+    assert_eq!(events[0]["message"], "user 42 logged in from 10.0.0.1.");
+    assert_eq!(events[0]["messageTemplate"], "user {} logged in from {}.");
+    assert_eq!(events[1]["messageTemplate"], events[0]["messageTemplate"]);
+    assert_eq!(
+        events[2]["messageTemplate"],
+        "renamed {} to {} after {} attempts"
+    );
+    assert_eq!(
+        events[3]["messageTemplate"],
+        "request done status={} path=/health (took {})"
+    );
+    assert_eq!(events[4]["messageTemplate"], "started");
+}
+
+#[test]
+fn omits_message_templates_by_default() {
+    let events = run_with_tracing_layer::<Value>(tracing_stackdriver_cw::layer(), || {
+        tracing::info!("user {} logged in", 42);
+    })
+    .expect("Error converting test buffer to JSON");
+
+    assert!(events[0].get("messageTemplate").is_none());
+}
@@ -0,0 +1,15 @@
+#![cfg(feature = "metadata")]
+use std::env;
+use tracing_stackdriver_cw::CloudTraceConfiguration;
+
+#[test]
+fn detects_the_project_from_the_environment() {
+    env::set_var("GOOGLE_CLOUD_PROJECT", "my-project");
+    let configuration = CloudTraceConfiguration::detect();
+    env::remove_var("GOOGLE_CLOUD_PROJECT");
+
+    assert_eq!(
+        configuration.map(|configuration| configuration.project_id),
+        Some("my-project".to_string())
+    );
+}
@@ -0,0 +1,38 @@
+use helpers::run_with_tracing_layer;
+use serde_json::{json, Value};
+
+mod helpers;
+
+#[test]
+fn gathers_metric_fields() {
+    let layer = tracing_stackdriver_cw::layer().with_metric_fields(true);
+    let events = run_with_tracing_layer::<Value>(layer, || {
+        tracing::info!(
+            metric.order_value = 12.5,
+            metric.orders = 1,
+            order_id = 7,
+            "order placed"
+        );
+        tracing::info!("nothing to measure");
+    })
+    .expect("Error converting test buffer to JSON");
+
+    assert_eq!(
+        events[0]["metrics"],
+        json!({ "orderValue": 12.5, "orders": 1 })
+    );
+    assert_eq!(events[0]["orderId"], 7);
+    assert!(events[0].get("metricOrders").is_none());
+    assert!(events[1].get("metrics").is_none());
+}
+
+#[test]
+fn writes_metric_fields_as_usual_by_default() {
+    let events = run_with_tracing_layer::<Value>(tracing_stackdriver_cw::layer(), || {
+        tracing::info!(metric.orders = 1, "order placed");
+    })
+    .expect("Error converting test buffer to JSON");
+
+    assert_eq!(events[0]["metricOrders"], 1);
+    assert!(events[0].get("metrics").is_none());
+}
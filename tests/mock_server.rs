@@ -0,0 +1,116 @@
+#![allow(dead_code)]
+use std::{
+    collections::BTreeMap,
+    io::{BufRead, BufReader, Read, Write},
+    net::{TcpListener, TcpStream},
+    sync::mpsc,
+    thread,
+};
+
+/// Request received by a MockServer, with lowercased header names
+#[derive(Debug)]
+pub struct MockRequest {
+    pub method: String,
+    pub path: String,
+    pub headers: BTreeMap<String, String>,
+    pub body: Vec<u8>,
+}
+
+/// Minimal HTTP/1.1 server responding to every request with a fixed status and body
+pub struct MockServer {
+    pub url: String,
+    requests: mpsc::Receiver<MockRequest>,
+}
+
+impl MockServer {
+    pub fn start(status: u16, body: &'static str) -> Self {
+        Self::start_with(move |_| (status, body.to_string()))
+    }
+
+    /// Start a server whose responses are computed from each request
+    pub fn start_with<F>(respond: F) -> Self
+    where
+        F: Fn(&MockRequest) -> (u16, String) + Send + Sync + Clone + 'static,
+    {
+        let listener = TcpListener::bind("127.0.0.1:0").expect("Couldn't bind mock server");
+        let url = format!("http://{}", listener.local_addr().unwrap());
+        let (sender, requests) = mpsc::channel();
+
+        thread::spawn(move || {
+            for stream in listener.incoming().flatten() {
+                let sender = sender.clone();
+                let respond = respond.clone();
+                thread::spawn(move || serve(stream, sender, respond));
+            }
+        });
+
+        Self { url, requests }
+    }
+
+    /// All requests received so far
+    pub fn requests(&self) -> Vec<MockRequest> {
+        self.requests.try_iter().collect()
+    }
+}
+
+fn serve<F>(stream: TcpStream, sender: mpsc::Sender<MockRequest>, respond: F)
+where
+    F: Fn(&MockRequest) -> (u16, String),
+{
+    let mut reader = BufReader::new(stream.try_clone().unwrap());
+    let mut stream = stream;
+
+    loop {
+        let mut request_line = String::new();
+
+        if reader.read_line(&mut request_line).unwrap_or(0) == 0 {
+            return;
+        }
+
+        let mut parts = request_line.split_whitespace();
+        let method = parts.next().unwrap_or_default().to_string();
+        let path = parts.next().unwrap_or_default().to_string();
+        let mut headers = BTreeMap::new();
+
+        loop {
+            let mut line = String::new();
+            reader.read_line(&mut line).unwrap();
+            let line = line.trim_end();
+
+            if line.is_empty() {
+                break;
+            }
+
+            if let Some((name, value)) = line.split_once(':') {
+                headers.insert(name.trim().to_lowercase(), value.trim().to_string());
+            }
+        }
+
+        let length = headers
+            .get("content-length")
+            .and_then(|length| length.parse().ok())
+            .unwrap_or(0);
+        let mut body = vec![0; length];
+        reader.read_exact(&mut body).unwrap();
+
+        let request = MockRequest {
+            method,
+            path,
+            headers,
+            body,
+        };
+        let (status, body) = respond(&request);
+        let _ = sender.send(request);
+
+        let response = format!(
+            "HTTP/1.1 {} Mock\r\nContent-Type: application/json\r\nContent-Length: {}\r\n\r\n{}",
+            status,
+            body.len(),
+            body
+        );
+
+        if stream.write_all(response.as_bytes()).is_err() {
+            return;
+        }
+    }
+}
@@ -1,3 +1,4 @@
+#![allow(dead_code)]
 use serde::Deserialize;
 use std::collections::BTreeMap;
 use time::OffsetDateTime;
@@ -47,3 +48,34 @@ pub struct MockHttpRequest {
 pub struct MockHttpEvent {
     pub http_request: MockHttpRequest,
 }
+
+#[derive(Debug, Deserialize)]
+pub struct MockTraceEvent {
+    #[serde(rename = "logging.googleapis.com/trace")]
+    pub trace: Option<String>,
+    #[serde(rename = "logging.googleapis.com/spanId")]
+    pub span_id: Option<String>,
+    #[serde(rename = "logging.googleapis.com/trace_sampled")]
+    pub trace_sampled: Option<bool>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct MockLegacyEvent {
+    pub timestamp_seconds: i64,
+    pub timestamp_nanos: u32,
+    pub time: Option<String>,
+    pub severity: String,
+}
+
+#[derive(Debug, Deserialize, PartialEq)]
+pub struct MockOperation {
+    pub id: String,
+    pub producer: String,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct MockOperationEvent {
+    #[serde(rename = "logging.googleapis.com/operation")]
+    pub operation: Option<MockOperation>,
+}
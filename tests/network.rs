@@ -0,0 +1,59 @@
+#![cfg(feature = "network")]
+use std::{
+    io::{BufRead, BufReader, Write},
+    net::{TcpListener, UdpSocket},
+    time::Duration,
+};
+use tracing_stackdriver_cw::NetworkWriter;
+
+#[test]
+fn ships_entries_over_tcp() {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let mut writer = NetworkWriter::tcp(listener.local_addr().unwrap().to_string());
+
+    writer
+        .write_all(b"{\"message\":\"first\"}\n{\"mess")
+        .unwrap();
+    writer.write_all(b"age\":\"second\"}\n").unwrap();
+
+    let (stream, _) = listener.accept().unwrap();
+    let mut lines = BufReader::new(stream).lines();
+    assert_eq!(lines.next().unwrap().unwrap(), r#"{"message":"first"}"#);
+    assert_eq!(lines.next().unwrap().unwrap(), r#"{"message":"second"}"#);
+}
+
+#[test]
+fn buffers_entries_until_reconnected() {
+    // reserve a port that nothing listens on yet
+    let address = TcpListener::bind("127.0.0.1:0")
+        .unwrap()
+        .local_addr()
+        .unwrap();
+    let mut writer =
+        NetworkWriter::tcp(address.to_string()).with_reconnect_interval(Duration::ZERO);
+
+    writer.write_all(b"{\"message\":\"buffered\"}\n").unwrap();
+
+    let listener = TcpListener::bind(address).unwrap();
+    writer.write_all(b"{\"message\":\"connected\"}\n").unwrap();
+
+    let (stream, _) = listener.accept().unwrap();
+    let mut lines = BufReader::new(stream).lines();
+    assert_eq!(lines.next().unwrap().unwrap(), r#"{"message":"buffered"}"#);
+    assert_eq!(lines.next().unwrap().unwrap(), r#"{"message":"connected"}"#);
+}
+
+#[test]
+fn ships_entries_over_udp() {
+    let socket = UdpSocket::bind("127.0.0.1:0").unwrap();
+    socket
+        .set_read_timeout(Some(Duration::from_secs(5)))
+        .unwrap();
+    let mut writer = NetworkWriter::udp(socket.local_addr().unwrap().to_string());
+
+    writer.write_all(b"{\"message\":\"datagram\"}\n").unwrap();
+
+    let mut datagram = [0; 1024];
+    let length = socket.recv(&mut datagram).unwrap();
+    assert_eq!(&datagram[..length], b"{\"message\":\"datagram\"}\n");
+}
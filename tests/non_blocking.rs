@@ -0,0 +1,404 @@
+use helpers::MockWriter;
+use mocks::MockDefaultEvent;
+use serde::Deserialize;
+use std::{
+    io,
+    sync::{mpsc, Arc, Mutex},
+    thread,
+    time::Duration,
+};
+use tracing_stackdriver_cw::{Backpressure, NonBlockingBuilder};
+use tracing_subscriber::{layer::SubscriberExt, Registry};
+
+mod helpers;
+mod mocks;
+
+/// Writer that takes a while to accept each entry
+struct SlowWriter(MockWriter);
+
+impl io::Write for SlowWriter {
+    fn write(&mut self, buffer: &[u8]) -> io::Result<usize> {
+        thread::sleep(Duration::from_millis(10));
+        self.0.write(buffer)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.0.flush()
+    }
+}
+
+/// Writer that holds up its first entry until released, so that the buffer fills up behind it
+struct GateWriter {
+    writer: MockWriter,
+    started: mpsc::Sender<()>,
+    release: Option<mpsc::Receiver<()>>,
+}
+
+impl io::Write for GateWriter {
+    fn write(&mut self, buffer: &[u8]) -> io::Result<usize> {
+        if let Some(release) = self.release.take() {
+            self.started.send(()).expect("Test hung up");
+            release.recv().expect("Test hung up");
+        }
+
+        self.writer.write(buffer)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.writer.flush()
+    }
+}
+
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum MockWorkerEntry {
+    #[serde(rename_all = "camelCase")]
+    DropReport {
+        severity: String,
+        dropped_entries: u64,
+    },
+    Indexed {
+        index: u16,
+    },
+}
+
+/// Emit `count` indexed events into a capacity-2 buffer stuck behind its first entry,
+/// returning the indices written and the number of drops reported by the worker
+fn run_with_full_buffer(backpressure: Backpressure, count: u16) -> (Vec<u16>, u64) {
+    let buffer = Arc::new(Mutex::new(vec![]));
+    let (started_sender, started) = mpsc::channel();
+    let (release, release_receiver) = mpsc::channel();
+    let writer = GateWriter {
+        writer: MockWriter(buffer.clone()),
+        started: started_sender,
+        release: Some(release_receiver),
+    };
+    let (non_blocking, guard) = NonBlockingBuilder::default()
+        .with_capacity(2)
+        .with_backpressure(backpressure)
+        .finish(writer);
+    let stackdriver = tracing_stackdriver_cw::layer().with_writer(non_blocking.clone());
+    let subscriber = Registry::default().with(stackdriver);
+
+    tracing::subscriber::with_default(subscriber, || {
+        tracing::info!(index = 0, "first message");
+        started.recv().expect("Worker hung up");
+
+        for index in 1..count {
+            tracing::info!(index, "buffered message");
+        }
+    });
+
+    release.send(()).expect("Worker hung up");
+    drop(guard);
+
+    let buffer = buffer
+        .lock()
+        .expect("Couldn't get lock on test write target");
+    let entries = serde_json::Deserializer::from_slice(&buffer)
+        .into_iter::<MockWorkerEntry>()
+        .collect::<serde_json::Result<Vec<_>>>()
+        .expect("Error converting test buffer to JSON");
+
+    let mut indices = vec![];
+    let mut reported = 0;
+
+    for entry in entries {
+        match entry {
+            MockWorkerEntry::Indexed { index } => indices.push(index),
+            MockWorkerEntry::DropReport {
+                severity,
+                dropped_entries,
+            } => {
+                assert_eq!(severity, "WARNING");
+                reported += dropped_entries;
+            }
+        }
+    }
+
+    assert_eq!(reported, non_blocking.dropped_entries());
+    (indices, reported)
+}
+
+fn parse(buffer: &Mutex<Vec<u8>>) -> Vec<MockDefaultEvent> {
+    let buffer = buffer
+        .lock()
+        .expect("Couldn't get lock on test write target");
+
+    serde_json::Deserializer::from_slice(&buffer)
+        .into_iter()
+        .collect::<serde_json::Result<_>>()
+        .expect("Error converting test buffer to JSON")
+}
+
+#[test]
+fn flushes_buffered_entries_on_guard_drop() {
+    let buffer = Arc::new(Mutex::new(vec![]));
+    let writer = SlowWriter(MockWriter(buffer.clone()));
+    let (stackdriver, guard) = tracing_stackdriver_cw::layer().with_non_blocking(writer);
+    let subscriber = Registry::default().with(stackdriver);
+
+    tracing::subscriber::with_default(subscriber, || {
+        for index in 0..10 {
+            tracing::info!(index, "buffered message");
+        }
+    });
+
+    drop(guard);
+    assert_eq!(parse(&buffer).len(), 10);
+}
+
+#[test]
+fn flushes_buffered_entries_on_demand() {
+    let buffer = Arc::new(Mutex::new(vec![]));
+    let writer = SlowWriter(MockWriter(buffer.clone()));
+    let (stackdriver, guard) = tracing_stackdriver_cw::layer().with_non_blocking(writer);
+    let subscriber = Registry::default().with(stackdriver);
+
+    tracing::subscriber::with_default(subscriber, || {
+        for index in 0..10 {
+            tracing::info!(index, "buffered message");
+        }
+
+        assert!(!guard.flush(Duration::from_millis(15)));
+        assert!(guard.flush(Duration::from_secs(5)));
+        assert_eq!(parse(&buffer).len(), 10);
+
+        // the worker keeps writing after a flush
+        tracing::info!("later message");
+        assert!(guard.flush(Duration::from_secs(5)));
+        assert_eq!(parse(&buffer).len(), 11);
+    });
+}
+
+#[test]
+fn stops_waiting_after_shutdown_timeout() {
+    let buffer = Arc::new(Mutex::new(vec![]));
+    let writer = SlowWriter(MockWriter(buffer.clone()));
+    let (non_blocking, guard) = tracing_stackdriver_cw::NonBlockingBuilder::default()
+        .with_shutdown_timeout(Duration::from_millis(15))
+        .finish(writer);
+    let stackdriver = tracing_stackdriver_cw::layer().with_writer(non_blocking);
+    let subscriber = Registry::default().with(stackdriver);
+
+    tracing::subscriber::with_default(subscriber, || {
+        for index in 0..100 {
+            tracing::info!(index, "buffered message");
+        }
+    });
+
+    drop(guard);
+    assert!(parse(&buffer).len() < 100);
+}
+
+#[test]
+fn drops_newest_entries_when_full() {
+    let (indices, dropped) = run_with_full_buffer(Backpressure::DropNewest, 5);
+    assert_eq!(indices, vec![0, 1, 2]);
+    assert_eq!(dropped, 2);
+}
+
+#[test]
+fn drops_oldest_entries_when_full() {
+    let (indices, dropped) = run_with_full_buffer(Backpressure::DropOldest, 5);
+    assert_eq!(indices, vec![0, 3, 4]);
+    assert_eq!(dropped, 2);
+}
+
+#[test]
+fn drops_blocked_entries_after_timeout() {
+    let backpressure = Backpressure::Block {
+        timeout: Some(Duration::from_millis(10)),
+    };
+    let (indices, dropped) = run_with_full_buffer(backpressure, 4);
+    assert_eq!(indices, vec![0, 1, 2]);
+    assert_eq!(dropped, 1);
+}
+
+#[test]
+fn omits_drop_reports_when_disabled() {
+    let buffer = Arc::new(Mutex::new(vec![]));
+    let (non_blocking, guard) = NonBlockingBuilder::default()
+        .with_capacity(1)
+        .with_drop_report_interval(None)
+        .finish(SlowWriter(MockWriter(buffer.clone())));
+    let stackdriver = tracing_stackdriver_cw::layer().with_writer(non_blocking.clone());
+    let subscriber = Registry::default().with(stackdriver);
+
+    tracing::subscriber::with_default(subscriber, || {
+        for index in 0..10 {
+            tracing::info!(index, "buffered message");
+        }
+    });
+
+    drop(guard);
+    assert!(non_blocking.dropped_entries() > 0);
+    assert_eq!(
+        parse(&buffer).len() as u64 + non_blocking.dropped_entries(),
+        10
+    );
+}
+
+#[test]
+fn counts_entries_written_after_shutdown_as_dropped() {
+    let buffer = Arc::new(Mutex::new(vec![]));
+    let (non_blocking, guard) = NonBlockingBuilder::default().finish(MockWriter(buffer.clone()));
+    let stackdriver = tracing_stackdriver_cw::layer().with_writer(non_blocking.clone());
+    let subscriber = Registry::default().with(stackdriver);
+
+    tracing::subscriber::with_default(subscriber, || {
+        tracing::info!("buffered message");
+        drop(guard);
+        tracing::info!("late message");
+    });
+
+    assert_eq!(parse(&buffer).len(), 1);
+    assert_eq!(non_blocking.dropped_entries(), 1);
+}
+
+#[cfg(feature = "tokio")]
+mod tokio_worker {
+    use super::{parse, MockWorkerEntry};
+    use helpers::MockWriter;
+    use std::{
+        io::Write,
+        pin::Pin,
+        sync::{Arc, Mutex},
+        task::{Context, Poll},
+        time::Duration,
+    };
+    use tokio::{io::AsyncWrite, runtime::Runtime};
+    use tracing_stackdriver_cw::{Backpressure, NonBlockingBuilder};
+    use tracing_subscriber::{layer::SubscriberExt, Registry};
+
+    use super::helpers;
+
+    /// Async writer to a shared buffer
+    struct MockAsyncWriter(MockWriter);
+
+    impl AsyncWrite for MockAsyncWriter {
+        fn poll_write(
+            mut self: Pin<&mut Self>,
+            _: &mut Context<'_>,
+            buffer: &[u8],
+        ) -> Poll<std::io::Result<usize>> {
+            Poll::Ready(self.0.write(buffer))
+        }
+
+        fn poll_flush(mut self: Pin<&mut Self>, _: &mut Context<'_>) -> Poll<std::io::Result<()>> {
+            Poll::Ready(self.0.flush())
+        }
+
+        fn poll_shutdown(self: Pin<&mut Self>, _: &mut Context<'_>) -> Poll<std::io::Result<()>> {
+            Poll::Ready(Ok(()))
+        }
+    }
+
+    /// Emit `count` indexed events into a capacity-2 channel of a worker task that doesn't run
+    /// until the guard is dropped, returning the indices written and the number of drops reported
+    fn run_with_full_channel(backpressure: Backpressure, count: u16) -> (Vec<u16>, u64) {
+        let runtime = tokio::runtime::Builder::new_current_thread()
+            .enable_time()
+            .build()
+            .unwrap();
+        let buffer = Arc::new(Mutex::new(vec![]));
+        let (non_blocking, guard) = {
+            let _runtime = runtime.enter();
+
+            NonBlockingBuilder::default()
+                .with_capacity(2)
+                .with_backpressure(backpressure)
+                .spawn(MockAsyncWriter(MockWriter(buffer.clone())))
+        };
+        let stackdriver = tracing_stackdriver_cw::layer().with_writer(non_blocking.clone());
+        let subscriber = Registry::default().with(stackdriver);
+
+        tracing::subscriber::with_default(subscriber, || {
+            for index in 0..count {
+                tracing::info!(index, "buffered message");
+            }
+        });
+
+        // the worker task runs while the guard waits for it on another thread
+        runtime
+            .block_on(async move { tokio::task::spawn_blocking(move || drop(guard)).await })
+            .unwrap();
+
+        let buffer = buffer
+            .lock()
+            .expect("Couldn't get lock on test write target");
+        let entries = serde_json::Deserializer::from_slice(&buffer)
+            .into_iter::<MockWorkerEntry>()
+            .collect::<serde_json::Result<Vec<_>>>()
+            .expect("Error converting test buffer to JSON");
+
+        let mut indices = vec![];
+        let mut reported = 0;
+
+        for entry in entries {
+            match entry {
+                MockWorkerEntry::Indexed { index } => indices.push(index),
+                MockWorkerEntry::DropReport {
+                    severity,
+                    dropped_entries,
+                } => {
+                    assert_eq!(severity, "WARNING");
+                    reported += dropped_entries;
+                }
+            }
+        }
+
+        assert_eq!(reported, non_blocking.dropped_entries());
+        (indices, reported)
+    }
+
+    #[test]
+    fn writes_entries_from_a_worker_task() {
+        let runtime = Runtime::new().unwrap();
+        let buffer = Arc::new(Mutex::new(vec![]));
+        let (non_blocking, guard) = {
+            let _runtime = runtime.enter();
+            NonBlockingBuilder::default().spawn(MockAsyncWriter(MockWriter(buffer.clone())))
+        };
+        let stackdriver = tracing_stackdriver_cw::layer().with_writer(non_blocking);
+        let subscriber = Registry::default().with(stackdriver);
+
+        tracing::subscriber::with_default(subscriber, || {
+            for index in 0..10 {
+                tracing::info!(index, "buffered message");
+            }
+
+            assert!(guard.flush(Duration::from_secs(5)));
+            assert_eq!(parse(&buffer).len(), 10);
+
+            tracing::info!("later message");
+        });
+
+        drop(guard);
+        assert_eq!(parse(&buffer).len(), 11);
+    }
+
+    #[test]
+    fn drops_newest_entries_when_the_channel_is_full() {
+        let (indices, dropped) = run_with_full_channel(Backpressure::DropNewest, 5);
+        assert_eq!(indices, vec![0, 1]);
+        assert_eq!(dropped, 3);
+    }
+
+    #[test]
+    fn drops_oldest_entries_when_the_channel_is_full() {
+        let (indices, dropped) = run_with_full_channel(Backpressure::DropOldest, 5);
+        assert_eq!(indices, vec![3, 4]);
+        assert_eq!(dropped, 3);
+    }
+
+    #[test]
+    fn drops_blocked_entries_after_timeout_when_the_channel_is_full() {
+        let backpressure = Backpressure::Block {
+            timeout: Some(Duration::from_millis(10)),
+        };
+        let (indices, dropped) = run_with_full_channel(backpressure, 3);
+        assert_eq!(indices, vec![0, 1]);
+        assert_eq!(dropped, 1);
+    }
+}
@@ -0,0 +1,41 @@
+use helpers::run_with_tracing_layer;
+use mocks::MockOperationEvent;
+
+mod helpers;
+mod mocks;
+
+#[test]
+fn groups_events_by_root_span() {
+    let layer = tracing_stackdriver_cw::layer().with_span_operations(true);
+    let events = run_with_tracing_layer::<MockOperationEvent>(layer, || {
+        tracing::info!("outside");
+
+        let root = tracing::info_span!("handle_request");
+        let _root = root.enter();
+        tracing::info!("in root");
+
+        let child = tracing::info_span!("query_database");
+        let _child = child.enter();
+        tracing::info!("in child");
+    })
+    .expect("Error converting test buffer to JSON");
+
+    assert!(events[0].operation.is_none());
+
+    let operation = events[1].operation.as_ref().expect("No operation");
+    assert_eq!(operation.producer, "handle_request");
+    assert_eq!(events[2].operation.as_ref(), Some(operation));
+}
+
+#[test]
+fn excludes_span_operations_by_default() {
+    let layer = tracing_stackdriver_cw::layer();
+    let events = run_with_tracing_layer::<MockOperationEvent>(layer, || {
+        let root = tracing::info_span!("handle_request");
+        let _root = root.enter();
+        tracing::info!("in root");
+    })
+    .expect("Error converting test buffer to JSON");
+
+    assert!(events[0].operation.is_none());
+}
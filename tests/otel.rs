@@ -0,0 +1,44 @@
+#![cfg(feature = "opentelemetry")]
+use opentelemetry::propagation::{Extractor, Injector};
+use std::collections::HashMap;
+use tracing_stackdriver_cw::{TraceContext, TraceContextExtractor, TraceContextInjector};
+
+fn trace_context() -> TraceContext {
+    TraceContext {
+        trace_id: "4bf92f3577b34da6a3ce929d0e0e4736".to_string(),
+        span_id: 0x00f067aa0ba902b7,
+        sampled: true,
+        project_id: None,
+    }
+}
+
+#[test]
+fn round_trips_through_carriers() {
+    let mut carrier = HashMap::new();
+    trace_context().inject(&mut carrier);
+
+    assert_eq!(
+        carrier.get("traceparent").map(String::as_str),
+        Some("00-4bf92f3577b34da6a3ce929d0e0e4736-00f067aa0ba902b7-01")
+    );
+    assert_eq!(TraceContext::extract(&carrier), Some(trace_context()));
+
+    let span_context = trace_context().to_span_context();
+    assert!(span_context.is_remote());
+    assert_eq!(
+        TraceContext::from_span_context(&span_context),
+        Some(trace_context())
+    );
+}
+
+#[test]
+fn adapts_trace_contexts_for_propagators() {
+    let extractor = TraceContextExtractor::from(&trace_context());
+    let mut injector = TraceContextInjector::default();
+
+    for key in extractor.keys() {
+        injector.set(key, extractor.get(key).unwrap().to_string());
+    }
+
+    assert_eq!(injector.into_trace_context(), Some(trace_context()));
+}
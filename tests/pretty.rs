@@ -0,0 +1,72 @@
+use helpers::MockWriter;
+use std::sync::{Arc, Mutex};
+use tracing_stackdriver_cw::PrettyFormatter;
+use tracing_subscriber::{layer::SubscriberExt, Registry};
+
+mod helpers;
+
+fn run_with_pretty_output(ansi: bool, callback: impl FnOnce()) -> String {
+    let buffer = Arc::new(Mutex::new(vec![]));
+    let shared = buffer.clone();
+    let layer = tracing_subscriber::fmt::layer()
+        .with_ansi(ansi)
+        .event_format(PrettyFormatter::default())
+        .with_writer(move || MockWriter(shared.clone()));
+    let subscriber = Registry::default().with(layer);
+
+    tracing::subscriber::with_default(subscriber, callback);
+
+    let buffer = buffer.lock().unwrap();
+    String::from_utf8(buffer.clone()).expect("Error reading pretty output")
+}
+
+#[test]
+fn renders_entries_compactly_with_span_context() {
+    let output = run_with_pretty_output(false, || {
+        let request = tracing::info_span!("request", id = 7);
+        let _request = request.enter();
+        let lookup = tracing::info_span!("lookup", table = "orders");
+        let _lookup = lookup.enter();
+
+        tracing::warn!(
+            http_request.request_method = "GET",
+            http_request.request_url = "/orders",
+            http_request.status = 200,
+            labels.team = "payments",
+            attempt = 2,
+            "order missing"
+        );
+    });
+
+    let lines: Vec<_> = output.lines().collect();
+    assert_eq!(lines.len(), 4, "{}", output);
+    assert!(
+        lines[0].ends_with(" WARNING pretty: order missing attempt=2"),
+        "{}",
+        lines[0]
+    );
+    assert_eq!(lines[1], "    GET /orders 200 [team=payments]");
+    assert_eq!(lines[2], "    in request id=7");
+    assert_eq!(lines[3], "      in lookup table=\"orders\"");
+    assert!(!output.contains('\x1b'));
+}
+
+#[test]
+fn colors_severities() {
+    let output = run_with_pretty_output(true, || {
+        tracing::error!("failed");
+        tracing::info!("done");
+    });
+
+    let lines: Vec<_> = output.lines().collect();
+    assert!(
+        lines[0].contains("\x1b[31m   ERROR\x1b[0m"),
+        "{:?}",
+        lines[0]
+    );
+    assert!(
+        lines[1].contains("\x1b[32m    INFO\x1b[0m"),
+        "{:?}",
+        lines[1]
+    );
+}
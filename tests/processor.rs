@@ -0,0 +1,51 @@
+use helpers::run_with_tracing_layer;
+use serde_json::Value;
+use std::sync::{
+    atomic::{AtomicUsize, Ordering},
+    Arc,
+};
+use tracing_stackdriver_cw::{LogEntry, Verdict};
+
+mod helpers;
+
+#[test]
+fn runs_processors_in_order() {
+    let layer = tracing_stackdriver_cw::layer()
+        .with_processor(|entry: &mut LogEntry| {
+            entry.message = entry.message.take().map(|message| message.to_uppercase());
+            Verdict::Keep
+        })
+        .with_entry_decorator(|entry: &mut LogEntry| {
+            let message = entry.message.clone().unwrap_or_default();
+            entry.fields.insert("seen".to_string(), message.into());
+        });
+    let events = run_with_tracing_layer::<Value>(layer, || tracing::info!("hello"))
+        .expect("Error converting test buffer to JSON");
+
+    assert_eq!(events[0]["message"], "HELLO");
+    assert_eq!(events[0]["seen"], "HELLO");
+}
+
+#[test]
+fn drops_entries_skipping_later_processors() {
+    let later = Arc::new(AtomicUsize::new(0));
+    let counted = later.clone();
+    let layer = tracing_stackdriver_cw::layer()
+        .with_processor(|entry: &mut LogEntry| match entry.fields.get("noisy") {
+            Some(Value::Bool(true)) => Verdict::Drop,
+            _ => Verdict::Keep,
+        })
+        .with_processor(move |_: &mut LogEntry| {
+            counted.fetch_add(1, Ordering::Relaxed);
+            Verdict::Keep
+        });
+    let events = run_with_tracing_layer::<Value>(layer, || {
+        tracing::info!(noisy = true, "dropped");
+        tracing::info!("kept");
+    })
+    .expect("Error converting test buffer to JSON");
+
+    assert_eq!(events.len(), 1);
+    assert_eq!(events[0]["message"], "kept");
+    assert_eq!(later.load(Ordering::Relaxed), 1);
+}
@@ -0,0 +1,101 @@
+#![cfg(feature = "reqwest")]
+use http::Extensions;
+use reqwest_middleware::{
+    reqwest::{header::HeaderMap, Client, Request, Response},
+    ClientBuilder, Middleware, Next,
+};
+use std::{
+    future::Future,
+    pin::pin,
+    sync::{Arc, Mutex},
+    task::{Context, Poll, Waker},
+};
+use tracing_stackdriver_cw::TraceContextMiddleware;
+use tracing_subscriber::{layer::SubscriberExt, Registry};
+
+/// Middleware capturing the headers of requests instead of sending them
+#[derive(Clone, Default)]
+struct CaptureHeaders(Arc<Mutex<Option<HeaderMap>>>);
+
+#[async_trait::async_trait]
+impl Middleware for CaptureHeaders {
+    async fn handle(
+        &self,
+        request: Request,
+        _extensions: &mut Extensions,
+        _next: Next<'_>,
+    ) -> reqwest_middleware::Result<Response> {
+        *self
+            .0
+            .lock()
+            .expect("Couldn't get lock on captured headers") = Some(request.headers().clone());
+
+        Ok(http::Response::new("").into())
+    }
+}
+
+/// Poll a future that never waits on I/O to completion
+fn block_on<F: Future>(future: F) -> F::Output {
+    let mut future = pin!(future);
+    let mut context = Context::from_waker(Waker::noop());
+
+    loop {
+        if let Poll::Ready(output) = future.as_mut().poll(&mut context) {
+            return output;
+        }
+    }
+}
+
+fn send_request() -> HeaderMap {
+    let capture = CaptureHeaders::default();
+    let client = ClientBuilder::new(Client::new())
+        .with(TraceContextMiddleware)
+        .with(capture.clone())
+        .build();
+
+    block_on(client.get("http://example.com").send()).expect("Error sending request");
+
+    let headers = capture
+        .0
+        .lock()
+        .expect("Couldn't get lock on captured headers");
+    headers.clone().expect("No request captured")
+}
+
+#[test]
+fn injects_trace_headers_from_the_current_span() {
+    let subscriber = Registry::default().with(tracing_stackdriver_cw::layer());
+
+    let headers = tracing::subscriber::with_default(subscriber, || {
+        let span = tracing::info_span!(
+            "request",
+            trace_id = "4bf92f3577b34da6a3ce929d0e0e4736",
+            span_id = "00f067aa0ba902b7",
+            trace_sampled = true
+        );
+        let _guard = span.enter();
+        send_request()
+    });
+
+    assert_eq!(
+        headers["traceparent"],
+        "00-4bf92f3577b34da6a3ce929d0e0e4736-00f067aa0ba902b7-01"
+    );
+    assert_eq!(
+        headers["x-cloud-trace-context"],
+        format!(
+            "4bf92f3577b34da6a3ce929d0e0e4736/{};o=1",
+            u64::from_str_radix("00f067aa0ba902b7", 16).unwrap()
+        )
+    );
+}
+
+#[test]
+fn leaves_requests_outside_of_traces_alone() {
+    let subscriber = Registry::default().with(tracing_stackdriver_cw::layer());
+
+    let headers = tracing::subscriber::with_default(subscriber, send_request);
+
+    assert!(headers.get("traceparent").is_none());
+    assert!(headers.get("x-cloud-trace-context").is_none());
+}
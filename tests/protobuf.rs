@@ -0,0 +1,66 @@
+#![cfg(feature = "protobuf")]
+use helpers::MockWriter;
+use std::sync::{Arc, Mutex};
+use tracing_stackdriver_cw::ProtobufWriter;
+use tracing_subscriber::{layer::SubscriberExt, Registry};
+
+mod helpers;
+
+/// Split length-delimited messages, reading each varint length prefix
+fn split_messages(mut buffer: &[u8]) -> Vec<&[u8]> {
+    let mut messages = vec![];
+
+    while !buffer.is_empty() {
+        let (mut length, mut shift, mut read) = (0usize, 0, 0);
+
+        for byte in buffer {
+            length |= usize::from(byte & 0x7f) << shift;
+            shift += 7;
+            read += 1;
+
+            if byte & 0x80 == 0 {
+                break;
+            }
+        }
+
+        messages.push(&buffer[read..read + length]);
+        buffer = &buffer[read + length..];
+    }
+
+    messages
+}
+
+fn contains(message: &[u8], bytes: &[u8]) -> bool {
+    message.windows(bytes.len()).any(|window| window == bytes)
+}
+
+#[test]
+fn writes_length_delimited_log_entries() {
+    let buffer = Arc::new(Mutex::new(vec![]));
+    let writer = Mutex::new(ProtobufWriter::new(
+        "my-project",
+        MockWriter(buffer.clone()),
+    ));
+    let stackdriver = tracing_stackdriver_cw::layer().with_writer(writer);
+    let subscriber = Registry::default().with(stackdriver);
+
+    tracing::subscriber::with_default(subscriber, || {
+        tracing::info!("first message");
+        tracing::error!(labels.service = "api", "second message");
+    });
+
+    let buffer = buffer.lock().unwrap();
+    let messages = split_messages(&buffer);
+    assert_eq!(messages.len(), 2);
+
+    // severity (field 10) as a varint of INFO (200) and ERROR (500)
+    assert!(contains(messages[0], &[0x50, 0xc8, 0x01]));
+    assert!(contains(messages[1], &[0x50, 0xf4, 0x03]));
+    assert!(contains(messages[0], b"first message"));
+    assert!(contains(
+        messages[0],
+        b"projects/my-project/logs/tracing-stackdriver"
+    ));
+    assert!(contains(messages[1], b"service"));
+    assert!(contains(messages[1], b"api"));
+}
@@ -0,0 +1,65 @@
+use helpers::run_with_tracing_layer;
+use serde_json::Value;
+use std::time::Duration;
+use tracing_stackdriver_cw::{DottedKeys, QueryLogging};
+
+mod helpers;
+
+#[test]
+fn reshapes_sqlx_query_logs() {
+    let layer = tracing_stackdriver_cw::layer()
+        .with_dotted_keys(DottedKeys::Flat)
+        .with_query_logging(QueryLogging::default());
+    let events = run_with_tracing_layer::<Value>(layer, || {
+        tracing::info!(
+            target: "sqlx::query",
+            summary = "select * from users …",
+            db.statement = "SELECT *\n  FROM users WHERE name = 'o''brien' AND age > 42 AND t1.id = $1",
+            rows_affected = 0_u64,
+            rows_returned = 3_u64,
+            elapsed = ?Duration::from_micros(1_500),
+            elapsed_secs = 0.0015,
+        );
+        tracing::info!(db.statement = "SELECT 1", "not a query log");
+    })
+    .expect("Error converting test buffer to JSON");
+
+    let event = &events[0];
+    assert_eq!(
+        event["db.statement"],
+        "SELECT *\n  FROM users WHERE name = ? AND age > ? AND t1.id = $1"
+    );
+    assert_eq!(
+        event["message"],
+        "SELECT * FROM users WHERE name = ? AND age > ? AND t1.id = $1"
+    );
+    assert_eq!(event["db.rows"], 3);
+    assert_eq!(event["db.elapsedMs"], 1.5);
+    assert!(event.get("summary").is_none());
+    assert!(event.get("elapsedSecs").is_none());
+    assert!(event.get("rowsReturned").is_none());
+
+    assert_eq!(events[1]["db.statement"], "SELECT 1");
+    assert_eq!(events[1]["message"], "not a query log");
+}
+
+#[test]
+fn reshapes_diesel_query_logs() {
+    let layer = tracing_stackdriver_cw::layer().with_query_logging(QueryLogging {
+        max_statement_length: 24,
+        redact_literals: false,
+    });
+    let events = run_with_tracing_layer::<Value>(layer, || {
+        tracing::debug!(
+            target: "diesel_logger",
+            "Query ran in 2.5 ms: SELECT name FROM users WHERE id = 7"
+        );
+    })
+    .expect("Error converting test buffer to JSON");
+
+    let event = &events[0];
+    assert_eq!(event["dbStatement"], "SELECT name FROM users W…");
+    assert_eq!(event["message"], "SELECT name FROM users WHERE id = 7");
+    assert_eq!(event["dbElapsedMs"], 2.5);
+    assert!(event.get("dbRows").is_none());
+}
@@ -0,0 +1,48 @@
+use helpers::run_with_tracing_layer;
+use serde_json::{json, Value};
+use tracing_stackdriver_cw::{emit_raw_entry, CloudTraceConfiguration};
+
+mod helpers;
+
+#[test]
+fn merges_raw_entries_with_their_context() {
+    let layer = tracing_stackdriver_cw::layer().with_cloud_trace(CloudTraceConfiguration {
+        project_id: "my-project".to_string(),
+    });
+
+    let events = run_with_tracing_layer::<Value>(layer, || {
+        let span = tracing::info_span!("request", trace_id = "4bf92f3577b34da6a3ce929d0e0e4736");
+        let _span = span.enter();
+
+        emit_raw_entry(&json!({
+            "severity": "WARNING",
+            "message": "from a subprocess",
+            "exitCode": 3,
+            "logging.googleapis.com/labels": { "worker": "7" },
+        }));
+    })
+    .expect("Error converting test buffer to JSON");
+
+    let entry = &events[0];
+    assert_eq!(entry["severity"], "WARNING");
+    assert_eq!(entry["message"], "from a subprocess");
+    assert_eq!(entry["exitCode"], 3);
+    assert_eq!(entry["logging.googleapis.com/labels"]["worker"], "7");
+    assert_eq!(
+        entry["logging.googleapis.com/trace"],
+        "projects/my-project/traces/4bf92f3577b34da6a3ce929d0e0e4736"
+    );
+    assert!(entry["time"].is_string());
+    assert!(entry.get("entry").is_none());
+}
+
+#[test]
+fn defaults_the_severity_of_raw_entries() {
+    let events = run_with_tracing_layer::<Value>(tracing_stackdriver_cw::layer(), || {
+        emit_raw_entry(&json!({ "message": "raw" }))
+    })
+    .expect("Error converting test buffer to JSON");
+
+    assert_eq!(events[0]["severity"], "INFO");
+    assert_eq!(events[0]["message"], "raw");
+}
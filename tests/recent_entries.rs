@@ -0,0 +1,68 @@
+use helpers::run_with_tracing_layer;
+use serde_json::Value;
+use std::{thread, time::Duration};
+use tracing_stackdriver_cw::{AdaptiveSampling, RecentEntries};
+
+mod helpers;
+
+#[test]
+fn keeps_the_last_entries() {
+    let recent_entries = RecentEntries::new(2);
+    let layer = tracing_stackdriver_cw::layer().with_recent_entries(recent_entries.clone());
+
+    run_with_tracing_layer::<Value>(layer, || {
+        tracing::info!("first");
+        tracing::info!("second");
+        tracing::warn!("third");
+    })
+    .expect("Error converting test buffer to JSON");
+
+    let messages: Vec<_> = recent_entries
+        .entries()
+        .iter()
+        .map(|entry| {
+            serde_json::from_str::<Value>(entry).expect("entry is JSON")["message"].clone()
+        })
+        .collect();
+    assert_eq!(messages, ["second", "third"]);
+}
+
+#[test]
+fn keeps_entries_dropped_by_sampling() {
+    let recent_entries = RecentEntries::new(1_000);
+    let layer = tracing_stackdriver_cw::layer()
+        .with_adaptive_sampling(AdaptiveSampling {
+            max_entries_per_second: 100,
+            interval: Duration::from_millis(50),
+            min_rate: 0.01,
+        })
+        .with_recent_entries(recent_entries.clone());
+
+    let events = run_with_tracing_layer::<Value>(layer, || {
+        for _ in 0..500 {
+            tracing::info!("burst");
+        }
+
+        thread::sleep(Duration::from_millis(60));
+
+        for _ in 0..200 {
+            tracing::info!("sampled");
+        }
+    })
+    .expect("Error converting test buffer to JSON");
+
+    let sampled = |entries: &[Value]| {
+        entries
+            .iter()
+            .filter(|entry| entry["message"] == "sampled")
+            .count()
+    };
+    let recent: Vec<Value> = recent_entries
+        .entries()
+        .iter()
+        .map(|entry| serde_json::from_str(entry).expect("entry is JSON"))
+        .collect();
+
+    assert!(sampled(&events) < 200);
+    assert_eq!(sampled(&recent), 200);
+}
@@ -0,0 +1,65 @@
+use helpers::run_with_tracing_layer;
+use serde_json::Value;
+
+mod helpers;
+
+#[test]
+fn renames_user_fields_named_after_reserved_keys() {
+    let events = run_with_tracing_layer::<Value>(tracing_stackdriver_cw::layer(), || {
+        tracing::info!(
+            time = "spoofed",
+            http_request = "spoofed",
+            http_request.status = 200,
+            "reserved"
+        )
+    })
+    .expect("Error converting test buffer to JSON");
+
+    let event = &events[0];
+    assert_eq!(event["user.time"], "spoofed");
+    assert_eq!(event["user.httpRequest"], "spoofed");
+    assert_ne!(event["time"], "spoofed");
+    assert_eq!(event["httpRequest"]["status"], 200);
+}
+
+#[test]
+fn renames_with_the_configured_prefix() {
+    let layer = tracing_stackdriver_cw::layer().with_reserved_key_prefix("fields_");
+    let events = run_with_tracing_layer::<Value>(layer, || {
+        tracing::info!(time = "spoofed", user_id = 1, "reserved")
+    })
+    .expect("Error converting test buffer to JSON");
+
+    let event = &events[0];
+    assert_eq!(event["fields_time"], "spoofed");
+    assert_eq!(event["userId"], 1);
+    assert!(event.get("user.time").is_none());
+}
+
+#[test]
+fn renames_user_fields_named_after_cloud_logging_keys() {
+    let events = run_with_tracing_layer::<Value>(tracing_stackdriver_cw::layer(), || {
+        tracing::info!(
+            message = "reserved",
+            "logging.googleapis.com/trace" = "spoofed"
+        )
+    })
+    .expect("Error converting test buffer to JSON");
+
+    let event = &events[0];
+    assert_eq!(event["user.logging.googleapis.com/trace"], "spoofed");
+    assert!(event.get("logging.googleapis.com/trace").is_none());
+}
+
+#[test]
+fn renames_user_fields_named_after_layer_keys() {
+    let events = run_with_tracing_layer::<Value>(tracing_stackdriver_cw::layer(), || {
+        tracing::info!(target = "spoofed", span = "spoofed", "reserved")
+    })
+    .expect("Error converting test buffer to JSON");
+
+    let event = &events[0];
+    assert_eq!(event["user.target"], "spoofed");
+    assert_eq!(event["user.span"], "spoofed");
+    assert_ne!(event["target"], "spoofed");
+}
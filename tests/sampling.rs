@@ -0,0 +1,92 @@
+use helpers::run_with_tracing_layer;
+use serde_json::Value;
+use std::{thread, time::Duration};
+use tracing_stackdriver_cw::AdaptiveSampling;
+use tracing_subscriber::fmt::{format::Writer, time::FormatTime};
+
+mod helpers;
+
+#[test]
+fn samples_debug_and_info_events_under_load() {
+    let layer = tracing_stackdriver_cw::layer().with_adaptive_sampling(AdaptiveSampling {
+        max_entries_per_second: 100,
+        interval: Duration::from_millis(50),
+        min_rate: 0.01,
+    });
+
+    let events = run_with_tracing_layer::<Value>(layer, || {
+        for _ in 0..500 {
+            tracing::info!("burst");
+        }
+
+        thread::sleep(Duration::from_millis(60));
+
+        for _ in 0..200 {
+            tracing::info!("sampled");
+        }
+
+        tracing::warn!("never sampled");
+
+        // two quiet intervals relax sampling again
+        thread::sleep(Duration::from_millis(60));
+        tracing::info!("quiet");
+        thread::sleep(Duration::from_millis(60));
+        tracing::info!("relaxed");
+    })
+    .expect("Error converting test buffer to JSON");
+
+    let notices: Vec<_> = events
+        .iter()
+        .filter(|event| event["severity"] == "NOTICE")
+        .collect();
+
+    let first_rate = notices[0]["samplingRate"].as_f64().unwrap();
+    assert!(first_rate < 1.0);
+    assert_eq!(notices.last().unwrap()["samplingRate"], 1.0);
+
+    let sampled = events
+        .iter()
+        .filter(|event| event["message"] == "sampled")
+        .count();
+    assert!(sampled < 200);
+    assert!(events
+        .iter()
+        .any(|event| event["message"] == "never sampled"));
+    assert!(events.iter().any(|event| event["message"] == "relaxed"));
+}
+
+struct FixedTime;
+
+impl FormatTime for FixedTime {
+    fn format_time(&self, writer: &mut Writer<'_>) -> std::fmt::Result {
+        writer.write_str("2024-05-01T12:30:00+02:00")
+    }
+}
+
+#[test]
+fn reports_rate_changes_with_the_configured_timer() {
+    let layer = tracing_stackdriver_cw::layer()
+        .with_timer(FixedTime)
+        .with_adaptive_sampling(AdaptiveSampling {
+            max_entries_per_second: 100,
+            interval: Duration::from_millis(50),
+            min_rate: 0.01,
+        });
+
+    let events = run_with_tracing_layer::<Value>(layer, || {
+        for _ in 0..500 {
+            tracing::info!("burst");
+        }
+
+        thread::sleep(Duration::from_millis(60));
+        tracing::info!("sampled");
+    })
+    .expect("Error converting test buffer to JSON");
+
+    let notice = events
+        .iter()
+        .find(|event| event["severity"] == "NOTICE")
+        .expect("a rate change entry");
+
+    assert_eq!(notice["time"], "2024-05-01T12:30:00+02:00");
+}
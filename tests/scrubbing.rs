@@ -0,0 +1,211 @@
+#![cfg(feature = "scrubbing")]
+use helpers::run_with_tracing_layer;
+use serde_json::Value;
+use std::{error::Error, fmt, sync::Arc};
+use tracing_stackdriver_cw::{
+    ErrorFormat, HashKey, InstrumentConfiguration, KeyRedaction, Redactor, ScrubAction,
+    ScrubPattern, Tokenizer, ValueScrubbing,
+};
+
+mod helpers;
+
+#[test]
+fn masks_built_in_patterns() {
+    let layer = tracing_stackdriver_cw::layer().with_value_scrubbing(ValueScrubbing::default());
+    let events = run_with_tracing_layer::<Value>(layer, || {
+        tracing::info!(
+            card = "4111 1111 1111 1111",
+            order = "1234567890123",
+            authorization = "Bearer abc.def-123",
+            "signed up as jane@example.com"
+        );
+    })
+    .expect("Error converting test buffer to JSON");
+
+    assert_eq!(events[0]["message"], "signed up as [REDACTED]");
+    assert_eq!(events[0]["card"], "[REDACTED]");
+    assert_eq!(events[0]["order"], "1234567890123");
+    assert_eq!(events[0]["authorization"], "[REDACTED]");
+}
+
+#[test]
+fn applies_per_pattern_actions() {
+    let scrubbing = ValueScrubbing {
+        patterns: vec![
+            ScrubPattern::email(ScrubAction::Hash(HashKey::new("secret"))),
+            ScrubPattern::new("ssn", r"\b\d{3}-\d{2}-\d{4}\b", ScrubAction::Drop).unwrap(),
+        ],
+        keys: vec![],
+    };
+    let layer = tracing_stackdriver_cw::layer().with_value_scrubbing(scrubbing);
+    let events = run_with_tracing_layer::<Value>(layer, || {
+        tracing::info!(user = "jane@example.com", ssn = "123-45-6789", "hello");
+        tracing::info!(user = "jane@example.com", "again");
+    })
+    .expect("Error converting test buffer to JSON");
+
+    let user = events[0]["user"].as_str().unwrap();
+    assert!(user.starts_with("[hmac:"), "{}", user);
+    assert_eq!(events[1]["user"], user);
+    assert!(events[0].get("ssn").is_none());
+    assert_eq!(events[0]["message"], "hello");
+}
+
+#[derive(Debug)]
+struct Reversed;
+
+impl Redactor for Reversed {
+    fn redact(&self, value: &str) -> Option<String> {
+        Some(value.chars().rev().collect())
+    }
+}
+
+#[test]
+fn redacts_fields_by_key_with_pluggable_redactors() {
+    let scrubbing = ValueScrubbing {
+        patterns: vec![ScrubPattern::email(ScrubAction::Mask)],
+        keys: vec![
+            KeyRedaction {
+                pattern: "*password*".to_string(),
+                action: ScrubAction::Drop,
+            },
+            KeyRedaction {
+                pattern: "user.*".to_string(),
+                action: ScrubAction::Redact(Arc::new(Tokenizer::default())),
+            },
+            KeyRedaction {
+                pattern: "account".to_string(),
+                action: ScrubAction::Redact(Arc::new(Reversed)),
+            },
+        ],
+    };
+    let layer = tracing_stackdriver_cw::layer().with_value_scrubbing(scrubbing);
+    let events = run_with_tracing_layer::<Value>(layer, || {
+        tracing::info!(
+            user.email = "jane@example.com",
+            user.id = 42,
+            account = "abc",
+            old_password = "hunter2",
+            contact = "jane@example.com",
+            "updated"
+        );
+        tracing::info!(user.email = "jane@example.com", "again");
+    })
+    .expect("Error converting test buffer to JSON");
+
+    assert_eq!(events[0]["userEmail"], "tok_1");
+    assert_eq!(events[0]["userId"], "tok_2");
+    assert_eq!(events[0]["account"], "cba");
+    assert_eq!(events[0]["contact"], "[REDACTED]");
+    assert!(events[0].get("oldPassword").is_none());
+    assert_eq!(events[1]["userEmail"], "tok_1");
+}
+
+#[test]
+fn scrubs_entries_as_a_processor() {
+    let scrubbing = ValueScrubbing {
+        keys: vec![KeyRedaction {
+            pattern: "userEmail".to_string(),
+            action: ScrubAction::Drop,
+        }],
+        ..ValueScrubbing::default()
+    };
+    let layer = tracing_stackdriver_cw::layer().with_processor(scrubbing);
+    let events = run_with_tracing_layer::<Value>(layer, || {
+        tracing::info!(user.email = "jane@example.com", "mailed jane@example.com");
+    })
+    .expect("Error converting test buffer to JSON");
+
+    assert_eq!(events[0]["message"], "mailed [REDACTED]");
+    assert!(events[0].get("userEmail").is_none());
+}
+
+#[derive(Debug)]
+struct SignupError {
+    email: &'static str,
+    source: Option<Box<SignupError>>,
+}
+
+impl fmt::Display for SignupError {
+    fn fmt(&self, formatter: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(formatter, "signup of {} failed", self.email)
+    }
+}
+
+impl Error for SignupError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        self.source
+            .as_deref()
+            .map(|source| source as &(dyn Error + 'static))
+    }
+}
+
+#[test]
+fn scrubs_span_fields() {
+    let layer = tracing_stackdriver_cw::layer().with_value_scrubbing(ValueScrubbing::default());
+    let events = run_with_tracing_layer::<Value>(layer, || {
+        let span = tracing::info_span!("signup", email = "jane@example.com");
+        let _guard = span.enter();
+        tracing::info!("signed up");
+    })
+    .expect("Error converting test buffer to JSON");
+
+    assert_eq!(events[0]["span"]["email"], "[REDACTED]");
+    assert_eq!(events[0]["span"]["name"], "signup");
+}
+
+#[test]
+fn scrubs_error_debug_renderings() {
+    let error = SignupError {
+        email: "jane@example.com",
+        source: None,
+    };
+    let layer = tracing_stackdriver_cw::layer()
+        .with_error_format(ErrorFormat::Display)
+        .with_value_scrubbing(ValueScrubbing::default());
+    let events = run_with_tracing_layer::<Value>(layer, || {
+        tracing::debug!(error = &error as &dyn Error, "failed");
+    })
+    .expect("Error converting test buffer to JSON");
+
+    assert_eq!(events[0]["error"], "signup of [REDACTED] failed");
+    let debug = events[0]["errorDebug"].as_str().expect("no errorDebug");
+    assert!(!debug.contains("jane@example.com"), "{}", debug);
+}
+
+#[test]
+fn scrubs_instrument_error_chains() {
+    let layer = tracing_stackdriver_cw::layer()
+        .with_instrument_fields(InstrumentConfiguration::default())
+        .with_value_scrubbing(ValueScrubbing::default());
+    let error = SignupError {
+        email: "jane@example.com",
+        source: Some(Box::new(SignupError {
+            email: "jane@example.org",
+            source: None,
+        })),
+    };
+    let events = run_with_tracing_layer::<Value>(layer, || {
+        tracing::error!(error = &error as &dyn Error);
+    })
+    .expect("Error converting test buffer to JSON");
+
+    let entry = events[0].to_string();
+    assert!(!entry.contains("jane@example"), "{}", entry);
+    assert_eq!(
+        events[0]["error"]["chain"],
+        serde_json::json!(["signup of [REDACTED] failed"])
+    );
+}
+
+#[test]
+fn hashes_matches_under_a_secret_key() {
+    let hash = |secret: &str| {
+        let action = ScrubAction::Hash(HashKey::new(secret));
+        action.redact("jane@example.com").unwrap()
+    };
+
+    assert_eq!(hash("first"), hash("first"));
+    assert_ne!(hash("first"), hash("second"));
+    assert_eq!(format!("{:?}", HashKey::new("first")), "HashKey(..)");
+}
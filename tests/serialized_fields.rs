@@ -0,0 +1,74 @@
+use helpers::{run_with_tracing, run_with_tracing_layer};
+use serde::Serialize;
+use serde_json::{json, Value};
+use std::{collections::BTreeMap, time::Duration};
+use tracing_stackdriver_cw::{as_duration, as_json, DurationFormat};
+
+mod helpers;
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct Payload {
+    user_id: u64,
+    tags: Vec<&'static str>,
+    attributes: BTreeMap<&'static str, f64>,
+}
+
+#[test]
+fn embeds_serialized_values() {
+    let payload = Payload {
+        user_id: 1,
+        tags: vec!["new", "vip"],
+        attributes: BTreeMap::from([("score", 0.5)]),
+    };
+
+    let events = run_with_tracing::<Value>(
+        || tracing::info!(payload = %as_json(&payload), count = ?as_json(&3), "received"),
+    )
+    .expect("Error converting test buffer to JSON");
+
+    assert_eq!(
+        events[0]["payload"],
+        json!({ "userId": 1, "tags": ["new", "vip"], "attributes": { "score": 0.5 } })
+    );
+    assert_eq!(events[0]["count"], 3);
+}
+
+#[test]
+fn leaves_other_debug_values_as_strings() {
+    let events = run_with_tracing::<Value>(
+        || tracing::info!(value = ?Some(5), text = %"{\"not\": \"embedded\"}", "received"),
+    )
+    .expect("Error converting test buffer to JSON");
+
+    assert_eq!(events[0]["value"], "Some(5)");
+    assert_eq!(events[0]["text"], "{\"not\": \"embedded\"}");
+}
+
+#[test]
+fn formats_durations() {
+    let events = run_with_tracing::<Value>(|| {
+        tracing::info!(
+            elapsed = %as_duration(Duration::from_millis(1200)),
+            http_request.latency = %as_duration(Duration::from_micros(230_500)),
+            "timed"
+        )
+    })
+    .expect("Error converting test buffer to JSON");
+
+    assert_eq!(
+        events[0]["elapsed"],
+        json!({ "seconds": 1, "nanos": 200_000_000 })
+    );
+    assert_eq!(events[0]["httpRequest"]["latency"], "0.2305s");
+
+    let layer = tracing_stackdriver_cw::layer().with_duration_format(DurationFormat::Seconds);
+    let events = run_with_tracing_layer::<Value>(
+        layer,
+        || tracing::info!(elapsed = %as_duration(Duration::from_secs(3)), "timed"),
+    )
+    .expect("Error converting test buffer to JSON");
+
+    assert_eq!(events[0]["elapsed"], "3s");
+    assert_eq!(as_duration(Duration::from_millis(1500)).to_string(), "1.5s");
+}
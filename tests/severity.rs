@@ -0,0 +1,52 @@
+use helpers::run_with_tracing_layer;
+use serde_json::Value;
+use tracing_stackdriver_cw::{LogSeverity, SeverityEscalation};
+
+mod helpers;
+
+#[test]
+fn caps_the_severity_of_matching_targets() {
+    let layer = tracing_stackdriver_cw::layer()
+        .with_severity_cap("hyper", LogSeverity::Debug)
+        .with_severity_cap("hyper::client", LogSeverity::Info);
+
+    let events = run_with_tracing_layer::<Value>(layer, || {
+        tracing::warn!(target: "hyper", "capped");
+        tracing::error!(target: "hyper::proto::h1", "capped submodule");
+        tracing::warn!(target: "hyper::client::pool", "more specific cap");
+        tracing::warn!(target: "hyperlocal", "different crate");
+        tracing::trace!(target: "hyper", "already below the cap");
+        tracing::warn!(target: "hyper", severity = "critical", "capped override");
+    })
+    .expect("Error converting test buffer to JSON");
+
+    let severities: Vec<_> = events.iter().map(|event| &event["severity"]).collect();
+    assert_eq!(
+        severities,
+        ["DEBUG", "DEBUG", "INFO", "WARNING", "DEBUG", "DEBUG"]
+    );
+    assert_eq!(events[1]["message"], "capped submodule");
+}
+
+#[test]
+fn escalates_the_severity_of_matching_field_values() {
+    let layer = tracing_stackdriver_cw::layer()
+        .with_severity_escalation(SeverityEscalation::new(
+            "http_request.status",
+            500,
+            LogSeverity::Error,
+        ))
+        .with_severity_escalation(SeverityEscalation::new("retries", 5, LogSeverity::Warning));
+
+    let events = run_with_tracing_layer::<Value>(layer, || {
+        tracing::info!(http_request.status = 503, "server error");
+        tracing::info!(http_request.status = 404, "client error");
+        tracing::info!(retries = 5, "retrying");
+        tracing::info!(retries = "7", http_request.status = 500, "both");
+        tracing::error!(retries = 9, "never lowered");
+    })
+    .expect("Error converting test buffer to JSON");
+
+    let severities: Vec<_> = events.iter().map(|event| &event["severity"]).collect();
+    assert_eq!(severities, ["ERROR", "INFO", "WARNING", "ERROR", "ERROR"]);
+}
@@ -0,0 +1,92 @@
+use helpers::MockWriter;
+use serde_json::Value;
+use std::sync::{Arc, Mutex};
+use tracing_stackdriver_cw::{LogSeverity, SeverityFilter};
+use tracing_subscriber::{
+    filter::LevelFilter,
+    layer::{Filter, SubscriberExt},
+    Layer, Registry,
+};
+
+mod helpers;
+
+#[test]
+fn filters_on_written_severities() {
+    let buffer = Arc::new(Mutex::new(vec![]));
+    let shared = buffer.clone();
+    let stackdriver = tracing_stackdriver_cw::layer()
+        .with_writer(move || MockWriter(shared.clone()))
+        .with_filter(SeverityFilter::new(LogSeverity::Notice));
+    let subscriber = Registry::default().with(stackdriver);
+
+    tracing::subscriber::with_default(subscriber, || {
+        tracing::info!("hidden");
+        tracing::info!(severity = "NOTICE", "raised");
+        tracing::warn!("shown");
+        tracing::error!(severity = "debug", "lowered");
+        tracing::info_span!("request").in_scope(|| tracing::error!("within a span"));
+    });
+
+    let buffer = buffer.lock().unwrap();
+    let messages: Vec<_> = serde_json::Deserializer::from_slice(&buffer)
+        .into_iter::<Value>()
+        .map(|event| event.unwrap()["message"].clone())
+        .collect();
+
+    assert_eq!(messages, ["raised", "shown", "within a span"]);
+}
+
+#[test]
+fn filters_on_severities_recorded_with_debug() {
+    let buffer = Arc::new(Mutex::new(vec![]));
+    let shared = buffer.clone();
+    let stackdriver = tracing_stackdriver_cw::layer()
+        .with_writer(move || MockWriter(shared.clone()))
+        .with_filter(SeverityFilter::new(LogSeverity::Notice));
+    let subscriber = Registry::default().with(stackdriver);
+
+    tracing::subscriber::with_default(subscriber, || {
+        tracing::info!(severity = ?"NOTICE", "quoted");
+        tracing::info!(severity = ?LogSeverity::Notice, "typed");
+        tracing::error!(severity = ?"DEBUG", "lowered");
+    });
+
+    let buffer = buffer.lock().unwrap();
+    let entries: Vec<_> = serde_json::Deserializer::from_slice(&buffer)
+        .into_iter::<Value>()
+        .map(|event| {
+            let event = event.unwrap();
+            (event["message"].clone(), event["severity"].clone())
+        })
+        .collect();
+
+    assert_eq!(
+        entries,
+        [
+            (Value::from("quoted"), Value::from("NOTICE")),
+            (Value::from("typed"), Value::from("NOTICE")),
+        ]
+    );
+}
+
+#[test]
+fn hints_the_most_verbose_level_allowed() {
+    let hint = |filter: SeverityFilter| Filter::<Registry>::max_level_hint(&filter);
+
+    assert_eq!(
+        hint(SeverityFilter::new(LogSeverity::Debug)),
+        Some(LevelFilter::TRACE)
+    );
+    assert_eq!(
+        hint(SeverityFilter::new(LogSeverity::Notice)),
+        Some(LevelFilter::INFO)
+    );
+    assert_eq!(
+        hint(SeverityFilter::new(LogSeverity::Notice).with_overrides_from(LevelFilter::OFF)),
+        Some(LevelFilter::WARN)
+    );
+    assert_eq!(
+        hint(SeverityFilter::new(LogSeverity::Critical).with_overrides_from(LevelFilter::OFF)),
+        Some(LevelFilter::OFF)
+    );
+}
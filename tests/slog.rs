@@ -0,0 +1,29 @@
+#![cfg(feature = "slog")]
+use helpers::MockWriter;
+use serde_json::Value;
+use slog::{o, Drain, Logger};
+use std::sync::{Arc, Mutex};
+use tracing_stackdriver_cw::StackdriverDrain;
+
+mod helpers;
+
+#[test]
+fn writes_slog_records_as_entries() {
+    let buffer = Arc::new(Mutex::new(vec![]));
+    let drain = StackdriverDrain::new(MockWriter(buffer.clone())).fuse();
+    let logger = Logger::root(drain, o!("service" => "api", "labels.team" => "core"));
+
+    slog::warn!(logger, "slow request"; "user_id" => 1, "http_request.status" => 200);
+
+    let buffer = buffer.lock().unwrap();
+    let entry: Value = serde_json::from_slice(&buffer).expect("Error converting buffer to JSON");
+
+    assert_eq!(entry["severity"], "WARNING");
+    assert_eq!(entry["message"], "slow request");
+    assert_eq!(entry["target"], "slog");
+    assert_eq!(entry["service"], "api");
+    assert_eq!(entry["userId"], 1);
+    assert_eq!(entry["httpRequest"]["status"], 200);
+    assert_eq!(entry["logging.googleapis.com/labels"]["team"], "core");
+    assert!(entry["time"].is_string());
+}
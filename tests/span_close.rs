@@ -0,0 +1,173 @@
+use helpers::run_with_tracing_layer;
+use serde_json::Value;
+use std::{thread, time::Duration};
+use tracing_stackdriver_cw::SpanEvents;
+
+mod helpers;
+
+#[test]
+fn writes_span_close_entries() {
+    let layer = tracing_stackdriver_cw::layer().with_span_close_entries(true);
+    let events = run_with_tracing_layer::<Value>(layer, || {
+        let span = tracing::warn_span!("request", id = 1);
+        let _span = span.enter();
+        tracing::info!("handling");
+    })
+    .expect("Error converting test buffer to JSON");
+
+    assert_eq!(events.len(), 2);
+    assert_eq!(events[1]["message"], "close");
+    assert_eq!(events[1]["severity"], "WARNING");
+    assert_eq!(events[1]["span"]["name"], "request");
+    assert_eq!(events[1]["span"]["id"], 1);
+    assert!(events[1].get("errorCount").is_none());
+}
+
+#[test]
+fn summarizes_errors_on_span_close_entries() {
+    let layer = tracing_stackdriver_cw::layer()
+        .with_span_close_entries(true)
+        .with_span_error_summaries(true);
+
+    let events = run_with_tracing_layer::<Value>(layer, || {
+        let span = tracing::info_span!("request");
+        let _span = span.enter();
+        tracing::warn!("slow upstream");
+        tracing::info!("retrying");
+
+        tracing::info_span!("query").in_scope(|| tracing::error!("connection reset"));
+    })
+    .expect("Error converting test buffer to JSON");
+
+    let close_entries: Vec<_> = events
+        .iter()
+        .filter(|event| event["message"] == "close")
+        .collect();
+
+    assert_eq!(close_entries.len(), 2);
+
+    let query = close_entries[0];
+    assert_eq!(query["span"]["name"], "query");
+    assert_eq!(query["errorCount"], 1);
+    assert_eq!(query["firstError"], "connection reset");
+
+    let request = close_entries[1];
+    assert_eq!(request["span"]["name"], "request");
+    assert_eq!(request["errorCount"], 2);
+    assert_eq!(request["firstError"], "slow upstream");
+    assert_eq!(request["lastError"], "connection reset");
+}
+
+#[test]
+fn times_busy_and_idle_spans() {
+    let layer = tracing_stackdriver_cw::layer().with_span_close_entries(true);
+    let events = run_with_tracing_layer::<Value>(layer, || {
+        let span = tracing::info_span!("request");
+
+        span.in_scope(|| thread::sleep(Duration::from_millis(20)));
+        thread::sleep(Duration::from_millis(40));
+        span.in_scope(|| thread::sleep(Duration::from_millis(20)));
+    })
+    .expect("Error converting test buffer to JSON");
+
+    let close = events.first().expect("No close entry written");
+    let busy = close["busyNs"].as_u64().unwrap();
+    let idle = close["idleNs"].as_u64().unwrap();
+
+    assert!(busy >= 40_000_000, "busy for {}ns", busy);
+    assert!(idle >= 40_000_000, "idle for {}ns", idle);
+}
+
+#[test]
+fn completes_the_http_requests_of_request_span_close_entries() {
+    let layer = tracing_stackdriver_cw::layer().with_span_close_entries(true);
+    let events = run_with_tracing_layer::<Value>(layer, || {
+        let span = tracing::info_span!(
+            "request",
+            http_request.request_method = "POST",
+            http_request.request_url = "/orders",
+            http_request.status = tracing::field::Empty,
+            http_request.response_size = tracing::field::Empty,
+        );
+        let _span = span.enter();
+        tracing::info!("handling");
+        thread::sleep(Duration::from_millis(5));
+
+        span.record("http_request.status", 201);
+        span.record("http_request.response_size", 512);
+    })
+    .expect("Error converting test buffer to JSON");
+
+    assert_eq!(events[0]["httpRequest"]["requestMethod"], "POST");
+    assert!(events[0]["httpRequest"].get("latency").is_none());
+
+    let close = &events[1];
+    assert_eq!(close["message"], "close");
+    assert_eq!(close["httpRequest"]["requestMethod"], "POST");
+    assert_eq!(close["httpRequest"]["requestUrl"], "/orders");
+    assert_eq!(close["httpRequest"]["status"], 201);
+    assert_eq!(close["httpRequest"]["responseSize"], 512);
+
+    let latency = close["httpRequest"]["latency"].as_str().unwrap();
+    let seconds: f64 = latency.strip_suffix('s').unwrap().parse().unwrap();
+    assert!(seconds >= 0.005);
+}
+
+#[test]
+fn counts_span_events_by_severity() {
+    let layer = tracing_stackdriver_cw::layer()
+        .with_span_close_entries(true)
+        .with_span_event_counts(true);
+    let events = run_with_tracing_layer::<Value>(layer, || {
+        let span = tracing::info_span!("request");
+        let _span = span.enter();
+        tracing::info!("handling");
+        tracing::info!("handled");
+
+        tracing::info_span!("lookup").in_scope(|| {
+            tracing::debug!("querying");
+            tracing::warn!("slow upstream");
+        });
+    })
+    .expect("Error converting test buffer to JSON");
+
+    let lookup = events
+        .iter()
+        .find(|event| event["span"]["name"] == "lookup" && event["message"] == "close")
+        .unwrap();
+    assert_eq!(
+        lookup["eventCounts"],
+        serde_json::json!({"DEBUG": 1, "WARNING": 1})
+    );
+
+    let request = events.last().unwrap();
+    assert_eq!(request["span"]["name"], "request");
+    assert_eq!(
+        request["eventCounts"],
+        serde_json::json!({"DEBUG": 1, "INFO": 2, "WARNING": 1})
+    );
+}
+
+#[test]
+fn writes_span_lifecycle_entries() {
+    let layer = tracing_stackdriver_cw::layer()
+        .with_span_close_entries(true)
+        .with_span_events(SpanEvents::ENTER | SpanEvents::EXIT);
+
+    let events = run_with_tracing_layer::<Value>(layer, || {
+        let span = tracing::warn_span!("request", id = 1);
+        span.in_scope(|| tracing::info!("handling"));
+    })
+    .expect("Error converting test buffer to JSON");
+
+    let messages: Vec<_> = events.iter().map(|event| &event["message"]).collect();
+    assert_eq!(messages, ["enter", "handling", "exit", "close"]);
+
+    let enter = &events[0];
+    assert_eq!(enter["severity"], "DEBUG");
+    assert_eq!(enter["span"]["name"], "request");
+    assert_eq!(enter["spanId"], events[2]["spanId"]);
+    assert!(enter["spanId"].is_u64());
+    assert!(enter.get("busyNs").is_none());
+    assert!(events[3].get("busyNs").is_some());
+}
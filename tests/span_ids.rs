@@ -0,0 +1,38 @@
+use helpers::run_with_tracing_layer;
+use serde_json::Value;
+
+mod helpers;
+
+#[test]
+fn includes_span_and_parent_ids() {
+    let layer = tracing_stackdriver_cw::layer().with_span_ids(true);
+    let events = run_with_tracing_layer::<Value>(layer, || {
+        let root = tracing::info_span!("batch");
+        let _root = root.enter();
+        tracing::info!("in root");
+
+        let child = tracing::info_span!("item");
+        let _child = child.enter();
+        tracing::info!("in child");
+    })
+    .expect("Error converting test buffer to JSON");
+
+    let root_id = &events[0]["span"]["id"];
+    assert!(root_id.is_string());
+    assert!(events[0]["span"].get("parentId").is_none());
+
+    assert_eq!(&events[1]["span"]["parentId"], root_id);
+    assert_ne!(&events[1]["span"]["id"], root_id);
+}
+
+#[test]
+fn excludes_span_ids_by_default() {
+    let events = run_with_tracing_layer::<Value>(tracing_stackdriver_cw::layer(), || {
+        let span = tracing::info_span!("batch");
+        let _span = span.enter();
+        tracing::info!("in span");
+    })
+    .expect("Error converting test buffer to JSON");
+
+    assert!(events[0]["span"].get("id").is_none());
+}
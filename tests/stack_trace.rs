@@ -0,0 +1,98 @@
+use helpers::run_with_tracing_layer;
+use serde_json::Value;
+use std::time::Duration;
+
+mod helpers;
+
+const REPORTED_ERROR_EVENT: &str =
+    "type.googleapis.com/google.devtools.clouderrorreporting.v1beta1.ReportedErrorEvent";
+
+#[test]
+fn deduplicates_stack_traces_within_the_window() {
+    let layer =
+        tracing_stackdriver_cw::layer().with_stack_trace_deduplication(Duration::from_secs(60));
+
+    let events = run_with_tracing_layer::<Value>(layer, || {
+        for _ in 0..3 {
+            tracing::error!(stack_trace = "at main.rs:1", "storm");
+        }
+
+        tracing::error!(stack_trace = "at lib.rs:2", "other");
+        tracing::warn!(stack_trace = "at main.rs:1", "not an error");
+    })
+    .expect("Error converting test buffer to JSON");
+
+    assert_eq!(events[0]["stackTrace"], "at main.rs:1");
+    assert!(events[0].get("stackTraceCount").is_none());
+
+    let reference = &events[0]["stackTraceRef"];
+    assert!(reference.is_string());
+
+    for (event, count) in events[1..3].iter().zip(2..) {
+        assert!(event.get("stackTrace").is_none());
+        assert_eq!(&event["stackTraceRef"], reference);
+        assert_eq!(event["stackTraceCount"], count);
+    }
+
+    assert_eq!(events[3]["stackTrace"], "at lib.rs:2");
+    assert_ne!(&events[3]["stackTraceRef"], reference);
+    assert_eq!(events[4]["stackTrace"], "at main.rs:1");
+    assert!(events[4].get("stackTraceRef").is_none());
+}
+
+#[test]
+fn moves_backtraces_out_of_messages() {
+    let layer = tracing_stackdriver_cw::layer().with_stack_trace_detection(true);
+    let events = run_with_tracing_layer::<Value>(layer, || {
+        tracing::error!(
+            "request failed\n\nCaused by:\n    timeout\n\nStack backtrace:\n   0: app::handler\n             at ./src/handler.rs:10:5\n   1: app::main"
+        );
+        tracing::error!("thread 'main' panicked at src/main.rs:3:5:\nboom");
+        tracing::warn!("not an error\n   0: app::handler\n   1: app::main");
+        tracing::error!("steps:\n1: first");
+    })
+    .expect("Error converting test buffer to JSON");
+
+    assert_eq!(
+        events[0]["message"],
+        "request failed\n\nCaused by:\n    timeout"
+    );
+    assert_eq!(
+        events[0]["stackTrace"],
+        "Stack backtrace:\n   0: app::handler\n             at ./src/handler.rs:10:5\n   1: app::main"
+    );
+    assert_eq!(events[0]["@type"], REPORTED_ERROR_EVENT);
+
+    assert_eq!(
+        events[1]["message"],
+        "thread 'main' panicked at src/main.rs:3:5:"
+    );
+    assert_eq!(
+        events[1]["stackTrace"],
+        "thread 'main' panicked at src/main.rs:3:5:\nboom"
+    );
+    assert_eq!(events[1]["@type"], REPORTED_ERROR_EVENT);
+
+    for event in &events[2..] {
+        assert!(event.get("stackTrace").is_none());
+        assert!(event.get("@type").is_none());
+    }
+}
+
+#[test]
+fn keeps_recorded_stack_traces() {
+    let layer = tracing_stackdriver_cw::layer().with_stack_trace_detection(true);
+    let events = run_with_tracing_layer::<Value>(layer, || {
+        tracing::error!(
+            stack_trace = "recorded",
+            "failed\nstack backtrace:\n   0: app::handler\n   1: app::main"
+        );
+    })
+    .expect("Error converting test buffer to JSON");
+
+    assert_eq!(events[0]["stackTrace"], "recorded");
+    assert!(events[0]["message"]
+        .as_str()
+        .unwrap()
+        .contains("0: app::handler"));
+}
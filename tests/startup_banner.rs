@@ -0,0 +1,56 @@
+use helpers::run_with_tracing_layer;
+use serde_json::Value;
+use tracing_stackdriver_cw::{AdaptiveSampling, BuildInfo, CloudTraceConfiguration};
+
+mod helpers;
+
+#[test]
+fn writes_the_configuration_on_startup() {
+    let layer = tracing_stackdriver_cw::layer()
+        .with_cloud_trace(CloudTraceConfiguration {
+            project_id: "my-project".to_string(),
+        })
+        .with_adaptive_sampling(AdaptiveSampling::default())
+        .with_startup_banner(true);
+
+    let events = run_with_tracing_layer::<Value>(layer, || tracing::info!("started"))
+        .expect("Error converting test buffer to JSON");
+
+    let banner = &events[0];
+    assert_eq!(banner["severity"], "NOTICE");
+    assert_eq!(banner["configuration"]["format"], "json");
+    assert_eq!(banner["configuration"]["projectId"], "my-project");
+    assert_eq!(
+        banner["configuration"]["sampling"]["maxEntriesPerSecond"],
+        1000
+    );
+    assert_eq!(events[1]["message"], "started");
+}
+
+#[test]
+fn omits_the_banner_by_default() {
+    let events = run_with_tracing_layer::<Value>(tracing_stackdriver_cw::layer(), || {
+        tracing::info!("started")
+    })
+    .expect("Error converting test buffer to JSON");
+
+    assert_eq!(events.len(), 1);
+}
+
+#[test]
+fn labels_the_banner_like_other_entries() {
+    let layer = tracing_stackdriver_cw::layer()
+        .with_build_info(BuildInfo {
+            version: Some("1.2.3".to_string()),
+            ..BuildInfo::default()
+        })
+        .with_startup_banner(true);
+
+    let events = run_with_tracing_layer::<Value>(layer, || tracing::info!("started"))
+        .expect("Error converting test buffer to JSON");
+
+    assert_eq!(
+        events[0]["logging.googleapis.com/labels"]["build_version"],
+        "1.2.3"
+    );
+}
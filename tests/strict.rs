@@ -0,0 +1,45 @@
+use helpers::run_with_tracing_layer;
+use serde_json::Value;
+use tracing_stackdriver_cw::CloudTraceConfiguration;
+
+mod helpers;
+
+#[test]
+fn accepts_well_formed_entries() {
+    let events = run_with_tracing_layer::<Value>(tracing_stackdriver_cw::strict_test(), || {
+        tracing::info!(labels.service = "api", user_id = 1, "well formed")
+    })
+    .expect("Error converting test buffer to JSON");
+
+    assert_eq!(events[0]["message"], "well formed");
+}
+
+#[test]
+#[should_panic(expected = "duplicate key `userId`")]
+fn rejects_duplicate_keys() {
+    let _ = run_with_tracing_layer::<Value>(tracing_stackdriver_cw::strict_test(), || {
+        tracing::info!(user_id = 1, userId = 2, "duplicated")
+    });
+}
+
+#[test]
+#[should_panic(expected = "label `attempt` has a non-string value")]
+fn rejects_coerced_label_values() {
+    let _ = run_with_tracing_layer::<Value>(tracing_stackdriver_cw::strict_test(), || {
+        tracing::info!(labels.attempt = 3, "coerced")
+    });
+}
+
+#[test]
+#[should_panic(expected = "invalid trace")]
+fn rejects_invalid_trace_ids() {
+    let layer = tracing_stackdriver_cw::strict_test().with_cloud_trace(CloudTraceConfiguration {
+        project_id: "my-project".to_string(),
+    });
+
+    let _ = run_with_tracing_layer::<Value>(layer, || {
+        let span = tracing::info_span!("request", trace_id = "not-a-trace-id");
+        let _span = span.enter();
+        tracing::info!("traced")
+    });
+}
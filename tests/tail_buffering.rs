@@ -0,0 +1,115 @@
+use helpers::run_with_tracing_layer;
+use serde_json::Value;
+use std::time::Duration;
+use tracing_stackdriver_cw::TailBuffering;
+
+mod helpers;
+
+#[test]
+fn writes_debug_entries_of_failed_spans() {
+    let layer = tracing_stackdriver_cw::layer()
+        .with_span_close_entries(true)
+        .with_tail_buffering(TailBuffering::default());
+    let events = run_with_tracing_layer::<Value>(layer, || {
+        let span = tracing::info_span!("request");
+        let _guard = span.enter();
+
+        tracing::debug!("lookup");
+        let child = tracing::debug_span!("query");
+        child.in_scope(|| tracing::trace!("rows"));
+        tracing::info!("handled");
+        tracing::error!("failed");
+    })
+    .expect("Error converting test buffer to JSON");
+
+    let messages: Vec<_> = events
+        .iter()
+        .map(|event| event["message"].clone())
+        .collect();
+    assert_eq!(
+        messages,
+        ["handled", "failed", "close", "lookup", "rows", "close"]
+    );
+}
+
+#[test]
+fn discards_debug_entries_of_successful_spans() {
+    let layer = tracing_stackdriver_cw::layer().with_tail_buffering(TailBuffering::default());
+    let events = run_with_tracing_layer::<Value>(layer, || {
+        let span = tracing::info_span!("request");
+        span.in_scope(|| {
+            tracing::debug!("lookup");
+            tracing::info!("handled");
+        });
+        drop(span);
+
+        tracing::debug!("outside of a span");
+    })
+    .expect("Error converting test buffer to JSON");
+
+    let messages: Vec<_> = events
+        .iter()
+        .map(|event| event["message"].clone())
+        .collect();
+    assert_eq!(messages, ["handled", "outside of a span"]);
+}
+
+#[test]
+fn writes_debug_entries_of_slow_spans() {
+    let layer = tracing_stackdriver_cw::layer().with_tail_buffering(TailBuffering {
+        latency_threshold: Some(Duration::from_millis(10)),
+        max_entries: 2,
+    });
+    let events = run_with_tracing_layer::<Value>(layer, || {
+        let span = tracing::info_span!("request");
+        span.in_scope(|| {
+            tracing::debug!("first");
+            tracing::debug!("second");
+            tracing::debug!("third");
+            std::thread::sleep(Duration::from_millis(20));
+        });
+    })
+    .expect("Error converting test buffer to JSON");
+
+    let messages: Vec<_> = events
+        .iter()
+        .map(|event| event["message"].clone())
+        .collect();
+    assert_eq!(messages, ["second", "third"]);
+}
+
+#[test]
+fn writes_flushed_entries_with_the_writers_for_their_events() {
+    use helpers::MockWriter;
+    use std::sync::{Arc, Mutex};
+    use tracing::Level;
+    use tracing_subscriber::{fmt::writer::MakeWriterExt, layer::SubscriberExt, Registry};
+
+    let (verbose, severe) = (Arc::new(Mutex::new(vec![])), Arc::new(Mutex::new(vec![])));
+    let (verbose_writer, severe_writer) = (verbose.clone(), severe.clone());
+    let make_writer = (move || MockWriter(verbose_writer.clone()))
+        .with_min_level(Level::DEBUG)
+        .and((move || MockWriter(severe_writer.clone())).with_max_level(Level::INFO));
+    let layer = tracing_stackdriver_cw::layer()
+        .with_writer(make_writer)
+        .with_tail_buffering(TailBuffering::default());
+    let subscriber = Registry::default().with(layer);
+
+    tracing::subscriber::with_default(subscriber, || {
+        tracing::info_span!("request").in_scope(|| {
+            tracing::debug!("lookup");
+            tracing::trace!("rows");
+            tracing::error!("failed");
+        });
+    });
+
+    let messages = |buffer: &Mutex<Vec<u8>>| -> Vec<Value> {
+        serde_json::Deserializer::from_slice(&buffer.lock().unwrap())
+            .into_iter::<Value>()
+            .map(|event| event.unwrap()["message"].clone())
+            .collect()
+    };
+
+    assert_eq!(messages(&verbose), ["lookup", "rows"]);
+    assert_eq!(messages(&severe), ["failed"]);
+}
@@ -0,0 +1,72 @@
+use helpers::run_with_tracing_layer;
+use serde_json::Value;
+use time::{format_description::well_known::Rfc3339, OffsetDateTime};
+use tracing_subscriber::fmt::{format::Writer, time::FormatTime};
+
+mod helpers;
+
+fn times(events: &[Value]) -> Vec<OffsetDateTime> {
+    events
+        .iter()
+        .map(|event| {
+            OffsetDateTime::parse(event["time"].as_str().expect("No time"), &Rfc3339)
+                .expect("Invalid time")
+        })
+        .collect()
+}
+
+#[test]
+fn writes_non_decreasing_monotonic_timestamps() {
+    let start = OffsetDateTime::now_utc();
+    let layer = tracing_stackdriver_cw::layer().with_monotonic_timestamps(true);
+    let events = run_with_tracing_layer::<Value>(layer, || {
+        for index in 0..100 {
+            tracing::info!(index, "monotonic");
+        }
+    })
+    .expect("Error converting test buffer to JSON");
+
+    let times = times(&events);
+    assert!(times.windows(2).all(|pair| pair[0] <= pair[1]));
+    assert!((times[0] - start).abs() < time::Duration::minutes(1));
+}
+
+#[test]
+fn overrides_timestamps_with_timestamp_fields() {
+    let layer = tracing_stackdriver_cw::layer().with_timestamp_overrides(true);
+    let events = run_with_tracing_layer::<Value>(layer, || {
+        tracing::info!(timestamp = "2024-05-01T12:30:00.5Z", "replayed");
+        tracing::info!(timestamp = 1_714_566_600_000_000_000_i64, "replayed");
+        tracing::info!(timestamp = "yesterday", "replayed");
+    })
+    .expect("Error converting test buffer to JSON");
+
+    assert_eq!(events[0]["time"], "2024-05-01T12:30:00.5Z");
+    assert!(events[0].get("timestamp").is_none());
+    assert_eq!(events[1]["time"], "2024-05-01T12:30:00Z");
+    assert_eq!(events[2]["timestamp"], "yesterday");
+    assert_ne!(events[2]["time"], "yesterday");
+}
+
+struct FixedTime;
+
+impl FormatTime for FixedTime {
+    fn format_time(&self, writer: &mut Writer<'_>) -> std::fmt::Result {
+        writer.write_str("2024-05-01T12:30:00+02:00")
+    }
+}
+
+#[test]
+fn writes_times_with_custom_timers() {
+    let layer = tracing_stackdriver_cw::layer()
+        .with_timer(FixedTime)
+        .with_timestamp_overrides(true);
+    let events = run_with_tracing_layer::<Value>(layer, || {
+        tracing::info!("fixed");
+        tracing::info!(timestamp = "2024-05-01T12:30:00.5Z", "replayed");
+    })
+    .expect("Error converting test buffer to JSON");
+
+    assert_eq!(events[0]["time"], "2024-05-01T12:30:00+02:00");
+    assert_eq!(events[1]["time"], "2024-05-01T12:30:00.5Z");
+}
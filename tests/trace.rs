@@ -0,0 +1,365 @@
+use helpers::{run_with_tracing, run_with_tracing_layer};
+use mocks::MockTraceEvent;
+use serde_json::Value;
+use tracing_stackdriver_cw::{
+    CloudTraceConfiguration, SpanIdEncoding, SpanTraceContext, TraceContext, TraceResolution,
+};
+use tracing_subscriber::{layer::SubscriberExt, Registry};
+
+mod helpers;
+mod mocks;
+
+fn cloud_trace_layer() -> tracing_stackdriver_cw::Layer<tracing_subscriber::Registry> {
+    tracing_stackdriver_cw::layer().with_cloud_trace(CloudTraceConfiguration {
+        project_id: "my-project".to_string(),
+    })
+}
+
+#[test]
+fn includes_trace_fields() {
+    let events = run_with_tracing_layer::<MockTraceEvent>(cloud_trace_layer(), || {
+        let span = tracing::info_span!(
+            "request",
+            trace_id = "4bf92f3577b34da6a3ce929d0e0e4736",
+            span_id = "00f067aa0ba902b7",
+            trace_sampled = true
+        );
+        let _guard = span.enter();
+        tracing::info!("traced message");
+    })
+    .expect("Error converting test buffer to JSON");
+
+    let event = events.first().expect("No event heard");
+    assert_eq!(
+        event.trace.as_deref(),
+        Some("projects/my-project/traces/4bf92f3577b34da6a3ce929d0e0e4736")
+    );
+    assert_eq!(event.span_id.as_deref(), Some("00f067aa0ba902b7"));
+    assert_eq!(event.trace_sampled, Some(true));
+}
+
+#[test]
+fn includes_trace_fields_recorded_with_debug() {
+    let trace_id = String::from("4bf92f3577b34da6a3ce929d0e0e4736");
+    let span_id = String::from("00f067aa0ba902b7");
+    let events = run_with_tracing_layer::<MockTraceEvent>(cloud_trace_layer(), || {
+        let span = tracing::info_span!("request", trace_id = ?trace_id, span_id = ?span_id);
+        let _guard = span.enter();
+        tracing::info!("traced message");
+    })
+    .expect("Error converting test buffer to JSON");
+
+    let event = events.first().expect("No event heard");
+    assert_eq!(
+        event.trace.as_deref(),
+        Some("projects/my-project/traces/4bf92f3577b34da6a3ce929d0e0e4736")
+    );
+    assert_eq!(event.span_id.as_deref(), Some("00f067aa0ba902b7"));
+}
+
+#[test]
+fn inherits_trace_fields_from_ancestors() {
+    let events = run_with_tracing_layer::<MockTraceEvent>(cloud_trace_layer(), || {
+        let root = tracing::info_span!("request", trace_id = "abc123");
+        let _root = root.enter();
+        let child = tracing::info_span!("child");
+        let _child = child.enter();
+        tracing::info!("traced message");
+    })
+    .expect("Error converting test buffer to JSON");
+
+    let event = events.first().expect("No event heard");
+    assert_eq!(
+        event.trace.as_deref(),
+        Some("projects/my-project/traces/abc123")
+    );
+    assert!(event.span_id.is_none());
+}
+
+#[test]
+fn omits_trace_fields_without_configuration() {
+    let events = run_with_tracing::<MockTraceEvent>(|| {
+        let span = tracing::info_span!("request", trace_id = "abc123");
+        let _guard = span.enter();
+        tracing::info!("untraced message");
+    })
+    .expect("Error converting test buffer to JSON");
+
+    let event = events.first().expect("No event heard");
+    assert!(event.trace.is_none());
+}
+
+#[test]
+fn includes_trace_fields_recorded_after_creation() {
+    let events = run_with_tracing_layer::<MockTraceEvent>(cloud_trace_layer(), || {
+        let span = tracing::info_span!(
+            "request",
+            trace_id = tracing::field::Empty,
+            span_id = tracing::field::Empty
+        );
+        let _guard = span.enter();
+        tracing::info!("before the headers were extracted");
+
+        span.record("trace_id", "abc123");
+        span.record("span_id", "00f067aa0ba902b7");
+        tracing::info!("after the headers were extracted");
+    })
+    .expect("Error converting test buffer to JSON");
+
+    assert!(events[0].trace.is_none());
+    assert_eq!(
+        events[1].trace.as_deref(),
+        Some("projects/my-project/traces/abc123")
+    );
+    assert_eq!(events[1].span_id.as_deref(), Some("00f067aa0ba902b7"));
+}
+
+fn nested_traces(layer: tracing_stackdriver_cw::Layer<Registry>) -> Vec<Value> {
+    run_with_tracing_layer::<Value>(layer, || {
+        let request = tracing::info_span!("request", trace_id = "root0123");
+        let _request = request.enter();
+        let sub_request = tracing::info_span!("sub_request", trace_id = "nested45");
+        let _sub_request = sub_request.enter();
+        tracing::info!("in a sub-request");
+    })
+    .expect("Error converting test buffer to JSON")
+}
+
+#[test]
+fn resolves_traces_by_policy() {
+    let nearest = nested_traces(cloud_trace_layer());
+    assert_eq!(
+        nearest[0]["logging.googleapis.com/trace"],
+        "projects/my-project/traces/nested45"
+    );
+
+    let root = nested_traces(cloud_trace_layer().with_trace_resolution(TraceResolution::Root));
+    assert_eq!(
+        root[0]["logging.googleapis.com/trace"],
+        "projects/my-project/traces/root0123"
+    );
+
+    let strict = nested_traces(cloud_trace_layer().with_trace_resolution(TraceResolution::Strict));
+    assert!(strict[0].get("logging.googleapis.com/trace").is_none());
+    assert_eq!(
+        strict[0]["traceConflict"],
+        serde_json::json!(["nested45", "root0123"])
+    );
+}
+
+#[test]
+fn includes_typed_trace_contexts() {
+    let events = run_with_tracing_layer::<MockTraceEvent>(cloud_trace_layer(), || {
+        let span = tracing::info_span!("request", trace_id = "from_field");
+        span.set_trace_context(TraceContext {
+            trace_id: "4bf92f3577b34da6a3ce929d0e0e4736".to_string(),
+            span_id: 0x00f067aa0ba902b7,
+            sampled: true,
+            project_id: Some("other-project".to_string()),
+        });
+        span.record("trace_id", "recorded_later");
+
+        let _guard = span.enter();
+        tracing::info_span!("child").in_scope(|| tracing::info!("traced message"));
+    })
+    .expect("Error converting test buffer to JSON");
+
+    assert_eq!(
+        events[0].trace.as_deref(),
+        Some("projects/other-project/traces/4bf92f3577b34da6a3ce929d0e0e4736")
+    );
+    assert_eq!(events[0].span_id.as_deref(), Some("00f067aa0ba902b7"));
+    assert_eq!(events[0].trace_sampled, Some(true));
+}
+
+#[test]
+fn encodes_app_engine_span_ids_as_decimal() {
+    let layer = tracing_stackdriver_cw::app_engine("my-project");
+
+    let events = run_with_tracing_layer::<MockTraceEvent>(layer, || {
+        let span =
+            tracing::info_span!("request", trace_id = "abc123", span_id = "00f067aa0ba902b7");
+        let _guard = span.enter();
+        tracing::info!("traced message");
+    })
+    .expect("Error converting test buffer to JSON");
+
+    let event = events.first().expect("No event heard");
+    assert_eq!(
+        event.trace.as_deref(),
+        Some("projects/my-project/traces/abc123")
+    );
+    assert_eq!(event.span_id.as_deref(), Some("67667974448284343"));
+}
+
+#[test]
+fn encodes_span_ids_as_configured() {
+    let layer = cloud_trace_layer().with_span_id_encoding(SpanIdEncoding::Decimal);
+
+    let events = run_with_tracing_layer::<MockTraceEvent>(layer, || {
+        let span = tracing::info_span!("request", trace_id = "abc123", span_id = "255");
+        let _guard = span.enter();
+        tracing::info!("traced message");
+    })
+    .expect("Error converting test buffer to JSON");
+
+    assert_eq!(events[0].span_id.as_deref(), Some("255"));
+
+    let events = run_with_tracing_layer::<MockTraceEvent>(cloud_trace_layer(), || {
+        let span = tracing::info_span!("request", trace_id = "abc123", span_id = "255");
+        let _guard = span.enter();
+        tracing::info!("traced message");
+    })
+    .expect("Error converting test buffer to JSON");
+
+    assert_eq!(events[0].span_id.as_deref(), Some("00000000000000ff"));
+}
+
+#[test]
+fn parses_span_ids_by_header_format() {
+    let layer = cloud_trace_layer().with_span_id_encoding(SpanIdEncoding::Decimal);
+
+    let events = run_with_tracing_layer::<MockTraceEvent>(layer, || {
+        let span = tracing::info_span!(
+            "request",
+            x_cloud_trace_context = "4bf92f3577b34da6a3ce929d0e0e4736/1234567890123456;o=1"
+        );
+        let _guard = span.enter();
+        tracing::info!("traced message");
+    })
+    .expect("Error converting test buffer to JSON");
+
+    assert_eq!(
+        events[0].trace.as_deref(),
+        Some("projects/my-project/traces/4bf92f3577b34da6a3ce929d0e0e4736")
+    );
+    assert_eq!(events[0].span_id.as_deref(), Some("1234567890123456"));
+    assert_eq!(events[0].trace_sampled, Some(true));
+
+    let events = run_with_tracing_layer::<MockTraceEvent>(cloud_trace_layer(), || {
+        let span = tracing::info_span!(
+            "request",
+            traceparent = "00-4bf92f3577b34da6a3ce929d0e0e4736-1234567890123456-00"
+        );
+        let _guard = span.enter();
+        tracing::info!("traced message");
+    })
+    .expect("Error converting test buffer to JSON");
+
+    assert_eq!(events[0].span_id.as_deref(), Some("1234567890123456"));
+    assert_eq!(events[0].trace_sampled, Some(false));
+}
+
+#[test]
+fn formats_the_current_trace_context() {
+    let subscriber = Registry::default().with(tracing_stackdriver_cw::layer());
+
+    tracing::subscriber::with_default(subscriber, || {
+        assert_eq!(TraceContext::current(), None);
+
+        let root = tracing::info_span!(
+            "request",
+            trace_id = "4bf92f3577b34da6a3ce929d0e0e4736",
+            span_id = "00f067aa0ba902b7",
+            trace_sampled = true
+        );
+        let _root = root.enter();
+        let child = tracing::info_span!("outgoing");
+        let _child = child.enter();
+
+        let context = TraceContext::current().expect("No trace context");
+        assert_eq!(
+            context.traceparent(),
+            "00-4bf92f3577b34da6a3ce929d0e0e4736-00f067aa0ba902b7-01"
+        );
+        assert_eq!(
+            context.x_cloud_trace_context(),
+            "4bf92f3577b34da6a3ce929d0e0e4736/67667974448284343;o=1"
+        );
+    });
+}
+
+#[test]
+fn parses_trace_context_headers() {
+    let context =
+        TraceContext::from_traceparent("00-4bf92f3577b34da6a3ce929d0e0e4736-00f067aa0ba902b7-01")
+            .expect("Invalid traceparent");
+    assert_eq!(context.trace_id, "4bf92f3577b34da6a3ce929d0e0e4736");
+    assert_eq!(context.span_id, 0x00f067aa0ba902b7);
+    assert!(context.sampled);
+
+    assert_eq!(
+        TraceContext::from_x_cloud_trace_context(&context.x_cloud_trace_context()),
+        Some(context)
+    );
+    assert_eq!(TraceContext::from_traceparent("00-abc-123-01"), None);
+}
+
+#[test]
+fn only_correlates_sampled_traces_when_configured() {
+    let layer = cloud_trace_layer().with_sampled_traces_only(true);
+    let events = run_with_tracing_layer::<Value>(layer, || {
+        for trace_sampled in [false, true] {
+            let span = tracing::info_span!(
+                "request",
+                trace_id = "4bf92f3577b34da6a3ce929d0e0e4736",
+                span_id = "00f067aa0ba902b7",
+                trace_sampled
+            );
+            span.in_scope(|| tracing::info!("traced message"));
+        }
+
+        tracing::info_span!("request", trace_id = "abc123")
+            .in_scope(|| tracing::info!("sampling unknown"));
+    })
+    .expect("Error converting test buffer to JSON");
+
+    let unsampled = &events[0];
+    assert!(unsampled.get("logging.googleapis.com/trace").is_none());
+    assert!(unsampled.get("logging.googleapis.com/spanId").is_none());
+    assert_eq!(unsampled["traceId"], "4bf92f3577b34da6a3ce929d0e0e4736");
+    assert_eq!(unsampled["logging.googleapis.com/trace_sampled"], false);
+
+    let sampled = &events[1];
+    assert_eq!(
+        sampled["logging.googleapis.com/trace"],
+        "projects/my-project/traces/4bf92f3577b34da6a3ce929d0e0e4736"
+    );
+    assert!(sampled.get("traceId").is_none());
+
+    assert_eq!(
+        events[2]["logging.googleapis.com/trace"],
+        "projects/my-project/traces/abc123"
+    );
+}
+
+#[test]
+fn overrides_the_project_of_traces_per_span() {
+    let events = run_with_tracing_layer::<Value>(cloud_trace_layer(), || {
+        let gateway = tracing::info_span!("gateway", gcp_project = "customer-project");
+        let _gateway = gateway.enter();
+
+        tracing::info_span!("request", trace_id = "abc123")
+            .in_scope(|| tracing::info!("proxied message"));
+
+        tracing::info_span!(
+            "request",
+            trace_id = "def456",
+            gcp_project = tracing::field::Empty
+        )
+        .in_scope(|| {
+            tracing::Span::current().record("gcp_project", "other-project");
+            tracing::info!("recorded project");
+        });
+    })
+    .expect("Error converting test buffer to JSON");
+
+    assert_eq!(
+        events[0]["logging.googleapis.com/trace"],
+        "projects/customer-project/traces/abc123"
+    );
+    assert_eq!(
+        events[1]["logging.googleapis.com/trace"],
+        "projects/other-project/traces/def456"
+    );
+}
@@ -0,0 +1,156 @@
+#![cfg(feature = "trace-export")]
+use mock_server::MockServer;
+use serde_json::Value;
+use std::sync::{Arc, Mutex};
+use tracing_stackdriver_cw::{ApiClientBuilder, TraceContext, TraceExporter};
+use tracing_subscriber::{layer::SubscriberExt, Registry};
+
+mod mock_server;
+
+fn run_with_trace_exporter(trace_exporter: TraceExporter, callback: impl FnOnce()) {
+    let (stackdriver, guard) = tracing_stackdriver_cw::layer()
+        .with_writer(std::io::sink)
+        .with_trace_exporter(trace_exporter);
+    let subscriber = Registry::default().with(stackdriver);
+    tracing::subscriber::with_default(subscriber, callback);
+    drop(guard);
+}
+
+#[test]
+fn exports_spans_to_the_trace_api() {
+    let server = MockServer::start(200, "{}");
+    let trace_exporter = TraceExporter::builder("my-project")
+        .with_endpoint(&server.url)
+        .with_access_token("token")
+        .build();
+
+    run_with_trace_exporter(trace_exporter, || {
+        let request = tracing::info_span!(
+            "request",
+            trace_id = "4bf92f3577b34da6a3ce929d0e0e4736",
+            span_id = "00f067aa0ba902b7",
+            route = "/users",
+        );
+        let _request = request.enter();
+
+        tracing::info_span!("query", rows = 3).in_scope(|| tracing::info!("queried"));
+        tracing::info_span!("sibling").in_scope(|| {});
+    });
+
+    let requests = server.requests();
+    assert!(requests
+        .iter()
+        .all(|request| request.path == "/projects/my-project/traces:batchWrite"));
+
+    let spans: Vec<Value> = requests
+        .iter()
+        .flat_map(|request| {
+            let body: Value = serde_json::from_slice(&request.body).expect("Invalid JSON body");
+            body["spans"].as_array().cloned().unwrap_or_default()
+        })
+        .collect();
+    let span = |name: &str| {
+        spans
+            .iter()
+            .find(|span| span["displayName"]["value"] == name)
+            .unwrap_or_else(|| panic!("No {} span", name))
+    };
+
+    let (request, query) = (span("request"), span("query"));
+    assert_eq!(spans.len(), 3);
+    assert_eq!(
+        request["name"],
+        format!(
+            "projects/my-project/traces/4bf92f3577b34da6a3ce929d0e0e4736/spans/{}",
+            request["spanId"].as_str().unwrap()
+        )
+    );
+    assert_eq!(request["parentSpanId"], "00f067aa0ba902b7");
+    assert_eq!(
+        request["attributes"]["attributeMap"]["route"]["stringValue"]["value"],
+        "/users"
+    );
+    assert!(request["attributes"]["attributeMap"]
+        .get("trace_id")
+        .is_none());
+    assert!(request["startTime"].is_string() && request["endTime"].is_string());
+
+    assert_eq!(query["parentSpanId"], request["spanId"]);
+    assert_ne!(query["spanId"], request["spanId"]);
+    assert_eq!(query["attributes"]["attributeMap"]["rows"]["intValue"], "3");
+    assert_ne!(span("sibling")["spanId"], query["spanId"]);
+}
+
+#[test]
+fn skips_spans_outside_of_sampled_traces() {
+    let server = MockServer::start(200, "{}");
+    let trace_exporter = TraceExporter::builder("my-project")
+        .with_endpoint(&server.url)
+        .with_access_token("token")
+        .build();
+
+    run_with_trace_exporter(trace_exporter, || {
+        tracing::info_span!("untraced").in_scope(|| {});
+        tracing::info_span!(
+            "unsampled",
+            trace_id = "4bf92f3577b34da6a3ce929d0e0e4736",
+            trace_sampled = false
+        )
+        .in_scope(|| {});
+    });
+
+    assert!(server.requests().is_empty());
+}
+
+#[test]
+fn propagates_the_ids_of_exported_spans() {
+    let server = MockServer::start(200, "{}");
+    let trace_exporter = TraceExporter::builder("my-project")
+        .with_endpoint(&server.url)
+        .with_access_token("token")
+        .build();
+    let mut context = None;
+
+    run_with_trace_exporter(trace_exporter, || {
+        let span = tracing::info_span!("request", trace_id = "4bf92f3577b34da6a3ce929d0e0e4736");
+        context = span.in_scope(TraceContext::current);
+    });
+
+    let context = context.expect("No trace context");
+    let body: Value =
+        serde_json::from_slice(&server.requests()[0].body).expect("Invalid JSON body");
+    assert_eq!(
+        body["spans"][0]["spanId"],
+        format!("{:016x}", context.span_id)
+    );
+    assert!(context.sampled);
+}
+
+#[test]
+fn reports_failures_to_the_diagnostics_hook() {
+    let server = MockServer::start(500, "{}");
+    let failures = Arc::new(Mutex::new(vec![]));
+    let trace_exporter = TraceExporter::builder("my-project")
+        .with_endpoint(&server.url)
+        .with_access_token("token")
+        .with_diagnostics({
+            let failures = failures.clone();
+            move |failure| {
+                failures
+                    .lock()
+                    .unwrap()
+                    .push((failure.entries.len(), failure.message.clone()))
+            }
+        })
+        .build();
+
+    run_with_trace_exporter(trace_exporter, || {
+        let request = tracing::info_span!("request", trace_id = "4bf92f3577b34da6a3ce929d0e0e4736");
+        request.in_scope(|| {});
+    });
+
+    assert_eq!(
+        *failures.lock().unwrap(),
+        [(1, "batchWrite failed with HTTP status 500".to_string())]
+    );
+}
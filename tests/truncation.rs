@@ -0,0 +1,25 @@
+use helpers::run_with_tracing_layer;
+use serde_json::Value;
+
+mod helpers;
+
+#[test]
+fn truncates_long_string_values() {
+    let layer = tracing_stackdriver_cw::layer().with_max_value_length(8);
+    let events = run_with_tracing_layer::<Value>(layer, || {
+        tracing::info!(
+            body = "0123456789abcdef",
+            short = "tiny",
+            count = 1234567890,
+            "héllo wörld"
+        )
+    })
+    .expect("Error converting test buffer to JSON");
+
+    let event = events.first().expect("No event heard");
+    assert_eq!(event["body"], "01234567…[truncated 8 bytes]");
+    assert_eq!(event["short"], "tiny");
+    assert_eq!(event["count"], 1234567890);
+    // truncation never splits a multi-byte character
+    assert_eq!(event["message"], "héllo w…[truncated 5 bytes]");
+}
@@ -0,0 +1,44 @@
+#![cfg(feature = "uuid")]
+use helpers::run_with_tracing_layer;
+use serde_json::Value;
+use tracing_stackdriver_cw::{as_uuid, CloudTraceConfiguration};
+use uuid::Uuid;
+
+mod helpers;
+
+const UUID: &str = "4bf92f35-77b3-4da6-a3ce-929d0e0e4736";
+
+#[test]
+fn writes_hyphenated_uuids() {
+    let uuid = Uuid::parse_str(UUID).unwrap();
+    let events = run_with_tracing_layer::<Value>(
+        tracing_stackdriver_cw::layer(),
+        || tracing::info!(user_id = %as_uuid(&uuid), request_id = ?as_uuid(&uuid), "uuids"),
+    )
+    .expect("Error converting test buffer to JSON");
+
+    assert_eq!(events[0]["userId"], UUID);
+    assert_eq!(events[0]["requestId"], UUID);
+}
+
+#[test]
+fn uses_uuid_trace_ids() {
+    let uuid = Uuid::parse_str(UUID).unwrap();
+    let layer = tracing_stackdriver_cw::layer()
+        .with_cloud_trace(CloudTraceConfiguration {
+            project_id: "my-project".to_string(),
+        })
+        .with_uuid_trace_ids(true);
+
+    let events = run_with_tracing_layer::<Value>(layer, || {
+        let span = tracing::info_span!("request", trace_id = %as_uuid(&uuid));
+        let _span = span.enter();
+        tracing::info!("traced")
+    })
+    .expect("Error converting test buffer to JSON");
+
+    assert_eq!(
+        events[0]["logging.googleapis.com/trace"],
+        "projects/my-project/traces/4bf92f3577b34da6a3ce929d0e0e4736"
+    );
+}
@@ -0,0 +1,70 @@
+use helpers::MockWriter;
+use serde_json::Value;
+use std::sync::{Arc, Mutex};
+use tracing_stackdriver_cw::RequestVerbosity;
+use tracing_subscriber::{filter::LevelFilter, layer::SubscriberExt, Layer, Registry};
+
+mod helpers;
+
+#[test]
+fn raises_the_verbosity_of_requests() {
+    let buffer = Arc::new(Mutex::new(vec![]));
+    let shared = buffer.clone();
+    let stackdriver = tracing_stackdriver_cw::layer()
+        .with_writer(move || MockWriter(shared.clone()))
+        .with_filter(RequestVerbosity::new(LevelFilter::INFO));
+    let subscriber = Registry::default().with(stackdriver);
+
+    tracing::subscriber::with_default(subscriber, || {
+        tracing::debug!("hidden");
+
+        let verbose = tracing::info_span!("request", log_verbosity = "debug");
+        verbose.in_scope(|| {
+            tracing::debug!("shown");
+            tracing::trace!("still hidden");
+        });
+
+        let quiet = tracing::info_span!("request", log_verbosity = tracing::field::Empty);
+        quiet.in_scope(|| tracing::debug!("hidden"));
+        quiet.record("log_verbosity", "trace");
+        quiet.in_scope(|| tracing::trace!("recorded later"));
+    });
+
+    let buffer = buffer.lock().unwrap();
+    let messages: Vec<_> = serde_json::Deserializer::from_slice(&buffer)
+        .into_iter::<Value>()
+        .map(|event| event.unwrap()["message"].clone())
+        .collect();
+
+    assert_eq!(messages, ["shown", "recorded later"]);
+}
+
+#[test]
+fn raises_the_verbosity_of_events_with_explicit_parents() {
+    let buffer = Arc::new(Mutex::new(vec![]));
+    let shared = buffer.clone();
+    let stackdriver = tracing_stackdriver_cw::layer()
+        .with_writer(move || MockWriter(shared.clone()))
+        .with_filter(RequestVerbosity::new(LevelFilter::INFO));
+    let subscriber = Registry::default().with(stackdriver);
+
+    tracing::subscriber::with_default(subscriber, || {
+        let verbose = tracing::info_span!("request", log_verbosity = "debug");
+        let quiet = tracing::info_span!("request");
+
+        tracing::debug!(parent: &verbose, "explicitly verbose");
+        verbose.in_scope(|| {
+            tracing::debug!(parent: &quiet, "hidden");
+            tracing::debug!(parent: None, "also hidden");
+            tracing::debug!("contextually verbose");
+        });
+    });
+
+    let buffer = buffer.lock().unwrap();
+    let messages: Vec<_> = serde_json::Deserializer::from_slice(&buffer)
+        .into_iter::<Value>()
+        .map(|event| event.unwrap()["message"].clone())
+        .collect();
+
+    assert_eq!(messages, ["explicitly verbose", "contextually verbose"]);
+}
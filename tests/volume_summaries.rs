@@ -0,0 +1,79 @@
+use helpers::run_with_tracing_layer;
+use serde_json::Value;
+use std::{thread, time::Duration};
+use tracing_stackdriver_cw::{BuildInfo, HealthCheckFilter};
+
+mod helpers;
+
+#[test]
+fn summarizes_entry_volume_by_severity() {
+    let layer = tracing_stackdriver_cw::layer()
+        .with_volume_summaries(Duration::from_millis(50))
+        .with_health_check_filter(HealthCheckFilter {
+            field: "url".to_string(),
+            sample_rate: 0,
+            ..Default::default()
+        });
+
+    let events = run_with_tracing_layer::<Value>(layer, || {
+        tracing::warn!("first");
+        tracing::info!(url = "/healthz", "health check");
+
+        thread::sleep(Duration::from_millis(60));
+        tracing::error!("after the interval");
+    })
+    .expect("Error converting test buffer to JSON");
+
+    let summary = events
+        .iter()
+        .find(|event| event["severity"] == "NOTICE" && event.get("entries").is_some())
+        .expect("no volume summary was written");
+
+    assert_eq!(summary["entries"]["WARNING"]["written"], 1);
+    assert_eq!(summary["entries"]["INFO"]["rateLimited"], 1);
+    assert_eq!(summary["entries"]["ERROR"]["written"], 1);
+    assert!(summary["message"]
+        .as_str()
+        .unwrap()
+        .starts_with("3 entries in the last"));
+}
+
+#[test]
+fn writes_no_summaries_within_the_interval() {
+    let layer = tracing_stackdriver_cw::layer().with_volume_summaries(Duration::from_secs(60));
+
+    let events = run_with_tracing_layer::<Value>(layer, || {
+        tracing::info!("first");
+        tracing::info!("second");
+    })
+    .expect("Error converting test buffer to JSON");
+
+    assert_eq!(events.len(), 2);
+}
+
+#[test]
+fn labels_summaries_like_other_entries() {
+    let layer = tracing_stackdriver_cw::layer()
+        .with_volume_summaries(Duration::from_millis(50))
+        .with_build_info(BuildInfo {
+            version: Some("1.2.3".to_string()),
+            ..BuildInfo::default()
+        });
+
+    let events = run_with_tracing_layer::<Value>(layer, || {
+        tracing::info!("first");
+        thread::sleep(Duration::from_millis(60));
+        tracing::info!("after the interval");
+    })
+    .expect("Error converting test buffer to JSON");
+
+    let summary = events
+        .iter()
+        .find(|event| event["severity"] == "NOTICE")
+        .expect("no volume summary was written");
+
+    assert_eq!(
+        summary["logging.googleapis.com/labels"]["build_version"],
+        "1.2.3"
+    );
+}
@@ -0,0 +1,115 @@
+use std::{
+    io,
+    sync::{Arc, Mutex},
+};
+use tracing_stackdriver_cw::{Fanout, Layer};
+use tracing_subscriber::{fmt::TestWriter, layer::SubscriberExt, Registry};
+
+#[test]
+fn builds_layers_for_common_writers() {
+    let _: Layer<Registry> = tracing_stackdriver_cw::layer().with_stderr().with_stdout();
+    let _: Layer<Registry, fn() -> io::Stderr> = tracing_stackdriver_cw::layer().with_stderr();
+
+    let layer: Layer<Registry, TestWriter> = tracing_stackdriver_cw::layer().with_test_writer();
+    let subscriber = Registry::default().with(layer);
+
+    tracing::subscriber::with_default(subscriber, || {
+        tracing::info!("captured by the test harness");
+    });
+}
+
+/// Writer of a destination that's down
+struct Failing;
+
+impl io::Write for Failing {
+    fn write(&mut self, _: &[u8]) -> io::Result<usize> {
+        Err(io::Error::new(
+            io::ErrorKind::BrokenPipe,
+            "collector is down",
+        ))
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+#[derive(Clone, Default)]
+struct Buffer(Arc<Mutex<Vec<u8>>>);
+
+impl io::Write for Buffer {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.0.lock().unwrap().extend_from_slice(buf);
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+#[test]
+fn fans_entries_out_to_independent_destinations() {
+    let (first, second) = (Buffer::default(), Buffer::default());
+    let (first_output, second_output) = (first.0.clone(), second.0.clone());
+    let fanout = Fanout::new()
+        .with_destination("stdout", move || first.clone())
+        .with_destination("socket", || Failing)
+        .with_destination("file", move || second.clone());
+
+    let layer = tracing_stackdriver_cw::layer().with_writer(fanout.clone());
+    let subscriber = Registry::default().with(layer);
+
+    tracing::subscriber::with_default(subscriber, || {
+        tracing::info!("first");
+        tracing::info!("second");
+    });
+
+    let first = String::from_utf8(first_output.lock().unwrap().clone()).unwrap();
+    let second = String::from_utf8(second_output.lock().unwrap().clone()).unwrap();
+
+    assert_eq!(first.lines().count(), 2);
+    assert_eq!(first, second);
+
+    let dropped = fanout.dropped_entries();
+    assert_eq!(dropped["socket"], 2);
+    assert_eq!(dropped["stdout"], 0);
+    assert_eq!(dropped["file"], 0);
+}
+
+/// Writer keeping each write it receives separately
+#[derive(Clone, Default)]
+struct Writes(Arc<Mutex<Vec<Vec<u8>>>>);
+
+impl io::Write for Writes {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.0.lock().unwrap().push(buf.to_vec());
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+#[test]
+fn writes_each_entry_with_its_newline_at_once() {
+    let writes = Writes::default();
+    let output = writes.0.clone();
+    let layer = tracing_stackdriver_cw::layer().with_writer(move || writes.clone());
+    let subscriber = Registry::default().with(layer);
+
+    tracing::subscriber::with_default(subscriber, || {
+        tracing::info!(payload = "x".repeat(100_000), "oversized");
+        tracing::info!("after an oversized entry");
+    });
+
+    let output = output.lock().unwrap();
+    assert_eq!(output.len(), 2);
+
+    for write in output.iter() {
+        assert_eq!(write.iter().filter(|byte| **byte == b'\n').count(), 1);
+        assert!(write.ends_with(b"\n"));
+        serde_json::from_slice::<serde_json::Value>(write).expect("a whole entry");
+    }
+}